@@ -0,0 +1,67 @@
+//! Size and speed of JSON vs CBOR vs MessagePack for stored transactions and sync envelopes.
+//! Run with `cargo bench --features cbor,msgpack --bench encoding`.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use true_ledger_core::codec::{self, Encoding};
+use true_ledger_core::demo::{self, DemoConfig};
+use true_ledger_core::protocol::{DateRange, SyncChunk};
+use true_ledger_core::{Envelope, Transaction};
+
+const ENCODINGS: [Encoding; 3] = [Encoding::Json, Encoding::Cbor, Encoding::MessagePack];
+
+fn chunk_envelope(book: &demo::DemoBook) -> Envelope {
+    Envelope::Chunk(SyncChunk {
+        seq: 0,
+        total: 1,
+        range: DateRange { from: None, to: None },
+        accounts: book.accounts.clone(),
+        transactions: book.transactions.clone(),
+    })
+}
+
+fn bench_transactions(c: &mut Criterion) {
+    let book = demo::generate(&DemoConfig::default());
+    let mut group = c.benchmark_group("transactions");
+    for encoding in ENCODINGS {
+        let encoded: Vec<Vec<u8>> = book.transactions.iter().map(|tx| encoding.encode(tx).unwrap()).collect();
+        let total: usize = encoded.iter().map(Vec::len).sum();
+        println!("{}: {} transactions, {} bytes", encoding.name(), encoded.len(), total);
+
+        group.bench_function(format!("encode/{}", encoding.name()), |b| {
+            b.iter(|| {
+                for tx in &book.transactions {
+                    black_box(encoding.encode(tx).unwrap());
+                }
+            })
+        });
+        group.bench_function(format!("decode/{}", encoding.name()), |b| {
+            b.iter(|| {
+                for data in &encoded {
+                    black_box(codec::decode::<Transaction>(data).unwrap());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_envelopes(c: &mut Criterion) {
+    let book = demo::generate(&DemoConfig::default());
+    let envelope = chunk_envelope(&book);
+    let mut group = c.benchmark_group("envelope");
+    for encoding in ENCODINGS {
+        let data = envelope.to_bytes_as(encoding).unwrap();
+        println!("{}: chunk envelope {} bytes", encoding.name(), data.len());
+
+        group.bench_function(format!("encode/{}", encoding.name()), |b| {
+            b.iter(|| black_box(envelope.to_bytes_as(encoding).unwrap()))
+        });
+        group.bench_function(format!("decode/{}", encoding.name()), |b| {
+            b.iter(|| black_box(Envelope::from_bytes(&data).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_transactions, bench_envelopes);
+criterion_main!(benches);
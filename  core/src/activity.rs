@@ -0,0 +1,123 @@
+//! Chronological activity log backing the "what happened while I was away" feed. Entries carry
+//! ids so the logs of several devices sync to their union.
+use chrono::{DateTime, NaiveDate, Utc};
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ActivityKind {
+    TransactionPosted { transaction_id: Uuid },
+    TransactionEdited { transaction_id: Uuid },
+    MergeReceived { peer: String, changes: usize },
+    BackupCreated { location: String },
+    PeriodClosed { period_end: NaiveDate },
+    TransactionVoided { transaction_id: Uuid, correction_id: Uuid },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActivityEntry {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub at: DateTime<Utc>,
+    pub kind: ActivityKind,
+}
+
+impl ActivityEntry {
+    pub fn new(at: DateTime<Utc>, kind: ActivityKind) -> Self {
+        Self { id: Uuid::new_v4(), at, kind }
+    }
+}
+
+/// Append-only activity log
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ActivityLog {
+    entries: Vec<ActivityEntry>,
+}
+
+impl ActivityLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an event happening now
+    pub fn push(&mut self, kind: ActivityKind) {
+        self.push_at(Utc::now(), kind);
+    }
+
+    /// Record an event with an explicit timestamp, keeping the log ordered
+    pub fn push_at(&mut self, at: DateTime<Utc>, kind: ActivityKind) {
+        self.insert(ActivityEntry::new(at, kind));
+    }
+
+    /// Add an entry, e.g. one synced from another device; entries already present are skipped
+    pub fn insert(&mut self, entry: ActivityEntry) {
+        if self.entries.iter().any(|e| e.id == entry.id) {
+            return;
+        }
+        let pos = self.entries.partition_point(|e| (e.at, e.id) <= (entry.at, entry.id));
+        self.entries.insert(pos, entry);
+    }
+
+    /// Log rebuilt from stored entries in any order
+    pub fn from_entries(entries: impl IntoIterator<Item = ActivityEntry>) -> Self {
+        let mut entries: Vec<ActivityEntry> = entries.into_iter().collect();
+        entries.sort_by_key(|e| (e.at, e.id));
+        entries.dedup_by_key(|e| e.id);
+        Self { entries }
+    }
+
+    /// Every entry, oldest first
+    pub fn entries(&self) -> &[ActivityEntry] {
+        &self.entries
+    }
+
+    /// Entries strictly after `since`, oldest first
+    pub fn since(&self, since: DateTime<Utc>) -> &[ActivityEntry] {
+        let start = self.entries.partition_point(|e| e.at <= since);
+        &self.entries[start..]
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn entries_stay_ordered_and_since_is_exclusive() {
+        let mut log = ActivityLog::new();
+        log.push_at(at(20), ActivityKind::BackupCreated { location: "b".to_string() });
+        log.push_at(at(10), ActivityKind::BackupCreated { location: "a".to_string() });
+        log.push_at(at(30), ActivityKind::PeriodClosed { period_end: NaiveDate::from_ymd_opt(2024, 1, 31).unwrap() });
+
+        let times: Vec<_> = log.entries().iter().map(|e| e.at).collect();
+        assert_eq!(times, vec![at(10), at(20), at(30)]);
+        assert_eq!(log.since(at(10)).len(), 2);
+        assert!(log.since(at(30)).is_empty());
+    }
+
+    #[test]
+    fn logs_from_two_devices_combine_without_duplicates() {
+        let mut local = ActivityLog::new();
+        local.push_at(at(10), ActivityKind::TransactionPosted { transaction_id: Uuid::new_v4() });
+        let mut peer = ActivityLog::new();
+        peer.push_at(at(5), ActivityKind::TransactionEdited { transaction_id: Uuid::new_v4() });
+
+        let mut merged = ActivityLog::from_entries(local.entries().iter().chain(peer.entries()).cloned());
+        merged.insert(local.entries()[0].clone());
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged.entries()[0], peer.entries()[0]);
+    }
+}
@@ -0,0 +1,303 @@
+//! Forgiving amount entry for CLI/UI fields: separators in either convention, currency
+//! symbols, accounting negatives and simple arithmetic, all in exact decimal math
+use rust_decimal::Decimal;
+use serde::{Serialize, Deserialize};
+
+use crate::currency::Commodity;
+use crate::locale::Locale;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AmountError {
+    #[error("No amount entered")]
+    Empty,
+    #[error("Invalid number: {0}")]
+    InvalidNumber(String),
+    #[error("Unexpected '{0}'")]
+    UnexpectedChar(char),
+    #[error("Unbalanced parentheses")]
+    UnbalancedParens,
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("Amount out of range")]
+    Overflow,
+    #[error("More than one currency in one amount")]
+    MixedCurrencies,
+}
+
+/// Entered amount and the currency it was written with, if any
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParsedAmount {
+    pub amount: Decimal,
+    pub commodity: Option<Commodity>,
+}
+
+/// Parse "1,234.56", "1.234,56", "€12", "(12.50)", "-12 EUR" or "3*4.99".
+/// `locale` only decides ambiguous single separators such as "1,234". Letters must spell a
+/// known currency code, so "12 abc" is rejected rather than read as a currency.
+pub fn parse_amount_input(input: &str, locale: &Locale) -> Result<ParsedAmount, AmountError> {
+    let mut parser = Parser { chars: input.chars().collect(), pos: 0, locale, commodity: None };
+    parser.skip_space()?;
+    if parser.peek().is_none() {
+        return Err(AmountError::Empty);
+    }
+    let amount = parser.expr()?;
+    parser.skip_space()?;
+    match parser.peek() {
+        None => Ok(ParsedAmount { amount: amount.normalize(), commodity: parser.commodity }),
+        Some(')') => Err(AmountError::UnbalancedParens),
+        Some(c) => Err(AmountError::UnexpectedChar(c)),
+    }
+}
+
+/// Characters that can appear inside one number literal
+fn is_number_char(c: char) -> bool {
+    c.is_ascii_digit() || matches!(c, '.' | ',' | '\'' | '\u{a0}' | '\u{202f}')
+}
+
+fn symbol_commodity(c: char) -> Option<&'static str> {
+    match c {
+        '$' => Some("USD"),
+        '€' => Some("EUR"),
+        '£' => Some("GBP"),
+        '¥' => Some("JPY"),
+        '₿' => Some("BTC"),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    locale: &'a Locale,
+    commodity: Option<Commodity>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    /// Skip whitespace and currency markers, remembering the currency
+    fn skip_space(&mut self) -> Result<(), AmountError> {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => self.pos += 1,
+                Some(c) if symbol_commodity(c).is_some() => {
+                    self.pos += 1;
+                    self.set_commodity(Commodity::new(symbol_commodity(c).unwrap()))?;
+                }
+                Some(c) if c.is_ascii_alphabetic() => {
+                    let start = self.pos;
+                    while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                        self.pos += 1;
+                    }
+                    let code: String = self.chars[start..self.pos].iter().collect();
+                    let commodity = Commodity::from_known_code(&code).ok_or(AmountError::UnexpectedChar(c))?;
+                    self.set_commodity(commodity)?;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn set_commodity(&mut self, commodity: Commodity) -> Result<(), AmountError> {
+        match &self.commodity {
+            Some(existing) if *existing != commodity => Err(AmountError::MixedCurrencies),
+            _ => {
+                self.commodity = Some(commodity);
+                Ok(())
+            }
+        }
+    }
+
+    fn expr(&mut self) -> Result<Decimal, AmountError> {
+        let mut value = self.term()?;
+        loop {
+            self.skip_space()?;
+            match self.peek() {
+                Some('+') => {
+                    self.pos += 1;
+                    value = value.checked_add(self.term()?).ok_or(AmountError::Overflow)?;
+                }
+                Some('-') => {
+                    self.pos += 1;
+                    value = value.checked_sub(self.term()?).ok_or(AmountError::Overflow)?;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Decimal, AmountError> {
+        let mut value = self.factor()?;
+        loop {
+            self.skip_space()?;
+            match self.peek() {
+                Some('*' | '×') => {
+                    self.pos += 1;
+                    value = value.checked_mul(self.factor()?).ok_or(AmountError::Overflow)?;
+                }
+                Some('/' | '÷') => {
+                    self.pos += 1;
+                    let divisor = self.factor()?;
+                    if divisor.is_zero() {
+                        return Err(AmountError::DivisionByZero);
+                    }
+                    value = value.checked_div(divisor).ok_or(AmountError::Overflow)?;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn factor(&mut self) -> Result<Decimal, AmountError> {
+        self.skip_space()?;
+        match self.peek() {
+            Some('-' | '−') => {
+                self.pos += 1;
+                Ok(-self.factor()?)
+            }
+            Some('+') => {
+                self.pos += 1;
+                self.factor()
+            }
+            // "(12.50)" on its own is an accounting negative; "(1+2)*3" is grouping
+            Some('(') => {
+                let open = self.pos;
+                self.pos += 1;
+                let value = self.expr()?;
+                self.skip_space()?;
+                if self.peek() != Some(')') {
+                    return Err(AmountError::UnbalancedParens);
+                }
+                self.pos += 1;
+                let decoration = |c: &char| c.is_whitespace() || c.is_ascii_alphabetic() || symbol_commodity(*c).is_some();
+                let whole_input = self.chars[..open].iter().all(decoration)
+                    && self.chars[self.pos..].iter().all(decoration);
+                let literal = self.chars[open + 1..self.pos - 1].iter()
+                    .all(|c| is_number_char(*c) || decoration(c));
+                Ok(if whole_input && literal { -value } else { value })
+            }
+            Some(c) if is_number_char(c) => self.number(),
+            Some(c) => Err(AmountError::UnexpectedChar(c)),
+            None => Err(AmountError::InvalidNumber(String::new())),
+        }
+    }
+
+    fn number(&mut self) -> Result<Decimal, AmountError> {
+        let start = self.pos;
+        while self.peek().is_some_and(is_number_char) {
+            self.pos += 1;
+        }
+        let raw: String = self.chars[start..self.pos].iter().collect();
+        parse_literal(&raw, self.locale.decimal_separator)
+    }
+}
+
+/// Work out which of '.' and ',' is the decimal mark in one literal
+fn parse_literal(raw: &str, locale_decimal: char) -> Result<Decimal, AmountError> {
+    let invalid = || AmountError::InvalidNumber(raw.to_string());
+    let digits: String = raw.chars().filter(|c| !matches!(c, '\'' | '\u{a0}' | '\u{202f}')).collect();
+    let last_dot = digits.rfind('.');
+    let last_comma = digits.rfind(',');
+    let decimal = match (last_dot, last_comma) {
+        (Some(d), Some(c)) => Some(if d > c { '.' } else { ',' }),
+        (Some(_), None) | (None, Some(_)) => {
+            let sep = if last_dot.is_some() { '.' } else { ',' };
+            let (head, tail) = digits.rsplit_once(sep).unwrap();
+            if digits.matches(sep).count() > 1 {
+                None
+            } else if tail.len() == 3 && !head.is_empty() && head != "0" {
+                // "1,234" is 1234 in en-US but 1.234 in de-DE: ask the locale
+                (sep == locale_decimal).then_some(sep)
+            } else {
+                Some(sep)
+            }
+        }
+        (None, None) => None,
+    };
+
+    let integer = match decimal {
+        Some(d) => digits.rsplit_once(d).unwrap().0,
+        None => digits.as_str(),
+    };
+    // Group separators must sit every three digits: "1,23" is a typo, not 123
+    let groups: Vec<&str> = integer.split(['.', ',']).collect();
+    if groups.len() > 1 && (groups[0].is_empty() || groups[0].len() > 3 || groups[1..].iter().any(|g| g.len() != 3)) {
+        return Err(invalid());
+    }
+
+    let mut normalized = String::with_capacity(digits.len());
+    for c in digits.chars() {
+        match c {
+            '0'..='9' => normalized.push(c),
+            c if Some(c) == decimal => normalized.push('.'),
+            _ => {}
+        }
+    }
+    if normalized.ends_with('.') {
+        normalized.pop();
+    }
+    if normalized.starts_with('.') {
+        normalized.insert(0, '0');
+    }
+    if normalized.is_empty() {
+        return Err(invalid());
+    }
+    // Exact parsing rejects literals that would have to be rounded to fit
+    Decimal::from_str_exact(&normalized).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str, locale: &Locale) -> Result<Decimal, AmountError> {
+        parse_amount_input(input, locale).map(|p| p.amount)
+    }
+
+    #[test]
+    fn a_single_separator_followed_by_three_digits_follows_the_locale() {
+        assert_eq!(parse("1,234", &Locale::en_us()), Ok(Decimal::from(1234)));
+        assert_eq!(parse("1,234", &Locale::de_de()), Ok(Decimal::new(1234, 3)));
+        assert_eq!(parse("1.234", &Locale::en_us()), Ok(Decimal::new(1234, 3)));
+        assert_eq!(parse("1.234", &Locale::de_de()), Ok(Decimal::from(1234)));
+        // Both marks or a short tail settle it whatever the locale
+        assert_eq!(parse("1.234,56", &Locale::en_us()), Ok(Decimal::new(123456, 2)));
+        assert_eq!(parse("1,5", &Locale::en_us()), Ok(Decimal::new(15, 1)));
+        assert_eq!(parse("0,125", &Locale::en_us()), Ok(Decimal::new(125, 3)));
+        assert!(matches!(parse("1,23,4", &Locale::en_us()), Err(AmountError::InvalidNumber(_))));
+    }
+
+    #[test]
+    fn parentheses_negate_only_a_whole_literal() {
+        let en = Locale::en_us();
+        assert_eq!(parse("(12.50)", &en), Ok(Decimal::new(-125, 1)));
+        assert_eq!(parse_amount_input("(€1.234,50)", &en).unwrap(), ParsedAmount {
+            amount: Decimal::new(-123450, 2),
+            commodity: Some(Commodity::new("EUR")),
+        });
+        assert_eq!(parse("(12.50) GBP", &en), Ok(Decimal::new(-125, 1)));
+        assert_eq!(parse("(1+2)*3", &en), Ok(Decimal::from(9)));
+        assert_eq!(parse("-(4)", &en), Ok(Decimal::from(-4)));
+        assert_eq!(parse("(12", &en), Err(AmountError::UnbalancedParens));
+    }
+
+    #[test]
+    fn only_known_currency_codes_are_accepted() {
+        let en = Locale::en_us();
+        assert_eq!(parse_amount_input("-12 eur", &en).unwrap().commodity, Some(Commodity::new("EUR")));
+        assert_eq!(parse("12 abc", &en), Err(AmountError::UnexpectedChar('a')));
+        assert_eq!(parse("12 euro", &en), Err(AmountError::UnexpectedChar('e')));
+        assert_eq!(parse("$12 EUR", &en), Err(AmountError::MixedCurrencies));
+        assert_eq!(parse("3*4.99 USD", &en), Ok(Decimal::new(1497, 2)));
+    }
+
+    #[test]
+    fn locale_parse_uses_the_same_grammar() {
+        let de = Locale::de_de();
+        assert_eq!(de.parse_amount("1.234,56 €").unwrap(), Decimal::new(123456, 2));
+        assert_eq!(de.parse_amount("(1.234)").unwrap(), Decimal::from(-1234));
+        assert!(de.parse_amount("12 abc").is_err());
+    }
+}
@@ -0,0 +1,134 @@
+//! Background anti-entropy: occasional state-hash exchange with a random peer so replicas that
+//! missed gossip converge on their own
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::{Serialize, Deserialize};
+
+/// How often rounds run; each round waits `interval` plus up to `jitter`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AntiEntropyConfig {
+    pub enabled: bool,
+    pub interval: Duration,
+    pub jitter: Duration,
+}
+
+impl Default for AntiEntropyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: Duration::from_secs(300),
+            jitter: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Round timer and peer picker
+#[derive(Debug, Clone)]
+pub struct AntiEntropy {
+    config: AntiEntropyConfig,
+    next_round: Instant,
+    rng: u64,
+}
+
+impl AntiEntropy {
+    pub fn new(config: AntiEntropyConfig) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(1);
+        let mut this = Self { config, next_round: Instant::now(), rng: seed | 1 };
+        this.schedule(Instant::now());
+        this
+    }
+
+    pub fn config(&self) -> &AntiEntropyConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: AntiEntropyConfig) {
+        self.config = config;
+        self.schedule(Instant::now());
+    }
+
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.config.enabled && now >= self.next_round
+    }
+
+    /// Start the next waiting period from `now`
+    pub fn schedule(&mut self, now: Instant) {
+        let jitter_ms = self.config.jitter.as_millis() as u64;
+        let jitter = if jitter_ms == 0 { 0 } else { self.next() % (jitter_ms + 1) };
+        self.next_round = now + self.config.interval + Duration::from_millis(jitter);
+    }
+
+    /// Uniformly random candidate
+    pub fn pick<'a, T>(&mut self, candidates: &'a [T]) -> Option<&'a T> {
+        if candidates.is_empty() {
+            return None;
+        }
+        let i = (self.next() % candidates.len() as u64) as usize;
+        candidates.get(i)
+    }
+
+    /// xorshift64*, as in the demo generator
+    fn next(&mut self) -> u64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+impl Default for AntiEntropy {
+    fn default() -> Self {
+        Self::new(AntiEntropyConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(interval: u64, jitter: u64) -> AntiEntropyConfig {
+        AntiEntropyConfig { enabled: true, interval: Duration::from_secs(interval), jitter: Duration::from_secs(jitter) }
+    }
+
+    #[test]
+    fn rounds_wait_the_interval_plus_at_most_the_jitter() {
+        let mut rounds = AntiEntropy::new(config(300, 60));
+        let now = Instant::now();
+        for _ in 0..100 {
+            rounds.schedule(now);
+            assert!(!rounds.is_due(now + Duration::from_secs(299)));
+            assert!(rounds.is_due(now + Duration::from_secs(360)));
+        }
+
+        rounds.set_config(config(10, 0));
+        rounds.schedule(now);
+        assert_eq!(rounds.next_round, now + Duration::from_secs(10));
+    }
+
+    #[test]
+    fn disabled_rounds_are_never_due() {
+        let rounds = AntiEntropy::new(AntiEntropyConfig { enabled: false, ..config(0, 0) });
+        assert!(!rounds.is_due(Instant::now() + Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn every_peer_gets_picked() {
+        let mut rounds = AntiEntropy::default();
+        assert_eq!(rounds.pick::<&str>(&[]), None);
+        let peers = ["a", "b", "c"];
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..200 {
+            seen.insert(*rounds.pick(&peers).unwrap());
+        }
+        assert_eq!(seen.len(), peers.len());
+    }
+
+    #[test]
+    fn missing_config_fields_take_defaults() {
+        let config: AntiEntropyConfig = serde_json::from_str(r#"{"enabled": false}"#).unwrap();
+        assert_eq!(config, AntiEntropyConfig { enabled: false, ..AntiEntropyConfig::default() });
+    }
+}
@@ -0,0 +1,79 @@
+//! Free-form preferences frontends share through the book, e.g. the default report period or a
+//! dashboard layout. Keys are namespaced per app ("dashboard.layout") and sync one entry each,
+//! so devices changing different keys merge cleanly and the later write wins on the same key.
+use std::collections::BTreeMap;
+use serde::de::DeserializeOwned;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AppSettingsError {
+    #[error("Invalid settings key: {0}")]
+    InvalidKey(String),
+    #[error("Setting value is not serializable: {0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
+/// Shared app settings by "namespace.key"
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppSettings(BTreeMap<String, Value>);
+
+impl AppSettings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Value under `namespace.key`; None when unset or stored in a different shape
+    pub fn get<T: DeserializeOwned>(&self, namespace: &str, key: &str) -> Option<T> {
+        let value = self.0.get(&full_key(namespace, key).ok()?)?;
+        serde_json::from_value(value.clone()).ok()
+    }
+
+    /// Store `value` under `namespace.key`. The namespace must be non-empty without dots; the
+    /// key must be non-empty.
+    pub fn set<T: Serialize>(&mut self, namespace: &str, key: &str, value: &T) -> Result<(), AppSettingsError> {
+        let full = full_key(namespace, key)?;
+        self.0.insert(full, serde_json::to_value(value)?);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, namespace: &str, key: &str) -> bool {
+        full_key(namespace, key).is_ok_and(|full| self.0.remove(&full).is_some())
+    }
+
+    /// Keys and values of one namespace, keys without the prefix
+    pub fn namespace<'a>(&'a self, namespace: &str) -> impl Iterator<Item = (&'a str, &'a Value)> {
+        let prefix = format!("{}.", namespace);
+        self.0.iter().filter_map(move |(k, v)| Some((k.strip_prefix(&prefix)?, v)))
+    }
+
+    /// Drop every key of a namespace, e.g. when an app resets its preferences
+    pub fn clear_namespace(&mut self, namespace: &str) -> usize {
+        let before = self.0.len();
+        let prefix = format!("{}.", namespace);
+        self.0.retain(|k, _| !k.starts_with(&prefix));
+        before - self.0.len()
+    }
+
+    /// All entries by full key
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Entry as stored in the document; invalid keys from a peer are kept but never returned by `get`
+    pub(crate) fn insert_raw(&mut self, full_key: String, value: Value) {
+        self.0.insert(full_key, value);
+    }
+}
+
+fn full_key(namespace: &str, key: &str) -> Result<String, AppSettingsError> {
+    if namespace.is_empty() || namespace.contains('.') || key.is_empty() {
+        return Err(AppSettingsError::InvalidKey(format!("{}.{}", namespace, key)));
+    }
+    Ok(format!("{}.{}", namespace, key))
+}
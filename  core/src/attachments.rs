@@ -0,0 +1,37 @@
+//! Files attached to transactions (receipts, invoices). Transactions only carry references; the
+//! bytes stay in local storage and are fetched from peers on demand by content hash.
+use serde::{Serialize, Deserialize};
+
+use crate::dedup::content_hash;
+
+/// Largest attachment accepted from a peer
+pub const MAX_ATTACHMENT_SIZE: usize = 32 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttachmentRef {
+    /// Hex SHA-256 of the file; also its storage key
+    pub hash: String,
+    pub mime_type: String,
+    pub filename: String,
+    pub size: u64,
+}
+
+impl AttachmentRef {
+    pub fn for_bytes(data: &[u8], mime_type: impl Into<String>, filename: impl Into<String>) -> Self {
+        Self {
+            hash: hash_hex(data),
+            mime_type: mime_type.into(),
+            filename: filename.into(),
+            size: data.len() as u64,
+        }
+    }
+
+    /// Whether `data` is the file this reference points to
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.len() as u64 == self.size && hash_hex(data) == self.hash
+    }
+}
+
+pub(crate) fn hash_hex(data: &[u8]) -> String {
+    content_hash(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
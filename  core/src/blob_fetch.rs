@@ -0,0 +1,278 @@
+//! Direct request-response protocol for attachment files. Each file is fetched on its own stream
+//! of the noise-encrypted connection to the peer holding it, so the bytes never go through
+//! gossip and arrive whole and in order.
+use std::collections::{HashMap, VecDeque};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use libp2p::core::{upgrade::ReadyUpgrade, Endpoint};
+use libp2p::futures::channel::oneshot;
+use libp2p::futures::future::BoxFuture;
+use libp2p::futures::stream::FuturesUnordered;
+use libp2p::futures::{AsyncReadExt, AsyncWriteExt, FutureExt, StreamExt};
+use libp2p::swarm::handler::{ConnectionEvent, DialUpgradeError, FullyNegotiatedInbound, FullyNegotiatedOutbound};
+use libp2p::swarm::{
+    ConnectionDenied, ConnectionHandler, ConnectionHandlerEvent, ConnectionId, FromSwarm, NetworkBehaviour,
+    NotifyHandler, SubstreamProtocol, THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
+};
+use libp2p::{Multiaddr, PeerId, Stream, StreamProtocol};
+
+use crate::attachments::MAX_ATTACHMENT_SIZE;
+
+pub const PROTOCOL: StreamProtocol = StreamProtocol::new("/true-ledger/blob/1");
+
+/// Longest hash accepted in a request
+const MAX_HASH_LEN: u32 = 128;
+/// How long either side waits for the other's frame
+const STREAM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What the protocol reports to the sync client
+#[derive(Debug)]
+pub enum BlobEvent {
+    /// A peer asked for the file with `hash`; answer with `BlobFetch::respond`
+    Request { peer: PeerId, request_id: u64, hash: String },
+    /// A requested file, or None when the peer does not have it
+    Response { peer: PeerId, hash: String, data: Option<Vec<u8>> },
+    /// A request that got no answer
+    Failed { peer: PeerId, hash: String, error: String },
+}
+
+/// Events between the behaviour and the per-connection handlers
+#[derive(Debug)]
+pub enum HandlerIn {
+    Fetch(String),
+}
+
+#[derive(Debug)]
+pub enum HandlerOut {
+    Request { hash: String, responder: oneshot::Sender<Option<Vec<u8>>> },
+    Response { hash: String, data: Option<Vec<u8>> },
+    Failed { hash: String, error: String },
+}
+
+/// Requests files from peers and hands incoming requests to the client
+#[derive(Default)]
+pub struct BlobFetch {
+    events: VecDeque<ToSwarm<BlobEvent, HandlerIn>>,
+    responders: HashMap<u64, oneshot::Sender<Option<Vec<u8>>>>,
+    next_request: u64,
+}
+
+impl BlobFetch {
+    /// Ask a connected peer for one file
+    pub fn fetch(&mut self, peer: PeerId, hash: String) {
+        self.events.push_back(ToSwarm::NotifyHandler {
+            peer_id: peer,
+            handler: NotifyHandler::Any,
+            event: HandlerIn::Fetch(hash),
+        });
+    }
+
+    /// Answer a `BlobEvent::Request`; None tells the peer we don't have the file. Returns false
+    /// when the request is unknown or its stream already closed.
+    pub fn respond(&mut self, request_id: u64, data: Option<Vec<u8>>) -> bool {
+        self.responders.remove(&request_id).is_some_and(|responder| responder.send(data).is_ok())
+    }
+}
+
+impl NetworkBehaviour for BlobFetch {
+    type ConnectionHandler = BlobHandler;
+    type ToSwarm = BlobEvent;
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        _peer: PeerId,
+        _local_addr: &Multiaddr,
+        _remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(BlobHandler::default())
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        _peer: PeerId,
+        _addr: &Multiaddr,
+        _role_override: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(BlobHandler::default())
+    }
+
+    fn on_swarm_event(&mut self, _event: FromSwarm) {}
+
+    fn on_connection_handler_event(&mut self, peer: PeerId, _connection_id: ConnectionId, event: THandlerOutEvent<Self>) {
+        let event = match event {
+            HandlerOut::Request { hash, responder } => {
+                let request_id = self.next_request;
+                self.next_request += 1;
+                self.responders.insert(request_id, responder);
+                BlobEvent::Request { peer, request_id, hash }
+            }
+            HandlerOut::Response { hash, data } => BlobEvent::Response { peer, hash, data },
+            HandlerOut::Failed { hash, error } => BlobEvent::Failed { peer, hash, error },
+        };
+        self.events.push_back(ToSwarm::GenerateEvent(event));
+    }
+
+    fn poll(&mut self, _cx: &mut Context<'_>) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        self.responders.retain(|_, responder| !responder.is_canceled());
+        match self.events.pop_front() {
+            Some(event) => Poll::Ready(event),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// One connection's blob streams
+#[derive(Default)]
+pub struct BlobHandler {
+    /// Fetches waiting for an outbound stream
+    queued: VecDeque<String>,
+    /// Fetches whose outbound stream is being opened
+    opening: usize,
+    /// Open streams; inbound ones yield the request first and then finish answering it
+    streams: FuturesUnordered<BoxFuture<'static, Option<HandlerOut>>>,
+}
+
+impl ConnectionHandler for BlobHandler {
+    type FromBehaviour = HandlerIn;
+    type ToBehaviour = HandlerOut;
+    type InboundProtocol = ReadyUpgrade<StreamProtocol>;
+    type OutboundProtocol = ReadyUpgrade<StreamProtocol>;
+    type InboundOpenInfo = ();
+    type OutboundOpenInfo = String;
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
+        SubstreamProtocol::new(ReadyUpgrade::new(PROTOCOL), ())
+    }
+
+    fn connection_keep_alive(&self) -> bool {
+        !self.queued.is_empty() || self.opening > 0 || !self.streams.is_empty()
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ConnectionHandlerEvent<Self::OutboundProtocol, Self::OutboundOpenInfo, Self::ToBehaviour>> {
+        if let Some(hash) = self.queued.pop_front() {
+            self.opening += 1;
+            return Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest {
+                protocol: SubstreamProtocol::new(ReadyUpgrade::new(PROTOCOL), hash),
+            });
+        }
+        while let Poll::Ready(Some(out)) = self.streams.poll_next_unpin(cx) {
+            if let Some(out) = out {
+                return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(out));
+            }
+        }
+        Poll::Pending
+    }
+
+    fn on_behaviour_event(&mut self, event: HandlerIn) {
+        let HandlerIn::Fetch(hash) = event;
+        self.queued.push_back(hash);
+    }
+
+    fn on_connection_event(
+        &mut self,
+        event: ConnectionEvent<Self::InboundProtocol, Self::OutboundProtocol, Self::InboundOpenInfo, Self::OutboundOpenInfo>,
+    ) {
+        match event {
+            ConnectionEvent::FullyNegotiatedInbound(FullyNegotiatedInbound { protocol: stream, .. }) => {
+                let (responder, answer) = oneshot::channel();
+                let (request, serve) = serve(stream, answer);
+                self.streams.push(async move {
+                    let hash = request.await?;
+                    Some(HandlerOut::Request { hash, responder })
+                }.boxed());
+                self.streams.push(serve.map(|_| None).boxed());
+            }
+            ConnectionEvent::FullyNegotiatedOutbound(FullyNegotiatedOutbound { protocol: stream, info: hash }) => {
+                self.opening = self.opening.saturating_sub(1);
+                self.streams.push(async move {
+                    let out = match with_timeout(fetch(stream, &hash)).await {
+                        Ok(data) => HandlerOut::Response { hash, data },
+                        Err(error) => HandlerOut::Failed { hash, error: error.to_string() },
+                    };
+                    Some(out)
+                }.boxed());
+            }
+            ConnectionEvent::DialUpgradeError(DialUpgradeError { info: hash, error }) => {
+                self.opening = self.opening.saturating_sub(1);
+                self.streams.push(async move { Some(HandlerOut::Failed { hash, error: error.to_string() }) }.boxed());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Read a request off an inbound stream, then write whatever arrives on `answer`. The first
+/// future yields the requested hash; the second runs the exchange and must be polled too.
+fn serve(
+    mut stream: Stream,
+    answer: oneshot::Receiver<Option<Vec<u8>>>,
+) -> (BoxFuture<'static, Option<String>>, BoxFuture<'static, ()>) {
+    let (requested, request) = oneshot::channel();
+    let exchange = async move {
+        let result = with_timeout(async {
+            let hash = String::from_utf8(read_frame(&mut stream, MAX_HASH_LEN).await?)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            let _ = requested.send(hash);
+            Ok(())
+        }).await;
+        if result.is_err() {
+            return;
+        }
+        // Dropping the responder unanswered closes the stream without a reply
+        let Ok(data) = answer.await else { return };
+        let _ = with_timeout(write_response(&mut stream, data.as_deref())).await;
+    };
+    (request.map(Result::ok).boxed(), exchange.boxed())
+}
+
+/// Send one request and read the file back
+async fn fetch(mut stream: Stream, hash: &str) -> std::io::Result<Option<Vec<u8>>> {
+    write_frame(&mut stream, hash.as_bytes()).await?;
+    stream.close().await?;
+    let mut found = [0u8; 1];
+    stream.read_exact(&mut found).await?;
+    if found[0] == 0 {
+        return Ok(None);
+    }
+    read_frame(&mut stream, MAX_ATTACHMENT_SIZE as u32).await.map(Some)
+}
+
+/// A one-byte found flag, then the file as a frame
+async fn write_response(stream: &mut Stream, data: Option<&[u8]>) -> std::io::Result<()> {
+    stream.write_all(&[u8::from(data.is_some())]).await?;
+    if let Some(data) = data {
+        write_frame(stream, data).await?;
+    }
+    stream.close().await
+}
+
+/// Length-prefixed (u32, big endian) frame
+async fn write_frame(stream: &mut Stream, data: &[u8]) -> std::io::Result<()> {
+    let len = u32::try_from(data.len()).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    stream.write_all(&len.to_be_bytes()).await?;
+    stream.write_all(data).await?;
+    stream.flush().await
+}
+
+async fn read_frame(stream: &mut Stream, max: u32) -> std::io::Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).await?;
+    let len = u32::from_be_bytes(len);
+    if len > max {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Frame too large: {} bytes", len)));
+    }
+    let mut data = vec![0; len as usize];
+    stream.read_exact(&mut data).await?;
+    Ok(data)
+}
+
+async fn with_timeout<T>(future: impl std::future::Future<Output = std::io::Result<T>>) -> std::io::Result<T> {
+    tokio::time::timeout(STREAM_TIMEOUT, future)
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "Timed out waiting for the peer"))?
+}
@@ -0,0 +1,99 @@
+//! Canonical encoding of monetary values so equal amounts encode identically on every device
+//!
+//! `Decimal::to_string()` keeps the scale ("10" vs "10.00"), which makes equal amounts look like
+//! conflicting edits in the CRDT. Values are normalized and stored as an integer mantissa plus a
+//! decimal exponent instead.
+use std::str::FromStr;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// `mantissa * 10^-exponent`, with trailing zeros stripped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CanonicalDecimal {
+    pub mantissa: i64,
+    pub exponent: u32,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OutOfRange {
+    #[error("Amount out of range for canonical encoding: {0}")]
+    Amount(Decimal),
+    /// Exponent a peer sent that no `Decimal` can have
+    #[error("Decimal exponent out of range: {0}")]
+    Exponent(i64),
+}
+
+impl CanonicalDecimal {
+    /// Largest exponent a `Decimal` supports
+    pub const MAX_EXPONENT: u32 = 28;
+
+    pub fn encode(value: Decimal) -> Result<Self, OutOfRange> {
+        let normalized = value.normalize();
+        let mantissa = i64::try_from(normalized.mantissa()).map_err(|_| OutOfRange::Amount(value))?;
+        Ok(Self { mantissa, exponent: normalized.scale() })
+    }
+
+    pub fn decode(&self) -> Result<Decimal, OutOfRange> {
+        if self.exponent > Self::MAX_EXPONENT {
+            return Err(OutOfRange::Exponent(self.exponent.into()));
+        }
+        Decimal::try_from_i128_with_scale(self.mantissa.into(), self.exponent)
+            .map_err(|_| OutOfRange::Exponent(self.exponent.into()))
+    }
+}
+
+/// Serde adapter: writes the canonical form, reads canonical, string or number forms
+pub mod serde_decimal {
+    use super::*;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum AnyDecimal {
+        Canonical(CanonicalDecimal),
+        Text(String),
+        Float(f64),
+    }
+
+    pub fn serialize<S: Serializer>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error> {
+        CanonicalDecimal::encode(*value)
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+        match AnyDecimal::deserialize(deserializer)? {
+            AnyDecimal::Canonical(c) => c.decode().map_err(serde::de::Error::custom),
+            AnyDecimal::Text(s) => Decimal::from_str(&s).map_err(serde::de::Error::custom),
+            AnyDecimal::Float(f) => Decimal::try_from(f).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_amounts_encode_identically() {
+        let a = CanonicalDecimal::encode(Decimal::new(1000, 2)).unwrap();
+        let b = CanonicalDecimal::encode(Decimal::new(10, 0)).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.decode().unwrap(), Decimal::new(10, 0));
+    }
+
+    #[test]
+    fn decode_rejects_exponents_beyond_decimal_precision() {
+        let decoded = CanonicalDecimal { mantissa: 1, exponent: 29 }.decode();
+        assert!(matches!(decoded, Err(OutOfRange::Exponent(29))));
+        let max = CanonicalDecimal { mantissa: 1, exponent: CanonicalDecimal::MAX_EXPONENT }.decode();
+        assert!(max.is_ok());
+    }
+
+    #[test]
+    fn serde_rejects_a_canonical_value_out_of_range() {
+        #[derive(Deserialize)]
+        struct Amount(#[serde(with = "serde_decimal")] #[allow(dead_code)] Decimal);
+        let json = r#"{"mantissa": 5, "exponent": 4000000000}"#;
+        assert!(serde_json::from_str::<Amount>(json).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Changesets with nested savepoints spanning ledger state, storage and the CRDT document
+use crate::ledger::Ledger;
+use crate::storage::LocalStorage;
+use crate::sync::SyncDoc;
+
+const ROOT_SAVEPOINT: &str = "changeset";
+
+struct Savepoint {
+    name: String,
+    ledger: Ledger,
+    doc: SyncDoc,
+}
+
+/// Group of mutations applied progressively and rolled back to any savepoint.
+/// Dropping an unfinished changeset rolls everything back.
+pub struct Changeset<'a> {
+    ledger: &'a mut Ledger,
+    doc: &'a mut SyncDoc,
+    storage: &'a LocalStorage,
+    savepoints: Vec<Savepoint>,
+    finished: bool,
+}
+
+impl<'a> Changeset<'a> {
+    pub fn begin(ledger: &'a mut Ledger, doc: &'a mut SyncDoc, storage: &'a LocalStorage) -> Self {
+        storage.savepoint(ROOT_SAVEPOINT);
+        let root = Savepoint {
+            name: ROOT_SAVEPOINT.to_string(),
+            ledger: ledger.clone(),
+            doc: doc.clone(),
+        };
+        Self {
+            ledger,
+            doc,
+            storage,
+            savepoints: vec![root],
+            finished: false,
+        }
+    }
+
+    pub fn ledger(&mut self) -> &mut Ledger {
+        self.ledger
+    }
+
+    pub fn doc(&mut self) -> &mut SyncDoc {
+        self.doc
+    }
+
+    pub fn storage(&self) -> &LocalStorage {
+        self.storage
+    }
+
+    /// Mark the current state so it can be returned to later
+    pub fn savepoint(&mut self, name: &str) -> Result<(), &'static str> {
+        if self.position(name).is_some() {
+            return Err("Savepoint already exists");
+        }
+        self.storage.savepoint(&self.sql_name(self.savepoints.len()));
+        self.savepoints.push(Savepoint {
+            name: name.to_string(),
+            ledger: self.ledger.clone(),
+            doc: self.doc.clone(),
+        });
+        Ok(())
+    }
+
+    /// Undo everything after the savepoint; the savepoint itself stays usable
+    pub fn rollback_to(&mut self, name: &str) -> Result<(), &'static str> {
+        let index = self.position(name).ok_or("Savepoint not found")?;
+        self.restore(index);
+        self.savepoints.truncate(index + 1);
+        Ok(())
+    }
+
+    /// Forget a savepoint (and any nested ones) keeping the changes
+    pub fn release(&mut self, name: &str) -> Result<(), &'static str> {
+        let index = self.position(name).ok_or("Savepoint not found")?;
+        if index == 0 {
+            return Err("Use commit() to release the changeset");
+        }
+        self.storage.release_savepoint(&self.sql_name(index));
+        self.savepoints.truncate(index);
+        Ok(())
+    }
+
+    /// Names of active savepoints, outermost first
+    pub fn savepoints(&self) -> Vec<&str> {
+        self.savepoints.iter().skip(1).map(|s| s.name.as_str()).collect()
+    }
+
+    /// Persist storage changes and commit the CRDT operations as one change
+    pub fn commit(mut self, message: &str) {
+        self.storage.release_savepoint(ROOT_SAVEPOINT);
+        self.doc.doc.commit_with(automerge::transaction::CommitOptions::default().with_message(message));
+        self.finished = true;
+    }
+
+    /// Undo the whole changeset
+    pub fn rollback(mut self) {
+        self.rollback_all();
+    }
+
+    fn rollback_all(&mut self) {
+        self.restore(0);
+        self.storage.release_savepoint(ROOT_SAVEPOINT);
+        self.finished = true;
+    }
+
+    fn restore(&mut self, index: usize) {
+        let savepoint = &self.savepoints[index];
+        *self.ledger = savepoint.ledger.clone();
+        *self.doc = savepoint.doc.clone();
+        self.storage.rollback_to_savepoint(&self.sql_name(index));
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.savepoints.iter().position(|s| s.name == name)
+    }
+
+    /// SQL savepoint identifiers are generated to avoid quoting user-supplied names
+    fn sql_name(&self, index: usize) -> String {
+        if index == 0 {
+            ROOT_SAVEPOINT.to_string()
+        } else {
+            format!("{}_{}", ROOT_SAVEPOINT, index)
+        }
+    }
+}
+
+impl Drop for Changeset<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.rollback_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+    use crate::ledger::{AccountType, Posting, Transaction};
+    use crate::storage::StorageConfig;
+
+    struct Book {
+        ledger: Ledger,
+        doc: SyncDoc,
+        storage: LocalStorage,
+        cash: Uuid,
+        sales: Uuid,
+    }
+
+    fn book() -> Book {
+        let mut ledger = Ledger::new();
+        let cash = ledger.new_account("Cash", AccountType::Asset);
+        let sales = ledger.new_account("Sales", AccountType::Revenue);
+        let (cash_id, sales_id) = (cash.id, sales.id);
+        ledger.add_account(cash).unwrap();
+        ledger.add_account(sales).unwrap();
+        Book {
+            ledger,
+            doc: SyncDoc::new().unwrap(),
+            storage: LocalStorage::with_config(&StorageConfig { path: ":memory:".to_string(), ..Default::default() }),
+            cash: cash_id,
+            sales: sales_id,
+        }
+    }
+
+    /// Record a sale in all three stores at once
+    fn sell(changes: &mut Changeset, cash: Uuid, sales: Uuid, memo: &str) {
+        let tx = Transaction::new(
+            NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(),
+            memo,
+            vec![Posting::new(cash, Decimal::from(10)), Posting::new(sales, Decimal::from(-10))],
+        );
+        changes.ledger().record_transaction(tx.clone()).unwrap();
+        let mut synced = changes.doc().to_ledger().unwrap();
+        synced.transactions.push(tx);
+        changes.doc().update_from_ledger(&synced).unwrap();
+        changes.storage().set_setting("last_memo", memo);
+    }
+
+    fn state(ledger: &Ledger, doc: &SyncDoc, storage: &LocalStorage) -> (usize, usize, Option<String>) {
+        (ledger.transactions().count(), doc.to_ledger().unwrap().transactions.len(), storage.get_setting("last_memo"))
+    }
+
+    #[test]
+    fn rollback_to_a_savepoint_restores_every_store() {
+        let Book { mut ledger, mut doc, storage, cash, sales } = book();
+        let mut changes = Changeset::begin(&mut ledger, &mut doc, &storage);
+        sell(&mut changes, cash, sales, "first");
+        changes.savepoint("second").unwrap();
+        sell(&mut changes, cash, sales, "second");
+        changes.savepoint("third").unwrap();
+        sell(&mut changes, cash, sales, "third");
+        assert_eq!(changes.savepoints(), vec!["second", "third"]);
+
+        changes.rollback_to("second").unwrap();
+        assert_eq!(changes.savepoints(), vec!["second"]);
+        assert!(changes.savepoint("second").is_err());
+        sell(&mut changes, cash, sales, "retried");
+        changes.commit("Two sales");
+
+        assert_eq!(state(&ledger, &doc, &storage), (2, 2, Some("retried".to_string())));
+    }
+
+    #[test]
+    fn dropping_an_unfinished_changeset_undoes_it() {
+        let Book { mut ledger, mut doc, storage, cash, sales } = book();
+        {
+            let mut changes = Changeset::begin(&mut ledger, &mut doc, &storage);
+            sell(&mut changes, cash, sales, "first");
+            changes.savepoint("nested").unwrap();
+            sell(&mut changes, cash, sales, "second");
+            changes.release("nested").unwrap();
+            assert!(changes.release("nested").is_err());
+        }
+        assert_eq!(state(&ledger, &doc, &storage), (0, 0, None));
+    }
+}
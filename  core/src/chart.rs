@@ -0,0 +1,205 @@
+//! Built-in starting charts of accounts for new books
+use serde::{Serialize, Deserialize};
+
+use crate::currency::Commodity;
+use crate::ledger::{AccountType, CashFlowActivity};
+use crate::locale::Locale;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChartKind {
+    Personal,
+    Freelancer,
+    SmallBusiness,
+}
+
+/// One account of a template; top-level entries are the five account type groups
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateAccount {
+    pub name: String,
+    pub r#type: AccountType,
+    /// Receives net income at period close
+    #[serde(default)]
+    pub retained_earnings: bool,
+    /// Offsets opening balances entered when the ledger starts mid-life
+    #[serde(default)]
+    pub opening_balances: bool,
+    /// Counted by cash-basis reports
+    #[serde(default)]
+    pub cash_equivalent: bool,
+    /// Cash flow section when it differs from the type's default
+    #[serde(default)]
+    pub cash_flow: Option<CashFlowActivity>,
+    #[serde(default)]
+    pub children: Vec<TemplateAccount>,
+}
+
+/// Starting chart of accounts, localized names and base currency
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChartTemplate {
+    pub kind: ChartKind,
+    pub base_currency: Commodity,
+    pub accounts: Vec<TemplateAccount>,
+}
+
+type Group = (&'static str, AccountType, &'static [&'static str]);
+
+/// Template keys of bank, cash and card accounts
+const CASH_EQUIVALENTS: &[&str] = &["checking", "savings", "cash", "petty_cash", "business_checking", "credit_card"];
+
+/// Template keys reported outside operating activities on the cash flow statement
+const INVESTING: &[&str] = &["fixed_assets"];
+const FINANCING: &[&str] = &["loans"];
+
+const PERSONAL: &[Group] = &[
+    ("assets", AccountType::Asset, &["checking", "savings", "cash"]),
+    ("liabilities", AccountType::Liability, &["credit_card", "loans"]),
+    ("equity", AccountType::Equity, &["opening_balances", "retained_earnings"]),
+    ("income", AccountType::Revenue, &["salary", "interest", "other_income"]),
+    ("expenses", AccountType::Expense, &[
+        "housing", "groceries", "dining", "transport", "utilities", "health", "entertainment", "other_expenses",
+    ]),
+];
+
+const FREELANCER: &[Group] = &[
+    ("assets", AccountType::Asset, &["business_checking", "receivables", "tax_reserve"]),
+    ("liabilities", AccountType::Liability, &["credit_card", "sales_tax_payable", "income_tax_payable"]),
+    ("equity", AccountType::Equity, &["owner_capital", "owner_drawings", "retained_earnings"]),
+    ("income", AccountType::Revenue, &["client_fees", "other_income"]),
+    ("expenses", AccountType::Expense, &[
+        "software", "equipment", "travel", "office", "professional_fees", "bank_fees", "marketing",
+    ]),
+];
+
+const SMALL_BUSINESS: &[Group] = &[
+    ("assets", AccountType::Asset, &["checking", "petty_cash", "receivables", "inventory", "fixed_assets"]),
+    ("liabilities", AccountType::Liability, &[
+        "payables", "credit_card", "sales_tax_payable", "payroll_liabilities", "loans",
+    ]),
+    ("equity", AccountType::Equity, &["owner_capital", "retained_earnings"]),
+    ("income", AccountType::Revenue, &["sales", "services", "other_income"]),
+    ("expenses", AccountType::Expense, &[
+        "cost_of_goods_sold", "wages", "rent", "utilities", "insurance", "marketing", "office",
+        "professional_fees", "bank_fees",
+    ]),
+];
+
+/// key, English, German, French
+const NAMES: &[(&str, &str, &str, &str)] = &[
+    ("assets", "Assets", "Aktiva", "Actifs"),
+    ("liabilities", "Liabilities", "Verbindlichkeiten", "Passifs"),
+    ("equity", "Equity", "Eigenkapital", "Capitaux propres"),
+    ("income", "Income", "Erträge", "Produits"),
+    ("expenses", "Expenses", "Aufwendungen", "Charges"),
+    ("checking", "Checking", "Girokonto", "Compte courant"),
+    ("business_checking", "Business Checking", "Geschäftskonto", "Compte professionnel"),
+    ("savings", "Savings", "Sparkonto", "Épargne"),
+    ("cash", "Cash", "Bargeld", "Espèces"),
+    ("petty_cash", "Petty Cash", "Kasse", "Caisse"),
+    ("receivables", "Accounts Receivable", "Forderungen", "Créances clients"),
+    ("tax_reserve", "Tax Reserve", "Steuerrücklage", "Réserve fiscale"),
+    ("inventory", "Inventory", "Warenbestand", "Stocks"),
+    ("fixed_assets", "Equipment", "Betriebs- und Geschäftsausstattung", "Immobilisations"),
+    ("credit_card", "Credit Card", "Kreditkarte", "Carte de crédit"),
+    ("loans", "Loans", "Darlehen", "Emprunts"),
+    ("payables", "Accounts Payable", "Verbindlichkeiten aus Lieferungen", "Dettes fournisseurs"),
+    ("sales_tax_payable", "Sales Tax Payable", "Umsatzsteuer", "TVA collectée"),
+    ("income_tax_payable", "Income Tax Payable", "Einkommensteuer", "Impôt sur le revenu"),
+    ("payroll_liabilities", "Payroll Liabilities", "Lohnverbindlichkeiten", "Dettes sociales"),
+    ("opening_balances", "Opening Balances", "Eröffnungsbilanz", "Soldes d'ouverture"),
+    ("retained_earnings", "Retained Earnings", "Gewinnvortrag", "Report à nouveau"),
+    ("owner_capital", "Owner's Capital", "Kapital", "Capital"),
+    ("owner_drawings", "Owner's Drawings", "Privatentnahmen", "Prélèvements de l'exploitant"),
+    ("salary", "Salary", "Gehalt", "Salaire"),
+    ("interest", "Interest", "Zinsen", "Intérêts"),
+    ("other_income", "Other Income", "Sonstige Erträge", "Autres produits"),
+    ("client_fees", "Client Fees", "Honorare", "Honoraires"),
+    ("sales", "Sales", "Umsatzerlöse", "Ventes"),
+    ("services", "Services", "Dienstleistungen", "Prestations de services"),
+    ("housing", "Housing", "Wohnen", "Logement"),
+    ("groceries", "Groceries", "Lebensmittel", "Alimentation"),
+    ("dining", "Dining", "Restaurants", "Restaurants"),
+    ("transport", "Transport", "Mobilität", "Transport"),
+    ("utilities", "Utilities", "Nebenkosten", "Énergie et eau"),
+    ("health", "Health", "Gesundheit", "Santé"),
+    ("entertainment", "Entertainment", "Freizeit", "Loisirs"),
+    ("other_expenses", "Other Expenses", "Sonstige Aufwendungen", "Autres charges"),
+    ("software", "Software & Subscriptions", "Software und Abonnements", "Logiciels et abonnements"),
+    ("equipment", "Equipment", "Arbeitsmittel", "Matériel"),
+    ("travel", "Travel", "Reisekosten", "Déplacements"),
+    ("office", "Office Supplies", "Bürobedarf", "Fournitures de bureau"),
+    ("professional_fees", "Professional Fees", "Beratungskosten", "Honoraires de conseil"),
+    ("bank_fees", "Bank Fees", "Bankgebühren", "Frais bancaires"),
+    ("marketing", "Marketing", "Werbung", "Marketing"),
+    ("cost_of_goods_sold", "Cost of Goods Sold", "Wareneinsatz", "Achats de marchandises"),
+    ("wages", "Wages", "Löhne und Gehälter", "Salaires et traitements"),
+    ("rent", "Rent", "Miete", "Loyer"),
+    ("insurance", "Insurance", "Versicherungen", "Assurances"),
+];
+
+fn name(key: &str, language: &str) -> String {
+    let (_, en, de, fr) = NAMES.iter().find(|(k, ..)| *k == key).copied().unwrap_or((key, key, key, key));
+    match language {
+        "de" => de,
+        "fr" => fr,
+        _ => en,
+    }
+    .to_string()
+}
+
+impl ChartTemplate {
+    /// Template with names in the locale's language and its usual currency
+    pub fn new(kind: ChartKind, locale: &Locale) -> Self {
+        let tag = locale.tag.to_lowercase();
+        let language = tag.split('-').next().unwrap_or("en");
+        let base_currency = match tag.as_str() {
+            "en-gb" => Commodity::new("GBP"),
+            _ if language == "de" || language == "fr" => Commodity::new("EUR"),
+            _ => Commodity::new("USD"),
+        };
+        let groups = match kind {
+            ChartKind::Personal => PERSONAL,
+            ChartKind::Freelancer => FREELANCER,
+            ChartKind::SmallBusiness => SMALL_BUSINESS,
+        };
+        let accounts = groups.iter()
+            .map(|(group, r#type, children)| TemplateAccount {
+                name: name(group, language),
+                r#type: *r#type,
+                retained_earnings: false,
+                opening_balances: false,
+                cash_equivalent: false,
+                cash_flow: None,
+                children: children.iter()
+                    .map(|key| TemplateAccount {
+                        name: name(key, language),
+                        r#type: *r#type,
+                        retained_earnings: *key == "retained_earnings",
+                        opening_balances: *key == "opening_balances",
+                        cash_equivalent: CASH_EQUIVALENTS.contains(key),
+                        cash_flow: if INVESTING.contains(key) {
+                            Some(CashFlowActivity::Investing)
+                        } else if FINANCING.contains(key) {
+                            Some(CashFlowActivity::Financing)
+                        } else {
+                            None
+                        },
+                        children: Vec::new(),
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self { kind, base_currency, accounts }
+    }
+
+    pub fn personal(locale: &Locale) -> Self {
+        Self::new(ChartKind::Personal, locale)
+    }
+
+    pub fn freelancer(locale: &Locale) -> Self {
+        Self::new(ChartKind::Freelancer, locale)
+    }
+
+    pub fn small_business(locale: &Locale) -> Self {
+        Self::new(ChartKind::SmallBusiness, locale)
+    }
+}
@@ -0,0 +1,76 @@
+//! Content-defined chunking (gear rolling hash) for deduplicating snapshot blobs
+use serde::{Serialize, Deserialize};
+
+/// Target chunk sizes; boundaries depend only on nearby content, so an edit only changes
+/// the chunks around it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkerConfig {
+    pub min_size: usize,
+    /// Must be a power of two
+    pub avg_size: usize,
+    /// Treated as 1 when 0
+    pub max_size: usize,
+}
+
+impl Default for ChunkerConfig {
+    fn default() -> Self {
+        Self { min_size: 16 * 1024, avg_size: 64 * 1024, max_size: 256 * 1024 }
+    }
+}
+
+const fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = splitmix64(i as u64);
+        i += 1;
+    }
+    table
+}
+
+/// Fixed table so every device cuts identical chunks
+static GEAR: [u64; 256] = gear_table();
+
+/// Split `data` into content-defined chunks
+pub fn chunks<'a>(data: &'a [u8], config: &ChunkerConfig) -> Vec<&'a [u8]> {
+    let mask = (config.avg_size.max(1).next_power_of_two() - 1) as u64;
+    let max_size = config.max_size.max(1);
+    let mut out = Vec::new();
+    let mut start = 0;
+    while start < data.len() {
+        let end = (start + max_size).min(data.len());
+        let mut cut = end;
+        let mut hash = 0u64;
+        for (i, byte) in data[start..end].iter().enumerate() {
+            hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+            if i + 1 >= config.min_size && hash & mask == 0 {
+                cut = start + i + 1;
+                break;
+            }
+        }
+        out.push(&data[start..cut]);
+        start = cut;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_max_size_still_makes_progress() {
+        let data = [7u8; 5];
+        let config = ChunkerConfig { min_size: 0, avg_size: 0, max_size: 0 };
+        let cut = chunks(&data, &config);
+        assert_eq!(cut.len(), 5);
+        assert_eq!(cut.concat(), data);
+    }
+}
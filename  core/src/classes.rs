@@ -0,0 +1,23 @@
+//! Class/department tags, a reporting dimension independent of accounts and projects
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportingClass {
+    pub id: Uuid,
+    pub name: String,
+    /// Parent class for nested departments (e.g. "Sales:West")
+    #[serde(default)]
+    pub parent_id: Option<Uuid>,
+}
+
+impl ReportingClass {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { id: Uuid::new_v4(), name: name.into(), parent_id: None }
+    }
+
+    pub fn under(mut self, parent_id: Uuid) -> Self {
+        self.parent_id = Some(parent_id);
+        self
+    }
+}
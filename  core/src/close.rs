@@ -0,0 +1,142 @@
+//! Month-end close checklist tracked per period
+use chrono::NaiveDate;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+/// Step that has to be done before a period can be closed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChecklistTask {
+    ReconcileAccount(Uuid),
+    PostDepreciation,
+    ReviewUncategorized,
+    Custom(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChecklistItem {
+    pub task: ChecklistTask,
+    pub completed_on: Option<NaiveDate>,
+    pub note: Option<String>,
+}
+
+impl ChecklistItem {
+    pub fn new(task: ChecklistTask) -> Self {
+        Self { task, completed_on: None, note: None }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.completed_on.is_some()
+    }
+}
+
+/// Close progress summary for UIs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CloseStatus {
+    NotStarted,
+    InProgress { done: usize, total: usize },
+    Complete,
+}
+
+/// Close checklist for one accounting period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloseChecklist {
+    pub id: Uuid,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    pub items: Vec<ChecklistItem>,
+}
+
+impl CloseChecklist {
+    /// Create empty checklist for a period
+    pub fn new(period_start: NaiveDate, period_end: NaiveDate) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            period_start,
+            period_end,
+            items: Vec::new(),
+        }
+    }
+
+    /// Standard month-end checklist: reconcile listed accounts, post depreciation, review uncategorized
+    pub fn month_end(period_start: NaiveDate, period_end: NaiveDate, reconcile: &[Uuid]) -> Self {
+        let mut checklist = Self::new(period_start, period_end);
+        for id in reconcile {
+            checklist.add(ChecklistTask::ReconcileAccount(*id));
+        }
+        checklist.add(ChecklistTask::PostDepreciation);
+        checklist.add(ChecklistTask::ReviewUncategorized);
+        checklist
+    }
+
+    pub fn add(&mut self, task: ChecklistTask) {
+        self.items.push(ChecklistItem::new(task));
+    }
+
+    /// Mark task as done on the given date
+    pub fn complete(&mut self, task: &ChecklistTask, on: NaiveDate) -> Result<(), &'static str> {
+        let item = self.items.iter_mut()
+            .find(|i| &i.task == task)
+            .ok_or("Checklist task not found")?;
+        item.completed_on = Some(on);
+        Ok(())
+    }
+
+    /// Undo a completed task
+    pub fn reopen(&mut self, task: &ChecklistTask) -> Result<(), &'static str> {
+        let item = self.items.iter_mut()
+            .find(|i| &i.task == task)
+            .ok_or("Checklist task not found")?;
+        item.completed_on = None;
+        Ok(())
+    }
+
+    /// Tasks still open
+    pub fn remaining(&self) -> impl Iterator<Item = &ChecklistTask> {
+        self.items.iter().filter(|i| !i.is_done()).map(|i| &i.task)
+    }
+
+    /// An empty checklist has nothing done yet, so it is not started rather than complete
+    pub fn status(&self) -> CloseStatus {
+        let done = self.items.iter().filter(|i| i.is_done()).count();
+        let total = self.items.len();
+        match done {
+            0 => CloseStatus::NotStarted,
+            d if d == total => CloseStatus::Complete,
+            _ => CloseStatus::InProgress { done, total },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, d).unwrap()
+    }
+
+    #[test]
+    fn empty_checklist_is_not_started() {
+        let checklist = CloseChecklist::new(date(1), date(31));
+        assert_eq!(checklist.status(), CloseStatus::NotStarted);
+        assert_eq!(checklist.remaining().count(), 0);
+    }
+
+    #[test]
+    fn status_follows_completed_tasks() {
+        let bank = Uuid::new_v4();
+        let mut checklist = CloseChecklist::month_end(date(1), date(31), &[bank]);
+        assert_eq!(checklist.status(), CloseStatus::NotStarted);
+
+        checklist.complete(&ChecklistTask::ReconcileAccount(bank), date(31)).unwrap();
+        assert_eq!(checklist.status(), CloseStatus::InProgress { done: 1, total: 3 });
+        checklist.complete(&ChecklistTask::PostDepreciation, date(31)).unwrap();
+        checklist.complete(&ChecklistTask::ReviewUncategorized, date(31)).unwrap();
+        assert_eq!(checklist.status(), CloseStatus::Complete);
+
+        checklist.reopen(&ChecklistTask::PostDepreciation).unwrap();
+        assert_eq!(checklist.remaining().collect::<Vec<_>>(), vec![&ChecklistTask::PostDepreciation]);
+        assert_eq!(checklist.status(), CloseStatus::InProgress { done: 2, total: 3 });
+        assert!(checklist.complete(&ChecklistTask::Custom("Payroll".to_string()), date(31)).is_err());
+    }
+}
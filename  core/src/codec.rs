@@ -0,0 +1,128 @@
+//! Serialization formats for blob-encoded entities (stored transactions, sync envelopes)
+use serde::{de::DeserializeOwned, Serialize, Deserialize};
+
+/// Leading byte of tagged (non-JSON) payloads; JSON is written untagged so existing rows stay readable
+const TAG_CBOR: u8 = 0x01;
+const TAG_MSGPACK: u8 = 0x02;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CodecError {
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("CBOR error: {0}")]
+    Cbor(String),
+    #[error("MessagePack error: {0}")]
+    MessagePack(String),
+    #[error("{0} support is not compiled in")]
+    Unsupported(&'static str),
+}
+
+/// Format used when writing; reading detects the format from the payload
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Encoding {
+    #[default]
+    Json,
+    /// Requires the `cbor` feature
+    Cbor,
+    /// Requires the `msgpack` feature
+    MessagePack,
+}
+
+impl Encoding {
+    /// Name used in capability negotiation
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Json => "json",
+            Encoding::Cbor => "cbor",
+            Encoding::MessagePack => "msgpack",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::available().into_iter().find(|e| e.name() == name)
+    }
+
+    /// Formats this build can read and write, most compact first
+    pub fn available() -> Vec<Self> {
+        let mut formats = Vec::new();
+        if cfg!(feature = "msgpack") {
+            formats.push(Encoding::MessagePack);
+        }
+        if cfg!(feature = "cbor") {
+            formats.push(Encoding::Cbor);
+        }
+        formats.push(Encoding::Json);
+        formats
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        match self {
+            Encoding::Json => Ok(serde_json::to_vec(value)?),
+            Encoding::Cbor => encode_cbor(value),
+            Encoding::MessagePack => encode_msgpack(value),
+        }
+    }
+
+    /// Format a payload was written in
+    pub fn detect(data: &[u8]) -> Self {
+        match data.first() {
+            Some(&TAG_CBOR) => Encoding::Cbor,
+            Some(&TAG_MSGPACK) => Encoding::MessagePack,
+            _ => Encoding::Json,
+        }
+    }
+}
+
+/// Decode a payload written with any `Encoding`
+pub fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, CodecError> {
+    match Encoding::detect(data) {
+        Encoding::Json => Ok(serde_json::from_slice(data)?),
+        Encoding::Cbor => decode_cbor(&data[1..]),
+        Encoding::MessagePack => decode_msgpack(&data[1..]),
+    }
+}
+
+#[cfg(feature = "cbor")]
+fn encode_cbor<T: Serialize>(value: &T) -> Result<Vec<u8>, CodecError> {
+    let mut out = vec![TAG_CBOR];
+    ciborium::into_writer(value, &mut out).map_err(|e| CodecError::Cbor(e.to_string()))?;
+    Ok(out)
+}
+
+#[cfg(not(feature = "cbor"))]
+fn encode_cbor<T: Serialize>(_value: &T) -> Result<Vec<u8>, CodecError> {
+    Err(CodecError::Unsupported("CBOR"))
+}
+
+#[cfg(feature = "cbor")]
+fn decode_cbor<T: DeserializeOwned>(data: &[u8]) -> Result<T, CodecError> {
+    ciborium::from_reader(data).map_err(|e| CodecError::Cbor(e.to_string()))
+}
+
+#[cfg(not(feature = "cbor"))]
+fn decode_cbor<T: DeserializeOwned>(_data: &[u8]) -> Result<T, CodecError> {
+    Err(CodecError::Unsupported("CBOR"))
+}
+
+#[cfg(feature = "msgpack")]
+fn encode_msgpack<T: Serialize>(value: &T) -> Result<Vec<u8>, CodecError> {
+    let mut out = vec![TAG_MSGPACK];
+    // Named fields keep records readable across versions that add optional fields
+    rmp_serde::encode::write_named(&mut out, value).map_err(|e| CodecError::MessagePack(e.to_string()))?;
+    Ok(out)
+}
+
+#[cfg(not(feature = "msgpack"))]
+fn encode_msgpack<T: Serialize>(_value: &T) -> Result<Vec<u8>, CodecError> {
+    Err(CodecError::Unsupported("MessagePack"))
+}
+
+#[cfg(feature = "msgpack")]
+fn decode_msgpack<T: DeserializeOwned>(data: &[u8]) -> Result<T, CodecError> {
+    rmp_serde::from_slice(data).map_err(|e| CodecError::MessagePack(e.to_string()))
+}
+
+#[cfg(not(feature = "msgpack"))]
+fn decode_msgpack<T: DeserializeOwned>(_data: &[u8]) -> Result<T, CodecError> {
+    Err(CodecError::Unsupported("MessagePack"))
+}
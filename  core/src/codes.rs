@@ -0,0 +1,743 @@
+//! Stable machine-readable codes and severities for every error and event the crate emits,
+//! so frontends in other languages can localize messages and branch on failures.
+//! Codes are never renamed or reused; new ones are only appended.
+use std::fmt;
+use serde::{Serialize, Deserialize};
+
+use crate::activity::ActivityKind;
+use crate::amount::AmountError;
+use crate::app_settings::AppSettingsError;
+use crate::canonical::OutOfRange;
+use crate::codec::CodecError;
+use crate::config::ConfigError;
+use crate::delegation::DelegationError;
+use crate::export::ExportError;
+use crate::inventory::InventoryError;
+use crate::import::RowErrorKind;
+use crate::invoicing::InvoiceError;
+use crate::keyring::KeyringError;
+use crate::ledger::LedgerError;
+use crate::ledger::lots::LotError;
+use crate::locale::LocaleError;
+use crate::mailbox::MailboxError;
+use crate::quickentry::QuickEntryError;
+use crate::quorum::QuorumError;
+use crate::receipts::ReceiptError;
+use crate::recovery::RecoveryError;
+use crate::reports::delivery::DeliveryError;
+use crate::reports::translation::TranslationError;
+use crate::snapshot::SnapshotError;
+use crate::sync::SyncError;
+use crate::syncable::SyncableError;
+use crate::workspace::WorkspaceError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Severity {
+    /// Something happened; nothing to handle
+    Info,
+    /// The operation was skipped or degraded but nothing was lost
+    Warning,
+    /// The operation failed
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventCode {
+    // Ledger
+    Unbalanced,
+    PeriodClosed,
+    AccountNotFound,
+    AccountArchived,
+    OutsideValidityWindow,
+    CommodityNotAllowed,
+    DuplicateTransaction,
+    BalanceAssertionFailed,
+    LedgerRejected,
+    LotInsufficientQuantity,
+    LotInvalidQuantity,
+    // Inventory
+    UnknownItem,
+    DuplicateSku,
+    InvalidStockQuantity,
+    InvalidUnitCost,
+    InsufficientStock,
+    // Sync
+    DocumentError,
+    SerializationError,
+    MissingField,
+    IncompatiblePeer,
+    AmountOutOfRange,
+    UnknownBook,
+    StaleKeyGeneration,
+    DecryptFailed,
+    CodecJson,
+    CodecCbor,
+    CodecMessagePack,
+    CodecUnsupported,
+    SnapshotIo,
+    SnapshotDenied,
+    SnapshotTooLarge,
+    SnapshotInvalidChange,
+    // Input
+    LocaleInvalidAmount,
+    LocaleInvalidDate,
+    UnknownLocale,
+    AmountEmpty,
+    AmountInvalidNumber,
+    AmountUnexpectedChar,
+    AmountUnbalancedParens,
+    AmountDivisionByZero,
+    AmountOverflow,
+    AmountMixedCurrencies,
+    QuickEntryMissingAmount,
+    QuickEntryUnknownAccount,
+    ReceiptUnsupportedFormat,
+    ReceiptExtractionFailed,
+    // Reports
+    MissingRate,
+    DeliveryIo,
+    DeliveryTransport,
+    // Activity
+    TransactionPosted,
+    TransactionEdited,
+    MergeReceived,
+    BackupCreated,
+    PeriodClosedEvent,
+    TransactionVoided,
+    // Config
+    ConfigIo,
+    ConfigParse,
+    ConfigInvalidValue,
+    ConfigInvalidPeer,
+    // Invoicing
+    InvoiceNotFound,
+    InvoiceNotDraft,
+    InvoiceNotOpen,
+    InvoiceEmpty,
+    InvoiceInvalidAmount,
+    InvoiceOverpayment,
+    // Export
+    ExportIo,
+    // Sync
+    BadSignature,
+    // Ledger
+    PeriodLocked,
+    // Import
+    ImportMalformedRow,
+    ImportMissingField,
+    ImportInvalidDate,
+    ImportInvalidAmount,
+    // Workspace
+    WorkspaceUnknownEntity,
+    WorkspaceDuplicateEntity,
+    WorkspaceInvalidName,
+    WorkspaceSameEntity,
+    WorkspaceReplay,
+    // Delegation
+    DelegationBadSignature,
+    DelegationExpired,
+    DelegationRevoked,
+    DelegationAlreadyRedeemed,
+    DelegationAccountNotAllowed,
+    // Recovery
+    RecoveryUnknownBook,
+    RecoveryMalformed,
+    RecoveryChecksum,
+    RecoveryStateMismatch,
+    // Quorum
+    QuorumUnknownProposal,
+    QuorumNotTrusted,
+    QuorumBadSignature,
+    QuorumExpired,
+    QuorumNotApproved,
+    QuorumDeclined,
+    QuorumWrongOperation,
+    // Mailbox
+    MailboxConnection,
+    MailboxAuthentication,
+    // App settings
+    AppSettingsInvalidKey,
+    AppSettingsSerialize,
+    // Snapshot transport
+    SnapshotTimeout,
+    // Inventory
+    InvalidUnitPrice,
+    // Keyring
+    EncryptFailed,
+}
+
+impl EventCode {
+    pub const ALL: [EventCode; 100] = [
+        EventCode::Unbalanced,
+        EventCode::PeriodClosed,
+        EventCode::AccountNotFound,
+        EventCode::AccountArchived,
+        EventCode::OutsideValidityWindow,
+        EventCode::CommodityNotAllowed,
+        EventCode::DuplicateTransaction,
+        EventCode::BalanceAssertionFailed,
+        EventCode::LedgerRejected,
+        EventCode::LotInsufficientQuantity,
+        EventCode::LotInvalidQuantity,
+        EventCode::UnknownItem,
+        EventCode::DuplicateSku,
+        EventCode::InvalidStockQuantity,
+        EventCode::InvalidUnitCost,
+        EventCode::InsufficientStock,
+        EventCode::DocumentError,
+        EventCode::SerializationError,
+        EventCode::MissingField,
+        EventCode::IncompatiblePeer,
+        EventCode::AmountOutOfRange,
+        EventCode::UnknownBook,
+        EventCode::StaleKeyGeneration,
+        EventCode::DecryptFailed,
+        EventCode::CodecJson,
+        EventCode::CodecCbor,
+        EventCode::CodecMessagePack,
+        EventCode::CodecUnsupported,
+        EventCode::SnapshotIo,
+        EventCode::SnapshotDenied,
+        EventCode::SnapshotTooLarge,
+        EventCode::SnapshotInvalidChange,
+        EventCode::LocaleInvalidAmount,
+        EventCode::LocaleInvalidDate,
+        EventCode::UnknownLocale,
+        EventCode::AmountEmpty,
+        EventCode::AmountInvalidNumber,
+        EventCode::AmountUnexpectedChar,
+        EventCode::AmountUnbalancedParens,
+        EventCode::AmountDivisionByZero,
+        EventCode::AmountOverflow,
+        EventCode::AmountMixedCurrencies,
+        EventCode::QuickEntryMissingAmount,
+        EventCode::QuickEntryUnknownAccount,
+        EventCode::ReceiptUnsupportedFormat,
+        EventCode::ReceiptExtractionFailed,
+        EventCode::MissingRate,
+        EventCode::DeliveryIo,
+        EventCode::DeliveryTransport,
+        EventCode::TransactionPosted,
+        EventCode::TransactionEdited,
+        EventCode::MergeReceived,
+        EventCode::BackupCreated,
+        EventCode::PeriodClosedEvent,
+        EventCode::TransactionVoided,
+        EventCode::ConfigIo,
+        EventCode::ConfigParse,
+        EventCode::ConfigInvalidValue,
+        EventCode::ConfigInvalidPeer,
+        EventCode::InvoiceNotFound,
+        EventCode::InvoiceNotDraft,
+        EventCode::InvoiceNotOpen,
+        EventCode::InvoiceEmpty,
+        EventCode::InvoiceInvalidAmount,
+        EventCode::InvoiceOverpayment,
+        EventCode::ExportIo,
+        EventCode::BadSignature,
+        EventCode::PeriodLocked,
+        EventCode::ImportMalformedRow,
+        EventCode::ImportMissingField,
+        EventCode::ImportInvalidDate,
+        EventCode::ImportInvalidAmount,
+        EventCode::WorkspaceUnknownEntity,
+        EventCode::WorkspaceDuplicateEntity,
+        EventCode::WorkspaceInvalidName,
+        EventCode::WorkspaceSameEntity,
+        EventCode::WorkspaceReplay,
+        EventCode::DelegationBadSignature,
+        EventCode::DelegationExpired,
+        EventCode::DelegationRevoked,
+        EventCode::DelegationAlreadyRedeemed,
+        EventCode::DelegationAccountNotAllowed,
+        EventCode::RecoveryUnknownBook,
+        EventCode::RecoveryMalformed,
+        EventCode::RecoveryChecksum,
+        EventCode::RecoveryStateMismatch,
+        EventCode::QuorumUnknownProposal,
+        EventCode::QuorumNotTrusted,
+        EventCode::QuorumBadSignature,
+        EventCode::QuorumExpired,
+        EventCode::QuorumNotApproved,
+        EventCode::QuorumDeclined,
+        EventCode::QuorumWrongOperation,
+        EventCode::MailboxConnection,
+        EventCode::MailboxAuthentication,
+        EventCode::AppSettingsInvalidKey,
+        EventCode::AppSettingsSerialize,
+        EventCode::SnapshotTimeout,
+        EventCode::InvalidUnitPrice,
+        EventCode::EncryptFailed,
+    ];
+
+    /// Wire form, e.g. "ledger.unbalanced"
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventCode::Unbalanced => "ledger.unbalanced",
+            EventCode::PeriodClosed => "ledger.period_closed",
+            EventCode::AccountNotFound => "ledger.account_not_found",
+            EventCode::AccountArchived => "ledger.account_archived",
+            EventCode::OutsideValidityWindow => "ledger.outside_validity_window",
+            EventCode::CommodityNotAllowed => "ledger.commodity_not_allowed",
+            EventCode::DuplicateTransaction => "ledger.duplicate_transaction",
+            EventCode::BalanceAssertionFailed => "ledger.balance_assertion_failed",
+            EventCode::LedgerRejected => "ledger.rejected",
+            EventCode::LotInsufficientQuantity => "lots.insufficient_quantity",
+            EventCode::LotInvalidQuantity => "lots.invalid_quantity",
+            EventCode::UnknownItem => "inventory.unknown_item",
+            EventCode::DuplicateSku => "inventory.duplicate_sku",
+            EventCode::InvalidStockQuantity => "inventory.invalid_quantity",
+            EventCode::InvalidUnitCost => "inventory.invalid_unit_cost",
+            EventCode::InsufficientStock => "inventory.insufficient_stock",
+            EventCode::DocumentError => "sync.document_error",
+            EventCode::SerializationError => "sync.serialization_error",
+            EventCode::MissingField => "sync.missing_field",
+            EventCode::IncompatiblePeer => "sync.incompatible_peer",
+            EventCode::AmountOutOfRange => "sync.amount_out_of_range",
+            EventCode::UnknownBook => "keyring.unknown_book",
+            EventCode::StaleKeyGeneration => "keyring.stale_generation",
+            EventCode::DecryptFailed => "keyring.decrypt_failed",
+            EventCode::CodecJson => "codec.json",
+            EventCode::CodecCbor => "codec.cbor",
+            EventCode::CodecMessagePack => "codec.messagepack",
+            EventCode::CodecUnsupported => "codec.unsupported",
+            EventCode::SnapshotIo => "snapshot.io",
+            EventCode::SnapshotDenied => "snapshot.denied",
+            EventCode::SnapshotTooLarge => "snapshot.too_large",
+            EventCode::SnapshotInvalidChange => "snapshot.invalid_change",
+            EventCode::LocaleInvalidAmount => "locale.invalid_amount",
+            EventCode::LocaleInvalidDate => "locale.invalid_date",
+            EventCode::UnknownLocale => "locale.unknown",
+            EventCode::AmountEmpty => "amount.empty",
+            EventCode::AmountInvalidNumber => "amount.invalid_number",
+            EventCode::AmountUnexpectedChar => "amount.unexpected_char",
+            EventCode::AmountUnbalancedParens => "amount.unbalanced_parens",
+            EventCode::AmountDivisionByZero => "amount.division_by_zero",
+            EventCode::AmountOverflow => "amount.overflow",
+            EventCode::AmountMixedCurrencies => "amount.mixed_currencies",
+            EventCode::QuickEntryMissingAmount => "quickentry.missing_amount",
+            EventCode::QuickEntryUnknownAccount => "quickentry.unknown_account",
+            EventCode::ReceiptUnsupportedFormat => "receipts.unsupported_format",
+            EventCode::ReceiptExtractionFailed => "receipts.extraction_failed",
+            EventCode::MissingRate => "reports.missing_rate",
+            EventCode::DeliveryIo => "delivery.io",
+            EventCode::DeliveryTransport => "delivery.transport",
+            EventCode::TransactionPosted => "activity.transaction_posted",
+            EventCode::TransactionEdited => "activity.transaction_edited",
+            EventCode::MergeReceived => "activity.merge_received",
+            EventCode::BackupCreated => "activity.backup_created",
+            EventCode::PeriodClosedEvent => "activity.period_closed",
+            EventCode::TransactionVoided => "activity.transaction_voided",
+            EventCode::ConfigIo => "config.io",
+            EventCode::ConfigParse => "config.parse",
+            EventCode::ConfigInvalidValue => "config.invalid_value",
+            EventCode::ConfigInvalidPeer => "config.invalid_peer",
+            EventCode::InvoiceNotFound => "invoicing.not_found",
+            EventCode::InvoiceNotDraft => "invoicing.not_draft",
+            EventCode::InvoiceNotOpen => "invoicing.not_open",
+            EventCode::InvoiceEmpty => "invoicing.empty",
+            EventCode::InvoiceInvalidAmount => "invoicing.invalid_amount",
+            EventCode::InvoiceOverpayment => "invoicing.overpayment",
+            EventCode::ExportIo => "export.io",
+            EventCode::BadSignature => "sync.bad_signature",
+            EventCode::PeriodLocked => "ledger.period_locked",
+            EventCode::ImportMalformedRow => "import.malformed_row",
+            EventCode::ImportMissingField => "import.missing_field",
+            EventCode::ImportInvalidDate => "import.invalid_date",
+            EventCode::ImportInvalidAmount => "import.invalid_amount",
+            EventCode::WorkspaceUnknownEntity => "workspace.unknown_entity",
+            EventCode::WorkspaceDuplicateEntity => "workspace.duplicate_entity",
+            EventCode::WorkspaceInvalidName => "workspace.invalid_name",
+            EventCode::WorkspaceSameEntity => "workspace.same_entity",
+            EventCode::WorkspaceReplay => "workspace.replay",
+            EventCode::DelegationBadSignature => "delegation.bad_signature",
+            EventCode::DelegationExpired => "delegation.expired",
+            EventCode::DelegationRevoked => "delegation.revoked",
+            EventCode::DelegationAlreadyRedeemed => "delegation.already_redeemed",
+            EventCode::DelegationAccountNotAllowed => "delegation.account_not_allowed",
+            EventCode::RecoveryUnknownBook => "recovery.unknown_book",
+            EventCode::RecoveryMalformed => "recovery.malformed",
+            EventCode::RecoveryChecksum => "recovery.checksum",
+            EventCode::RecoveryStateMismatch => "recovery.state_mismatch",
+            EventCode::QuorumUnknownProposal => "quorum.unknown_proposal",
+            EventCode::QuorumNotTrusted => "quorum.not_trusted",
+            EventCode::QuorumBadSignature => "quorum.bad_signature",
+            EventCode::QuorumExpired => "quorum.expired",
+            EventCode::QuorumNotApproved => "quorum.not_approved",
+            EventCode::QuorumDeclined => "quorum.declined",
+            EventCode::QuorumWrongOperation => "quorum.wrong_operation",
+            EventCode::MailboxConnection => "mailbox.connection",
+            EventCode::MailboxAuthentication => "mailbox.authentication",
+            EventCode::AppSettingsInvalidKey => "app_settings.invalid_key",
+            EventCode::AppSettingsSerialize => "app_settings.serialize",
+            EventCode::SnapshotTimeout => "snapshot.timeout",
+            EventCode::InvalidUnitPrice => "inventory.invalid_unit_price",
+            EventCode::EncryptFailed => "keyring.encrypt_failed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|c| c.as_str() == s)
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            EventCode::TransactionPosted
+            | EventCode::TransactionEdited
+            | EventCode::MergeReceived
+            | EventCode::BackupCreated
+            | EventCode::PeriodClosedEvent
+            | EventCode::TransactionVoided => Severity::Info,
+            // Replays, peers on other versions and old key generations are expected during sync
+            EventCode::DuplicateTransaction
+            | EventCode::IncompatiblePeer
+            | EventCode::StaleKeyGeneration
+            | EventCode::SnapshotDenied => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for EventCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for EventCode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for EventCode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        EventCode::parse(&s).ok_or_else(|| serde::de::Error::custom(format!("unknown event code: {}", s)))
+    }
+}
+
+/// Code, severity and English message, ready to hand to a frontend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodedEvent {
+    pub code: EventCode,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Errors and events with a catalogued code
+pub trait Coded {
+    fn code(&self) -> EventCode;
+
+    fn severity(&self) -> Severity {
+        self.code().severity()
+    }
+
+    fn to_event(&self) -> CodedEvent
+    where
+        Self: fmt::Display,
+    {
+        CodedEvent { code: self.code(), severity: self.severity(), message: self.to_string() }
+    }
+}
+
+impl Coded for LedgerError {
+    fn code(&self) -> EventCode {
+        match self {
+            LedgerError::Duplicate(_) => EventCode::DuplicateTransaction,
+            LedgerError::BalanceAssertion { .. } => EventCode::BalanceAssertionFailed,
+            // Validation failures are plain messages; map the ones frontends need to tell apart
+            LedgerError::Rejected(message) => match *message {
+                "Unbalanced transaction" => EventCode::Unbalanced,
+                "Period is closed" | "Period already closed" => EventCode::PeriodClosed,
+                "Period is locked" => EventCode::PeriodLocked,
+                "Account not found" => EventCode::AccountNotFound,
+                "Posting to archived account" => EventCode::AccountArchived,
+                "Posting outside account validity window" => EventCode::OutsideValidityWindow,
+                "Commodity not allowed in account" => EventCode::CommodityNotAllowed,
+                _ => EventCode::LedgerRejected,
+            },
+        }
+    }
+}
+
+impl Coded for LotError {
+    fn code(&self) -> EventCode {
+        match self {
+            LotError::InsufficientQuantity { .. } => EventCode::LotInsufficientQuantity,
+            LotError::InvalidQuantity => EventCode::LotInvalidQuantity,
+        }
+    }
+}
+
+impl Coded for InventoryError {
+    fn code(&self) -> EventCode {
+        match self {
+            InventoryError::UnknownItem(_) => EventCode::UnknownItem,
+            InventoryError::DuplicateSku(_) => EventCode::DuplicateSku,
+            InventoryError::InvalidQuantity => EventCode::InvalidStockQuantity,
+            InventoryError::InvalidUnitCost => EventCode::InvalidUnitCost,
+            InventoryError::InsufficientStock { .. } => EventCode::InsufficientStock,
+            InventoryError::InvalidUnitPrice => EventCode::InvalidUnitPrice,
+            InventoryError::Ledger(e) => e.code(),
+        }
+    }
+}
+
+impl Coded for SyncError {
+    fn code(&self) -> EventCode {
+        match self {
+            SyncError::Automerge(_) => EventCode::DocumentError,
+            SyncError::Serde(_) => EventCode::SerializationError,
+            SyncError::MissingField(_) => EventCode::MissingField,
+            SyncError::IncompatiblePeer(_) => EventCode::IncompatiblePeer,
+            SyncError::OutOfRange(e) => e.code(),
+            SyncError::Keyring(e) => e.code(),
+            SyncError::Codec(e) => e.code(),
+            SyncError::BadSignature => EventCode::BadSignature,
+        }
+    }
+}
+
+impl Coded for OutOfRange {
+    fn code(&self) -> EventCode {
+        EventCode::AmountOutOfRange
+    }
+}
+
+impl Coded for KeyringError {
+    fn code(&self) -> EventCode {
+        match self {
+            KeyringError::UnknownBook(_) => EventCode::UnknownBook,
+            KeyringError::StaleGeneration { .. } => EventCode::StaleKeyGeneration,
+            KeyringError::Decrypt => EventCode::DecryptFailed,
+            KeyringError::Encrypt => EventCode::EncryptFailed,
+        }
+    }
+}
+
+impl Coded for CodecError {
+    fn code(&self) -> EventCode {
+        match self {
+            CodecError::Json(_) => EventCode::CodecJson,
+            CodecError::Cbor(_) => EventCode::CodecCbor,
+            CodecError::MessagePack(_) => EventCode::CodecMessagePack,
+            CodecError::Unsupported(_) => EventCode::CodecUnsupported,
+        }
+    }
+}
+
+impl Coded for SnapshotError {
+    fn code(&self) -> EventCode {
+        match self {
+            SnapshotError::Io(_) => EventCode::SnapshotIo,
+            SnapshotError::Sync(e) => e.code(),
+            SnapshotError::Serde(_) => EventCode::SerializationError,
+            SnapshotError::Denied => EventCode::SnapshotDenied,
+            SnapshotError::TooLarge(_) => EventCode::SnapshotTooLarge,
+            SnapshotError::InvalidChange => EventCode::SnapshotInvalidChange,
+            SnapshotError::Timeout => EventCode::SnapshotTimeout,
+            SnapshotError::Keyring(e) => e.code(),
+        }
+    }
+}
+
+impl Coded for LocaleError {
+    fn code(&self) -> EventCode {
+        match self {
+            LocaleError::InvalidAmount(_) => EventCode::LocaleInvalidAmount,
+            LocaleError::InvalidDate(_) => EventCode::LocaleInvalidDate,
+            LocaleError::UnknownLocale(_) => EventCode::UnknownLocale,
+        }
+    }
+}
+
+impl Coded for AmountError {
+    fn code(&self) -> EventCode {
+        match self {
+            AmountError::Empty => EventCode::AmountEmpty,
+            AmountError::InvalidNumber(_) => EventCode::AmountInvalidNumber,
+            AmountError::UnexpectedChar(_) => EventCode::AmountUnexpectedChar,
+            AmountError::UnbalancedParens => EventCode::AmountUnbalancedParens,
+            AmountError::DivisionByZero => EventCode::AmountDivisionByZero,
+            AmountError::Overflow => EventCode::AmountOverflow,
+            AmountError::MixedCurrencies => EventCode::AmountMixedCurrencies,
+        }
+    }
+}
+
+impl Coded for QuickEntryError {
+    fn code(&self) -> EventCode {
+        match self {
+            QuickEntryError::MissingAmount => EventCode::QuickEntryMissingAmount,
+            QuickEntryError::UnknownAccount(_) => EventCode::QuickEntryUnknownAccount,
+        }
+    }
+}
+
+impl Coded for ReceiptError {
+    fn code(&self) -> EventCode {
+        match self {
+            ReceiptError::UnsupportedFormat => EventCode::ReceiptUnsupportedFormat,
+            ReceiptError::Extraction(_) => EventCode::ReceiptExtractionFailed,
+        }
+    }
+}
+
+impl Coded for TranslationError {
+    fn code(&self) -> EventCode {
+        match self {
+            TranslationError::MissingRate { .. } => EventCode::MissingRate,
+        }
+    }
+}
+
+impl Coded for DeliveryError {
+    fn code(&self) -> EventCode {
+        match self {
+            DeliveryError::Io(_) => EventCode::DeliveryIo,
+            DeliveryError::Transport(_) => EventCode::DeliveryTransport,
+        }
+    }
+}
+
+impl Coded for ActivityKind {
+    fn code(&self) -> EventCode {
+        match self {
+            ActivityKind::TransactionPosted { .. } => EventCode::TransactionPosted,
+            ActivityKind::TransactionEdited { .. } => EventCode::TransactionEdited,
+            ActivityKind::MergeReceived { .. } => EventCode::MergeReceived,
+            ActivityKind::BackupCreated { .. } => EventCode::BackupCreated,
+            ActivityKind::PeriodClosed { .. } => EventCode::PeriodClosedEvent,
+            ActivityKind::TransactionVoided { .. } => EventCode::TransactionVoided,
+        }
+    }
+}
+
+impl Coded for ConfigError {
+    fn code(&self) -> EventCode {
+        match self {
+            ConfigError::Io(_) => EventCode::ConfigIo,
+            ConfigError::Parse(_) => EventCode::ConfigParse,
+            ConfigError::InvalidValue { .. } => EventCode::ConfigInvalidValue,
+            ConfigError::InvalidPeer(_) => EventCode::ConfigInvalidPeer,
+        }
+    }
+}
+
+impl Coded for InvoiceError {
+    fn code(&self) -> EventCode {
+        match self {
+            InvoiceError::NotFound => EventCode::InvoiceNotFound,
+            InvoiceError::NotDraft => EventCode::InvoiceNotDraft,
+            InvoiceError::NotOpen => EventCode::InvoiceNotOpen,
+            InvoiceError::Empty => EventCode::InvoiceEmpty,
+            InvoiceError::InvalidAmount => EventCode::InvoiceInvalidAmount,
+            InvoiceError::Overpayment { .. } => EventCode::InvoiceOverpayment,
+        }
+    }
+}
+
+impl Coded for ExportError {
+    fn code(&self) -> EventCode {
+        match self {
+            ExportError::Io(_) => EventCode::ExportIo,
+            ExportError::Codec(e) => e.code(),
+        }
+    }
+}
+
+impl Coded for RowErrorKind {
+    fn code(&self) -> EventCode {
+        match self {
+            RowErrorKind::MalformedRow => EventCode::ImportMalformedRow,
+            RowErrorKind::MissingField(_) => EventCode::ImportMissingField,
+            RowErrorKind::InvalidDate(_) => EventCode::ImportInvalidDate,
+            RowErrorKind::InvalidAmount(_) => EventCode::ImportInvalidAmount,
+        }
+    }
+}
+
+impl Coded for WorkspaceError {
+    fn code(&self) -> EventCode {
+        match self {
+            WorkspaceError::UnknownEntity(_) => EventCode::WorkspaceUnknownEntity,
+            WorkspaceError::DuplicateEntity(_) => EventCode::WorkspaceDuplicateEntity,
+            WorkspaceError::InvalidName(_) => EventCode::WorkspaceInvalidName,
+            WorkspaceError::SameEntity => EventCode::WorkspaceSameEntity,
+            WorkspaceError::Replay { .. } => EventCode::WorkspaceReplay,
+            WorkspaceError::Ledger(e) => e.code(),
+            WorkspaceError::Sync(e) => e.code(),
+            WorkspaceError::Delegation(e) => e.code(),
+            WorkspaceError::Inventory(e) => e.code(),
+        }
+    }
+}
+
+impl Coded for DelegationError {
+    fn code(&self) -> EventCode {
+        match self {
+            DelegationError::BadSignature => EventCode::DelegationBadSignature,
+            DelegationError::Expired => EventCode::DelegationExpired,
+            DelegationError::Revoked => EventCode::DelegationRevoked,
+            DelegationError::AlreadyRedeemed => EventCode::DelegationAlreadyRedeemed,
+            DelegationError::AccountNotAllowed(_) => EventCode::DelegationAccountNotAllowed,
+        }
+    }
+}
+
+impl Coded for RecoveryError {
+    fn code(&self) -> EventCode {
+        match self {
+            RecoveryError::UnknownBook(_) => EventCode::RecoveryUnknownBook,
+            RecoveryError::Malformed(_) => EventCode::RecoveryMalformed,
+            RecoveryError::Checksum => EventCode::RecoveryChecksum,
+            RecoveryError::StateMismatch => EventCode::RecoveryStateMismatch,
+            RecoveryError::Snapshot(e) => e.code(),
+        }
+    }
+}
+
+impl Coded for SyncableError {
+    fn code(&self) -> EventCode {
+        match self {
+            SyncableError::Ledger(e) => e.code(),
+            SyncableError::Sync(e) => e.code(),
+            SyncableError::Delegation(e) => e.code(),
+            SyncableError::Inventory(e) => e.code(),
+        }
+    }
+}
+
+impl Coded for QuorumError {
+    fn code(&self) -> EventCode {
+        match self {
+            QuorumError::UnknownProposal(_) => EventCode::QuorumUnknownProposal,
+            QuorumError::NotTrusted => EventCode::QuorumNotTrusted,
+            QuorumError::BadSignature => EventCode::QuorumBadSignature,
+            QuorumError::Expired => EventCode::QuorumExpired,
+            QuorumError::NotApproved { .. } => EventCode::QuorumNotApproved,
+            QuorumError::Declined => EventCode::QuorumDeclined,
+            QuorumError::WrongOperation => EventCode::QuorumWrongOperation,
+            QuorumError::Keyring(e) => e.code(),
+        }
+    }
+}
+
+impl Coded for MailboxError {
+    fn code(&self) -> EventCode {
+        match self {
+            MailboxError::Connection(_) => EventCode::MailboxConnection,
+            MailboxError::Authentication => EventCode::MailboxAuthentication,
+        }
+    }
+}
+
+impl Coded for AppSettingsError {
+    fn code(&self) -> EventCode {
+        match self {
+            AppSettingsError::InvalidKey(_) => EventCode::AppSettingsInvalidKey,
+            AppSettingsError::Serialize(_) => EventCode::AppSettingsSerialize,
+        }
+    }
+}
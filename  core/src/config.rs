@@ -0,0 +1,294 @@
+//! Typed application configuration: defaults, overlaid by a TOML file, `TRUE_LEDGER_*`
+//! environment variables, locally persisted overrides and book-wide settings synced with peers.
+//! Running services subscribe to changes instead of re-reading the config.
+use std::path::Path;
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use thiserror::Error;
+use tokio::sync::watch;
+use uuid::Uuid;
+
+use crate::antientropy::AntiEntropyConfig;
+use crate::currency::Commodity;
+use crate::ledger::tax::TaxTable;
+use crate::qos::QosConfig;
+use crate::reports::{ReportFormat, ReportOptions};
+use crate::rounding::RoundingPolicy;
+use crate::storage::{LocalStorage, StorageConfig};
+use crate::NetworkConfig;
+
+const SETTINGS_KEY: &str = "config_overrides";
+const ENV_PREFIX: &str = "TRUE_LEDGER_";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Cannot read config file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid config file: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Invalid value for {key}: {message}")]
+    InvalidValue { key: String, message: String },
+    #[error("Invalid peer address: {0}")]
+    InvalidPeer(String),
+}
+
+/// Network settings as written in config files; peers are parsed on `to_network_config`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    pub privacy_mode: bool,
+    pub peers: Vec<String>,
+    pub listen_port: Option<u16>,
+}
+
+impl NetworkSettings {
+    pub fn to_network_config(&self) -> Result<NetworkConfig, ConfigError> {
+        let peers = self.peers.iter()
+            .map(|p| p.parse().map_err(|_| ConfigError::InvalidPeer(p.clone())))
+            .collect::<Result<_, _>>()?;
+        Ok(NetworkConfig { privacy_mode: self.privacy_mode, peers, listen_port: self.listen_port })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncSettings {
+    pub qos: QosConfig,
+    pub anti_entropy: AntiEntropyConfig,
+}
+
+/// Defaults for generated and scheduled reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportSettings {
+    pub options: ReportOptions,
+    pub format: ReportFormat,
+    /// Locale tag used to format amounts and dates, e.g. "de-DE"
+    pub locale: String,
+}
+
+impl Default for ReportSettings {
+    fn default() -> Self {
+        Self {
+            options: ReportOptions::default(),
+            format: ReportFormat::Text,
+            locale: "en-US".to_string(),
+        }
+    }
+}
+
+/// Settings that belong to the book rather than the device
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BookSettings {
+    pub base_currency: Commodity,
+    pub rounding: RoundingPolicy,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub storage: StorageConfig,
+    pub network: NetworkSettings,
+    pub sync: SyncSettings,
+    pub reports: ReportSettings,
+    pub book: BookSettings,
+}
+
+impl Config {
+    /// Parse a TOML document; missing sections and keys keep their defaults
+    pub fn from_toml_str(toml: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Self::from_toml_str(&std::fs::read_to_string(path)?)
+    }
+
+    /// Apply `TRUE_LEDGER_*` variables from the process environment
+    pub fn apply_env(self) -> Result<Self, ConfigError> {
+        self.apply_env_from(std::env::vars())
+    }
+
+    /// Apply `TRUE_LEDGER_<SECTION>__<KEY>` variables, e.g. `TRUE_LEDGER_STORAGE__PATH` or
+    /// `TRUE_LEDGER_SYNC__ANTI_ENTROPY__ENABLED`. Values are read as JSON, falling back to a string.
+    pub fn apply_env_from(self, vars: impl IntoIterator<Item = (String, String)>) -> Result<Self, ConfigError> {
+        let mut tree = to_tree(&self);
+        for (name, raw) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else { continue };
+            let path = key.to_lowercase().replace("__", ".");
+            let value = serde_json::from_str(&raw).unwrap_or(Value::String(raw));
+            set_path(&mut tree, &path, value);
+        }
+        from_tree(tree, "environment")
+    }
+}
+
+/// Book-wide settings carried in the sync document; unset fields fall back to the local config
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_currency: Option<Commodity>,
+    /// Rounding of generated amounts, so every device splits and converts the same way
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rounding: Option<RoundingPolicy>,
+    /// Equity account `close_period` moves net income into
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retained_earnings_account: Option<Uuid>,
+    /// Equity account offsetting opening balances
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opening_balances_account: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tax_table: Option<TaxTable>,
+}
+
+/// Effective configuration: file/env base, then local overrides, then shared book settings.
+/// Every change is published to subscribers.
+pub struct ConfigStore {
+    base: Config,
+    /// Persisted overrides as a partial config tree
+    overrides: Value,
+    shared: SharedSettings,
+    tx: watch::Sender<Arc<Config>>,
+}
+
+impl ConfigStore {
+    /// Start from `base` with the overrides saved in `storage`; invalid saved overrides are ignored
+    pub fn new(base: Config, storage: &LocalStorage) -> Self {
+        let overrides = storage.get_setting(SETTINGS_KEY)
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .filter(|o| effective(&base, o, &SharedSettings::default()).is_ok())
+            .unwrap_or_else(|| Value::Object(Default::default()));
+        let (tx, _) = watch::channel(Arc::new(base.clone()));
+        let store = Self { base, overrides, shared: SharedSettings::default(), tx };
+        store.publish();
+        store
+    }
+
+    pub fn current(&self) -> Arc<Config> {
+        self.tx.borrow().clone()
+    }
+
+    /// Receiver that sees the current config and wakes on every change
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Config>> {
+        self.tx.subscribe()
+    }
+
+    /// Replace the file/env layer, e.g. after the config file changed on disk
+    pub fn reload(&mut self, base: Config) -> Result<(), ConfigError> {
+        effective(&base, &self.overrides, &self.shared)?;
+        self.base = base;
+        self.publish();
+        Ok(())
+    }
+
+    /// Persist a local override for a dotted key such as "reports.format"
+    pub fn set_override(&mut self, storage: &LocalStorage, key: &str, value: Value) -> Result<(), ConfigError> {
+        let mut overrides = self.overrides.clone();
+        set_path(&mut overrides, key, value);
+        effective(&self.base, &overrides, &self.shared).map_err(|e| match e {
+            ConfigError::InvalidValue { message, .. } => ConfigError::InvalidValue { key: key.to_string(), message },
+            e => e,
+        })?;
+        self.overrides = overrides;
+        self.save(storage);
+        self.publish();
+        Ok(())
+    }
+
+    /// Drop a local override so the key falls back to the file/env value
+    pub fn clear_override(&mut self, storage: &LocalStorage, key: &str) {
+        remove_path(&mut self.overrides, key);
+        self.save(storage);
+        self.publish();
+    }
+
+    pub fn shared(&self) -> &SharedSettings {
+        &self.shared
+    }
+
+    /// Pick up book settings after a local edit or a merge from a peer
+    pub fn apply_shared(&mut self, shared: &SharedSettings) {
+        if self.shared != *shared {
+            self.shared = shared.clone();
+            self.publish();
+        }
+    }
+
+    fn save(&self, storage: &LocalStorage) {
+        storage.set_setting(SETTINGS_KEY, &serde_json::to_string(&self.overrides).unwrap());
+    }
+
+    /// Recompute the effective config and notify subscribers if it changed
+    fn publish(&self) {
+        // Every layer was validated when it was set
+        let Ok(config) = effective(&self.base, &self.overrides, &self.shared) else { return };
+        let changed = to_tree(&config) != to_tree(self.tx.borrow().as_ref());
+        if changed {
+            self.tx.send_replace(Arc::new(config));
+        }
+    }
+}
+
+fn effective(base: &Config, overrides: &Value, shared: &SharedSettings) -> Result<Config, ConfigError> {
+    let mut tree = to_tree(base);
+    merge(&mut tree, overrides);
+    let mut config = from_tree(tree, "override")?;
+    if let Some(currency) = &shared.base_currency {
+        config.book.base_currency = currency.clone();
+    }
+    if let Some(rounding) = &shared.rounding {
+        config.book.rounding = rounding.clone();
+    }
+    Ok(config)
+}
+
+fn to_tree(config: &Config) -> Value {
+    serde_json::to_value(config).unwrap()
+}
+
+fn from_tree(tree: Value, source: &str) -> Result<Config, ConfigError> {
+    serde_json::from_value(tree).map_err(|e| ConfigError::InvalidValue {
+        key: source.to_string(),
+        message: e.to_string(),
+    })
+}
+
+/// Deep-merge `overlay` into `tree`; non-object values replace
+fn merge(tree: &mut Value, overlay: &Value) {
+    match (tree, overlay) {
+        (Value::Object(tree), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                merge(tree.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (tree, overlay) => *tree = overlay.clone(),
+    }
+}
+
+fn set_path(tree: &mut Value, path: &str, value: Value) {
+    let mut node = tree;
+    for part in path.split('.') {
+        if !node.is_object() {
+            *node = Value::Object(Default::default());
+        }
+        node = node.as_object_mut().unwrap().entry(part.to_string()).or_insert(Value::Null);
+    }
+    *node = value;
+}
+
+fn remove_path(tree: &mut Value, path: &str) {
+    let (parent, last) = match path.rsplit_once('.') {
+        Some((parent, last)) => (parent, last),
+        None => ("", path),
+    };
+    let mut node = tree;
+    for part in parent.split('.').filter(|p| !p.is_empty()) {
+        let Some(next) = node.get_mut(part) else { return };
+        node = next;
+    }
+    if let Some(map) = node.as_object_mut() {
+        map.remove(last);
+    }
+}
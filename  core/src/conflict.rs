@@ -0,0 +1,88 @@
+//! Interactive conflict resolution hook for the sync merge pipeline
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::ledger::Transaction;
+use crate::sync::SyncableLedger;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Same transaction edited differently on this device and a peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conflict {
+    pub transaction_id: Uuid,
+    pub peer: String,
+    pub local: Transaction,
+    pub remote: Transaction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Resolution {
+    AcceptRemote,
+    KeepLocal,
+    /// Keep local and add the remote version as a separate transaction
+    Fork,
+}
+
+/// Registered by the application to ask the user about conflicts
+pub trait ConflictHandler: Send + Sync {
+    fn resolve<'a>(&'a self, conflict: &'a Conflict) -> BoxFuture<'a, Resolution>;
+}
+
+/// Fallback when no handler is registered or it doesn't answer in time
+#[derive(Debug, Clone, Copy)]
+pub struct ConflictPolicy {
+    pub timeout: Duration,
+    pub default: Resolution,
+}
+
+impl Default for ConflictPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            default: Resolution::AcceptRemote,
+        }
+    }
+}
+
+/// Transactions present on both sides with different content
+pub fn detect(local: &SyncableLedger, remote: &SyncableLedger, peer: &str) -> Vec<Conflict> {
+    local.transactions.iter()
+        .filter_map(|l| {
+            let r = remote.transactions.iter().find(|r| r.id == l.id)?;
+            (l != r).then(|| Conflict {
+                transaction_id: l.id,
+                peer: peer.to_string(),
+                local: l.clone(),
+                remote: r.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Apply a decision to the merged ledger
+pub fn apply(ledger: &mut SyncableLedger, conflict: &Conflict, resolution: Resolution) {
+    let chosen = match resolution {
+        Resolution::AcceptRemote => conflict.remote.clone(),
+        Resolution::KeepLocal | Resolution::Fork => conflict.local.clone(),
+    };
+    match ledger.transactions.iter_mut().find(|t| t.id == conflict.transaction_id) {
+        Some(tx) => *tx = chosen,
+        None => ledger.transactions.push(chosen),
+    }
+    if resolution == Resolution::Fork {
+        let mut forked = conflict.remote.clone();
+        forked.id = Uuid::new_v4();
+        ledger.transactions.push(forked);
+    }
+}
+
+/// Accounts whose balances a resolved conflict can change
+pub fn affected_accounts(conflict: &Conflict) -> impl Iterator<Item = Uuid> + '_ {
+    conflict.local.postings.iter()
+        .chain(&conflict.remote.postings)
+        .map(|p| p.account_id)
+}
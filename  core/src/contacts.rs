@@ -0,0 +1,179 @@
+//! Customers, vendors and other counterparties: addresses, payment terms, bank accounts
+//! and the payee names they appear under, used to link and categorize imports
+use chrono::{Datelike, Duration, NaiveDate};
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::ledger::Posting;
+use crate::staging::{StagedTransaction, StagingSource};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContactRole {
+    Customer,
+    Vendor,
+    Both,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Address {
+    pub lines: Vec<String>,
+    pub city: String,
+    pub postal_code: String,
+    /// ISO 3166 country code
+    pub country: String,
+}
+
+/// When invoices to/from the contact fall due
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PaymentTerms {
+    DueOnReceipt,
+    /// Due a number of days after the invoice date (Net 30)
+    Net(u32),
+    /// Due on the last day of the month after the invoice date
+    EndOfFollowingMonth,
+}
+
+impl Default for PaymentTerms {
+    fn default() -> Self {
+        PaymentTerms::Net(30)
+    }
+}
+
+impl PaymentTerms {
+    pub fn due_date(&self, issued: NaiveDate) -> NaiveDate {
+        match self {
+            PaymentTerms::DueOnReceipt => issued,
+            PaymentTerms::Net(days) => issued + Duration::days(*days as i64),
+            PaymentTerms::EndOfFollowingMonth => {
+                let first_of_next = issued
+                    .with_day(1)
+                    .and_then(|d| d.checked_add_months(chrono::Months::new(2)))
+                    .unwrap_or(issued);
+                first_of_next.pred_opt().unwrap_or(issued)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Contact {
+    pub id: Uuid,
+    pub name: String,
+    pub role: ContactRole,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub billing_address: Option<Address>,
+    #[serde(default)]
+    pub terms: PaymentTerms,
+    /// Payee strings seen on imports and receipts that belong to this contact
+    #[serde(default)]
+    pub payee_aliases: Vec<String>,
+    /// Receivable/payable account used for the contact's invoices
+    #[serde(default)]
+    pub account_id: Option<Uuid>,
+    /// IBANs and account numbers the contact pays from or is paid into
+    #[serde(default)]
+    pub bank_accounts: Vec<String>,
+    /// Expense (or income) account imported payments to/from the contact are booked against
+    #[serde(default)]
+    pub default_account_id: Option<Uuid>,
+}
+
+/// Any party a transaction is with; contacts serve as the counterparty registry
+pub type Counterparty = Contact;
+
+impl Contact {
+    pub fn new(name: impl Into<String>, role: ContactRole) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            role,
+            email: None,
+            billing_address: None,
+            terms: PaymentTerms::default(),
+            payee_aliases: Vec::new(),
+            account_id: None,
+            bank_accounts: Vec::new(),
+            default_account_id: None,
+        }
+    }
+
+    pub fn with_alias(mut self, alias: impl Into<String>) -> Self {
+        self.payee_aliases.push(alias.into());
+        self
+    }
+
+    pub fn with_bank_account(mut self, account: impl Into<String>) -> Self {
+        self.bank_accounts.push(account.into());
+        self
+    }
+
+    pub fn with_default_account(mut self, account_id: Uuid) -> Self {
+        self.default_account_id = Some(account_id);
+        self
+    }
+
+    /// Match an IBAN or account number, ignoring spaces and case
+    pub fn matches_bank_account(&self, account: &str) -> bool {
+        let account = normalize_account(account);
+        !account.is_empty() && self.bank_accounts.iter().any(|a| normalize_account(a) == account)
+    }
+
+    /// Case-insensitive match against the contact name and aliases
+    pub fn matches_payee(&self, payee: &str) -> bool {
+        let payee = payee.trim();
+        self.name.eq_ignore_ascii_case(payee)
+            || self.payee_aliases.iter().any(|a| a.trim().eq_ignore_ascii_case(payee))
+    }
+}
+
+/// Contact an imported payee string belongs to
+pub fn find_by_payee<'a>(contacts: impl IntoIterator<Item = &'a Contact>, payee: &str) -> Option<&'a Contact> {
+    contacts.into_iter().find(|c| c.matches_payee(payee))
+}
+
+fn normalize_account(account: &str) -> String {
+    account.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_uppercase()
+}
+
+/// Contact owning an IBAN or account number
+pub fn find_by_bank_account<'a>(contacts: impl IntoIterator<Item = &'a Contact>, account: &str) -> Option<&'a Contact> {
+    contacts.into_iter().find(|c| c.matches_bank_account(account))
+}
+
+/// Contact for a staged entry: by counterparty account first, then by payee
+fn find_for_staged<'a>(contacts: impl IntoIterator<Item = &'a Contact>, entry: &StagedTransaction) -> Option<&'a Contact> {
+    let contacts: Vec<&Contact> = contacts.into_iter().collect();
+    entry.counterparty_account.as_deref()
+        .and_then(|account| find_by_bank_account(contacts.iter().copied(), account))
+        .or_else(|| find_by_payee(contacts.iter().copied(), entry.payee.as_deref()?))
+}
+
+/// Link a staged entry to the contact matching its counterparty account or payee; returns the contact id
+pub fn link_staged<'a>(
+    contacts: impl IntoIterator<Item = &'a Contact>,
+    entry: &mut StagedTransaction,
+) -> Option<Uuid> {
+    let contact = find_for_staged(contacts, entry)?;
+    entry.contact_id = Some(contact.id);
+    Some(contact.id)
+}
+
+/// Link an imported entry and, when it only has its bank-side posting, book the other side
+/// to the contact's default account. Returns the contact id when one matched.
+pub fn categorize_staged<'a>(
+    contacts: impl IntoIterator<Item = &'a Contact>,
+    entry: &mut StagedTransaction,
+) -> Option<Uuid> {
+    let contact = find_for_staged(contacts, entry)?;
+    entry.contact_id = Some(contact.id);
+    let counter = match (&entry.source, contact.default_account_id, entry.postings.as_slice()) {
+        (StagingSource::Import, Some(account_id), [bank]) => {
+            Some(Posting::in_commodity(account_id, -bank.amount, bank.commodity.clone()))
+        }
+        _ => None,
+    };
+    entry.postings.extend(counter);
+    Some(contact.id)
+}
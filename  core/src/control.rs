@@ -0,0 +1,94 @@
+//! User-controlled sync switches (pause, per-peer mute and direction) persisted across restarts
+use std::collections::{BTreeMap, BTreeSet};
+use serde::{Serialize, Deserialize};
+
+use crate::protocol::Envelope;
+use crate::storage::LocalStorage;
+
+const SETTINGS_KEY: &str = "sync_control";
+
+/// Which way changes flow with a peer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncDirection {
+    #[default]
+    Both,
+    /// We send to the peer but never merge from it (e.g. a backup target)
+    PushOnly,
+    /// We merge from the peer but never send to it (e.g. a read feed from the family server)
+    PullOnly,
+}
+
+impl SyncDirection {
+    pub fn allows_push(&self) -> bool {
+        *self != SyncDirection::PullOnly
+    }
+
+    pub fn allows_pull(&self) -> bool {
+        *self != SyncDirection::PushOnly
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncControl {
+    /// No publishing or merging while set
+    pub paused: bool,
+    /// Peers whose changes are held for review instead of merged
+    pub muted_peers: BTreeSet<String>,
+    /// Peers limited to one direction; absent peers sync both ways
+    #[serde(default)]
+    pub directions: BTreeMap<String, SyncDirection>,
+}
+
+impl SyncControl {
+    /// Load saved state, defaulting to active sync with nobody muted
+    pub fn load(storage: &LocalStorage) -> Self {
+        storage.get_setting(SETTINGS_KEY)
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &LocalStorage) {
+        storage.set_setting(SETTINGS_KEY, &serde_json::to_string(self).unwrap());
+    }
+
+    pub fn is_muted(&self, peer: &str) -> bool {
+        self.muted_peers.contains(peer)
+    }
+
+    pub fn direction(&self, peer: &str) -> SyncDirection {
+        self.directions.get(peer).copied().unwrap_or_default()
+    }
+
+    /// Limit a peer to one direction; `Both` removes the limit
+    pub fn set_direction(&mut self, peer: &str, direction: SyncDirection) {
+        match direction {
+            SyncDirection::Both => self.directions.remove(peer),
+            _ => self.directions.insert(peer.to_string(), direction),
+        };
+    }
+
+    /// Whether changes from this peer may be merged right now
+    pub fn accepts_from(&self, peer: &str) -> bool {
+        !self.paused && !self.is_muted(peer) && self.direction(peer).allows_pull()
+    }
+
+    /// Whether an envelope from this peer should be handled at all: state from push-only peers
+    /// and requests for our state from pull-only peers are dropped. Signed envelopes are
+    /// checked again once unwrapped.
+    pub fn accepts_envelope(&self, peer: &str, envelope: &Envelope) -> bool {
+        let direction = self.direction(peer);
+        match envelope {
+            Envelope::FullDoc(_) | Envelope::Chunk(_) | Envelope::Sealed(_) => {
+                direction.allows_pull()
+            }
+            Envelope::ChunkRequest { .. } | Envelope::StateHash { .. } => {
+                direction.allows_push()
+            }
+            Envelope::Hello(_)
+            | Envelope::Signed { .. }
+            | Envelope::QuorumProposal(_)
+            | Envelope::QuorumVote(_)
+            | Envelope::Unknown => true,
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Hash and signature algorithms behind traits, so state hashes, dedup and change signatures
+//! can move to another algorithm (or a FIPS-validated one) without touching their callers.
+//! Every digest and signature carries its algorithm id so old data stays verifiable.
+use std::sync::Arc;
+use libp2p::identity::ed25519;
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::dedup::ContentHash;
+
+/// 32-byte digest function
+pub trait HashAlgorithm: Send + Sync {
+    /// Stable identifier stored next to digests, e.g. "sha256"
+    fn id(&self) -> &'static str;
+
+    fn digest(&self, data: &[u8]) -> ContentHash;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha256Hash;
+
+impl HashAlgorithm for Sha256Hash {
+    fn id(&self) -> &'static str {
+        "sha256"
+    }
+
+    fn digest(&self, data: &[u8]) -> ContentHash {
+        Sha256::digest(data).into()
+    }
+}
+
+/// Holds a private key and signs with it
+pub trait Signer: Send + Sync {
+    fn algorithm(&self) -> &'static str;
+
+    fn public_key(&self) -> Vec<u8>;
+
+    fn sign(&self, message: &[u8]) -> Vec<u8>;
+}
+
+/// Checks signatures of one algorithm
+pub trait Verifier: Send + Sync {
+    fn algorithm(&self) -> &'static str;
+
+    /// False for malformed keys as well as bad signatures
+    fn verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> bool;
+}
+
+/// Ed25519 signing key, the default for change signatures
+#[derive(Clone)]
+pub struct Ed25519Signer {
+    keypair: ed25519::Keypair,
+}
+
+impl Ed25519Signer {
+    pub fn generate() -> Self {
+        Self { keypair: ed25519::Keypair::generate() }
+    }
+
+    pub fn from_keypair(keypair: ed25519::Keypair) -> Self {
+        Self { keypair }
+    }
+
+    /// Restore from the 64-byte secret+public encoding produced by `to_bytes`
+    pub fn from_bytes(bytes: &mut [u8]) -> Option<Self> {
+        ed25519::Keypair::try_from_bytes(bytes).ok().map(Self::from_keypair)
+    }
+
+    pub fn to_bytes(&self) -> [u8; 64] {
+        self.keypair.to_bytes()
+    }
+}
+
+impl Signer for Ed25519Signer {
+    fn algorithm(&self) -> &'static str {
+        "ed25519"
+    }
+
+    fn public_key(&self) -> Vec<u8> {
+        self.keypair.public().to_bytes().to_vec()
+    }
+
+    fn sign(&self, message: &[u8]) -> Vec<u8> {
+        self.keypair.sign(message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ed25519Verifier;
+
+impl Verifier for Ed25519Verifier {
+    fn algorithm(&self) -> &'static str {
+        "ed25519"
+    }
+
+    fn verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+        ed25519::PublicKey::try_from_bytes(public_key).is_ok_and(|key| key.verify(message, signature))
+    }
+}
+
+/// Signature over a change, with the algorithm and key needed to check it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signature {
+    pub algorithm: String,
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl Signature {
+    pub fn create(signer: &dyn Signer, message: &[u8]) -> Self {
+        Self {
+            algorithm: signer.algorithm().to_string(),
+            public_key: signer.public_key(),
+            signature: signer.sign(message),
+        }
+    }
+}
+
+/// Algorithms in use: the hash for new digests and every verifier still accepted.
+/// Migrating means switching `hash` or the signer and keeping the old verifier registered.
+#[derive(Clone)]
+pub struct CryptoSuite {
+    pub hash: Arc<dyn HashAlgorithm>,
+    verifiers: Vec<Arc<dyn Verifier>>,
+}
+
+impl Default for CryptoSuite {
+    fn default() -> Self {
+        Self {
+            hash: Arc::new(Sha256Hash),
+            verifiers: vec![Arc::new(Ed25519Verifier)],
+        }
+    }
+}
+
+impl CryptoSuite {
+    pub fn with_hash(mut self, hash: Arc<dyn HashAlgorithm>) -> Self {
+        self.hash = hash;
+        self
+    }
+
+    /// Accept signatures of another algorithm; replaces a verifier with the same id
+    pub fn with_verifier(mut self, verifier: Arc<dyn Verifier>) -> Self {
+        self.verifiers.retain(|v| v.algorithm() != verifier.algorithm());
+        self.verifiers.push(verifier);
+        self
+    }
+
+    pub fn digest(&self, data: &[u8]) -> ContentHash {
+        self.hash.digest(data)
+    }
+
+    pub fn verifier(&self, algorithm: &str) -> Option<&dyn Verifier> {
+        self.verifiers.iter().find(|v| v.algorithm() == algorithm).map(|v| v.as_ref())
+    }
+
+    /// False for unknown algorithms as well as bad signatures
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+        self.verifier(&signature.algorithm)
+            .is_some_and(|v| v.verify(&signature.public_key, message, &signature.signature))
+    }
+}
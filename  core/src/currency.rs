@@ -0,0 +1,148 @@
+//! Currency/commodity types and base currency re-translation
+use std::fmt;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::rounding::RoundingPolicy;
+
+/// Currency or other commodity code (e.g. "EUR", "BTC")
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Commodity(pub String);
+
+impl Commodity {
+    pub fn new(code: &str) -> Self {
+        Self(code.trim().to_uppercase())
+    }
+
+    pub fn code(&self) -> &str {
+        &self.0
+    }
+
+    /// Parse a typed currency code, accepting only ISO 4217 codes (and BTC) in any case
+    pub fn from_known_code(code: &str) -> Option<Self> {
+        let commodity = Self::new(code);
+        CURRENCY_CODES.binary_search(&commodity.code()).is_ok().then_some(commodity)
+    }
+}
+
+/// Currency codes accepted in typed amounts, sorted for binary search
+const CURRENCY_CODES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT", "BGN", "BHD", "BIF",
+    "BMD", "BND", "BOB", "BRL", "BSD", "BTC", "BTN", "BWP", "BYN", "BZD", "CAD", "CDF", "CHF", "CLP", "CNY", "COP",
+    "CRC", "CUP", "CVE", "CZK", "DJF", "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL",
+    "GHS", "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR", "IQD", "IRR", "ISK",
+    "JMD", "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW", "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD",
+    "LSL", "LYD", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN", "MYR", "MZN",
+    "NAD", "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN", "PYG", "QAR", "RON",
+    "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD", "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC",
+    "SYP", "SZL", "THB", "TJS", "TMT", "TND", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "UYU", "UZS",
+    "VES", "VND", "VUV", "WST", "XAF", "XCD", "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWL",
+];
+
+impl Default for Commodity {
+    fn default() -> Self {
+        Self::new("USD")
+    }
+}
+
+impl fmt::Display for Commodity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// One account balance translated into the new base currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetranslatedBalance {
+    pub account_id: Uuid,
+    pub original: Decimal,
+    pub translated: Option<Decimal>,
+}
+
+/// Guided base currency change: review the lines, supply a rate, then apply
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Retranslation {
+    pub from: Commodity,
+    pub to: Commodity,
+    pub rate: Option<Decimal>,
+    pub lines: Vec<RetranslatedBalance>,
+}
+
+impl Retranslation {
+    /// Set conversion rate (1 `from` = `rate` `to`) and translate all lines
+    pub fn with_rate(mut self, rate: Decimal) -> Self {
+        for line in &mut self.lines {
+            line.translated = Some(line.original * rate);
+        }
+        self.rate = Some(rate);
+        self
+    }
+
+    /// True once every line has a translated amount
+    pub fn is_ready(&self) -> bool {
+        self.rate.is_some() && self.lines.iter().all(|l| l.translated.is_some())
+    }
+}
+
+/// Conversion the bank actually applied to a foreign-currency posting
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConversionCapture {
+    pub foreign: Commodity,
+    pub foreign_amount: Decimal,
+    /// Base currency units per foreign unit as charged by the bank
+    pub rate: Decimal,
+    /// Explicit conversion fee in base currency
+    #[serde(default)]
+    pub fee: Decimal,
+}
+
+impl ConversionCapture {
+    /// Base currency amount implied by the bank rate (excluding fee), rounded by the ledger's policy
+    pub fn base_amount(&self, base: &Commodity, policy: &RoundingPolicy) -> Decimal {
+        policy.round(self.foreign_amount * self.rate, base)
+    }
+}
+
+/// Realized FX outcome of one converted posting against the market rate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversionLine {
+    pub transaction_id: Uuid,
+    pub date: NaiveDate,
+    pub account_id: Uuid,
+    pub foreign: Commodity,
+    pub foreign_amount: Decimal,
+    pub bank_rate: Decimal,
+    pub market_rate: Option<Decimal>,
+    /// Bank valuation minus market valuation (positive debit = paid more than market)
+    pub rate_difference: Option<Decimal>,
+    pub fee: Decimal,
+}
+
+/// Realized FX and fee report over captured conversions; `market_rate` supplies reference rates
+/// and `rounding` rounds the rate difference in each posting's commodity
+pub fn conversion_report(
+    transactions: &[crate::ledger::Transaction],
+    market_rate: impl Fn(&Commodity, NaiveDate) -> Option<Decimal>,
+    rounding: &RoundingPolicy,
+) -> Vec<ConversionLine> {
+    transactions.iter()
+        .flat_map(|tx| tx.postings.iter().map(move |p| (tx, p)))
+        .filter_map(|(tx, p)| {
+            let c = p.conversion.as_ref()?;
+            let market = market_rate(&c.foreign, tx.date);
+            Some(ConversionLine {
+                transaction_id: tx.id,
+                date: tx.date,
+                account_id: p.account_id,
+                foreign: c.foreign.clone(),
+                foreign_amount: c.foreign_amount,
+                bank_rate: c.rate,
+                market_rate: market,
+                rate_difference: market.map(|m| rounding.round(c.foreign_amount * (c.rate - m), &p.commodity)),
+                fee: c.fee,
+            })
+        })
+        .collect()
+}
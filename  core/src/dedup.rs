@@ -0,0 +1,67 @@
+//! Content-hash cache so relayed copies of the same gossip message are merged once
+use std::collections::{HashSet, VecDeque};
+
+use crate::crypto::{HashAlgorithm, Sha256Hash};
+
+pub type ContentHash = [u8; 32];
+
+/// Digest with the default algorithm; use a `CryptoSuite` where the algorithm is configurable
+pub fn content_hash(data: &[u8]) -> ContentHash {
+    Sha256Hash.digest(data)
+}
+
+/// Bounded FIFO set of recently seen message hashes
+#[derive(Debug, Clone)]
+pub struct DedupCache {
+    capacity: usize,
+    order: VecDeque<ContentHash>,
+    seen: HashSet<ContentHash>,
+}
+
+impl DedupCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            order: VecDeque::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Returns true the first time a payload is seen, false for repeats
+    pub fn insert(&mut self, data: &[u8]) -> bool {
+        let hash = content_hash(data);
+        if !self.seen.insert(hash) {
+            return false;
+        }
+        self.order.push_back(hash);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    pub fn contains(&self, data: &[u8]) -> bool {
+        self.seen.contains(&content_hash(data))
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.order.clear();
+        self.seen.clear();
+    }
+}
+
+impl Default for DedupCache {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
@@ -0,0 +1,282 @@
+//! Delegated data entry: signed, expiring write tokens that let a helper device add transactions
+//! to a few accounts without being a full member of the book. A token is redeemed by one device
+//! only; its scope is checked when the helper records and again when the helper's changes merge.
+use std::collections::{BTreeMap, BTreeSet};
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::crypto::{CryptoSuite, Signature, Signer};
+use crate::ledger::Transaction;
+use crate::presence::device_id;
+use crate::storage::LocalStorage;
+
+const SETTINGS_KEY: &str = "write_tokens";
+const TRUST_KEY: &str = "device_trust";
+
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum DelegationError {
+    #[error("Token signature is invalid")]
+    BadSignature,
+    #[error("Token expired")]
+    Expired,
+    #[error("Token revoked")]
+    Revoked,
+    #[error("Token was already redeemed by another device")]
+    AlreadyRedeemed,
+    #[error("Account {0} is outside the token's scope")]
+    AccountNotAllowed(Uuid),
+}
+
+/// What a token allows: new transactions whose postings all hit the listed accounts, until expiry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteGrant {
+    pub id: Uuid,
+    /// Who or what the token was made for, e.g. "Receipt scanner at front desk"
+    pub label: String,
+    pub accounts: BTreeSet<Uuid>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl WriteGrant {
+    pub fn new(label: impl Into<String>, accounts: impl IntoIterator<Item = Uuid>, expires_at: DateTime<Utc>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            label: label.into(),
+            accounts: accounts.into_iter().collect(),
+            expires_at,
+        }
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Whether a new transaction falls inside the grant
+    pub fn permits(&self, tx: &Transaction, now: DateTime<Utc>) -> Result<(), DelegationError> {
+        if self.is_expired(now) {
+            return Err(DelegationError::Expired);
+        }
+        match tx.postings.iter().find(|p| !self.accounts.contains(&p.account_id)) {
+            Some(posting) => Err(DelegationError::AccountNotAllowed(posting.account_id)),
+            None => Ok(()),
+        }
+    }
+
+    fn signing_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+}
+
+/// A grant signed by the book owner, handed to the helper device or HTTP client
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteToken {
+    pub grant: WriteGrant,
+    pub signature: Signature,
+}
+
+impl WriteToken {
+    pub fn issue(grant: WriteGrant, signer: &dyn Signer) -> Self {
+        let signature = Signature::create(signer, &grant.signing_bytes());
+        Self { grant, signature }
+    }
+
+    /// Check the token was signed by `issuer_key` and has not expired
+    pub fn verify(&self, suite: &CryptoSuite, issuer_key: &[u8], now: DateTime<Utc>) -> Result<(), DelegationError> {
+        if self.signature.public_key != issuer_key || !suite.verify(&self.grant.signing_bytes(), &self.signature) {
+            return Err(DelegationError::BadSignature);
+        }
+        if self.grant.is_expired(now) {
+            return Err(DelegationError::Expired);
+        }
+        Ok(())
+    }
+}
+
+/// Owner-side record of issued tokens: which device redeemed each one and which were revoked
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DelegationRegistry {
+    issued: BTreeMap<Uuid, WriteGrant>,
+    redeemed: BTreeMap<Uuid, String>,
+    revoked: BTreeSet<Uuid>,
+}
+
+impl DelegationRegistry {
+    pub fn load(storage: &LocalStorage) -> Self {
+        storage.get_setting(SETTINGS_KEY)
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &LocalStorage) {
+        storage.set_setting(SETTINGS_KEY, &serde_json::to_string(self).unwrap());
+    }
+
+    /// Sign a new token and remember its grant
+    pub fn issue(&mut self, grant: WriteGrant, signer: &dyn Signer) -> WriteToken {
+        self.issued.insert(grant.id, grant.clone());
+        WriteToken::issue(grant, signer)
+    }
+
+    /// Bind a token to the device presenting it. The first device wins; the same device may
+    /// present it again, e.g. after reconnecting.
+    pub fn redeem(
+        &mut self,
+        token: &WriteToken,
+        device: &str,
+        suite: &CryptoSuite,
+        issuer_key: &[u8],
+        now: DateTime<Utc>,
+    ) -> Result<&WriteGrant, DelegationError> {
+        token.verify(suite, issuer_key, now)?;
+        let id = token.grant.id;
+        // Tokens signed elsewhere with our key are accepted only if we issued them
+        if self.revoked.contains(&id) || !self.issued.contains_key(&id) {
+            return Err(DelegationError::Revoked);
+        }
+        match self.redeemed.get(&id) {
+            Some(holder) if holder != device => return Err(DelegationError::AlreadyRedeemed),
+            Some(_) => {}
+            None => {
+                self.redeemed.insert(id, device.to_string());
+            }
+        }
+        Ok(&self.issued[&id])
+    }
+
+    /// Revoke a token; returns false if it was never issued
+    pub fn revoke(&mut self, id: &Uuid) -> bool {
+        self.issued.contains_key(id) && self.revoked.insert(*id)
+    }
+
+    pub fn is_revoked(&self, id: &Uuid) -> bool {
+        self.revoked.contains(id)
+    }
+
+    /// Device that redeemed a token
+    pub fn holder(&self, id: &Uuid) -> Option<&str> {
+        self.redeemed.get(id).map(String::as_str)
+    }
+
+    /// Issued tokens that are neither revoked nor expired
+    pub fn active(&self, now: DateTime<Utc>) -> impl Iterator<Item = &WriteGrant> {
+        self.issued.values().filter(move |g| !self.revoked.contains(&g.id) && !g.is_expired(now))
+    }
+}
+
+/// How far a received payload is trusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trust<'a> {
+    Full,
+    /// A helper's changes, kept only within its grant
+    Limited(&'a WriteGrant),
+    Refused,
+}
+
+/// Member-side record of which signing keys are trusted, persisted so helpers stay limited after
+/// a restart. A book with no members or helpers enrolled is open and takes unsigned documents;
+/// once one is, every document must be signed by a member or an admitted helper.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceTrust {
+    /// Signing keys of full members, hex encoded
+    members: BTreeSet<String>,
+    /// Admitted helpers by signing key; `None` once dismissed or revoked, so a former helper
+    /// never counts as a full member
+    delegates: BTreeMap<String, Option<WriteGrant>>,
+}
+
+impl DeviceTrust {
+    pub fn load(storage: &LocalStorage) -> Self {
+        storage.get_setting(TRUST_KEY)
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &LocalStorage) {
+        storage.set_setting(TRUST_KEY, &serde_json::to_string(self).unwrap());
+    }
+
+    /// Trust documents signed with `device_key` fully
+    pub fn add_member(&mut self, device_key: &[u8]) {
+        self.members.insert(device_id(device_key));
+    }
+
+    pub fn remove_member(&mut self, device_key: &[u8]) -> bool {
+        self.members.remove(&device_id(device_key))
+    }
+
+    /// Limit documents signed with `device_key` to `grant`
+    pub fn admit(&mut self, device_key: &[u8], grant: WriteGrant) {
+        self.delegates.insert(device_id(device_key), Some(grant));
+    }
+
+    /// Refuse a helper's documents from now on; false if it was not admitted or already dismissed
+    pub fn dismiss(&mut self, device_key: &[u8]) -> bool {
+        self.delegates.get_mut(&device_id(device_key)).is_some_and(|grant| grant.take().is_some())
+    }
+
+    /// Dismiss helpers whose tokens the registry has revoked; returns whether any were
+    pub fn apply_revocations(&mut self, registry: &DelegationRegistry) -> bool {
+        let mut changed = false;
+        for grant in self.delegates.values_mut() {
+            if grant.as_ref().is_some_and(|g| registry.is_revoked(&g.id)) {
+                *grant = None;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Whether nobody is enrolled yet, so unsigned documents are still taken
+    pub fn is_open(&self) -> bool {
+        self.members.is_empty() && self.delegates.is_empty()
+    }
+
+    /// Trust for a payload signed by `signer`, or unsigned. Unsigned payloads can come from
+    /// anyone, helpers included, so they are only trusted while the book is open.
+    pub fn trust(&self, signer: Option<&[u8]>) -> Trust<'_> {
+        let Some(key) = signer.map(device_id) else {
+            return if self.is_open() { Trust::Full } else { Trust::Refused };
+        };
+        if self.members.contains(&key) {
+            return Trust::Full;
+        }
+        match self.delegates.get(&key) {
+            Some(Some(grant)) => Trust::Limited(grant),
+            Some(None) => Trust::Refused,
+            None if self.is_open() => Trust::Full,
+            None => Trust::Refused,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::StorageConfig;
+
+    #[test]
+    fn unsigned_payloads_are_refused_once_a_helper_is_admitted() {
+        let storage = LocalStorage::with_config(&StorageConfig { path: ":memory:".into(), ..StorageConfig::default() });
+        let (member, helper, stranger) = (b"member".as_slice(), b"helper".as_slice(), b"stranger".as_slice());
+        let grant = WriteGrant::new("Scanner", [Uuid::new_v4()], Utc::now() + chrono::Duration::days(1));
+
+        let mut trust = DeviceTrust::load(&storage);
+        assert_eq!(trust.trust(None), Trust::Full);
+        trust.add_member(member);
+        trust.admit(helper, grant.clone());
+        trust.save(&storage);
+
+        // A restart must not forget the helper
+        let mut trust = DeviceTrust::load(&storage);
+        assert_eq!(trust.trust(None), Trust::Refused);
+        assert_eq!(trust.trust(Some(stranger)), Trust::Refused);
+        assert_eq!(trust.trust(Some(member)), Trust::Full);
+        assert_eq!(trust.trust(Some(helper)), Trust::Limited(&grant));
+
+        assert!(trust.dismiss(helper));
+        assert_eq!(trust.trust(Some(helper)), Trust::Refused);
+        assert!(!trust.dismiss(helper));
+    }
+}
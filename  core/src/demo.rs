@@ -0,0 +1,132 @@
+//! Synthetic but believable books for demos, tests and benchmarks
+// Amounts are written in cents with the decimal point as `_`, e.g. 4200_00
+#![allow(clippy::inconsistent_digit_grouping)]
+use chrono::{Datelike, Duration, NaiveDate};
+use rust_decimal::Decimal;
+
+use crate::ledger::{Account, AccountType, Ledger, Posting, Transaction};
+
+/// Parameters for a generated book
+#[derive(Debug, Clone)]
+pub struct DemoConfig {
+    pub start: NaiveDate,
+    pub years: u32,
+    /// Same seed, same book
+    pub seed: u64,
+    pub monthly_salary: Decimal,
+    pub monthly_rent: Decimal,
+}
+
+impl Default for DemoConfig {
+    fn default() -> Self {
+        Self {
+            start: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+            years: 3,
+            seed: 42,
+            monthly_salary: Decimal::new(4200_00, 2),
+            monthly_rent: Decimal::new(1350_00, 2),
+        }
+    }
+}
+
+/// Generated accounts, transactions and the ledger they were recorded into
+#[derive(Debug, Clone)]
+pub struct DemoBook {
+    pub ledger: Ledger,
+    pub accounts: Vec<Account>,
+    pub transactions: Vec<Transaction>,
+}
+
+/// xorshift64*; good enough for demo data without pulling in an RNG crate
+struct DemoRng(u64);
+
+impl DemoRng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Amount in cents uniformly between min and max
+    fn amount(&mut self, min_cents: i64, max_cents: i64) -> Decimal {
+        let span = (max_cents - min_cents).max(1) as u64;
+        Decimal::new(min_cents + (self.next() % span) as i64, 2)
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[(self.next() % items.len() as u64) as usize]
+    }
+}
+
+const GROCERS: &[&str] = &["Corner Market", "FreshCo", "Green Basket", "Daily Foods"];
+const RESTAURANTS: &[&str] = &["Luigi's", "Sushi Bar", "Taco Stand", "The Diner"];
+
+/// Generate a personal book with salary, rent, groceries, dining, utilities and savings transfers
+pub fn generate(config: &DemoConfig) -> DemoBook {
+    let mut rng = DemoRng(config.seed.max(1));
+
+    let checking = Account::new("Assets:Checking", AccountType::Asset);
+    let savings = Account::new("Assets:Savings", AccountType::Asset);
+    let credit_card = Account::new("Liabilities:Credit Card", AccountType::Liability);
+    let opening = Account::new("Equity:Opening Balances", AccountType::Equity);
+    let salary = Account::new("Income:Salary", AccountType::Revenue);
+    let rent = Account::new("Expenses:Rent", AccountType::Expense);
+    let groceries = Account::new("Expenses:Groceries", AccountType::Expense);
+    let dining = Account::new("Expenses:Dining", AccountType::Expense);
+    let utilities = Account::new("Expenses:Utilities", AccountType::Expense);
+    let accounts = vec![
+        checking.clone(), savings.clone(), credit_card.clone(), opening.clone(), salary.clone(),
+        rent.clone(), groceries.clone(), dining.clone(), utilities.clone(),
+    ];
+
+    let post = |date, description: String, debit: &Account, credit: &Account, amount: Decimal| {
+        Transaction::new(date, description, vec![
+            Posting::new(debit.id, amount),
+            Posting::new(credit.id, -amount),
+        ])
+    };
+
+    let mut transactions = vec![post(
+        config.start,
+        "Opening balance".to_string(),
+        &checking,
+        &opening,
+        Decimal::new(2500_00, 2),
+    )];
+
+    let end = NaiveDate::from_ymd_opt(config.start.year() + config.years as i32, config.start.month(), 1)
+        .unwrap_or(config.start);
+    let mut date = config.start;
+    while date < end {
+        match date.day() {
+            1 => transactions.push(post(date, "Rent".to_string(), &rent, &checking, config.monthly_rent)),
+            15 => transactions.push(post(date, "Utilities".to_string(), &utilities, &checking, rng.amount(80_00, 190_00))),
+            25 => {
+                transactions.push(post(date, "Salary".to_string(), &checking, &salary, config.monthly_salary));
+                transactions.push(post(date, "Transfer to savings".to_string(), &savings, &checking, rng.amount(200_00, 600_00)));
+            }
+            28 => transactions.push(post(date, "Credit card payment".to_string(), &credit_card, &checking, rng.amount(300_00, 700_00))),
+            _ => {}
+        }
+        if date.weekday() == chrono::Weekday::Sat {
+            let store = rng.pick(GROCERS).to_string();
+            transactions.push(post(date, store, &groceries, &credit_card, rng.amount(35_00, 140_00)));
+        }
+        if rng.next().is_multiple_of(6) {
+            let place = rng.pick(RESTAURANTS).to_string();
+            transactions.push(post(date, place, &dining, &credit_card, rng.amount(12_00, 75_00)));
+        }
+        date += Duration::days(1);
+    }
+
+    let mut ledger = Ledger::new();
+    for account in &accounts {
+        ledger.add_account(account.clone()).expect("demo accounts have no codes");
+    }
+    for tx in &transactions {
+        ledger.record_transaction(tx.clone()).expect("demo transactions are balanced");
+    }
+
+    DemoBook { ledger, accounts, transactions }
+}
@@ -0,0 +1,172 @@
+//! Diagnostics for replicas that should be identical but aren't
+use std::collections::HashSet;
+use serde::{Serialize, Deserialize};
+
+use crate::sync::{SyncDoc, SyncError};
+
+/// Short description of one Automerge change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeSummary {
+    pub index: usize,
+    pub hash: String,
+    pub actor: String,
+    pub seq: u64,
+    pub timestamp: i64,
+    pub message: Option<String>,
+}
+
+/// Result of comparing two replicas
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DivergenceReport {
+    /// Length of the shared history prefix
+    pub common_prefix: usize,
+    /// First change after the shared prefix, on each side
+    pub first_divergent_left: Option<ChangeSummary>,
+    pub first_divergent_right: Option<ChangeSummary>,
+    pub only_in_left: Vec<ChangeSummary>,
+    pub only_in_right: Vec<ChangeSummary>,
+    /// Same change set but different materialized ledger (merge non-determinism)
+    pub state_differs_with_same_changes: bool,
+    /// Hex-encoded exports so the report can be replayed
+    pub left_export: String,
+    pub right_export: String,
+}
+
+impl DivergenceReport {
+    pub fn is_divergent(&self) -> bool {
+        !self.only_in_left.is_empty() || !self.only_in_right.is_empty() || self.state_differs_with_same_changes
+    }
+
+    /// Human-readable summary for bug reports
+    pub fn to_text(&self) -> String {
+        let mut out = format!("Shared history: {} changes\n", self.common_prefix);
+        if let Some(c) = &self.first_divergent_left {
+            out.push_str(&format!("First divergent change (left):  #{} {} actor={} seq={}\n", c.index, c.hash, c.actor, c.seq));
+        }
+        if let Some(c) = &self.first_divergent_right {
+            out.push_str(&format!("First divergent change (right): #{} {} actor={} seq={}\n", c.index, c.hash, c.actor, c.seq));
+        }
+        out.push_str(&format!("Only in left: {}, only in right: {}\n", self.only_in_left.len(), self.only_in_right.len()));
+        if self.state_differs_with_same_changes {
+            out.push_str("Identical change sets produce different ledger state\n");
+        }
+        out
+    }
+
+    /// Self-contained JSON bundle including both exports
+    pub fn to_json(&self) -> Result<String, SyncError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+fn summarize(doc: &SyncDoc) -> Vec<ChangeSummary> {
+    let mut doc = doc.doc.clone();
+    doc.get_changes(&[])
+        .into_iter()
+        .enumerate()
+        .map(|(index, change)| ChangeSummary {
+            index,
+            hash: change.hash().to_string(),
+            actor: change.actor_id().to_hex_string(),
+            seq: change.seq(),
+            timestamp: change.timestamp(),
+            message: change.message().cloned(),
+        })
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Bisect the causally ordered change histories of two exports to find where they part ways
+pub fn bisect_divergence(left_bytes: &[u8], right_bytes: &[u8]) -> Result<DivergenceReport, SyncError> {
+    let left = SyncDoc::from_bytes(left_bytes)?;
+    let right = SyncDoc::from_bytes(right_bytes)?;
+    let left_changes = summarize(&left);
+    let right_changes = summarize(&right);
+
+    // Prefix equality is monotonic, so binary search for the longest shared prefix
+    let (mut lo, mut hi) = (0, left_changes.len().min(right_changes.len()));
+    while lo < hi {
+        let mid = (lo + hi).div_ceil(2);
+        let same = left_changes[..mid].iter().zip(&right_changes[..mid]).all(|(a, b)| a.hash == b.hash);
+        if same {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    let common_prefix = lo;
+
+    let left_hashes: HashSet<&str> = left_changes.iter().map(|c| c.hash.as_str()).collect();
+    let right_hashes: HashSet<&str> = right_changes.iter().map(|c| c.hash.as_str()).collect();
+    let only_in_left: Vec<_> = left_changes.iter().filter(|c| !right_hashes.contains(c.hash.as_str())).cloned().collect();
+    let only_in_right: Vec<_> = right_changes.iter().filter(|c| !left_hashes.contains(c.hash.as_str())).cloned().collect();
+
+    let state_differs_with_same_changes = only_in_left.is_empty()
+        && only_in_right.is_empty()
+        && serde_json::to_value(left.to_ledger()?)? != serde_json::to_value(right.to_ledger()?)?;
+
+    Ok(DivergenceReport {
+        common_prefix,
+        first_divergent_left: left_changes.get(common_prefix).cloned(),
+        first_divergent_right: right_changes.get(common_prefix).cloned(),
+        only_in_left,
+        only_in_right,
+        state_differs_with_same_changes,
+        left_export: to_hex(left_bytes),
+        right_export: to_hex(right_bytes),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::{Account, AccountType};
+
+    /// Copy of `base` with one more change adding an account
+    fn branch(base: &SyncDoc, account: &str) -> SyncDoc {
+        let mut doc = SyncDoc::from_bytes(&base.to_bytes()).unwrap();
+        let mut ledger = doc.to_ledger().unwrap();
+        let account = Account::new(account, AccountType::Asset);
+        ledger.accounts.insert(account.id, account);
+        doc.update_from_ledger(&ledger).unwrap();
+        doc.commit();
+        doc
+    }
+
+    #[test]
+    fn identical_replicas_share_their_whole_history() {
+        let base = SyncDoc::new().unwrap().to_bytes();
+        let report = bisect_divergence(&base, &base).unwrap();
+        assert!(!report.is_divergent());
+        assert_eq!(report.common_prefix, summarize(&SyncDoc::from_bytes(&base).unwrap()).len());
+        assert!(report.first_divergent_left.is_none() && report.first_divergent_right.is_none());
+    }
+
+    #[test]
+    fn forks_are_reported_from_the_first_change_they_disagree_on() {
+        let base = SyncDoc::new().unwrap();
+        let shared = summarize(&base).len();
+        let left = branch(&base, "Cash");
+        let right = branch(&base, "Savings");
+
+        let report = bisect_divergence(&left.to_bytes(), &right.to_bytes()).unwrap();
+        assert!(report.is_divergent());
+        assert_eq!(report.common_prefix, shared);
+        assert_eq!(report.only_in_left.len(), 1);
+        assert_eq!(report.only_in_right.len(), 1);
+        assert_eq!(report.first_divergent_left.as_ref(), report.only_in_left.first());
+        assert_ne!(report.only_in_left[0].actor, report.only_in_right[0].actor);
+        assert!(report.to_text().contains("Only in left: 1, only in right: 1"));
+        assert!(report.to_json().unwrap().contains(&to_hex(&left.to_bytes())));
+
+        let (mut merged_left, mut merged_right) = (left.clone(), right.clone());
+        merged_left.merge(&right).unwrap();
+        merged_right.merge(&left).unwrap();
+        let report = bisect_divergence(&merged_left.to_bytes(), &merged_right.to_bytes()).unwrap();
+        assert!(!report.is_divergent());
+        assert!(!report.state_differs_with_same_changes);
+    }
+}
@@ -0,0 +1,63 @@
+//! Non-transaction records (notes, contracts, warranties) kept alongside the books
+use chrono::NaiveDate;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DocumentKind {
+    Note,
+    Contract,
+    Warranty,
+    Other(String),
+}
+
+/// Ledger record a document refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DocumentLink {
+    Account(Uuid),
+    Transaction(Uuid),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Document {
+    pub id: Uuid,
+    pub kind: DocumentKind,
+    pub title: String,
+    pub body: String,
+    #[serde(default)]
+    pub links: Vec<DocumentLink>,
+    pub created_on: NaiveDate,
+    /// End of a contract term or warranty period
+    #[serde(default)]
+    pub expires_on: Option<NaiveDate>,
+}
+
+impl Document {
+    pub fn new(kind: DocumentKind, title: impl Into<String>, body: impl Into<String>, created_on: NaiveDate) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            kind,
+            title: title.into(),
+            body: body.into(),
+            links: Vec::new(),
+            created_on,
+            expires_on: None,
+        }
+    }
+
+    pub fn link(mut self, link: DocumentLink) -> Self {
+        if !self.links.contains(&link) {
+            self.links.push(link);
+        }
+        self
+    }
+
+    pub fn is_linked_to(&self, link: &DocumentLink) -> bool {
+        self.links.contains(link)
+    }
+
+    /// True if the document has an expiry on or before `date`
+    pub fn expired_on(&self, date: NaiveDate) -> bool {
+        self.expires_on.is_some_and(|d| d <= date)
+    }
+}
@@ -0,0 +1,166 @@
+//! Streaming transaction export: rows are written to any `io::Write` as they are read,
+//! so a decade of history can go to a file or a chunked HTTP response on a low-RAM device
+use std::io::{self, Write};
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+use crate::codec::CodecError;
+use crate::ledger::{Transaction, TransactionStatus};
+use crate::reports::csv_field;
+use crate::storage::{LocalStorage, StorageReader};
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("Export write failed: {0}")]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Codec(#[from] CodecError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    /// One row per posting with the transaction fields repeated
+    Csv,
+    /// A single JSON array of transactions
+    Json,
+    /// One JSON transaction per line
+    JsonLines,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::JsonLines => "jsonl",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Json => "application/json",
+            ExportFormat::JsonLines => "application/x-ndjson",
+        }
+    }
+}
+
+const CSV_HEADER: &str = "date,transaction_id,reference,payee,description,status,account_id,amount,commodity,memo\n";
+
+/// Writes transactions one at a time; only the current transaction is held in memory
+pub struct TransactionExporter<W: Write> {
+    writer: W,
+    format: ExportFormat,
+    written: u64,
+}
+
+impl<W: Write> TransactionExporter<W> {
+    pub fn new(writer: W, format: ExportFormat) -> Self {
+        Self { writer, format, written: 0 }
+    }
+
+    pub fn write(&mut self, tx: &Transaction) -> io::Result<()> {
+        let first = self.written == 0;
+        match self.format {
+            ExportFormat::Csv => {
+                if first {
+                    self.writer.write_all(CSV_HEADER.as_bytes())?;
+                }
+                write_csv_rows(&mut self.writer, tx)?;
+            }
+            ExportFormat::Json => {
+                self.writer.write_all(if first { b"[\n" } else { b",\n" })?;
+                serde_json::to_writer(&mut self.writer, tx)?;
+            }
+            ExportFormat::JsonLines => {
+                serde_json::to_writer(&mut self.writer, tx)?;
+                self.writer.write_all(b"\n")?;
+            }
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Transactions written so far
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Close the document (header or brackets for empty exports) and hand back the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        match (self.format, self.written) {
+            (ExportFormat::Csv, 0) => self.writer.write_all(CSV_HEADER.as_bytes())?,
+            (ExportFormat::Json, 0) => self.writer.write_all(b"[]\n")?,
+            (ExportFormat::Json, _) => self.writer.write_all(b"\n]\n")?,
+            _ => {}
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+fn write_csv_rows(writer: &mut impl Write, tx: &Transaction) -> io::Result<()> {
+    let status = match tx.status {
+        TransactionStatus::Posted => "posted",
+        TransactionStatus::Voided => "voided",
+        TransactionStatus::Correction => "correction",
+    };
+    let prefix = format!(
+        "{},{},{},{},{},{}",
+        tx.date,
+        tx.id,
+        csv_field(tx.reference.as_deref().unwrap_or("")),
+        csv_field(tx.payee.as_deref().unwrap_or("")),
+        csv_field(&tx.description),
+        status,
+    );
+    for posting in &tx.postings {
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            prefix,
+            posting.account_id,
+            posting.amount,
+            csv_field(&posting.commodity.to_string()),
+            csv_field(posting.memo.as_deref().unwrap_or("")),
+        )?;
+    }
+    Ok(())
+}
+
+/// Export transactions from any iterator (e.g. `Ledger::transactions`); returns the count written
+pub fn export_transactions<'a, W: Write>(
+    transactions: impl IntoIterator<Item = &'a Transaction>,
+    writer: W,
+    format: ExportFormat,
+) -> io::Result<u64> {
+    let mut exporter = TransactionExporter::new(writer, format);
+    for tx in transactions {
+        exporter.write(tx)?;
+    }
+    let written = exporter.written();
+    exporter.finish()?;
+    Ok(written)
+}
+
+/// Export every stored transaction, decoding one row at a time
+pub fn export_storage<W: Write>(storage: &LocalStorage, writer: W, format: ExportFormat) -> Result<u64, ExportError> {
+    let mut exporter = TransactionExporter::new(writer, format);
+    storage.for_each_transaction(|row| export_row(&mut exporter, &row.decode()?))?;
+    let written = exporter.written();
+    exporter.finish()?;
+    Ok(written)
+}
+
+/// Same as `export_storage` on a read-only connection, so an export doesn't block the writer
+pub fn export_reader<W: Write>(reader: &StorageReader, writer: W, format: ExportFormat) -> Result<u64, ExportError> {
+    let mut exporter = TransactionExporter::new(writer, format);
+    reader.for_each_transaction(|row| export_row(&mut exporter, &row.decode()?))?;
+    let written = exporter.written();
+    exporter.finish()?;
+    Ok(written)
+}
+
+fn export_row<W: Write>(exporter: &mut TransactionExporter<W>, tx: &Transaction) -> Result<(), ExportError> {
+    Ok(exporter.write(tx)?)
+}
@@ -0,0 +1,249 @@
+//! Bank statement import from CSV. Each row becomes a staged entry with its bank-side posting;
+//! malformed rows are reported by line while the rest of the file is still staged,
+//! and corrected rows can be resubmitted on their own.
+use std::collections::BTreeMap;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::ledger::reconcile::StatementLine;
+use crate::ledger::Posting;
+use crate::locale::Locale;
+use crate::rules::{apply_rules_staged, CategorizationRule};
+use crate::staging::{StagedTransaction, StagingArea, StagingSource};
+
+/// Why a single row could not be staged
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum RowErrorKind {
+    #[error("Unterminated quoted field")]
+    MalformedRow,
+    #[error("Missing {0} column")]
+    MissingField(String),
+    #[error("Invalid date: {0}")]
+    InvalidDate(String),
+    #[error("Invalid amount: {0}")]
+    InvalidAmount(String),
+}
+
+/// A rejected row with its 1-based line number and original text, so it can be corrected
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RowError {
+    pub line: usize,
+    pub raw: String,
+    pub kind: RowErrorKind,
+}
+
+/// Outcome of an import: entries staged (by line) and rows rejected
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub staged: BTreeMap<usize, Uuid>,
+    pub errors: Vec<RowError>,
+}
+
+impl ImportReport {
+    /// True when every row was staged
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Fold in the report of a resubmission: fixed rows move from errors to staged
+    pub fn merge(&mut self, resubmitted: ImportReport) {
+        self.errors.retain(|e| !resubmitted.staged.contains_key(&e.line));
+        for error in resubmitted.errors {
+            match self.errors.iter_mut().find(|e| e.line == error.line) {
+                Some(existing) => *existing = error,
+                None => self.errors.push(error),
+            }
+        }
+        self.errors.sort_by_key(|e| e.line);
+        self.staged.extend(resubmitted.staged);
+    }
+}
+
+/// Column positions (0-based) of a statement layout
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatementColumns {
+    pub date: usize,
+    pub amount: usize,
+    #[serde(default)]
+    pub payee: Option<usize>,
+    #[serde(default)]
+    pub description: Option<usize>,
+    /// IBAN or account number of the other party
+    #[serde(default)]
+    pub counterparty_account: Option<usize>,
+}
+
+/// Reads one bank's CSV layout into entries against a single account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementImporter {
+    /// Bank account the statement belongs to
+    pub account_id: Uuid,
+    pub columns: StatementColumns,
+    pub delimiter: char,
+    /// Skip the first line
+    pub has_header: bool,
+    /// Date format and separators used by the bank
+    pub locale: Locale,
+    /// Categorization rules applied to each staged row
+    #[serde(default)]
+    pub rules: Vec<CategorizationRule>,
+}
+
+impl StatementImporter {
+    pub fn new(account_id: Uuid, columns: StatementColumns) -> Self {
+        Self {
+            account_id,
+            columns,
+            delimiter: ',',
+            has_header: true,
+            locale: Locale::default(),
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    pub fn with_rules(mut self, rules: Vec<CategorizationRule>) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn without_header(mut self) -> Self {
+        self.has_header = false;
+        self
+    }
+
+    /// Stage every valid row and report the rest; blank lines are ignored
+    pub fn import(&self, input: &str, staging: &mut StagingArea) -> ImportReport {
+        let rows = input.lines()
+            .enumerate()
+            .skip(usize::from(self.has_header))
+            .map(|(i, line)| (i + 1, line.to_string()));
+        self.stage_rows(rows, staging)
+    }
+
+    /// Stage corrected rows, keyed by the line number they were reported under
+    pub fn resubmit(&self, corrections: impl IntoIterator<Item = (usize, String)>, staging: &mut StagingArea) -> ImportReport {
+        self.stage_rows(corrections, staging)
+    }
+
+    fn stage_rows(&self, rows: impl IntoIterator<Item = (usize, String)>, staging: &mut StagingArea) -> ImportReport {
+        let mut report = ImportReport::default();
+        for (line, raw) in rows {
+            if raw.trim().is_empty() {
+                continue;
+            }
+            match self.parse_row(&raw) {
+                Ok(mut entry) => {
+                    apply_rules_staged(&self.rules, &mut entry);
+                    report.staged.insert(line, staging.stage(entry));
+                }
+                Err(kind) => report.errors.push(RowError { line, raw, kind }),
+            }
+        }
+        report
+    }
+
+    /// Parse one row into a staged entry holding only the bank-side posting
+    pub fn parse_row(&self, raw: &str) -> Result<StagedTransaction, RowErrorKind> {
+        let row = self.parse_fields(raw)?;
+        let mut entry = StagedTransaction::new(StagingSource::Import);
+        entry.date = Some(row.date);
+        entry.amount = Some(row.amount);
+        entry.description = row.description.or_else(|| row.payee.clone()).unwrap_or_default();
+        entry.payee = row.payee;
+        entry.counterparty_account = row.counterparty_account;
+        entry.postings.push(Posting::new(self.account_id, row.amount));
+        Ok(entry)
+    }
+
+    /// Read an official statement's lines, e.g. the CSV export accompanying a PDF statement.
+    /// Malformed rows are reported like in `import`.
+    pub fn parse_statement_lines(&self, input: &str) -> (Vec<StatementLine>, Vec<RowError>) {
+        let mut lines = Vec::new();
+        let mut errors = Vec::new();
+        for (i, raw) in input.lines().enumerate().skip(usize::from(self.has_header)) {
+            if raw.trim().is_empty() {
+                continue;
+            }
+            match self.parse_fields(raw) {
+                Ok(row) => lines.push(StatementLine {
+                    date: row.date,
+                    amount: row.amount,
+                    description: row.description.or(row.payee).unwrap_or_default(),
+                }),
+                Err(kind) => errors.push(RowError { line: i + 1, raw: raw.to_string(), kind }),
+            }
+        }
+        (lines, errors)
+    }
+
+    fn parse_fields(&self, raw: &str) -> Result<ParsedRow, RowErrorKind> {
+        let fields = split_fields(raw, self.delimiter).ok_or(RowErrorKind::MalformedRow)?;
+        let field = |index: usize, name: &str| {
+            fields.get(index)
+                .map(|f| f.trim())
+                .filter(|f| !f.is_empty())
+                .ok_or_else(|| RowErrorKind::MissingField(name.to_string()))
+        };
+        let optional = |index: Option<usize>| {
+            index.and_then(|i| fields.get(i)).map(|f| f.trim().to_string()).filter(|f| !f.is_empty())
+        };
+
+        let date = field(self.columns.date, "date")?;
+        let date = self.locale.parse_date(date).map_err(|_| RowErrorKind::InvalidDate(date.to_string()))?;
+        let amount = field(self.columns.amount, "amount")?;
+        let amount = self.locale.parse_amount(amount).map_err(|_| RowErrorKind::InvalidAmount(amount.to_string()))?;
+        Ok(ParsedRow {
+            date,
+            amount,
+            payee: optional(self.columns.payee),
+            description: optional(self.columns.description),
+            counterparty_account: optional(self.columns.counterparty_account),
+        })
+    }
+}
+
+/// Fields of one row before they become a staged entry or statement line
+struct ParsedRow {
+    date: NaiveDate,
+    amount: Decimal,
+    payee: Option<String>,
+    description: Option<String>,
+    counterparty_account: Option<String>,
+}
+
+/// Split a CSV line, honouring double quotes and "" escapes; None for an unterminated quote
+fn split_fields(line: &str, delimiter: char) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    fields.push(current);
+    Some(fields)
+}
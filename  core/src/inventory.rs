@@ -0,0 +1,341 @@
+//! Perpetual inventory with average-cost COGS. Stock levels are the sum of recorded movements,
+//! so movements from different devices merge by id like transactions do.
+use std::collections::HashMap;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::ledger::{Ledger, LedgerError, Transaction};
+
+/// Catalog entry for a stocked item
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Item {
+    pub id: Uuid,
+    pub sku: String,
+    pub name: String,
+    pub inventory_account: Uuid,
+    pub cogs_account: Uuid,
+    pub revenue_account: Uuid,
+}
+
+/// Quantity on hand and its total carrying cost
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StockLevel {
+    pub quantity: Decimal,
+    pub total_cost: Decimal,
+}
+
+impl StockLevel {
+    /// Current moving average unit cost
+    pub fn average_cost(&self) -> Decimal {
+        if self.quantity.is_zero() {
+            Decimal::ZERO
+        } else {
+            self.total_cost / self.quantity
+        }
+    }
+}
+
+/// Stock received (positive) or issued (negative) by one recorded transaction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StockMovement {
+    pub id: Uuid,
+    pub item_id: Uuid,
+    pub date: NaiveDate,
+    #[serde(with = "crate::canonical::serde_decimal")]
+    pub quantity: Decimal,
+    /// Carrying cost added or removed
+    #[serde(with = "crate::canonical::serde_decimal")]
+    pub cost: Decimal,
+    pub transaction_id: Uuid,
+}
+
+/// Inventory valuation report line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValuationLine {
+    pub item_id: Uuid,
+    pub sku: String,
+    pub name: String,
+    pub inventory_account: Uuid,
+    pub quantity: Decimal,
+    pub average_cost: Decimal,
+    pub value: Decimal,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InventoryError {
+    #[error("Unknown item: {0}")]
+    UnknownItem(Uuid),
+    #[error("Duplicate SKU: {0}")]
+    DuplicateSku(String),
+    #[error("Quantity must be positive")]
+    InvalidQuantity,
+    #[error("Unit cost must be positive")]
+    InvalidUnitCost,
+    #[error("Unit price must be positive")]
+    InvalidUnitPrice,
+    #[error("Insufficient stock: {available} available")]
+    InsufficientStock { available: Decimal },
+    #[error(transparent)]
+    Ledger(#[from] LedgerError),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Inventory {
+    items: HashMap<Uuid, Item>,
+    movements: HashMap<Uuid, StockMovement>,
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inventory rebuilt from stored items and movements
+    pub fn from_records(items: impl IntoIterator<Item = Item>, movements: impl IntoIterator<Item = StockMovement>) -> Self {
+        Self {
+            items: items.into_iter().map(|i| (i.id, i)).collect(),
+            movements: movements.into_iter().map(|m| (m.id, m)).collect(),
+        }
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &Item> {
+        self.items.values()
+    }
+
+    pub fn movements(&self) -> impl Iterator<Item = &StockMovement> {
+        self.movements.values()
+    }
+
+    /// Add item to the catalog
+    pub fn add_item(&mut self, item: Item) -> Result<(), InventoryError> {
+        if self.items.values().any(|i| i.sku == item.sku && i.id != item.id) {
+            return Err(InventoryError::DuplicateSku(item.sku));
+        }
+        self.items.insert(item.id, item);
+        Ok(())
+    }
+
+    pub fn item(&self, id: &Uuid) -> Option<&Item> {
+        self.items.get(id)
+    }
+
+    pub fn item_by_sku(&self, sku: &str) -> Option<&Item> {
+        self.items.values().find(|i| i.sku == sku)
+    }
+
+    pub fn stock(&self, id: &Uuid) -> StockLevel {
+        self.stock_as_of(id, NaiveDate::MAX)
+    }
+
+    /// Stock from movements dated on or before `date`
+    pub fn stock_as_of(&self, id: &Uuid, date: NaiveDate) -> StockLevel {
+        let mut level = StockLevel::default();
+        for m in self.movements.values().filter(|m| m.item_id == *id && m.date <= date) {
+            level.quantity += m.quantity;
+            level.total_cost += m.cost;
+        }
+        level
+    }
+
+    /// Record the transaction, then the movement; a rejected transaction leaves stock untouched
+    fn record(
+        &mut self,
+        ledger: &mut Ledger,
+        tx: Transaction,
+        item_id: Uuid,
+        quantity: Decimal,
+        cost: Decimal,
+    ) -> Result<Transaction, InventoryError> {
+        ledger.record_transaction(tx.clone())?;
+        let movement = StockMovement { id: Uuid::new_v4(), item_id, date: tx.date, quantity, cost, transaction_id: tx.id };
+        self.movements.insert(movement.id, movement);
+        Ok(tx)
+    }
+
+    /// Receive stock: records Dr inventory, Cr the paying account, in the ledger's base currency.
+    /// Quantity and unit cost must be positive.
+    pub fn purchase(
+        &mut self,
+        ledger: &mut Ledger,
+        item_id: Uuid,
+        quantity: Decimal,
+        unit_cost: Decimal,
+        date: NaiveDate,
+        pay_from: Uuid,
+    ) -> Result<Transaction, InventoryError> {
+        if quantity <= Decimal::ZERO {
+            return Err(InventoryError::InvalidQuantity);
+        }
+        if unit_cost <= Decimal::ZERO {
+            return Err(InventoryError::InvalidUnitCost);
+        }
+        let item = self.items.get(&item_id).ok_or(InventoryError::UnknownItem(item_id))?;
+        let cost = quantity * unit_cost;
+
+        let tx = Transaction::new(
+            date,
+            format!("Purchase {} x {}", quantity, item.name),
+            vec![
+                ledger.posting(item.inventory_account, cost),
+                ledger.posting(pay_from, -cost),
+            ],
+        );
+
+        self.record(ledger, tx, item_id, quantity, cost)
+    }
+
+    /// Sell stock: records Dr receivable / Cr revenue, and Dr COGS / Cr inventory at average cost,
+    /// in the ledger's base currency. Quantity and unit price must be positive.
+    pub fn sale(
+        &mut self,
+        ledger: &mut Ledger,
+        item_id: Uuid,
+        quantity: Decimal,
+        unit_price: Decimal,
+        date: NaiveDate,
+        receive_into: Uuid,
+    ) -> Result<Transaction, InventoryError> {
+        if quantity <= Decimal::ZERO {
+            return Err(InventoryError::InvalidQuantity);
+        }
+        if unit_price <= Decimal::ZERO {
+            return Err(InventoryError::InvalidUnitPrice);
+        }
+        let item = self.items.get(&item_id).ok_or(InventoryError::UnknownItem(item_id))?;
+        let level = self.stock(&item_id);
+        if level.quantity < quantity {
+            return Err(InventoryError::InsufficientStock { available: level.quantity });
+        }
+
+        let revenue = quantity * unit_price;
+        // Selling everything clears the remaining cost exactly, avoiding rounding dust
+        let cogs = if level.quantity == quantity {
+            level.total_cost
+        } else {
+            ledger.rounding_policy().round(level.average_cost() * quantity, ledger.base_currency())
+        };
+
+        let tx = Transaction::new(
+            date,
+            format!("Sale {} x {}", quantity, item.name),
+            vec![
+                ledger.posting(receive_into, revenue),
+                ledger.posting(item.revenue_account, -revenue),
+                ledger.posting(item.cogs_account, cogs),
+                ledger.posting(item.inventory_account, -cogs),
+            ],
+        );
+
+        self.record(ledger, tx, item_id, -quantity, -cogs)
+    }
+
+    /// Valuation of stock on hand, per item
+    pub fn valuation(&self) -> Vec<ValuationLine> {
+        self.valuation_as_of(NaiveDate::MAX)
+    }
+
+    /// Valuation of stock held at the end of `date`, per item
+    pub fn valuation_as_of(&self, date: NaiveDate) -> Vec<ValuationLine> {
+        let mut lines: Vec<ValuationLine> = self.items.values()
+            .map(|item| {
+                let level = self.stock_as_of(&item.id, date);
+                ValuationLine {
+                    item_id: item.id,
+                    sku: item.sku.clone(),
+                    name: item.name.clone(),
+                    inventory_account: item.inventory_account,
+                    quantity: level.quantity,
+                    average_cost: level.average_cost(),
+                    value: level.total_cost,
+                }
+            })
+            .collect();
+        lines.sort_by(|a, b| a.sku.cmp(&b.sku));
+        lines
+    }
+
+    /// Total inventory value per inventory account at the end of `date`, to tie into the
+    /// balance sheet
+    pub fn value_by_account(&self, date: NaiveDate) -> HashMap<Uuid, Decimal> {
+        let mut totals = HashMap::new();
+        for line in self.valuation_as_of(date) {
+            *totals.entry(line.inventory_account).or_insert(Decimal::ZERO) += line.value;
+        }
+        totals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::AccountType;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    /// Ledger with the item's accounts and a cash account, and an inventory stocking one widget
+    fn books() -> (Ledger, Inventory, Uuid, Uuid) {
+        let mut ledger = Ledger::new();
+        let mut add = |name: &str, r#type| {
+            let account = ledger.new_account(name, r#type);
+            let id = account.id;
+            ledger.add_account(account).unwrap();
+            id
+        };
+        let cash = add("Cash", AccountType::Asset);
+        let item = Item {
+            id: Uuid::new_v4(),
+            sku: "W-1".to_string(),
+            name: "Widget".to_string(),
+            inventory_account: add("Inventory", AccountType::Asset),
+            cogs_account: add("Cost of goods sold", AccountType::Expense),
+            revenue_account: add("Sales", AccountType::Revenue),
+        };
+        let id = item.id;
+        let mut inventory = Inventory::new();
+        inventory.add_item(item).unwrap();
+        (ledger, inventory, id, cash)
+    }
+
+    #[test]
+    fn purchase_rejects_non_positive_unit_cost() {
+        let (mut ledger, mut inventory, id, cash) = books();
+        let on = date(2024, 5, 1);
+
+        for cost in [Decimal::ZERO, Decimal::from(-3)] {
+            let result = inventory.purchase(&mut ledger, id, Decimal::from(2), cost, on, cash);
+            assert!(matches!(result, Err(InventoryError::InvalidUnitCost)));
+        }
+        assert!(inventory.stock(&id).quantity.is_zero());
+        assert!(inventory.purchase(&mut ledger, id, Decimal::from(2), Decimal::from(3), on, cash).is_ok());
+        assert_eq!(ledger.transactions().count(), 1);
+    }
+
+    #[test]
+    fn sale_rejects_non_positive_unit_price() {
+        let (mut ledger, mut inventory, id, cash) = books();
+        inventory.purchase(&mut ledger, id, Decimal::from(2), Decimal::from(3), date(2024, 5, 1), cash).unwrap();
+
+        for price in [Decimal::ZERO, Decimal::from(-5)] {
+            let result = inventory.sale(&mut ledger, id, Decimal::ONE, price, date(2024, 5, 2), cash);
+            assert!(matches!(result, Err(InventoryError::InvalidUnitPrice)));
+        }
+        assert_eq!(inventory.stock(&id).quantity, Decimal::from(2));
+    }
+
+    #[test]
+    fn rejected_entry_leaves_stock_untouched() {
+        let (mut ledger, mut inventory, id, cash) = books();
+        inventory.purchase(&mut ledger, id, Decimal::from(4), Decimal::from(5), date(2024, 5, 1), cash).unwrap();
+        ledger.lock_period(date(2024, 5, 31)).unwrap();
+
+        let result = inventory.sale(&mut ledger, id, Decimal::ONE, Decimal::from(9), date(2024, 5, 20), cash);
+        assert!(matches!(result, Err(InventoryError::Ledger(_))));
+        assert_eq!(inventory.stock(&id).quantity, Decimal::from(4));
+        assert_eq!(inventory.value_by_account(date(2024, 5, 31)).values().sum::<Decimal>(), Decimal::from(20));
+        assert!(inventory.value_by_account(date(2024, 4, 30)).values().all(|v| v.is_zero()));
+    }
+}
@@ -0,0 +1,239 @@
+//! Customer invoices and supplier bills: line items, due dates and payment status, with the
+//! receivable or payable entry generated on issue and a settlement entry for each payment
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+use crate::contacts::PaymentTerms;
+use crate::currency::Commodity;
+use crate::ledger::{Posting, Transaction};
+use crate::locale::minor_units;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum InvoiceError {
+    #[error("Invoice not found")]
+    NotFound,
+    #[error("Only draft invoices can be issued or edited")]
+    NotDraft,
+    #[error("Invoice is not open for payment")]
+    NotOpen,
+    #[error("Invoice has no lines or a non-positive total")]
+    Empty,
+    #[error("Payment amount must be positive")]
+    InvalidAmount,
+    #[error("Payment of {amount} exceeds the outstanding {outstanding}")]
+    Overpayment { amount: Decimal, outstanding: Decimal },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum InvoiceStatus {
+    #[default]
+    Draft,
+    Issued,
+    PartiallyPaid,
+    Paid,
+    Void,
+}
+
+/// Whether the invoice is owed to us or by us
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum InvoiceDirection {
+    /// Sent to a customer: receivable debited, revenue credited
+    #[default]
+    Sales,
+    /// Received from a supplier: expense debited, payable credited
+    Purchase,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InvoiceLine {
+    pub description: String,
+    pub quantity: Decimal,
+    pub unit_price: Decimal,
+    /// Revenue account credited on issue, or expense account debited for a bill
+    pub account_id: Uuid,
+}
+
+impl InvoiceLine {
+    pub fn new(description: impl Into<String>, quantity: Decimal, unit_price: Decimal, account_id: Uuid) -> Self {
+        Self { description: description.into(), quantity, unit_price, account_id }
+    }
+
+    pub fn amount(&self) -> Decimal {
+        self.quantity * self.unit_price
+    }
+}
+
+/// Settlement recorded against an invoice
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InvoicePayment {
+    pub transaction_id: Uuid,
+    pub date: NaiveDate,
+    pub amount: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Invoice {
+    pub id: Uuid,
+    /// Invoice number, assigned on issue
+    #[serde(default)]
+    pub number: Option<String>,
+    pub contact_id: Uuid,
+    pub lines: Vec<InvoiceLine>,
+    pub commodity: Commodity,
+    #[serde(default)]
+    pub direction: InvoiceDirection,
+    /// Receivable debited on issue and credited by payments; for a bill, the payable credited
+    /// on issue and debited by payments
+    pub receivable_account: Uuid,
+    #[serde(default)]
+    pub status: InvoiceStatus,
+    #[serde(default)]
+    pub issue_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub due_date: Option<NaiveDate>,
+    /// Receivable entry recorded on issue
+    #[serde(default)]
+    pub issue_transaction: Option<Uuid>,
+    #[serde(default)]
+    pub payments: Vec<InvoicePayment>,
+}
+
+impl Invoice {
+    /// New draft for a customer, billed in `commodity` (usually the ledger's base currency)
+    pub fn new(contact_id: Uuid, receivable_account: Uuid, commodity: Commodity) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            number: None,
+            contact_id,
+            lines: Vec::new(),
+            commodity,
+            direction: InvoiceDirection::Sales,
+            receivable_account,
+            status: InvoiceStatus::Draft,
+            issue_date: None,
+            due_date: None,
+            issue_transaction: None,
+            payments: Vec::new(),
+        }
+    }
+
+    /// New draft for a bill received from a supplier
+    pub fn bill(contact_id: Uuid, payable_account: Uuid, commodity: Commodity) -> Self {
+        Self { direction: InvoiceDirection::Purchase, ..Self::new(contact_id, payable_account, commodity) }
+    }
+
+    pub fn with_line(mut self, line: InvoiceLine) -> Self {
+        self.lines.push(line);
+        self
+    }
+
+    /// Sum of line amounts, rounded to the commodity's minor units
+    pub fn total(&self) -> Decimal {
+        self.lines.iter().map(InvoiceLine::amount).sum::<Decimal>().round_dp(minor_units(&self.commodity))
+    }
+
+    pub fn paid(&self) -> Decimal {
+        self.payments.iter().map(|p| p.amount).sum()
+    }
+
+    pub fn outstanding(&self) -> Decimal {
+        match self.status {
+            InvoiceStatus::Issued | InvoiceStatus::PartiallyPaid => self.total() - self.paid(),
+            _ => Decimal::ZERO,
+        }
+    }
+
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        !self.outstanding().is_zero() && self.due_date.is_some_and(|due| due < today)
+    }
+
+    fn label(&self) -> String {
+        let kind = match self.direction {
+            InvoiceDirection::Sales => "Invoice",
+            InvoiceDirection::Purchase => "Bill",
+        };
+        match &self.number {
+            Some(number) => format!("{} {}", kind, number),
+            None => kind.to_string(),
+        }
+    }
+
+    /// +1 when the invoice account is debited on issue, -1 for a bill
+    fn sign(&self) -> Decimal {
+        match self.direction {
+            InvoiceDirection::Sales => Decimal::ONE,
+            InvoiceDirection::Purchase => -Decimal::ONE,
+        }
+    }
+
+    /// Issue a draft: fix the number and due date and build the receivable entry
+    /// (receivable debited, each line's revenue account credited). A bill is the mirror image:
+    /// each line's expense account debited, the payable credited.
+    pub fn issue(&mut self, date: NaiveDate, terms: PaymentTerms, number: Option<String>) -> Result<Transaction, InvoiceError> {
+        if self.status != InvoiceStatus::Draft {
+            return Err(InvoiceError::NotDraft);
+        }
+        let total = self.total();
+        if self.lines.is_empty() || total <= Decimal::ZERO {
+            return Err(InvoiceError::Empty);
+        }
+        self.number = number;
+
+        let dp = minor_units(&self.commodity);
+        let sign = self.sign();
+        let mut postings = vec![Posting::in_commodity(self.receivable_account, total * sign, self.commodity.clone())];
+        postings.extend(self.lines.iter().map(|line| {
+            Posting::in_commodity(line.account_id, -line.amount().round_dp(dp) * sign, self.commodity.clone())
+                .with_memo(line.description.clone())
+        }));
+        // Per-line rounding can leave a cent over; absorb it in the first revenue line
+        let residual: Decimal = postings.iter().map(|p| p.amount).sum();
+        postings[1].amount -= residual;
+
+        let mut tx = Transaction::new(date, self.label(), postings);
+        tx.reference = self.number.clone();
+        tx.contact_id = Some(self.contact_id);
+        self.status = InvoiceStatus::Issued;
+        self.issue_date = Some(date);
+        self.due_date = Some(terms.due_date(date));
+        self.issue_transaction = Some(tx.id);
+        Ok(tx)
+    }
+
+    /// Record a payment received into `deposit_account` and build the settlement entry; for a
+    /// bill, `deposit_account` is the account paid from
+    pub fn record_payment(&mut self, date: NaiveDate, amount: Decimal, deposit_account: Uuid) -> Result<Transaction, InvoiceError> {
+        if !matches!(self.status, InvoiceStatus::Issued | InvoiceStatus::PartiallyPaid) {
+            return Err(InvoiceError::NotOpen);
+        }
+        if amount <= Decimal::ZERO {
+            return Err(InvoiceError::InvalidAmount);
+        }
+        let outstanding = self.outstanding();
+        if amount > outstanding {
+            return Err(InvoiceError::Overpayment { amount, outstanding });
+        }
+
+        let mut tx = Transaction::new(date, format!("Payment: {}", self.label()), vec![
+            Posting::in_commodity(deposit_account, amount * self.sign(), self.commodity.clone()),
+            Posting::in_commodity(self.receivable_account, -amount * self.sign(), self.commodity.clone()),
+        ]);
+        tx.reference = self.number.clone();
+        tx.contact_id = Some(self.contact_id);
+        self.payments.push(InvoicePayment { transaction_id: tx.id, date, amount });
+        self.status = if amount == outstanding { InvoiceStatus::Paid } else { InvoiceStatus::PartiallyPaid };
+        Ok(tx)
+    }
+
+    /// Void a draft; issued invoices are reversed through their transactions instead
+    pub fn void(&mut self) -> Result<(), InvoiceError> {
+        if self.status != InvoiceStatus::Draft {
+            return Err(InvoiceError::NotDraft);
+        }
+        self.status = InvoiceStatus::Void;
+        Ok(())
+    }
+}
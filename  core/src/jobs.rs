@@ -0,0 +1,213 @@
+//! Background job scheduler shared by backups, anti-entropy, recurring transactions and
+//! report schedules: interval jobs, last-run times persisted across restarts, retry with backoff
+use std::collections::BTreeMap;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::storage::LocalStorage;
+
+const SETTINGS_KEY: &str = "job_state";
+
+/// Retries after a failure: `base_delay` doubling per attempt, capped at `max_delay`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay: Duration::from_secs(30),
+            max_delay: Duration::from_secs(30 * 60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `attempt` (1-based); None once retries are exhausted
+    pub fn delay(&self, attempt: u32) -> Option<Duration> {
+        if attempt == 0 || attempt > self.max_retries {
+            return None;
+        }
+        let factor = 2u32.saturating_pow(attempt - 1);
+        Some(self.base_delay.saturating_mul(factor).min(self.max_delay))
+    }
+}
+
+/// Persisted run history of one job
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobState {
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_success: Option<DateTime<Utc>>,
+    /// Failures since the last success
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+/// Outcome of one job run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobRun {
+    pub name: String,
+    pub result: Result<(), String>,
+}
+
+type JobFn<C> = Box<dyn FnMut(&mut C) -> Result<(), String> + Send>;
+
+struct ScheduledJob<C> {
+    name: String,
+    interval: Duration,
+    retry: RetryPolicy,
+    enabled: bool,
+    state: JobState,
+    /// None for jobs the app runs itself (e.g. async ones) and reports with `complete`
+    run: Option<JobFn<C>>,
+}
+
+impl<C> ScheduledJob<C> {
+    fn next_run(&self) -> Option<DateTime<Utc>> {
+        let last_run = self.state.last_run?;
+        let wait = self.retry.delay(self.state.consecutive_failures).unwrap_or(self.interval);
+        Some(last_run + chrono::Duration::from_std(wait).unwrap_or(chrono::Duration::MAX))
+    }
+
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.enabled && self.next_run().is_none_or(|next| next <= now)
+    }
+}
+
+/// Interval jobs run against an app-defined context `C` (ledger, storage, clients).
+/// Jobs that never ran are due immediately.
+pub struct JobScheduler<C> {
+    jobs: Vec<ScheduledJob<C>>,
+    /// Loaded history of jobs not registered yet
+    restored: BTreeMap<String, JobState>,
+}
+
+impl<C> Default for JobScheduler<C> {
+    fn default() -> Self {
+        Self { jobs: Vec::new(), restored: BTreeMap::new() }
+    }
+}
+
+impl<C> JobScheduler<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a job run by `run_due`; replaces a job with the same name
+    pub fn add(&mut self, name: &str, interval: Duration, run: impl FnMut(&mut C) -> Result<(), String> + Send + 'static) {
+        self.insert(name, interval, Some(Box::new(run)));
+    }
+
+    /// Register a job the app runs itself when listed by `due`, reporting back with `complete`
+    pub fn add_external(&mut self, name: &str, interval: Duration) {
+        self.insert(name, interval, None);
+    }
+
+    fn insert(&mut self, name: &str, interval: Duration, run: Option<JobFn<C>>) {
+        let state = self.remove(name)
+            .or_else(|| self.restored.remove(name))
+            .unwrap_or_default();
+        self.jobs.push(ScheduledJob {
+            name: name.to_string(),
+            interval,
+            retry: RetryPolicy::default(),
+            enabled: true,
+            state,
+            run,
+        });
+    }
+
+    /// Unregister a job, returning its run history
+    pub fn remove(&mut self, name: &str) -> Option<JobState> {
+        let index = self.jobs.iter().position(|j| j.name == name)?;
+        Some(self.jobs.remove(index).state)
+    }
+
+    pub fn set_retry(&mut self, name: &str, retry: RetryPolicy) -> Result<(), &'static str> {
+        self.job_mut(name)?.retry = retry;
+        Ok(())
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<(), &'static str> {
+        self.job_mut(name)?.enabled = enabled;
+        Ok(())
+    }
+
+    pub fn state(&self, name: &str) -> Option<&JobState> {
+        self.jobs.iter().find(|j| j.name == name).map(|j| &j.state)
+    }
+
+    /// Names of enabled jobs due at `now`, in registration order
+    pub fn due(&self, now: DateTime<Utc>) -> Vec<&str> {
+        self.jobs.iter().filter(|j| j.is_due(now)).map(|j| j.name.as_str()).collect()
+    }
+
+    /// Time until the next enabled job is due; zero when one is due already
+    pub fn next_due_in(&self, now: DateTime<Utc>) -> Option<Duration> {
+        self.jobs.iter()
+            .filter(|j| j.enabled)
+            .map(|j| j.next_run().map_or(Duration::ZERO, |next| (next - now).to_std().unwrap_or(Duration::ZERO)))
+            .min()
+    }
+
+    /// Run every due job that has a function; external jobs are left for the app
+    pub fn run_due(&mut self, ctx: &mut C, now: DateTime<Utc>) -> Vec<JobRun> {
+        let mut runs = Vec::new();
+        for job in self.jobs.iter_mut().filter(|j| j.is_due(now)) {
+            let Some(run) = job.run.as_mut() else { continue };
+            let result = run(ctx);
+            record(&mut job.state, &result, now);
+            runs.push(JobRun { name: job.name.clone(), result });
+        }
+        runs
+    }
+
+    /// Record the outcome of an external job run
+    pub fn complete(&mut self, name: &str, result: Result<(), String>, now: DateTime<Utc>) -> Result<(), &'static str> {
+        record(&mut self.job_mut(name)?.state, &result, now);
+        Ok(())
+    }
+
+    /// Restore run history saved by a previous run; jobs registered later pick theirs up on `add`
+    pub fn load(&mut self, storage: &LocalStorage) {
+        let mut saved: BTreeMap<String, JobState> = storage.get_setting(SETTINGS_KEY)
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        for job in &mut self.jobs {
+            if let Some(state) = saved.remove(&job.name) {
+                job.state = state;
+            }
+        }
+        self.restored = saved;
+    }
+
+    pub fn save(&self, storage: &LocalStorage) {
+        let mut states: BTreeMap<&str, &JobState> = self.restored.iter().map(|(n, s)| (n.as_str(), s)).collect();
+        states.extend(self.jobs.iter().map(|j| (j.name.as_str(), &j.state)));
+        storage.set_setting(SETTINGS_KEY, &serde_json::to_string(&states).unwrap());
+    }
+
+    fn job_mut(&mut self, name: &str) -> Result<&mut ScheduledJob<C>, &'static str> {
+        self.jobs.iter_mut().find(|j| j.name == name).ok_or("Job not found")
+    }
+}
+
+fn record(state: &mut JobState, result: &Result<(), String>, now: DateTime<Utc>) {
+    state.last_run = Some(now);
+    match result {
+        Ok(()) => {
+            state.last_success = Some(now);
+            state.consecutive_failures = 0;
+            state.last_error = None;
+        }
+        Err(e) => {
+            state.consecutive_failures += 1;
+            state.last_error = Some(e.clone());
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! Per-book sync encryption keys, so leaving one shared book only affects that book's key
+use std::collections::{BTreeSet, HashMap};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Serialize, Deserialize};
+
+use crate::storage::LocalStorage;
+
+const SETTINGS_KEY: &str = "book_keys";
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeyringError {
+    #[error("No key for book {0}")]
+    UnknownBook(String),
+    #[error("Book {book} has no key generation {generation}")]
+    StaleGeneration { book: String, generation: u32 },
+    #[error("Payload could not be decrypted")]
+    Decrypt,
+    #[error("Payload could not be encrypted")]
+    Encrypt,
+}
+
+/// Current key of one book and the members it is shared with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookKey {
+    pub generation: u32,
+    key: [u8; 32],
+    pub members: BTreeSet<String>,
+}
+
+/// Sync payload encrypted under one book's key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedPayload {
+    pub book: String,
+    pub generation: u32,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+/// Keys for every book this device participates in
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BookKeyring {
+    books: HashMap<String, BookKey>,
+}
+
+impl BookKeyring {
+    pub fn load(storage: &LocalStorage) -> Self {
+        storage.get_setting(SETTINGS_KEY)
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &LocalStorage) {
+        storage.set_setting(SETTINGS_KEY, &serde_json::to_string(self).unwrap());
+    }
+
+    /// Generate a fresh key for a new book shared with `members`
+    pub fn create_book(&mut self, book: &str, members: BTreeSet<String>) -> &BookKey {
+        let key = BookKey { generation: 1, key: XChaCha20Poly1305::generate_key(&mut OsRng).into(), members };
+        self.books.entry(book.to_string()).insert_entry(key).into_mut()
+    }
+
+    /// Install a key received from another member
+    pub fn import_key(&mut self, book: &str, generation: u32, key: [u8; 32], members: BTreeSet<String>) {
+        let newer = self.books.get(book).is_none_or(|k| generation > k.generation);
+        if newer {
+            self.books.insert(book.to_string(), BookKey { generation, key, members });
+        }
+    }
+
+    /// Raw key for handing to a member over a secure channel
+    pub fn export_key(&self, book: &str) -> Option<(u32, [u8; 32])> {
+        self.books.get(book).map(|k| (k.generation, k.key))
+    }
+
+    pub fn book(&self, book: &str) -> Option<&BookKey> {
+        self.books.get(book)
+    }
+
+    pub fn books(&self) -> impl Iterator<Item = &String> {
+        self.books.keys()
+    }
+
+    /// Forget a book this device left; keys of other books are untouched
+    pub fn leave_book(&mut self, book: &str) -> bool {
+        self.books.remove(book).is_some()
+    }
+
+    /// New key generation for the remaining members after someone leaves (only this book rotates).
+    /// Only reachable through a quorum `Approval`.
+    pub(crate) fn rotate(&mut self, book: &str, members: BTreeSet<String>) -> Result<u32, KeyringError> {
+        let entry = self.books.get_mut(book).ok_or_else(|| KeyringError::UnknownBook(book.to_string()))?;
+        entry.generation += 1;
+        entry.key = XChaCha20Poly1305::generate_key(&mut OsRng).into();
+        entry.members = members;
+        Ok(entry.generation)
+    }
+
+    pub fn seal(&self, book: &str, data: &[u8]) -> Result<SealedPayload, KeyringError> {
+        let entry = self.books.get(book).ok_or_else(|| KeyringError::UnknownBook(book.to_string()))?;
+        let cipher = XChaCha20Poly1305::new(&entry.key.into());
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = associated_data(book, entry.generation);
+        let ciphertext = cipher
+            .encrypt(&nonce, Payload { msg: data, aad: &aad })
+            .map_err(|_| KeyringError::Encrypt)?;
+        Ok(SealedPayload {
+            book: book.to_string(),
+            generation: entry.generation,
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })
+    }
+
+    pub fn open(&self, sealed: &SealedPayload) -> Result<Vec<u8>, KeyringError> {
+        let entry = self.books.get(&sealed.book).ok_or_else(|| KeyringError::UnknownBook(sealed.book.clone()))?;
+        if entry.generation != sealed.generation {
+            return Err(KeyringError::StaleGeneration { book: sealed.book.clone(), generation: sealed.generation });
+        }
+        if sealed.nonce.len() != 24 {
+            return Err(KeyringError::Decrypt);
+        }
+        let cipher = XChaCha20Poly1305::new(&entry.key.into());
+        let aad = associated_data(&sealed.book, sealed.generation);
+        cipher
+            .decrypt(XNonce::from_slice(&sealed.nonce), Payload { msg: &sealed.ciphertext, aad: &aad })
+            .map_err(|_| KeyringError::Decrypt)
+    }
+}
+
+/// Binds ciphertext to its book and key generation
+fn associated_data(book: &str, generation: u32) -> Vec<u8> {
+    format!("true-ledger/{}/{}", book, generation).into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members() -> BTreeSet<String> {
+        BTreeSet::from(["alice".to_string(), "bob".to_string()])
+    }
+
+    #[test]
+    fn sealed_payloads_open_only_untampered() {
+        let mut keyring = BookKeyring::default();
+        keyring.create_book("shop", members());
+        let sealed = keyring.seal("shop", b"ledger changes").unwrap();
+        assert_eq!(keyring.open(&sealed).unwrap(), b"ledger changes");
+
+        let mut tampered = sealed.clone();
+        tampered.ciphertext[0] ^= 1;
+        assert!(matches!(keyring.open(&tampered), Err(KeyringError::Decrypt)));
+
+        let mut short_nonce = sealed;
+        short_nonce.nonce.pop();
+        assert!(matches!(keyring.open(&short_nonce), Err(KeyringError::Decrypt)));
+    }
+
+    #[test]
+    fn ciphertext_is_bound_to_its_book_and_generation() {
+        let mut keyring = BookKeyring::default();
+        keyring.create_book("shop", members());
+        let (generation, key) = keyring.export_key("shop").unwrap();
+        // Same key under another book name: only the associated data differs
+        keyring.import_key("home", generation, key, members());
+        let mut moved = keyring.seal("shop", b"payload").unwrap();
+        moved.book = "home".to_string();
+        assert!(matches!(keyring.open(&moved), Err(KeyringError::Decrypt)));
+
+        let sealed = keyring.seal("shop", b"payload").unwrap();
+        keyring.import_key("shop", generation + 1, key, members());
+        assert!(matches!(
+            keyring.open(&sealed),
+            Err(KeyringError::StaleGeneration { generation: 1, .. })
+        ));
+        let mut relabelled = sealed;
+        relabelled.generation = generation + 1;
+        assert!(matches!(keyring.open(&relabelled), Err(KeyringError::Decrypt)));
+    }
+
+    #[test]
+    fn import_ignores_older_generations() {
+        let mut keyring = BookKeyring::default();
+        keyring.create_book("shop", members());
+        keyring.rotate("shop", members()).unwrap();
+        let current = keyring.export_key("shop").unwrap();
+
+        keyring.import_key("shop", 1, [7; 32], BTreeSet::new());
+        keyring.import_key("shop", 2, [7; 32], BTreeSet::new());
+        assert_eq!(keyring.export_key("shop"), Some(current));
+        assert_eq!(keyring.book("shop").unwrap().members, members());
+
+        keyring.import_key("shop", 3, [7; 32], BTreeSet::new());
+        assert_eq!(keyring.export_key("shop"), Some((3, [7; 32])));
+    }
+}
@@ -1,19 +1,191 @@
+pub mod depreciation;
+pub mod lots;
+pub mod reconcile;
+pub mod tax;
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
 use rust_decimal::Decimal;
 use uuid::Uuid;
 use serde::{Serialize, Deserialize};
 
+use crate::activity::{ActivityEntry, ActivityKind, ActivityLog};
+use crate::attachments::AttachmentRef;
+use crate::chart::{ChartTemplate, TemplateAccount};
+use crate::currency::{Commodity, ConversionCapture, RetranslatedBalance, Retranslation};
+use crate::rounding::RoundingPolicy;
+use crate::rules::{self, CategorizationRule};
+use lots::Lot;
+use reconcile::{ClearedState, MatchTolerance, PostingRef, ReconciliationReport, ReconciliationSession, SessionStatus, Statement, StatementMatches};
+use tax::{TaxTable, VatReport};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Account {
     pub id: Uuid,
     pub name: String,
     pub r#type: AccountType,
+    /// Number or external code from the chart of accounts (e.g. "4000"); unique per ledger
+    #[serde(default)]
+    pub code: Option<String>,
+    /// Parent in the chart of accounts; None for top-level accounts
+    #[serde(default)]
+    pub parent_id: Option<Uuid>,
+    #[serde(default)]
+    pub opened_on: Option<chrono::NaiveDate>,
+    #[serde(default)]
+    pub closed_on: Option<chrono::NaiveDate>,
+    /// Currency or commodity the account is denominated in
+    #[serde(default)]
+    pub commodity: Commodity,
+    /// False once archived: kept for history, closed to new postings
+    #[serde(default = "default_active")]
+    pub active: bool,
+    #[serde(default)]
+    pub display: AccountDisplay,
+    /// Cash, bank or card account; cash-basis reports only count transactions touching one
+    #[serde(default)]
+    pub cash_equivalent: bool,
+    /// Commodities postings may be in, e.g. only USD for a checking account; empty allows any
+    #[serde(default)]
+    pub allowed_commodities: Vec<Commodity>,
+    /// Cash flow statement section; None uses the default for the account type
+    #[serde(default)]
+    pub cash_flow: Option<CashFlowActivity>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn default_active() -> bool {
+    true
+}
+
+/// How frontends render an account in the chart of accounts
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountDisplay {
+    /// Emoji or frontend icon id
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// `#rrggbb`
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Position among siblings; ties are ordered by name
+    #[serde(default)]
+    pub sort_order: i32,
+    #[serde(default)]
+    pub favorite: bool,
+}
+
+impl Account {
+    /// Create account with a fresh id and no validity window, held in the default commodity;
+    /// `Ledger::new_account` creates one in the ledger's base currency
+    pub fn new(name: impl Into<String>, r#type: AccountType) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            r#type,
+            code: None,
+            parent_id: None,
+            opened_on: None,
+            closed_on: None,
+            commodity: Commodity::default(),
+            active: true,
+            display: AccountDisplay::default(),
+            cash_equivalent: false,
+            allowed_commodities: Vec::new(),
+            cash_flow: None,
+        }
+    }
+
+    pub fn with_parent(mut self, parent_id: Uuid) -> Self {
+        self.parent_id = Some(parent_id);
+        self
+    }
+
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    pub fn with_commodity(mut self, commodity: Commodity) -> Self {
+        self.commodity = commodity;
+        self
+    }
+
+    pub fn with_display(mut self, display: AccountDisplay) -> Self {
+        self.display = display;
+        self
+    }
+
+    pub fn with_cash_equivalent(mut self, cash_equivalent: bool) -> Self {
+        self.cash_equivalent = cash_equivalent;
+        self
+    }
+
+    pub fn with_allowed_commodities(mut self, commodities: impl IntoIterator<Item = Commodity>) -> Self {
+        self.allowed_commodities = commodities.into_iter().collect();
+        self
+    }
+
+    pub fn with_cash_flow(mut self, activity: CashFlowActivity) -> Self {
+        self.cash_flow = Some(activity);
+        self
+    }
+
+    /// Cash flow section for changes in this account's balance: operating for assets and
+    /// liabilities, financing for equity, unless set explicitly (e.g. investing for fixed assets)
+    pub fn cash_flow_activity(&self) -> CashFlowActivity {
+        self.cash_flow.unwrap_or(match self.r#type {
+            AccountType::Equity => CashFlowActivity::Financing,
+            _ => CashFlowActivity::Operating,
+        })
+    }
+
+    /// Whether a posting in `commodity` may be booked to the account
+    pub fn accepts(&self, commodity: &Commodity) -> bool {
+        self.allowed_commodities.is_empty() || self.allowed_commodities.contains(commodity)
+    }
+
+    /// Whether postings dated `date` are allowed
+    pub fn is_open_on(&self, date: chrono::NaiveDate) -> bool {
+        self.opened_on.is_none_or(|o| date >= o) && self.closed_on.is_none_or(|c| date <= c)
+    }
+
+    /// Closed before the given date (used to hide stale accounts in reports)
+    pub fn closed_before(&self, date: chrono::NaiveDate) -> bool {
+        self.closed_on.is_some_and(|c| c < date)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum AccountType {
     Asset, Liability, Equity, Revenue, Expense,
 }
 
+/// Section of the cash flow statement an account's balance changes are reported in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CashFlowActivity {
+    Operating,
+    Investing,
+    Financing,
+}
+
+impl CashFlowActivity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CashFlowActivity::Operating => "Operating",
+            CashFlowActivity::Investing => "Investing",
+            CashFlowActivity::Financing => "Financing",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "Operating" => Some(CashFlowActivity::Operating),
+            "Investing" => Some(CashFlowActivity::Investing),
+            "Financing" => Some(CashFlowActivity::Financing),
+            _ => None,
+        }
+    }
+}
+
 impl AccountType {
     pub fn natural_balance(&self) -> AccountKind {
         match self {
@@ -28,59 +200,1753 @@ pub enum AccountKind {
     Debit, Credit,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Posting {
     pub account_id: Uuid,
+    #[serde(with = "crate::canonical::serde_decimal")]
     pub amount: Decimal, // +debit, -credit
+    #[serde(default)]
+    pub commodity: Commodity,
+    /// Bank conversion actually applied when the posting was paid in a foreign currency
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversion: Option<ConversionCapture>,
+    /// Expected account balance (in this posting's commodity) after the transaction is applied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assert_balance: Option<Decimal>,
+    /// Project/job the amount is costed to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<Uuid>,
+    /// Class/department the amount is reported under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class_id: Option<Uuid>,
+    /// Security lot bought or sold by this posting (investment accounts)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lot: Option<Lot>,
+    /// Line-level note, e.g. the bank statement text for this leg
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    /// Tax code from the ledger's `TaxTable` the posting is reported under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tax_code: Option<String>,
+    /// Whether the posting has been matched against a bank statement
+    #[serde(default, skip_serializing_if = "ClearedState::is_uncleared")]
+    pub cleared: ClearedState,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Posting {
+    /// Posting in the default commodity; `Ledger::posting` posts in the ledger's base currency
+    pub fn new(account_id: Uuid, amount: Decimal) -> Self {
+        Self {
+            account_id,
+            amount,
+            commodity: Commodity::default(),
+            conversion: None,
+            assert_balance: None,
+            project_id: None,
+            class_id: None,
+            lot: None,
+            memo: None,
+            tax_code: None,
+            cleared: ClearedState::Uncleared,
+        }
+    }
+
+    /// Posting of an amount in a specific commodity (e.g. EUR, BTC)
+    pub fn in_commodity(account_id: Uuid, amount: Decimal, commodity: Commodity) -> Self {
+        Self { commodity, ..Self::new(account_id, amount) }
+    }
+
+    /// Attach the bank's conversion details
+    pub fn with_conversion(mut self, conversion: ConversionCapture) -> Self {
+        self.conversion = Some(conversion);
+        self
+    }
+
+    /// hledger-style balance assertion checked when the transaction is recorded
+    pub fn with_assertion(mut self, expected: Decimal) -> Self {
+        self.assert_balance = Some(expected);
+        self
+    }
+
+    pub fn with_project(mut self, project_id: Uuid) -> Self {
+        self.project_id = Some(project_id);
+        self
+    }
+
+    pub fn with_class(mut self, class_id: Uuid) -> Self {
+        self.class_id = Some(class_id);
+        self
+    }
+
+    pub fn with_lot(mut self, lot: Lot) -> Self {
+        self.lot = Some(lot);
+        self
+    }
+
+    pub fn with_memo(mut self, memo: impl Into<String>) -> Self {
+        self.memo = Some(memo.into());
+        self
+    }
+
+    pub fn with_tax_code(mut self, code: impl Into<String>) -> Self {
+        self.tax_code = Some(code.into());
+        self
+    }
+}
+
+/// Lifecycle of a journal entry; entries are never deleted, only voided by a correction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TransactionStatus {
+    #[default]
+    Posted,
+    /// Cancelled by a linked correction entry
+    Voided,
+    /// Reversing entry that voids another transaction
+    Correction,
+}
+
+impl TransactionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionStatus::Posted => "Posted",
+            TransactionStatus::Voided => "Voided",
+            TransactionStatus::Correction => "Correction",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "Posted" => Some(TransactionStatus::Posted),
+            "Voided" => Some(TransactionStatus::Voided),
+            "Correction" => Some(TransactionStatus::Correction),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Transaction {
     pub id: Uuid,
     pub date: chrono::NaiveDate,
     pub description: String,
     pub postings: Vec<Posting>,
+    /// Who was paid or paid us, as it appears on the bank statement
+    #[serde(default)]
+    pub payee: Option<String>,
+    /// Counterparty the payee resolved to
+    #[serde(default)]
+    pub contact_id: Option<Uuid>,
+    /// Journal/invoice number issued from a reference sequence
+    #[serde(default)]
+    pub reference: Option<String>,
+    /// Device the transaction was first entered on
+    #[serde(default)]
+    pub origin_device: Option<String>,
+    /// Moves revenue/expense balances into equity at period end
+    #[serde(default)]
+    pub is_closing_entry: bool,
+    /// Undoes an earlier entry (e.g. an accrual reversed on the 1st)
+    #[serde(default)]
+    pub is_reversing_entry: bool,
+    /// Brings in balances from before the ledger started; reports can leave it out
+    #[serde(default)]
+    pub is_opening_balance: bool,
+    #[serde(default)]
+    pub status: TransactionStatus,
+    /// Transaction this correction voids
+    #[serde(default)]
+    pub corrects: Option<Uuid>,
+    /// Why the transaction was voided (set on both the voided entry and its correction)
+    #[serde(default)]
+    pub void_reason: Option<String>,
+    /// Inter-entity transfer this entry is one side of; the other entity's entry shares the id
+    #[serde(default)]
+    pub transfer_id: Option<Uuid>,
+    /// Receipts and other files; only references, the bytes are in local storage
+    #[serde(default)]
+    pub attachments: Vec<AttachmentRef>,
 }
 
 impl Transaction {
+    /// Create transaction with a fresh id
+    pub fn new(date: chrono::NaiveDate, description: impl Into<String>, postings: Vec<Posting>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            date,
+            description: description.into(),
+            postings,
+            payee: None,
+            contact_id: None,
+            reference: None,
+            origin_device: None,
+            is_closing_entry: false,
+            is_reversing_entry: false,
+            is_opening_balance: false,
+            status: TransactionStatus::Posted,
+            corrects: None,
+            void_reason: None,
+            transfer_id: None,
+            attachments: Vec::new(),
+        }
+    }
+
+    /// Attach a file reference; the same file is only attached once
+    pub fn with_attachment(mut self, attachment: AttachmentRef) -> Self {
+        if !self.attachments.iter().any(|a| a.hash == attachment.hash) {
+            self.attachments.push(attachment);
+        }
+        self
+    }
+
+    pub fn status(&self) -> TransactionStatus {
+        self.status
+    }
+
+    /// Reversal dated `date`: same accounts, opposite amounts
+    pub fn reversing(&self, date: chrono::NaiveDate) -> Transaction {
+        let postings = self.postings.iter()
+            .map(|p| Posting {
+                amount: -p.amount,
+                assert_balance: None,
+                lot: p.lot.clone().map(|l| Lot { quantity: -l.quantity, ..l }),
+                cleared: ClearedState::Uncleared,
+                ..p.clone()
+            })
+            .collect();
+        let mut reversal = Transaction::new(date, format!("Reversal of {}", self.description), postings);
+        reversal.is_reversing_entry = true;
+        reversal
+    }
+
+    /// Correction entry voiding this transaction, dated `date`; fails unless the entry is `Posted`
+    pub fn void_correction(&self, date: chrono::NaiveDate, reason: &str) -> Result<Transaction, &'static str> {
+        match self.status {
+            TransactionStatus::Voided => return Err("Transaction already voided"),
+            TransactionStatus::Correction => return Err("Corrections cannot be voided"),
+            TransactionStatus::Posted => {}
+        }
+        let mut correction = self.reversing(date);
+        correction.description = format!("Void of {}: {}", self.description, reason);
+        correction.status = TransactionStatus::Correction;
+        correction.corrects = Some(self.id);
+        correction.void_reason = Some(reason.to_string());
+        Ok(correction)
+    }
+
+    pub fn with_payee(mut self, payee: impl Into<String>) -> Self {
+        self.payee = Some(payee.into());
+        self
+    }
+
+    /// Stamp the device the transaction was entered on
+    pub fn with_origin(mut self, device_id: impl Into<String>) -> Self {
+        self.origin_device = Some(device_id.into());
+        self
+    }
+
+    /// Postings sum to zero separately in every commodity
     pub fn is_balanced(&self) -> bool {
-        self.postings.iter().map(|p| p.amount).sum::<Decimal>().is_zero()
+        self.commodity_totals().values().all(|total| total.is_zero())
+    }
+
+    /// Sum of postings per commodity
+    pub fn commodity_totals(&self) -> HashMap<Commodity, Decimal> {
+        let mut totals = HashMap::new();
+        for p in &self.postings {
+            *totals.entry(p.commodity.clone()).or_insert(Decimal::ZERO) += p.amount;
+        }
+        totals
+    }
+}
+
+/// Snapshot of Assets = Liabilities + Equity (+ current-period income)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountingEquation {
+    pub assets: Decimal,
+    pub liabilities: Decimal,
+    pub equity: Decimal,
+    /// Revenue minus expenses not yet closed into equity
+    pub net_income: Decimal,
+    pub balanced: bool,
+}
+
+impl AccountingEquation {
+    /// Assets minus the right-hand side; non-zero means corrupted state
+    pub fn difference(&self) -> Decimal {
+        self.assets - (self.liabilities + self.equity + self.net_income)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum LedgerError {
+    #[error("{0}")]
+    Rejected(&'static str),
+    #[error("Transaction {0} already recorded")]
+    Duplicate(Uuid),
+    #[error("Balance assertion failed for {account_id}: expected {expected} {commodity}, found {actual}")]
+    BalanceAssertion {
+        account_id: Uuid,
+        commodity: Commodity,
+        expected: Decimal,
+        actual: Decimal,
+    },
+}
+
+impl From<&'static str> for LedgerError {
+    fn from(reason: &'static str) -> Self {
+        LedgerError::Rejected(reason)
     }
 }
 
+/// Outcome of a bulk `record_transactions_dedup`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordSummary {
+    pub recorded: usize,
+    /// Already recorded (e.g. replayed by sync) and skipped
+    pub duplicates: usize,
+    pub rejected: Vec<(Uuid, LedgerError)>,
+}
+
+/// Balance of one account in one commodity before and after a simulated operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BalanceChange {
+    pub account_id: Uuid,
+    pub commodity: Commodity,
+    pub before: Decimal,
+    pub after: Decimal,
+}
+
+impl BalanceChange {
+    pub fn delta(&self) -> Decimal {
+        self.after - self.before
+    }
+}
+
+/// Outcome of a dry run: what the operation returned and what it would have recorded
+#[derive(Debug, Clone)]
+pub struct Simulation<T> {
+    pub result: T,
+    /// Transactions the operation would have recorded, in order
+    pub transactions: Vec<Transaction>,
+    /// Accounts whose balance would change, by account id
+    pub changes: Vec<BalanceChange>,
+}
+
+/// Calendar month budgets are set for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BudgetPeriod {
+    pub year: i32,
+    pub month: u32,
+}
+
+impl BudgetPeriod {
+    pub fn of(date: chrono::NaiveDate) -> Self {
+        use chrono::Datelike;
+        Self { year: date.year(), month: date.month() }
+    }
+
+    pub fn contains(&self, date: chrono::NaiveDate) -> bool {
+        Self::of(date) == *self
+    }
+}
+
+/// Spending (or income) limit for one account in one period, in the account's commodity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Budget {
+    pub id: Uuid,
+    pub account_id: Uuid,
+    pub period: BudgetPeriod,
+    #[serde(with = "crate::canonical::serde_decimal")]
+    pub amount: Decimal,
+}
+
+impl Budget {
+    pub fn new(account_id: Uuid, period: BudgetPeriod, amount: Decimal) -> Self {
+        Self { id: Uuid::new_v4(), account_id, period, amount }
+    }
+}
+
+/// Actual vs. budgeted for one account; amounts use the account's natural sign
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BudgetLine {
+    pub account_id: Uuid,
+    pub budgeted: Decimal,
+    pub actual: Decimal,
+    /// Budget left (negative when over budget)
+    pub remaining: Decimal,
+}
+
+/// Recognition basis for balances and income statements
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Basis {
+    /// Every recorded transaction
+    #[default]
+    Accrual,
+    /// Only transactions with a posting to a cash-equivalent account
+    Cash,
+}
+
+/// Spacing of balance history samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Granularity {
+    Day,
+    /// Weeks end on Sunday
+    Week,
+    Month,
+}
+
+impl Granularity {
+    /// Last day of the period containing `date`
+    pub fn period_end(&self, date: chrono::NaiveDate) -> chrono::NaiveDate {
+        use chrono::Datelike;
+        match self {
+            Granularity::Day => date,
+            Granularity::Week => date + chrono::Duration::days(6 - date.weekday().num_days_from_monday() as i64),
+            Granularity::Month => {
+                let first = date.with_day(1).unwrap();
+                (first + chrono::Months::new(1)).pred_opt().unwrap_or(date)
+            }
+        }
+    }
+}
+
+/// Closing balance of one sample period
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BalanceSample {
+    pub date: chrono::NaiveDate,
+    pub balance: Decimal,
+}
+
 #[derive(Debug, Clone)]
 pub struct Ledger {
-    accounts: std::collections::HashMap<Uuid, Account>,
-    balances: std::collections::HashMap<Uuid, Decimal>,
+    accounts: HashMap<Uuid, Account>,
+    balances: HashMap<Uuid, HashMap<Commodity, Decimal>>,
+    base_currency: Commodity,
+    activity: ActivityLog,
+    /// Running debit-positive totals per account type and commodity, kept for `equation()`
+    type_totals: HashMap<(AccountType, Commodity), Decimal>,
+    /// Pending per-account deltas while a batch is open (dirty accounts)
+    batch: Option<HashMap<(Uuid, Commodity), Decimal>>,
+    /// Equity account receiving net income at period close
+    retained_earnings: Option<Uuid>,
+    /// Equity account offsetting opening balances
+    opening_balances: Option<Uuid>,
+    /// Last closed period end; only closing entries may be dated on or before it
+    closed_through: Option<chrono::NaiveDate>,
+    /// Nothing dated on or before it may be recorded or changed, not even closing entries
+    locked_through: Option<chrono::NaiveDate>,
+    budgets: HashMap<Uuid, Budget>,
+    /// Net postings per account, budget period and commodity, excluding closing and opening entries
+    period_activity: HashMap<(Uuid, BudgetPeriod, Commodity), Decimal>,
+    /// Every recorded transaction, in recording order
+    journal: Vec<Transaction>,
+    /// Journal position by transaction id, so replays never double-count
+    recorded: HashMap<Uuid, usize>,
+    /// Account id by account code
+    codes: HashMap<String, Uuid>,
+    /// Net postings per account, commodity and day, derived from the journal for balance history
+    daily_deltas: HashMap<(Uuid, Commodity), BTreeMap<chrono::NaiveDate, Decimal>>,
+    tax_table: TaxTable,
+    rules: Vec<CategorizationRule>,
+    /// How generated amounts (splits, conversions, depreciation) are rounded
+    rounding: RoundingPolicy,
+}
+
+impl Default for Ledger {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Ledger {
     pub fn new() -> Self {
         Self {
-            accounts: std::collections::HashMap::new(),
-            balances: std::collections::HashMap::new(),
+            accounts: HashMap::new(),
+            balances: HashMap::new(),
+            base_currency: Commodity::default(),
+            activity: ActivityLog::new(),
+            type_totals: HashMap::new(),
+            batch: None,
+            retained_earnings: None,
+            opening_balances: None,
+            closed_through: None,
+            locked_through: None,
+            budgets: HashMap::new(),
+            period_activity: HashMap::new(),
+            journal: Vec::new(),
+            recorded: HashMap::new(),
+            codes: HashMap::new(),
+            daily_deltas: HashMap::new(),
+            tax_table: TaxTable::new(),
+            rules: Vec::new(),
+            rounding: RoundingPolicy::default(),
+        }
+    }
+
+    /// Create ledger reporting in the given base currency
+    pub fn with_base_currency(base_currency: Commodity) -> Self {
+        Self { base_currency, ..Self::new() }
+    }
+
+    /// Default currency for reports and conversions
+    pub fn base_currency(&self) -> &Commodity {
+        &self.base_currency
+    }
+
+    /// Start a base currency change; supply a rate on the result, then apply it
+    pub fn begin_base_currency_change(&self, to: Commodity) -> Retranslation {
+        // Only amounts held in the old base currency are re-translated
+        let lines = self.balances.iter()
+            .filter_map(|(id, amounts)| Some((id, amounts.get(&self.base_currency)?)))
+            .map(|(id, amount)| RetranslatedBalance {
+                account_id: *id,
+                original: *amount,
+                translated: None,
+            })
+            .collect();
+        Retranslation { from: self.base_currency.clone(), to, rate: None, lines }
+    }
+
+    /// Switch base currency once the re-translation has been reviewed. The translated balances
+    /// are booked by one entry dated `date` that moves each account out of the old base and into
+    /// the new one, and accounts held in the old base follow. Returns the entry's id, or None
+    /// when no balance was held in the old base.
+    pub fn apply_base_currency_change(&mut self, change: &Retranslation, date: chrono::NaiveDate) -> Result<Option<Uuid>, LedgerError> {
+        if change.from != self.base_currency {
+            return Err("Base currency changed since re-translation started".into());
+        }
+        if !change.is_ready() {
+            return Err("Re-translation is missing a rate".into());
+        }
+        let held = self.balances.values()
+            .filter(|b| b.get(&change.from).is_some_and(|a| !a.is_zero()))
+            .count();
+        let mut reviewed = 0;
+        let mut postings = Vec::new();
+        for line in &change.lines {
+            let current = self.balances.get(&line.account_id).and_then(|b| b.get(&change.from));
+            if current.copied().unwrap_or(Decimal::ZERO) != line.original {
+                return Err("Balances changed since re-translation started".into());
+            }
+            if line.original.is_zero() {
+                continue;
+            }
+            reviewed += 1;
+            let translated = self.rounding.round(line.translated.unwrap_or_default(), &change.to);
+            postings.push(Posting::in_commodity(line.account_id, -line.original, change.from.clone()));
+            postings.push(Posting::in_commodity(line.account_id, translated, change.to.clone()));
+        }
+        if reviewed != held {
+            return Err("Balances changed since re-translation started".into());
+        }
+
+        // Lines rounded one by one may not net to zero; the leftover goes to the policy's
+        // residual account, or else to the largest translated line
+        let leftover: Decimal = -postings.iter().filter(|p| p.commodity == change.to).map(|p| p.amount).sum::<Decimal>();
+        if !leftover.is_zero() {
+            match self.rounding.residual_account {
+                Some(account_id) => postings.push(Posting::in_commodity(account_id, leftover, change.to.clone())),
+                None => {
+                    let largest = postings.iter_mut()
+                        .filter(|p| p.commodity == change.to)
+                        .max_by_key(|p| p.amount.abs());
+                    if let Some(largest) = largest {
+                        largest.amount += leftover;
+                    }
+                }
+            }
+        }
+
+        let entry = if postings.is_empty() {
+            None
+        } else {
+            let tx = Transaction::new(date, format!("Base currency change {} to {}", change.from, change.to), postings);
+            let id = tx.id;
+            self.record_transaction(tx)?;
+            Some(id)
+        };
+        for account in self.accounts.values_mut().filter(|a| a.commodity == change.from && a.accepts(&change.to)) {
+            account.commodity = change.to.clone();
+        }
+        self.base_currency = change.to.clone();
+        Ok(entry)
+    }
+
+    /// Account held in the base currency, ready for `add_account`
+    pub fn new_account(&self, name: impl Into<String>, r#type: AccountType) -> Account {
+        Account::new(name, r#type).with_commodity(self.base_currency.clone())
+    }
+
+    /// Posting in the base currency
+    pub fn posting(&self, account_id: Uuid, amount: Decimal) -> Posting {
+        Posting::in_commodity(account_id, amount, self.base_currency.clone())
+    }
+
+    /// Add or replace an account; the code is trimmed and refused when empty, containing
+    /// control characters or already used by another account
+    pub fn add_account(&mut self, mut account: Account) -> Result<(), &'static str> {
+        if let Some(code) = account.code.take() {
+            let code = code.trim();
+            if code.is_empty() {
+                return Err("Account code is empty");
+            }
+            if code.chars().any(char::is_control) {
+                return Err("Account code contains control characters");
+            }
+            account.code = Some(code.to_string());
+        }
+        if account.cash_equivalent && !matches!(account.r#type, AccountType::Asset | AccountType::Liability) {
+            return Err("Only asset and liability accounts can be cash equivalents");
+        }
+        if !account.accepts(&account.commodity) {
+            return Err("Account commodity is not among its allowed commodities");
+        }
+        if let Some(code) = &account.code {
+            if self.codes.get(code).is_some_and(|id| *id != account.id) {
+                return Err("Account code already in use");
+            }
         }
+        if let Some(old) = self.accounts.get(&account.id).and_then(|a| a.code.as_ref()) {
+            self.codes.remove(old);
+        }
+        if let Some(code) = &account.code {
+            self.codes.insert(code.clone(), account.id);
+        }
+        self.balances.entry(account.id).or_default();
+        self.accounts.insert(account.id, account);
+        Ok(())
+    }
+
+    /// Fill an empty ledger with a starting chart of accounts in the template's currency.
+    /// Returns the created account ids, parents before children.
+    pub fn bootstrap(&mut self, template: &ChartTemplate) -> Result<Vec<Uuid>, &'static str> {
+        if !self.accounts.is_empty() {
+            return Err("Ledger already has accounts");
+        }
+        self.base_currency = template.base_currency.clone();
+        let mut created = Vec::new();
+        let mut stack: Vec<(Option<Uuid>, &TemplateAccount)> = template.accounts.iter().rev().map(|a| (None, a)).collect();
+        while let Some((parent_id, entry)) = stack.pop() {
+            let mut account = Account::new(entry.name.clone(), entry.r#type)
+                .with_commodity(template.base_currency.clone())
+                .with_cash_equivalent(entry.cash_equivalent);
+            account.parent_id = parent_id;
+            account.cash_flow = entry.cash_flow;
+            let id = account.id;
+            self.add_account(account)?;
+            if entry.retained_earnings {
+                self.retained_earnings = Some(id);
+            }
+            if entry.opening_balances {
+                self.opening_balances = Some(id);
+            }
+            created.push(id);
+            stack.extend(entry.children.iter().rev().map(|c| (Some(id), c)));
+        }
+        Ok(created)
+    }
+
+    /// Add an account under an existing parent, refusing unknown parents and cycles
+    pub fn add_child_account(&mut self, account: Account) -> Result<(), &'static str> {
+        let parent = account.parent_id.ok_or("Account has no parent")?;
+        if !self.accounts.contains_key(&parent) {
+            return Err("Parent account not found");
+        }
+        if self.ancestors(&parent).any(|a| a == account.id) || parent == account.id {
+            return Err("Account hierarchy cycle");
+        }
+        self.add_account(account)
+    }
+
+    pub fn account(&self, id: &Uuid) -> Option<&Account> {
+        self.accounts.get(id)
+    }
+
+    /// Account with the given chart code, for importers that reference accounts by number
+    pub fn account_by_code(&self, code: &str) -> Option<&Account> {
+        self.codes.get(code.trim()).and_then(|id| self.accounts.get(id))
+    }
+
+    /// Direct children sorted by name
+    pub fn children(&self, id: &Uuid) -> Vec<&Account> {
+        let mut children: Vec<&Account> = self.accounts.values()
+            .filter(|a| a.parent_id == Some(*id))
+            .collect();
+        children.sort_by(|a, b| display_order(a, b));
+        children
+    }
+
+    /// Parent chain from the direct parent up to the root
+    fn ancestors<'a>(&'a self, id: &Uuid) -> impl Iterator<Item = Uuid> + 'a {
+        let mut current = self.accounts.get(id).and_then(|a| a.parent_id);
+        let mut steps = 0;
+        std::iter::from_fn(move || {
+            // Guard against cycles that arrived through sync
+            if steps > self.accounts.len() {
+                return None;
+            }
+            steps += 1;
+            let id = current?;
+            current = self.accounts.get(&id).and_then(|a| a.parent_id);
+            Some(id)
+        })
+    }
+
+    /// Chart of accounts depth-first, as (depth, account); accounts with a missing parent are roots
+    pub fn account_tree(&self) -> Vec<(usize, &Account)> {
+        let mut roots: Vec<&Account> = self.accounts.values()
+            .filter(|a| a.parent_id.is_none_or(|p| !self.accounts.contains_key(&p)))
+            .collect();
+        roots.sort_by(|a, b| display_order(a, b));
+
+        let mut tree = Vec::new();
+        let mut stack: Vec<(usize, &Account)> = roots.into_iter().rev().map(|a| (0, a)).collect();
+        while let Some((depth, account)) = stack.pop() {
+            if tree.len() > self.accounts.len() {
+                break;
+            }
+            tree.push((depth, account));
+            stack.extend(self.children(&account.id).into_iter().rev().map(|c| (depth + 1, c)));
+        }
+        tree
+    }
+
+    /// Balance of an account plus all of its descendants, per commodity
+    pub fn balance_rollup(&self, id: &Uuid) -> HashMap<Commodity, Decimal> {
+        let mut totals = HashMap::new();
+        for account in self.accounts.values() {
+            if account.id == *id || self.ancestors(&account.id).any(|a| a == *id) {
+                for (commodity, amount) in self.balances.get(&account.id).into_iter().flatten() {
+                    *totals.entry(commodity.clone()).or_insert(Decimal::ZERO) += *amount;
+                }
+            }
+        }
+        totals
     }
 
-    pub fn add_account(&mut self, account: Account) {
-        self.accounts.insert(account.id, account.clone());
-        self.balances.insert(account.id, Decimal::ZERO);
+    pub fn record_transaction(&mut self, tx: Transaction) -> Result<(), LedgerError> {
+        self.record(tx, true)
     }
 
-    pub fn record_transaction(&mut self, tx: Transaction) -> Result<(), &'static str> {
+    /// Record with or without the closed and locked period checks; entries replayed from the
+    /// book passed them when first recorded
+    fn record(&mut self, tx: Transaction, check_periods: bool) -> Result<(), LedgerError> {
+        if self.recorded.contains_key(&tx.id) {
+            return Err(LedgerError::Duplicate(tx.id));
+        }
         if !tx.is_balanced() {
-            return Err("Unbalanced transaction");
+            return Err("Unbalanced transaction".into());
+        }
+        if check_periods && self.is_locked(tx.date) {
+            return Err("Period is locked".into());
+        }
+        if check_periods && !tx.is_closing_entry && self.closed_through.is_some_and(|c| tx.date <= c) {
+            return Err("Period is closed".into());
         }
         for p in &tx.postings {
-            if !self.accounts.contains_key(&p.account_id) {
-                return Err("Account not found");
+            let account = self.accounts.get(&p.account_id).ok_or("Account not found")?;
+            if !account.active {
+                return Err("Posting to archived account".into());
+            }
+            if !account.is_open_on(tx.date) {
+                return Err("Posting outside account validity window".into());
+            }
+            if !account.accepts(&p.commodity) {
+                return Err("Commodity not allowed in account".into());
+            }
+        }
+        self.check_assertions(&tx)?;
+        match &mut self.batch {
+            Some(dirty) => {
+                for p in &tx.postings {
+                    *dirty.entry((p.account_id, p.commodity.clone())).or_insert(Decimal::ZERO) += p.amount;
+                }
+            }
+            None => {
+                for p in &tx.postings {
+                    self.apply_delta(p.account_id, &p.commodity, p.amount);
+                }
+            }
+        }
+        if !tx.is_closing_entry && !tx.is_opening_balance {
+            let period = BudgetPeriod::of(tx.date);
+            for p in &tx.postings {
+                *self.period_activity.entry((p.account_id, period, p.commodity.clone())).or_insert(Decimal::ZERO) += p.amount;
+            }
+        }
+        for p in &tx.postings {
+            *self.daily_deltas.entry((p.account_id, p.commodity.clone())).or_default().entry(tx.date).or_insert(Decimal::ZERO) += p.amount;
+        }
+        self.recorded.insert(tx.id, self.journal.len());
+        self.activity.push(ActivityKind::TransactionPosted { transaction_id: tx.id });
+        self.journal.push(tx);
+        Ok(())
+    }
+
+    /// Whether a transaction id has already been applied
+    pub fn is_recorded(&self, id: &Uuid) -> bool {
+        self.recorded.contains_key(id)
+    }
+
+    /// Recorded transaction by id
+    pub fn transaction(&self, id: &Uuid) -> Option<&Transaction> {
+        self.recorded.get(id).map(|i| &self.journal[*i])
+    }
+
+    /// All recorded transactions in recording order
+    pub fn transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.journal.iter()
+    }
+
+    /// Recorded transactions with at least one posting to the account
+    pub fn transactions_for_account(&self, id: Uuid) -> impl Iterator<Item = &Transaction> {
+        self.journal.iter().filter(move |t| t.postings.iter().any(|p| p.account_id == id))
+    }
+
+    /// Recorded transactions dated `from` through `to`, inclusive
+    pub fn transactions_in_range(&self, from: chrono::NaiveDate, to: chrono::NaiveDate) -> impl Iterator<Item = &Transaction> {
+        self.journal.iter().filter(move |t| from <= t.date && t.date <= to)
+    }
+
+    /// Recompute balances, equation totals and budget activity from the journal
+    pub fn rebuild_balances(&mut self) -> Result<(), &'static str> {
+        if self.in_batch() {
+            return Err("Cannot rebuild balances during a batch");
+        }
+        for balances in self.balances.values_mut() {
+            balances.clear();
+        }
+        self.type_totals.clear();
+        self.period_activity.clear();
+        self.daily_deltas.clear();
+        let journal = std::mem::take(&mut self.journal);
+        for tx in &journal {
+            for p in &tx.postings {
+                self.apply_delta(p.account_id, &p.commodity, p.amount);
+                *self.daily_deltas.entry((p.account_id, p.commodity.clone())).or_default().entry(tx.date).or_insert(Decimal::ZERO) += p.amount;
+                if !tx.is_closing_entry && !tx.is_opening_balance {
+                    let key = (p.account_id, BudgetPeriod::of(tx.date), p.commodity.clone());
+                    *self.period_activity.entry(key).or_insert(Decimal::ZERO) += p.amount;
+                }
+            }
+        }
+        self.journal = journal;
+        Ok(())
+    }
+
+    /// Record many transactions, skipping ones already recorded instead of failing;
+    /// other rejections are collected and the rest still recorded
+    pub fn record_transactions_dedup(&mut self, transactions: impl IntoIterator<Item = Transaction>) -> RecordSummary {
+        self.record_all(transactions, true)
+    }
+
+    /// Replay a book's transactions after `restore_periods`; entries dated in the closed or
+    /// locked period are history and skip the period checks, everything else is validated in full
+    pub(crate) fn replay_transactions(&mut self, transactions: impl IntoIterator<Item = Transaction>) -> RecordSummary {
+        self.record_all(transactions, false)
+    }
+
+    fn record_all(&mut self, transactions: impl IntoIterator<Item = Transaction>, check_periods: bool) -> RecordSummary {
+        let mut summary = RecordSummary::default();
+        for tx in transactions {
+            let id = tx.id;
+            match self.record(tx, check_periods) {
+                Ok(()) => summary.recorded += 1,
+                Err(LedgerError::Duplicate(_)) => summary.duplicates += 1,
+                Err(e) => summary.rejected.push((id, e)),
+            }
+        }
+        summary
+    }
+
+    /// Dry run: apply `f` to a scratch copy with full validation and report what it would record.
+    /// This ledger is untouched, and nothing reaches storage or the sync document.
+    pub fn simulate<T, E>(&self, f: impl FnOnce(&mut Ledger) -> Result<T, E>) -> Result<Simulation<T>, E> {
+        let mut scratch = self.clone();
+        let result = f(&mut scratch)?;
+        let transactions = scratch.journal.split_off(self.journal.len());
+
+        let mut deltas: BTreeMap<(Uuid, Commodity), Decimal> = BTreeMap::new();
+        for p in transactions.iter().flat_map(|t| &t.postings) {
+            *deltas.entry((p.account_id, p.commodity.clone())).or_insert(Decimal::ZERO) += p.amount;
+        }
+        let changes = deltas.into_iter()
+            .filter(|(_, delta)| !delta.is_zero())
+            .map(|((account_id, commodity), delta)| {
+                let pending = self.batch.as_ref()
+                    .and_then(|b| b.get(&(account_id, commodity.clone())))
+                    .copied()
+                    .unwrap_or(Decimal::ZERO);
+                let before = self.balance_in(&account_id, &commodity) + pending;
+                BalanceChange { account_id, commodity, before, after: before + delta }
+            })
+            .collect();
+        Ok(Simulation { result, transactions, changes })
+    }
+
+    /// Validate a transaction and preview its balance changes without recording it
+    pub fn simulate_transaction(&self, tx: Transaction) -> Result<Simulation<()>, LedgerError> {
+        self.simulate(|ledger| ledger.record_transaction(tx))
+    }
+
+    /// Preview a bulk import: which transactions would be recorded, skipped or rejected
+    pub fn simulate_transactions(&self, transactions: impl IntoIterator<Item = Transaction>) -> Simulation<RecordSummary> {
+        self.simulate(|ledger| Ok::<_, std::convert::Infallible>(ledger.record_transactions_dedup(transactions)))
+            .unwrap_or_else(|never| match never {})
+    }
+
+    /// Verify balance assertions against the balance the transaction would leave behind
+    fn check_assertions(&self, tx: &Transaction) -> Result<(), LedgerError> {
+        for p in tx.postings.iter().filter(|p| p.assert_balance.is_some()) {
+            let key = (p.account_id, p.commodity.clone());
+            let pending = self.batch.as_ref().and_then(|b| b.get(&key)).copied().unwrap_or(Decimal::ZERO);
+            let this_tx: Decimal = tx.postings.iter()
+                .filter(|q| q.account_id == p.account_id && q.commodity == p.commodity)
+                .map(|q| q.amount)
+                .sum();
+            let actual = self.balance_in(&p.account_id, &p.commodity) + pending + this_tx;
+            let expected = p.assert_balance.unwrap();
+            if actual != expected {
+                return Err(LedgerError::BalanceAssertion {
+                    account_id: p.account_id,
+                    commodity: p.commodity.clone(),
+                    expected,
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_delta(&mut self, account_id: Uuid, commodity: &Commodity, amount: Decimal) {
+        *self.balances.get_mut(&account_id).unwrap().entry(commodity.clone()).or_insert(Decimal::ZERO) += amount;
+        let account_type = self.accounts[&account_id].r#type;
+        *self.type_totals.entry((account_type, commodity.clone())).or_insert(Decimal::ZERO) += amount;
+    }
+
+    /// Defer balance updates until `commit_batch`, for bulk imports and large merges
+    pub fn begin_batch(&mut self) {
+        self.batch.get_or_insert_with(HashMap::new);
+    }
+
+    /// Apply accumulated deltas once per dirty account; returns how many accounts changed
+    pub fn commit_batch(&mut self) -> usize {
+        let dirty = self.batch.take().unwrap_or_default();
+        let count = dirty.keys().map(|(id, _)| id).collect::<std::collections::HashSet<_>>().len();
+        for ((account_id, commodity), amount) in dirty {
+            self.apply_delta(account_id, &commodity, amount);
+        }
+        count
+    }
+
+    /// Whether balances are currently deferred
+    pub fn in_batch(&self) -> bool {
+        self.batch.is_some()
+    }
+
+    /// Account balance per commodity
+    pub fn balance(&self, id: &Uuid) -> HashMap<Commodity, Decimal> {
+        self.balances.get(id).cloned().unwrap_or_default()
+    }
+
+    /// Account balance in a single commodity
+    pub fn balance_in(&self, id: &Uuid, commodity: &Commodity) -> Decimal {
+        self.balances.get(id).and_then(|b| b.get(commodity)).copied().unwrap_or(Decimal::ZERO)
+    }
+
+    /// Balance in the account's own commodity at the end of each day, week or month in `range`;
+    /// the last sample is cut off at the range end. Debit-positive, like `balance_in`.
+    pub fn balance_history(&self, account_id: &Uuid, granularity: Granularity, range: RangeInclusive<chrono::NaiveDate>) -> Vec<BalanceSample> {
+        let Some(account) = self.accounts.get(account_id) else { return Vec::new() };
+        let empty = BTreeMap::new();
+        let deltas = self.daily_deltas.get(&(*account_id, account.commodity.clone())).unwrap_or(&empty);
+        let (from, to) = range.into_inner();
+
+        let mut balance: Decimal = deltas.range(..from).map(|(_, d)| *d).sum();
+        let mut samples = Vec::new();
+        let mut cursor = from;
+        while cursor <= to {
+            let end = granularity.period_end(cursor).min(to);
+            balance += deltas.range(cursor..=end).map(|(_, d)| *d).sum::<Decimal>();
+            samples.push(BalanceSample { date: end, balance });
+            match end.succ_opt() {
+                Some(next) => cursor = next,
+                None => break,
+            }
+        }
+        samples
+    }
+
+    /// Account balances on the given basis; accrual uses the running balances, cash replays the journal
+    pub fn balances(&self, basis: Basis) -> HashMap<Uuid, HashMap<Commodity, Decimal>> {
+        match basis {
+            Basis::Accrual => self.balances.clone(),
+            Basis::Cash => {
+                let mut balances: HashMap<Uuid, HashMap<Commodity, Decimal>> =
+                    self.accounts.keys().map(|id| (*id, HashMap::new())).collect();
+                for tx in self.journal.iter().filter(|t| self.counts_on(basis, t)) {
+                    for p in &tx.postings {
+                        *balances.entry(p.account_id).or_default().entry(p.commodity.clone()).or_insert(Decimal::ZERO) += p.amount;
+                    }
+                }
+                balances
+            }
+        }
+    }
+
+    /// Revenue and expense activity per account over `range` on the given basis, excluding closing and opening entries
+    pub fn income_statement(&self, basis: Basis, range: RangeInclusive<chrono::NaiveDate>) -> HashMap<Uuid, HashMap<Commodity, Decimal>> {
+        let mut totals: HashMap<Uuid, HashMap<Commodity, Decimal>> = HashMap::new();
+        let transactions = self.journal.iter()
+            .filter(|t| range.contains(&t.date) && !t.is_closing_entry && !t.is_opening_balance && self.counts_on(basis, t));
+        for tx in transactions {
+            for p in &tx.postings {
+                let nominal = self.accounts.get(&p.account_id)
+                    .is_some_and(|a| matches!(a.r#type, AccountType::Revenue | AccountType::Expense));
+                if nominal {
+                    *totals.entry(p.account_id).or_default().entry(p.commodity.clone()).or_insert(Decimal::ZERO) += p.amount;
+                }
             }
-            *self.balances.get_mut(&p.account_id).unwrap() += p.amount;
         }
+        totals
+    }
+
+    pub fn tax_table(&self) -> &TaxTable {
+        &self.tax_table
+    }
+
+    pub fn set_tax_table(&mut self, table: TaxTable) {
+        self.tax_table = table;
+    }
+
+    pub fn rules(&self) -> &[CategorizationRule] {
+        &self.rules
+    }
+
+    /// Add or replace (by id) a categorization rule; fails on an invalid pattern or amount range
+    pub fn add_rule(&mut self, rule: CategorizationRule) -> Result<(), &'static str> {
+        rule.validate()?;
+        self.rules.retain(|r| r.id != rule.id);
+        self.rules.push(rule);
         Ok(())
     }
 
-    pub fn balance(&self, id: &Uuid) -> Decimal {
-        *self.balances.get(id).unwrap_or(&Decimal::ZERO)
+    pub fn remove_rule(&mut self, id: &Uuid) -> Option<CategorizationRule> {
+        let index = self.rules.iter().position(|r| r.id == *id)?;
+        Some(self.rules.remove(index))
     }
-}
\ No newline at end of file
+
+    /// Fill in or re-point the counter side of an unrecorded transaction with the first matching
+    /// rule; returns the rule's id
+    pub fn apply_rules(&self, tx: &mut Transaction) -> Option<Uuid> {
+        rules::apply_rules(&self.rules, tx)
+    }
+
+    pub fn rounding_policy(&self) -> &RoundingPolicy {
+        &self.rounding
+    }
+
+    /// Replace the rounding policy; the residual account must exist and be active
+    pub fn set_rounding_policy(&mut self, policy: RoundingPolicy) -> Result<(), &'static str> {
+        if let Some(account_id) = policy.residual_account {
+            let account = self.accounts.get(&account_id).ok_or("Account not found")?;
+            if !account.active {
+                return Err("Residual account is archived");
+            }
+        }
+        self.rounding = policy;
+        Ok(())
+    }
+
+    /// Input and output VAT per tax code for postings dated within `period`
+    pub fn vat_report(&self, period: RangeInclusive<chrono::NaiveDate>) -> VatReport {
+        tax::vat_report(&self.journal, &self.tax_table, period)
+    }
+
+    /// Whether a transaction is recognized on the given basis
+    pub fn counts_on(&self, basis: Basis, tx: &Transaction) -> bool {
+        match basis {
+            Basis::Accrual => true,
+            Basis::Cash => tx.postings.iter().any(|p| self.accounts.get(&p.account_id).is_some_and(|a| a.cash_equivalent)),
+        }
+    }
+
+    /// Mark or unmark an asset or liability account as cash equivalent
+    pub fn set_cash_equivalent(&mut self, id: &Uuid, cash_equivalent: bool) -> Result<(), &'static str> {
+        let account = self.accounts.get_mut(id).ok_or("Account not found")?;
+        if cash_equivalent && !matches!(account.r#type, AccountType::Asset | AccountType::Liability) {
+            return Err("Only asset and liability accounts can be cash equivalents");
+        }
+        account.cash_equivalent = cash_equivalent;
+        Ok(())
+    }
+
+    /// Move an account to another cash flow section, or back to its type's default with None
+    pub fn set_cash_flow_activity(&mut self, id: &Uuid, activity: Option<CashFlowActivity>) -> Result<(), &'static str> {
+        self.accounts.get_mut(id).ok_or("Account not found")?.cash_flow = activity;
+        Ok(())
+    }
+
+    /// Restrict the commodities an account takes postings in; an empty list lifts the restriction.
+    /// Already recorded postings are not checked.
+    pub fn set_allowed_commodities(&mut self, id: &Uuid, commodities: Vec<Commodity>) -> Result<(), &'static str> {
+        let account = self.accounts.get_mut(id).ok_or("Account not found")?;
+        if !commodities.is_empty() && !commodities.contains(&account.commodity) {
+            return Err("Account commodity is not among its allowed commodities");
+        }
+        account.allowed_commodities = commodities;
+        Ok(())
+    }
+
+    /// Retire an account without touching its history; refuses while any balance remains
+    pub fn archive_account(&mut self, id: &Uuid) -> Result<(), &'static str> {
+        if !self.accounts.contains_key(id) {
+            return Err("Account not found");
+        }
+        if self.batch.as_ref().is_some_and(|b| b.keys().any(|(a, _)| a == id)) {
+            return Err("Account has pending batch postings");
+        }
+        if self.balance(id).values().any(|b| !b.is_zero()) {
+            return Err("Account balance is not zero");
+        }
+        let account = self.accounts.get_mut(id).unwrap();
+        account.active = false;
+        Ok(())
+    }
+
+    /// Reopen an archived account for postings
+    pub fn unarchive_account(&mut self, id: &Uuid) -> Result<(), &'static str> {
+        self.accounts.get_mut(id).ok_or("Account not found")?.active = true;
+        Ok(())
+    }
+
+    /// Add or replace a budget (e.g. one received through sync)
+    pub fn set_budget(&mut self, budget: Budget) -> Result<(), &'static str> {
+        if !self.accounts.contains_key(&budget.account_id) {
+            return Err("Account not found");
+        }
+        self.budgets.insert(budget.id, budget);
+        Ok(())
+    }
+
+    pub fn remove_budget(&mut self, id: &Uuid) -> Option<Budget> {
+        self.budgets.remove(id)
+    }
+
+    pub fn budgets(&self) -> impl Iterator<Item = &Budget> {
+        self.budgets.values()
+    }
+
+    /// Actual vs. budgeted for every account with a budget in `period`, by account name
+    pub fn budget_status(&self, period: BudgetPeriod) -> Vec<BudgetLine> {
+        let mut budgeted: HashMap<Uuid, Decimal> = HashMap::new();
+        for budget in self.budgets.values().filter(|b| b.period == period) {
+            *budgeted.entry(budget.account_id).or_insert(Decimal::ZERO) += budget.amount;
+        }
+        let mut lines: Vec<(&str, BudgetLine)> = budgeted.into_iter()
+            .filter_map(|(account_id, budgeted)| {
+                let account = self.accounts.get(&account_id)?;
+                let net = self.period_activity
+                    .get(&(account_id, period, account.commodity.clone()))
+                    .copied()
+                    .unwrap_or(Decimal::ZERO);
+                let actual = match account.r#type.natural_balance() {
+                    AccountKind::Debit => net,
+                    AccountKind::Credit => -net,
+                };
+                Some((account.name.as_str(), BudgetLine { account_id, budgeted, actual, remaining: budgeted - actual }))
+            })
+            .collect();
+        lines.sort_by(|a, b| a.0.cmp(b.0));
+        lines.into_iter().map(|(_, line)| line).collect()
+    }
+
+    /// Replace an account's icon, color, sort order and favorite flag
+    pub fn set_account_display(&mut self, id: &Uuid, display: AccountDisplay) -> Result<(), &'static str> {
+        self.accounts.get_mut(id).ok_or("Account not found")?.display = display;
+        Ok(())
+    }
+
+    /// Accounts flagged as favorites, in display order
+    pub fn favorite_accounts(&self) -> Vec<&Account> {
+        let mut favorites: Vec<&Account> = self.accounts.values().filter(|a| a.display.favorite).collect();
+        favorites.sort_by(|a, b| display_order(a, b));
+        favorites
+    }
+
+    /// Equity account that `close_period` moves net income into
+    pub fn set_retained_earnings_account(&mut self, account_id: Uuid) -> Result<(), &'static str> {
+        match self.accounts.get(&account_id) {
+            Some(a) if a.r#type == AccountType::Equity => {
+                self.retained_earnings = Some(account_id);
+                Ok(())
+            }
+            Some(_) => Err("Retained earnings must be an equity account"),
+            None => Err("Account not found"),
+        }
+    }
+
+    pub fn set_opening_balances_account(&mut self, account_id: Uuid) -> Result<(), &'static str> {
+        match self.accounts.get(&account_id) {
+            Some(a) if a.r#type == AccountType::Equity => {
+                self.opening_balances = Some(account_id);
+                Ok(())
+            }
+            Some(_) => Err("Opening balances must be an equity account"),
+            None => Err("Account not found"),
+        }
+    }
+
+    pub fn opening_balances_account(&self) -> Option<Uuid> {
+        self.opening_balances
+    }
+
+    pub fn retained_earnings_account(&self) -> Option<Uuid> {
+        self.retained_earnings
+    }
+
+    /// Start a ledger mid-life: record the given debit-positive balances (in each account's own
+    /// commodity) as one opening entry offset against the opening balances equity account.
+    /// If none is set, an "Opening Balances" account is created under the top-level equity
+    /// account (itself created when the chart has none), and only once the entry validated.
+    /// Only one live opening entry is allowed; void it to enter the balances again.
+    pub fn set_opening_balances(&mut self, date: chrono::NaiveDate, balances: Vec<(Uuid, Decimal)>) -> Result<Uuid, LedgerError> {
+        if self.journal.iter().any(|t| t.is_opening_balance && t.status == TransactionStatus::Posted) {
+            return Err("Opening balances already recorded".into());
+        }
+        let mut postings = Vec::new();
+        let mut offsets: BTreeMap<Commodity, Decimal> = BTreeMap::new();
+        for (account_id, amount) in balances.into_iter().filter(|(_, amount)| !amount.is_zero()) {
+            if Some(account_id) == self.opening_balances {
+                return Err("Opening balances account cannot have an opening balance".into());
+            }
+            let account = self.accounts.get(&account_id).ok_or("Account not found")?;
+            *offsets.entry(account.commodity.clone()).or_insert(Decimal::ZERO) += amount;
+            postings.push(Posting::in_commodity(account_id, amount, account.commodity.clone()));
+        }
+        if postings.is_empty() {
+            return Err("No opening balances given".into());
+        }
+
+        // New accounts go into a trial copy so nothing is created unless the entry records
+        let mut trial = None;
+        let equity = match self.opening_balances {
+            Some(id) => id,
+            None => {
+                let ledger = trial.insert(self.clone());
+                let parent = match ledger.equity_root() {
+                    Some(id) => id,
+                    None => {
+                        let root = Account::new("Equity", AccountType::Equity).with_commodity(self.base_currency.clone());
+                        let id = root.id;
+                        ledger.add_account(root)?;
+                        id
+                    }
+                };
+                let account = Account::new("Opening Balances", AccountType::Equity)
+                    .with_parent(parent)
+                    .with_commodity(self.base_currency.clone());
+                let id = account.id;
+                ledger.add_account(account)?;
+                ledger.opening_balances = Some(id);
+                id
+            }
+        };
+        for (commodity, total) in offsets.into_iter().filter(|(_, total)| !total.is_zero()) {
+            postings.push(Posting::in_commodity(equity, -total, commodity));
+        }
+
+        let mut tx = Transaction::new(date, "Opening balances", postings);
+        tx.is_opening_balance = true;
+        let id = tx.id;
+        match trial {
+            Some(mut ledger) => {
+                ledger.record_transaction(tx)?;
+                *self = ledger;
+            }
+            None => self.record_transaction(tx)?,
+        }
+        Ok(id)
+    }
+
+    /// Top-level equity account new equity accounts belong under: one that already groups
+    /// others if there is one, by name
+    fn equity_root(&self) -> Option<Uuid> {
+        self.accounts.values()
+            .filter(|a| a.r#type == AccountType::Equity && a.parent_id.is_none())
+            .min_by_key(|a| (self.children(&a.id).is_empty(), a.name.clone()))
+            .map(|a| a.id)
+    }
+
+    /// Mark a posting cleared or uncleared; reconciled postings are locked
+    pub fn set_cleared(&mut self, posting: PostingRef, state: ClearedState) -> Result<(), &'static str> {
+        if state == ClearedState::Reconciled {
+            return Err("Postings are reconciled by completing a session");
+        }
+        let index = *self.recorded.get(&posting.transaction_id).ok_or("Transaction not found")?;
+        let target = self.journal[index].postings.get_mut(posting.index).ok_or("Posting not found")?;
+        if target.cleared == ClearedState::Reconciled {
+            return Err("Posting is already reconciled");
+        }
+        if target.cleared != state {
+            target.cleared = state;
+            self.activity.push(ActivityKind::TransactionEdited { transaction_id: posting.transaction_id });
+        }
+        Ok(())
+    }
+
+    /// Compare the account's cleared balance (debit-positive, in its own commodity) with a statement balance
+    pub fn reconcile(&self, account_id: Uuid, statement_balance: Decimal) -> Result<ReconciliationReport, &'static str> {
+        self.reconciliation_report(account_id, statement_balance, None)
+    }
+
+    /// Same as `reconcile` for a session, counting only postings dated through the statement end
+    pub fn reconcile_session(&self, session: &ReconciliationSession) -> Result<ReconciliationReport, &'static str> {
+        self.reconciliation_report(session.account_id, session.end_balance, Some(session.statement_end))
+    }
+
+    fn reconciliation_report(
+        &self,
+        account_id: Uuid,
+        statement_balance: Decimal,
+        through: Option<chrono::NaiveDate>,
+    ) -> Result<ReconciliationReport, &'static str> {
+        let account = self.accounts.get(&account_id).ok_or("Account not found")?;
+        let mut cleared_balance = Decimal::ZERO;
+        let mut uncleared = Vec::new();
+        let mut uncleared_total = Decimal::ZERO;
+        for tx in self.journal.iter().filter(|t| through.is_none_or(|end| t.date <= end)) {
+            let postings = tx.postings.iter().enumerate()
+                .filter(|(_, p)| p.account_id == account_id && p.commodity == account.commodity);
+            for (index, p) in postings {
+                if p.cleared.is_uncleared() {
+                    uncleared.push((tx.date, PostingRef { transaction_id: tx.id, index }));
+                    uncleared_total += p.amount;
+                } else {
+                    cleared_balance += p.amount;
+                }
+            }
+        }
+        uncleared.sort();
+        Ok(ReconciliationReport {
+            account_id,
+            statement_balance,
+            cleared_balance,
+            difference: statement_balance - cleared_balance,
+            uncleared: uncleared.into_iter().map(|(_, posting)| posting).collect(),
+            uncleared_total,
+        })
+    }
+
+    /// Match a statement's lines against uncleared postings of its account dated within the
+    /// statement period (widened by the date tolerance, up to the earliest and latest dates)
+    pub fn match_statement(&self, statement: &Statement, tolerance: MatchTolerance) -> Result<StatementMatches, &'static str> {
+        if tolerance.days < 0 || tolerance.amount < Decimal::ZERO {
+            return Err("Match tolerance must not be negative");
+        }
+        let account = self.accounts.get(&statement.account_id).ok_or("Account not found")?;
+        let widen = chrono::TimeDelta::try_days(tolerance.days);
+        let from = widen.and_then(|d| statement.start_date.checked_sub_signed(d)).unwrap_or(chrono::NaiveDate::MIN);
+        let to = widen.and_then(|d| statement.end_date.checked_add_signed(d)).unwrap_or(chrono::NaiveDate::MAX);
+        let mut candidates = Vec::new();
+        for tx in self.journal.iter().filter(|t| from <= t.date && t.date <= to) {
+            let postings = tx.postings.iter().enumerate()
+                .filter(|(_, p)| p.account_id == account.id && p.commodity == account.commodity && p.cleared.is_uncleared());
+            for (index, p) in postings {
+                candidates.push((PostingRef { transaction_id: tx.id, index }, tx.date, p.amount));
+            }
+        }
+        Ok(reconcile::match_statement(statement, &candidates, tolerance))
+    }
+
+    /// Mark every matched posting cleared; returns how many were marked
+    pub fn apply_statement_matches(&mut self, matches: &StatementMatches) -> Result<usize, &'static str> {
+        for m in &matches.matched {
+            self.set_cleared(m.posting, ClearedState::Cleared)?;
+        }
+        Ok(matches.matched.len())
+    }
+
+    /// Finish a session whose cleared balance matches the statement: cleared postings through the
+    /// statement end become reconciled. Returns the postings locked in.
+    pub fn complete_reconciliation(
+        &mut self,
+        session: &mut ReconciliationSession,
+        today: chrono::NaiveDate,
+    ) -> Result<Vec<PostingRef>, &'static str> {
+        if session.is_completed() {
+            return Err("Reconciliation already completed");
+        }
+        if !self.reconcile_session(session)?.is_balanced() {
+            return Err("Cleared balance does not match the statement");
+        }
+        let mut reconciled = Vec::new();
+        for tx in self.journal.iter_mut().filter(|t| t.date <= session.statement_end) {
+            for (index, p) in tx.postings.iter_mut().enumerate() {
+                if p.account_id == session.account_id && p.cleared == ClearedState::Cleared {
+                    p.cleared = ClearedState::Reconciled;
+                    reconciled.push(PostingRef { transaction_id: tx.id, index });
+                }
+            }
+        }
+        session.status = SessionStatus::Completed;
+        session.completed_on = Some(today);
+        session.reconciled = reconciled.clone();
+        Ok(reconciled)
+    }
+
+    pub fn closed_through(&self) -> Option<chrono::NaiveDate> {
+        self.closed_through
+    }
+
+    /// Set the closed and locked periods of a book being rebuilt, before its transactions are replayed
+    pub(crate) fn restore_periods(&mut self, closed_through: Option<chrono::NaiveDate>, locked_through: Option<chrono::NaiveDate>) {
+        self.closed_through = closed_through;
+        self.locked_through = locked_through;
+    }
+
+    /// Lock everything dated on or before `until`, e.g. once taxes are filed. Locks only move forward.
+    pub fn lock_period(&mut self, until: chrono::NaiveDate) -> Result<(), &'static str> {
+        if self.in_batch() {
+            return Err("Cannot lock a period during a batch");
+        }
+        if self.locked_through.is_some_and(|l| until < l) {
+            return Err("Period is already locked past that date");
+        }
+        self.locked_through = Some(until);
+        Ok(())
+    }
+
+    pub fn locked_through(&self) -> Option<chrono::NaiveDate> {
+        self.locked_through
+    }
+
+    pub fn is_locked(&self, date: chrono::NaiveDate) -> bool {
+        self.locked_through.is_some_and(|l| date <= l)
+    }
+
+    /// Zero the revenue and expense activity since the previous close up to `end_date` into
+    /// retained earnings with a closing entry dated `end_date`. Afterwards only closing entries
+    /// may be dated in the period; use `lock_period` to freeze it completely.
+    pub fn close_period(&mut self, end_date: chrono::NaiveDate) -> Result<Transaction, LedgerError> {
+        let retained = self.retained_earnings.ok_or("No retained earnings account set")?;
+        if self.in_batch() {
+            return Err("Cannot close a period during a batch".into());
+        }
+        if self.closed_through.is_some_and(|c| end_date <= c) {
+            return Err("Period already closed".into());
+        }
+
+        // Earlier periods were zeroed by their own closing entries
+        let mut activity: HashMap<Uuid, BTreeMap<Commodity, Decimal>> = HashMap::new();
+        let period = self.journal.iter()
+            .filter(|t| !t.is_closing_entry && t.date <= end_date && self.closed_through.is_none_or(|c| t.date > c));
+        for tx in period {
+            for p in &tx.postings {
+                *activity.entry(p.account_id).or_default().entry(p.commodity.clone()).or_insert(Decimal::ZERO) += p.amount;
+            }
+        }
+
+        let mut postings = Vec::new();
+        let mut net: BTreeMap<Commodity, Decimal> = BTreeMap::new();
+        let mut nominal: Vec<&Account> = self.accounts.values()
+            .filter(|a| matches!(a.r#type, AccountType::Revenue | AccountType::Expense))
+            .collect();
+        nominal.sort_by(|a, b| a.name.cmp(&b.name));
+        for account in nominal {
+            for (commodity, amount) in activity.get(&account.id).into_iter().flatten() {
+                if amount.is_zero() {
+                    continue;
+                }
+                postings.push(Posting::in_commodity(account.id, -*amount, commodity.clone()));
+                *net.entry(commodity.clone()).or_insert(Decimal::ZERO) += *amount;
+            }
+        }
+        for (commodity, amount) in net {
+            postings.push(Posting::in_commodity(retained, amount, commodity));
+        }
+
+        let mut closing = Transaction::new(end_date, format!("Close period ending {}", end_date), postings);
+        closing.is_closing_entry = true;
+        if !closing.postings.is_empty() {
+            self.record_transaction(closing.clone())?;
+        }
+        self.closed_through = Some(end_date);
+        self.activity.push(ActivityKind::PeriodClosed { period_end: end_date });
+        Ok(closing)
+    }
+
+    /// Void a recorded transaction with a linked reversing entry; both stay in the journal. The
+    /// correction is dated like the original, or on the first open day if that period is closed.
+    pub fn void_transaction(&mut self, id: &Uuid, reason: impl Into<String>) -> Result<Transaction, LedgerError> {
+        let index = *self.recorded.get(id).ok_or("Transaction not found")?;
+        let original = &self.journal[index];
+        if self.is_locked(original.date) {
+            return Err("Period is locked".into());
+        }
+        let date = match self.closed_through {
+            Some(closed) if original.date <= closed => closed.succ_opt().ok_or("Invalid date")?,
+            _ => original.date,
+        };
+        let reason = reason.into();
+        let correction = original.void_correction(date, &reason)?;
+
+        self.record_transaction(correction.clone())?;
+        let original = &mut self.journal[index];
+        original.status = TransactionStatus::Voided;
+        original.void_reason = Some(reason);
+        self.activity.push(ActivityKind::TransactionVoided {
+            transaction_id: *id,
+            correction_id: correction.id,
+        });
+        Ok(correction)
+    }
+
+    /// Record an event from outside the ledger (edit, peer merge, backup, period close)
+    pub fn log_activity(&mut self, kind: ActivityKind) {
+        self.activity.push(kind);
+    }
+
+    /// Record an event that happened at `at`, e.g. a merge the sync client saw earlier
+    pub fn log_activity_at(&mut self, at: chrono::DateTime<chrono::Utc>, kind: ActivityKind) {
+        self.activity.push_at(at, kind);
+    }
+
+    /// Replace the feed with the stored one, e.g. after a merge rebuilt the ledger
+    pub(crate) fn restore_activity(&mut self, entries: impl IntoIterator<Item = ActivityEntry>) {
+        self.activity = ActivityLog::from_entries(entries);
+    }
+
+    /// Chronological feed of everything that happened after `since`
+    pub fn activity(&self, since: chrono::DateTime<chrono::Utc>) -> &[ActivityEntry] {
+        self.activity.since(since)
+    }
+
+    /// Current accounting equation in the base currency from incrementally maintained totals
+    pub fn equation(&self) -> AccountingEquation {
+        self.equation_in(&self.base_currency)
+    }
+
+    /// Accounting equation for amounts held in one commodity
+    pub fn equation_in(&self, commodity: &Commodity) -> AccountingEquation {
+        let total = |t: AccountType| *self.type_totals.get(&(t, commodity.clone())).unwrap_or(&Decimal::ZERO);
+        let assets = total(AccountType::Asset);
+        let liabilities = -total(AccountType::Liability);
+        let equity = -total(AccountType::Equity);
+        let net_income = -total(AccountType::Revenue) - total(AccountType::Expense);
+        AccountingEquation {
+            assets,
+            liabilities,
+            equity,
+            net_income,
+            balanced: assets == liabilities + equity + net_income,
+        }
+    }
+}
+
+/// Sibling order shown to users: explicit sort order, then name
+fn display_order(a: &Account, b: &Account) -> std::cmp::Ordering {
+    a.display.sort_order.cmp(&b.display.sort_order).then_with(|| a.name.cmp(&b.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn add(ledger: &mut Ledger, name: &str, r#type: AccountType) -> Uuid {
+        let account = ledger.new_account(name, r#type);
+        let id = account.id;
+        ledger.add_account(account).unwrap();
+        id
+    }
+
+    fn post(ledger: &mut Ledger, on: NaiveDate, debit: Uuid, credit: Uuid, amount: i64) {
+        let postings = vec![Posting::new(debit, Decimal::from(amount)), Posting::new(credit, Decimal::from(-amount))];
+        ledger.record_transaction(Transaction::new(on, "test", postings)).unwrap();
+    }
+
+    #[test]
+    fn close_period_only_closes_activity_up_to_end_date() {
+        let mut ledger = Ledger::new();
+        let cash = add(&mut ledger, "Cash", AccountType::Asset);
+        let sales = add(&mut ledger, "Sales", AccountType::Revenue);
+        let retained = add(&mut ledger, "Retained Earnings", AccountType::Equity);
+        ledger.set_retained_earnings_account(retained).unwrap();
+
+        post(&mut ledger, date(2024, 12, 10), cash, sales, 100);
+        post(&mut ledger, date(2025, 1, 5), cash, sales, 40);
+        let closing = ledger.close_period(date(2024, 12, 31)).unwrap();
+
+        assert_eq!(closing.postings.len(), 2);
+        assert_eq!(closing.postings[0].account_id, sales);
+        assert_eq!(closing.postings[0].amount, Decimal::from(100));
+        assert_eq!(ledger.balance(&sales)[&Commodity::default()], Decimal::from(-40));
+        assert_eq!(ledger.closed_through(), Some(date(2024, 12, 31)));
+        assert_eq!(ledger.locked_through(), None);
+    }
+
+    #[test]
+    fn close_period_skips_earlier_closing_entries() {
+        let mut ledger = Ledger::new();
+        let cash = add(&mut ledger, "Cash", AccountType::Asset);
+        let sales = add(&mut ledger, "Sales", AccountType::Revenue);
+        let retained = add(&mut ledger, "Retained Earnings", AccountType::Equity);
+        ledger.set_retained_earnings_account(retained).unwrap();
+
+        post(&mut ledger, date(2024, 6, 1), cash, sales, 100);
+        ledger.close_period(date(2024, 6, 30)).unwrap();
+        post(&mut ledger, date(2024, 7, 1), cash, sales, 25);
+        let closing = ledger.close_period(date(2024, 7, 31)).unwrap();
+
+        let closed: Decimal = closing.postings.iter().filter(|p| p.account_id == sales).map(|p| p.amount).sum();
+        assert_eq!(closed, Decimal::from(25));
+        assert!(ledger.balance(&sales).values().all(|a| a.is_zero()));
+        assert_eq!(ledger.balance(&retained)[&Commodity::default()], Decimal::from(-125));
+    }
+
+    #[test]
+    fn account_codes_are_trimmed_and_validated() {
+        let mut ledger = Ledger::new();
+        let cash = Account::new("Cash", AccountType::Asset).with_code(" 1000 ");
+        let id = cash.id;
+        ledger.add_account(cash).unwrap();
+        assert_eq!(ledger.account_by_code("1000").map(|a| a.id), Some(id));
+        assert_eq!(ledger.account(&id).and_then(|a| a.code.as_deref()), Some("1000"));
+
+        assert!(ledger.add_account(Account::new("Bank", AccountType::Asset).with_code("1000")).is_err());
+        assert!(ledger.add_account(Account::new("Blank", AccountType::Asset).with_code("  ")).is_err());
+        assert!(ledger.add_account(Account::new("Tab", AccountType::Asset).with_code("10\t00")).is_err());
+    }
+
+    #[test]
+    fn opening_balances_account_is_created_under_equity_only_when_valid() {
+        let mut ledger = Ledger::new();
+        let equity = add(&mut ledger, "Equity", AccountType::Equity);
+        let cash = add(&mut ledger, "Cash", AccountType::Asset);
+        ledger.lock_period(date(2024, 1, 31)).unwrap();
+
+        assert!(ledger.set_opening_balances(date(2024, 1, 1), vec![(cash, Decimal::from(100))]).is_err());
+        assert_eq!(ledger.opening_balances_account(), None);
+        assert!(!ledger.accounts.values().any(|a| a.name == "Opening Balances"));
+
+        ledger.set_opening_balances(date(2024, 2, 1), vec![(cash, Decimal::from(100))]).unwrap();
+        let opening = ledger.opening_balances_account().and_then(|id| ledger.account(&id)).unwrap();
+        assert_eq!(opening.name, "Opening Balances");
+        assert_eq!(opening.parent_id, Some(equity));
+    }
+
+    #[test]
+    fn eur_book_posts_generated_entries_in_eur_and_retranslates_to_gbp() {
+        use crate::inventory::{Inventory, Item};
+        use crate::ledger::depreciation::{DepreciationMethod, DepreciationSchedule};
+        use crate::payroll::PayrollTemplate;
+
+        let (eur, gbp) = (Commodity::new("EUR"), Commodity::new("GBP"));
+        let mut ledger = Ledger::with_base_currency(eur.clone());
+        let cash = add(&mut ledger, "Cash", AccountType::Asset);
+        let stock = add(&mut ledger, "Inventory", AccountType::Asset);
+        let equipment = add(&mut ledger, "Equipment", AccountType::Asset);
+        let accumulated = add(&mut ledger, "Accumulated depreciation", AccountType::Asset);
+        let capital = add(&mut ledger, "Capital", AccountType::Equity);
+        let sales = add(&mut ledger, "Sales", AccountType::Revenue);
+        let cogs = add(&mut ledger, "Cost of goods sold", AccountType::Expense);
+        let wages = add(&mut ledger, "Wages", AccountType::Expense);
+        let depreciation = add(&mut ledger, "Depreciation", AccountType::Expense);
+
+        let opening = Transaction::new(date(2024, 1, 1), "Capital", vec![
+            ledger.posting(cash, Decimal::from(5000)),
+            ledger.posting(capital, Decimal::from(-5000)),
+        ]);
+        ledger.record_transaction(opening).unwrap();
+        let purchase = Transaction::new(date(2024, 1, 2), "Laptop", vec![
+            ledger.posting(equipment, Decimal::from(1200)),
+            ledger.posting(cash, Decimal::from(-1200)),
+        ]);
+        ledger.record_transaction(purchase).unwrap();
+
+        let mut schedule = DepreciationSchedule::new(
+            Decimal::from(1200), ledger.base_currency().clone(), Decimal::ZERO, DepreciationMethod::StraightLine,
+            12, date(2024, 1, 2), depreciation, accumulated,
+        ).unwrap();
+        schedule.post_due(&mut ledger, date(2024, 1, 31)).unwrap();
+
+        let payroll = PayrollTemplate::new("DE", wages, cash, ledger.base_currency().clone());
+        let run = payroll.generate(Decimal::from(2000), date(2024, 1, 25), "January payroll", ledger.rounding_policy()).unwrap();
+        ledger.record_transaction(run.transaction).unwrap();
+
+        let mut inventory = Inventory::new();
+        let item = Item {
+            id: Uuid::new_v4(),
+            sku: "W-1".to_string(),
+            name: "Widget".to_string(),
+            inventory_account: stock,
+            cogs_account: cogs,
+            revenue_account: sales,
+        };
+        let item_id = item.id;
+        inventory.add_item(item).unwrap();
+        inventory.purchase(&mut ledger, item_id, Decimal::from(3), Decimal::from(10), date(2024, 1, 10), cash).unwrap();
+        inventory.sale(&mut ledger, item_id, Decimal::ONE, Decimal::from(25), date(2024, 1, 20), cash).unwrap();
+
+        assert!(ledger.transactions().flat_map(|t| &t.postings).all(|p| p.commodity == eur));
+        assert!(ledger.accounts.values().all(|a| a.commodity == eur));
+        assert!(ledger.equation().balanced);
+        assert_eq!(ledger.balance(&cash)[&eur], Decimal::from(1795));
+
+        let change = ledger.begin_base_currency_change(gbp.clone()).with_rate(Decimal::new(8567, 4));
+        let entry = ledger.apply_base_currency_change(&change, date(2024, 2, 1)).unwrap().unwrap();
+
+        assert_eq!(ledger.base_currency(), &gbp);
+        assert!(ledger.accounts.values().all(|a| a.commodity == gbp));
+        assert!(ledger.transaction(&entry).unwrap().is_balanced());
+        assert!(ledger.balances.values().all(|b| b.get(&eur).is_none_or(|a| a.is_zero())));
+        assert_eq!(ledger.balance(&cash)[&gbp], Decimal::new(153778, 2));
+        assert_eq!(ledger.balance(&capital)[&gbp], Decimal::new(-428350, 2));
+        assert!(ledger.equation().balanced);
+    }
+
+    #[test]
+    fn base_currency_change_refuses_stale_lines() {
+        let mut ledger = Ledger::with_base_currency(Commodity::new("EUR"));
+        let cash = add(&mut ledger, "Cash", AccountType::Asset);
+        let capital = add(&mut ledger, "Capital", AccountType::Equity);
+        let change = ledger.begin_base_currency_change(Commodity::new("GBP")).with_rate(Decimal::new(85, 2));
+        let tx = Transaction::new(date(2024, 1, 1), "Capital", vec![
+            ledger.posting(cash, Decimal::from(100)),
+            ledger.posting(capital, Decimal::from(-100)),
+        ]);
+        ledger.record_transaction(tx).unwrap();
+
+        assert!(ledger.apply_base_currency_change(&change, date(2024, 1, 2)).is_err());
+        assert_eq!(ledger.base_currency(), &Commodity::new("EUR"));
+    }
+
+    #[test]
+    fn match_statement_clamps_huge_tolerances_and_refuses_negative_ones() {
+        use reconcile::StatementLine;
+
+        let mut ledger = Ledger::new();
+        let cash = add(&mut ledger, "Cash", AccountType::Asset);
+        let sales = add(&mut ledger, "Sales", AccountType::Revenue);
+        let tx = Transaction::new(date(2020, 1, 1), "Sale", vec![
+            ledger.posting(cash, Decimal::from(40)),
+            ledger.posting(sales, Decimal::from(-40)),
+        ]);
+        ledger.record_transaction(tx).unwrap();
+        let line = StatementLine { date: date(2024, 3, 5), amount: Decimal::from(40), description: String::new() };
+        let statement = Statement::new(cash, date(2024, 3, 1), date(2024, 3, 31), Decimal::ZERO, Decimal::from(40))
+            .with_lines(vec![line]);
+
+        let wide = MatchTolerance { days: i64::MAX, amount: Decimal::ZERO };
+        assert_eq!(ledger.match_statement(&statement, wide).unwrap().matched.len(), 1);
+        let negative = MatchTolerance { days: -1, amount: Decimal::ZERO };
+        assert!(ledger.match_statement(&statement, negative).is_err());
+        let negative = MatchTolerance { days: 3, amount: Decimal::NEGATIVE_ONE };
+        assert!(ledger.match_statement(&statement, negative).is_err());
+    }
+}
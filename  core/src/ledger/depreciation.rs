@@ -0,0 +1,195 @@
+//! Fixed-asset depreciation schedules posting one entry per month
+use chrono::{Datelike, Months, NaiveDate};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::currency::Commodity;
+use crate::ledger::{Ledger, LedgerError, Posting, Transaction};
+use crate::rounding::RoundingPolicy;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DepreciationMethod {
+    /// Equal monthly amounts over the life
+    StraightLine,
+    /// `annual_rate` of the remaining book value (e.g. 0.4 for double-declining over 5 years),
+    /// switching to straight-line once that depreciates faster
+    DecliningBalance { annual_rate: Decimal },
+}
+
+/// One month's depreciation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DepreciationEntry {
+    /// Month end the entry is dated
+    pub date: NaiveDate,
+    pub amount: Decimal,
+    /// Book value after this entry
+    pub book_value: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DepreciationSchedule {
+    pub id: Uuid,
+    pub description: String,
+    #[serde(with = "crate::canonical::serde_decimal")]
+    pub cost: Decimal,
+    #[serde(with = "crate::canonical::serde_decimal")]
+    pub salvage_value: Decimal,
+    pub method: DepreciationMethod,
+    pub life_months: u32,
+    /// In-service date; the first entry is at the end of this month
+    pub start: NaiveDate,
+    pub commodity: Commodity,
+    /// Debited each month
+    pub expense_account: Uuid,
+    /// Contra-asset credited each month
+    pub accumulated_account: Uuid,
+    /// Last month end already posted
+    #[serde(default)]
+    pub posted_through: Option<NaiveDate>,
+}
+
+impl DepreciationSchedule {
+    /// Schedule for an asset bought for `cost` in `commodity`, usually the ledger's base currency
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        cost: Decimal,
+        commodity: Commodity,
+        salvage_value: Decimal,
+        method: DepreciationMethod,
+        life_months: u32,
+        start: NaiveDate,
+        expense_account: Uuid,
+        accumulated_account: Uuid,
+    ) -> Result<Self, &'static str> {
+        if life_months == 0 {
+            return Err("Useful life must be at least one month");
+        }
+        if cost <= Decimal::ZERO || salvage_value < Decimal::ZERO || salvage_value > cost {
+            return Err("Salvage value must be between zero and the asset cost");
+        }
+        if let DepreciationMethod::DecliningBalance { annual_rate } = method {
+            if annual_rate <= Decimal::ZERO || annual_rate > Decimal::ONE {
+                return Err("Declining balance rate must be between 0 and 1");
+            }
+        }
+        Ok(Self {
+            id: Uuid::new_v4(),
+            description: String::new(),
+            cost,
+            salvage_value,
+            method,
+            life_months,
+            start,
+            commodity,
+            expense_account,
+            accumulated_account,
+            posted_through: None,
+        })
+    }
+
+    /// Asset name used in the entries' descriptions
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Every monthly entry over the asset's life, rounded by the ledger's policy; the last one
+    /// lands exactly on the salvage value
+    pub fn entries(&self, policy: &RoundingPolicy) -> Vec<DepreciationEntry> {
+        let first_month = self.start.with_day(1).unwrap();
+        let mut book_value = self.cost;
+        let mut entries = Vec::with_capacity(self.life_months as usize);
+        for month in 0..self.life_months {
+            let remaining_months = Decimal::from(self.life_months - month);
+            let remaining = book_value - self.salvage_value;
+            let amount = if month + 1 == self.life_months {
+                remaining
+            } else {
+                let straight_line = remaining / remaining_months;
+                let amount = match self.method {
+                    DepreciationMethod::StraightLine => (self.cost - self.salvage_value) / Decimal::from(self.life_months),
+                    DepreciationMethod::DecliningBalance { annual_rate } => {
+                        (book_value * annual_rate / Decimal::from(12)).max(straight_line)
+                    }
+                };
+                policy.round(amount, &self.commodity).min(remaining)
+            };
+            book_value -= amount;
+            let date = (first_month + Months::new(month + 1)).pred_opt().unwrap();
+            entries.push(DepreciationEntry { date, amount, book_value });
+        }
+        entries
+    }
+
+    /// Book value after all entries dated on or before `date`, under the policy the entries are
+    /// posted with
+    pub fn book_value(&self, date: NaiveDate, policy: &RoundingPolicy) -> Decimal {
+        self.entries(policy).iter()
+            .take_while(|e| e.date <= date)
+            .last()
+            .map_or(self.cost, |e| e.book_value)
+    }
+
+    fn transaction(&self, entry: &DepreciationEntry) -> Transaction {
+        let description = match self.description.as_str() {
+            "" => "Depreciation".to_string(),
+            asset => format!("Depreciation: {}", asset),
+        };
+        Transaction::new(entry.date, description, vec![
+            Posting::in_commodity(self.expense_account, entry.amount, self.commodity.clone()),
+            Posting::in_commodity(self.accumulated_account, -entry.amount, self.commodity.clone()),
+        ])
+    }
+
+    /// Depreciation transactions dated through `today` that haven't been posted yet
+    pub fn due(&self, today: NaiveDate, policy: &RoundingPolicy) -> Vec<Transaction> {
+        self.entries(policy).iter()
+            .filter(|e| e.date <= today && self.posted_through.is_none_or(|p| e.date > p))
+            .filter(|e| !e.amount.is_zero())
+            .map(|e| self.transaction(e))
+            .collect()
+    }
+
+    /// Record every due entry and advance `posted_through`; meant to run as a scheduled job.
+    /// Stops at the first rejected entry so a later run resumes from there.
+    pub fn post_due(&mut self, ledger: &mut Ledger, today: NaiveDate) -> Result<Vec<Uuid>, LedgerError> {
+        let mut posted = Vec::new();
+        for tx in self.due(today, ledger.rounding_policy()) {
+            let (id, date) = (tx.id, tx.date);
+            ledger.record_transaction(tx)?;
+            self.posted_through = Some(date);
+            posted.push(id);
+        }
+        Ok(posted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::{Account, AccountType};
+    use crate::rounding::RoundingMode;
+
+    #[test]
+    fn book_value_follows_the_posting_policy() {
+        let mut ledger = Ledger::new();
+        let expense = Account::new("Depreciation", AccountType::Expense);
+        let accumulated = Account::new("Accumulated depreciation", AccountType::Asset);
+        let (expense_id, accumulated_id) = (expense.id, accumulated.id);
+        ledger.add_account(expense).unwrap();
+        ledger.add_account(accumulated).unwrap();
+        ledger.set_rounding_policy(RoundingPolicy::new(RoundingMode::Down).with_scale(0)).unwrap();
+
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let mut schedule = DepreciationSchedule::new(
+            Decimal::from(1000), ledger.base_currency().clone(), Decimal::ZERO, DepreciationMethod::StraightLine, 3, start, expense_id, accumulated_id,
+        ).unwrap();
+        let january = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+        schedule.post_due(&mut ledger, january).unwrap();
+
+        let posted = ledger.journal.last().unwrap().postings[0].amount;
+        assert_eq!(posted, Decimal::from(333));
+        assert_eq!(schedule.book_value(january, ledger.rounding_policy()), Decimal::from(1000) - posted);
+    }
+}
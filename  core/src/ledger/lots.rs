@@ -0,0 +1,261 @@
+//! Cost-basis lots for investment accounts and realized gains on disposal
+use std::collections::{HashMap, VecDeque};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::currency::Commodity;
+use crate::ledger::Transaction;
+
+/// Security quantity carried by a posting; the posting amount is the cost (or proceeds) in cash.
+/// Positive quantity acquires a lot, negative quantity disposes of units at `price`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lot {
+    pub security: Commodity,
+    #[serde(with = "crate::canonical::serde_decimal")]
+    pub quantity: Decimal,
+    /// Cost per unit when acquiring, sale price per unit when disposing
+    #[serde(with = "crate::canonical::serde_decimal")]
+    pub price: Decimal,
+    pub acquired_on: NaiveDate,
+}
+
+impl Lot {
+    pub fn new(security: Commodity, quantity: Decimal, price: Decimal, acquired_on: NaiveDate) -> Self {
+        Self { security, quantity, price, acquired_on }
+    }
+
+    pub fn cost(&self) -> Decimal {
+        self.quantity * self.price
+    }
+}
+
+/// Which open lots a disposal is matched against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CostBasisMethod {
+    #[default]
+    Fifo,
+    Lifo,
+    /// Every unit carries the pool's average cost
+    AverageCost,
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum LotError {
+    #[error("Cannot dispose of {requested} {security}: only {available} held")]
+    InsufficientQuantity { security: Commodity, requested: Decimal, available: Decimal },
+    #[error("Disposal quantity must be positive")]
+    InvalidQuantity,
+}
+
+/// Part of an open lot consumed by a disposal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LotMatch {
+    pub acquired_on: NaiveDate,
+    pub quantity: Decimal,
+    pub cost: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RealizedGain {
+    pub date: NaiveDate,
+    pub security: Commodity,
+    pub quantity: Decimal,
+    pub proceeds: Decimal,
+    pub cost_basis: Decimal,
+    pub gain: Decimal,
+    pub matched: Vec<LotMatch>,
+}
+
+/// Open lots per security for one account
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Holdings {
+    pub method: CostBasisMethod,
+    lots: HashMap<Commodity, VecDeque<Lot>>,
+}
+
+impl Holdings {
+    pub fn new(method: CostBasisMethod) -> Self {
+        Self { method, lots: HashMap::new() }
+    }
+
+    /// Open lots of a security, oldest first
+    pub fn lots(&self, security: &Commodity) -> impl Iterator<Item = &Lot> {
+        self.lots.get(security).into_iter().flatten()
+    }
+
+    pub fn quantity(&self, security: &Commodity) -> Decimal {
+        self.lots(security).map(|l| l.quantity).sum()
+    }
+
+    pub fn cost_basis(&self, security: &Commodity) -> Decimal {
+        self.lots(security).map(Lot::cost).sum()
+    }
+
+    pub fn acquire(&mut self, lot: Lot) {
+        let lots = self.lots.entry(lot.security.clone()).or_default();
+        let pos = lots.partition_point(|l| l.acquired_on <= lot.acquired_on);
+        lots.insert(pos, lot);
+    }
+
+    /// Relieve `quantity` units sold at `price` each and return the realized gain
+    pub fn dispose(
+        &mut self,
+        security: &Commodity,
+        quantity: Decimal,
+        price: Decimal,
+        date: NaiveDate,
+    ) -> Result<RealizedGain, LotError> {
+        if quantity <= Decimal::ZERO {
+            return Err(LotError::InvalidQuantity);
+        }
+        let available = self.quantity(security);
+        if quantity > available {
+            return Err(LotError::InsufficientQuantity { security: security.clone(), requested: quantity, available });
+        }
+        let lots = self.lots.get_mut(security).unwrap();
+        if self.method == CostBasisMethod::AverageCost {
+            let average = lots.iter().map(Lot::cost).sum::<Decimal>() / available;
+            for lot in lots.iter_mut() {
+                lot.price = average;
+            }
+        }
+
+        let mut remaining = quantity;
+        let mut matched = Vec::new();
+        while remaining > Decimal::ZERO {
+            let lot = match self.method {
+                CostBasisMethod::Lifo => lots.back_mut(),
+                CostBasisMethod::Fifo | CostBasisMethod::AverageCost => lots.front_mut(),
+            }
+            .unwrap();
+            let taken = remaining.min(lot.quantity);
+            matched.push(LotMatch { acquired_on: lot.acquired_on, quantity: taken, cost: taken * lot.price });
+            lot.quantity -= taken;
+            remaining -= taken;
+            if lot.quantity.is_zero() {
+                match self.method {
+                    CostBasisMethod::Lifo => lots.pop_back(),
+                    CostBasisMethod::Fifo | CostBasisMethod::AverageCost => lots.pop_front(),
+                };
+            }
+        }
+
+        let proceeds = quantity * price;
+        let cost_basis: Decimal = matched.iter().map(|m| m.cost).sum();
+        Ok(RealizedGain {
+            date,
+            security: security.clone(),
+            quantity,
+            proceeds,
+            cost_basis,
+            gain: proceeds - cost_basis,
+            matched,
+        })
+    }
+}
+
+/// Replay lot postings on `account_id` in date order; returns remaining holdings and realized gains
+pub fn realize(
+    transactions: &[Transaction],
+    account_id: Uuid,
+    method: CostBasisMethod,
+) -> Result<(Holdings, Vec<RealizedGain>), LotError> {
+    let mut ordered: Vec<&Transaction> = transactions.iter().collect();
+    ordered.sort_by_key(|tx| tx.date);
+
+    let mut holdings = Holdings::new(method);
+    let mut gains = Vec::new();
+    for tx in ordered {
+        for lot in tx.postings.iter().filter(|p| p.account_id == account_id).filter_map(|p| p.lot.as_ref()) {
+            if lot.quantity > Decimal::ZERO {
+                holdings.acquire(lot.clone());
+            } else if lot.quantity < Decimal::ZERO {
+                gains.push(holdings.dispose(&lot.security, -lot.quantity, lot.price, tx.date)?);
+            }
+        }
+    }
+    Ok((holdings, gains))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::Posting;
+
+    fn date(m: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, m, 1).unwrap()
+    }
+
+    fn acme() -> Commodity {
+        Commodity::new("ACME")
+    }
+
+    /// 10 units at 100 in January, 10 at 120 in February (acquired out of order)
+    fn holdings(method: CostBasisMethod) -> Holdings {
+        let mut holdings = Holdings::new(method);
+        holdings.acquire(Lot::new(acme(), Decimal::from(10), Decimal::from(120), date(2)));
+        holdings.acquire(Lot::new(acme(), Decimal::from(10), Decimal::from(100), date(1)));
+        holdings
+    }
+
+    #[test]
+    fn each_method_relieves_its_own_lots() {
+        for (method, cost, left) in [
+            (CostBasisMethod::Fifo, 1600, 120),
+            (CostBasisMethod::Lifo, 1700, 100),
+            (CostBasisMethod::AverageCost, 1650, 110),
+        ] {
+            let mut holdings = holdings(method);
+            let gain = holdings.dispose(&acme(), Decimal::from(15), Decimal::from(150), date(3)).unwrap();
+            assert_eq!(gain.proceeds, Decimal::from(2250), "{:?}", method);
+            assert_eq!(gain.cost_basis, Decimal::from(cost), "{:?}", method);
+            assert_eq!(gain.gain, Decimal::from(2250 - cost), "{:?}", method);
+            assert_eq!(gain.matched.iter().map(|m| m.quantity).sum::<Decimal>(), Decimal::from(15));
+            assert_eq!(holdings.quantity(&acme()), Decimal::from(5));
+            assert_eq!(holdings.cost_basis(&acme()), Decimal::from(5 * left), "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn fifo_matches_the_oldest_lot_first() {
+        let mut holdings = holdings(CostBasisMethod::Fifo);
+        let gain = holdings.dispose(&acme(), Decimal::from(12), Decimal::from(150), date(3)).unwrap();
+        assert_eq!(gain.matched, vec![
+            LotMatch { acquired_on: date(1), quantity: Decimal::from(10), cost: Decimal::from(1000) },
+            LotMatch { acquired_on: date(2), quantity: Decimal::from(2), cost: Decimal::from(240) },
+        ]);
+    }
+
+    #[test]
+    fn disposals_are_checked_against_holdings() {
+        let mut holdings = holdings(CostBasisMethod::Fifo);
+        assert_eq!(
+            holdings.dispose(&acme(), Decimal::from(21), Decimal::ONE, date(3)),
+            Err(LotError::InsufficientQuantity { security: acme(), requested: Decimal::from(21), available: Decimal::from(20) }),
+        );
+        assert_eq!(holdings.dispose(&acme(), Decimal::ZERO, Decimal::ONE, date(3)), Err(LotError::InvalidQuantity));
+        assert_eq!(holdings.quantity(&acme()), Decimal::from(20));
+    }
+
+    #[test]
+    fn realize_replays_postings_in_date_order() {
+        let (broker, cash) = (Uuid::new_v4(), Uuid::new_v4());
+        let trade = |on: NaiveDate, quantity: i64, price: i64| {
+            let amount = Decimal::from(quantity * price);
+            Transaction::new(on, "Trade", vec![
+                Posting::new(broker, amount).with_lot(Lot::new(acme(), Decimal::from(quantity), Decimal::from(price), on)),
+                Posting::new(cash, -amount),
+            ])
+        };
+        let transactions = vec![trade(date(3), -5, 130), trade(date(1), 10, 100)];
+        let (holdings, gains) = realize(&transactions, broker, CostBasisMethod::Fifo).unwrap();
+        assert_eq!(gains.len(), 1);
+        assert_eq!(gains[0].gain, Decimal::from(150));
+        assert_eq!(holdings.quantity(&acme()), Decimal::from(5));
+
+        let backwards = vec![trade(date(1), -5, 130)];
+        assert!(matches!(realize(&backwards, broker, CostBasisMethod::Fifo), Err(LotError::InsufficientQuantity { .. })));
+    }
+}
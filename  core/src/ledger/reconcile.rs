@@ -0,0 +1,327 @@
+//! Bank reconciliation: cleared state on postings, official statements matched against them
+//! and statement sessions that lock the result in
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+/// How far a posting has been matched against a statement
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ClearedState {
+    #[default]
+    Uncleared,
+    /// Seen on the bank statement
+    Cleared,
+    /// Locked in by a completed reconciliation session
+    Reconciled,
+}
+
+impl ClearedState {
+    pub fn is_uncleared(&self) -> bool {
+        *self == ClearedState::Uncleared
+    }
+}
+
+/// One posting of a recorded transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PostingRef {
+    pub transaction_id: Uuid,
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionStatus {
+    #[default]
+    InProgress,
+    Completed,
+}
+
+/// Reconciliation of one account against one statement; balances are debit-positive
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReconciliationSession {
+    pub id: Uuid,
+    pub account_id: Uuid,
+    pub statement_start: NaiveDate,
+    pub statement_end: NaiveDate,
+    #[serde(with = "crate::canonical::serde_decimal")]
+    pub start_balance: Decimal,
+    #[serde(with = "crate::canonical::serde_decimal")]
+    pub end_balance: Decimal,
+    #[serde(default)]
+    pub status: SessionStatus,
+    #[serde(default)]
+    pub completed_on: Option<NaiveDate>,
+    /// Postings locked in when the session was completed
+    #[serde(default)]
+    pub reconciled: Vec<PostingRef>,
+}
+
+impl ReconciliationSession {
+    pub fn new(
+        account_id: Uuid,
+        statement_start: NaiveDate,
+        statement_end: NaiveDate,
+        start_balance: Decimal,
+        end_balance: Decimal,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            account_id,
+            statement_start,
+            statement_end,
+            start_balance,
+            end_balance,
+            status: SessionStatus::InProgress,
+            completed_on: None,
+            reconciled: Vec::new(),
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.status == SessionStatus::Completed
+    }
+}
+
+/// Cleared balance against a statement balance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReconciliationReport {
+    pub account_id: Uuid,
+    pub statement_balance: Decimal,
+    /// Sum of cleared and reconciled postings
+    pub cleared_balance: Decimal,
+    /// Statement minus cleared balance; zero once everything on the statement is cleared
+    pub difference: Decimal,
+    /// Postings not yet cleared, oldest first
+    pub uncleared: Vec<PostingRef>,
+    /// Total of the uncleared postings
+    pub uncleared_total: Decimal,
+}
+
+impl ReconciliationReport {
+    pub fn is_balanced(&self) -> bool {
+        self.difference.is_zero()
+    }
+}
+
+/// One line of an official bank statement
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatementLine {
+    pub date: NaiveDate,
+    /// Debit-positive from the account's point of view, like postings
+    #[serde(with = "crate::canonical::serde_decimal")]
+    pub amount: Decimal,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Statement issued by the bank for one account and period
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Statement {
+    pub id: Uuid,
+    pub account_id: Uuid,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    #[serde(with = "crate::canonical::serde_decimal")]
+    pub start_balance: Decimal,
+    #[serde(with = "crate::canonical::serde_decimal")]
+    pub end_balance: Decimal,
+    pub lines: Vec<StatementLine>,
+    /// Stored document (e.g. the PDF) the lines were taken from
+    #[serde(default)]
+    pub document_id: Option<Uuid>,
+}
+
+impl Statement {
+    pub fn new(
+        account_id: Uuid,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+        start_balance: Decimal,
+        end_balance: Decimal,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            account_id,
+            start_date,
+            end_date,
+            start_balance,
+            end_balance,
+            lines: Vec::new(),
+            document_id: None,
+        }
+    }
+
+    pub fn with_lines(mut self, lines: Vec<StatementLine>) -> Self {
+        self.lines = lines;
+        self
+    }
+
+    pub fn with_document(mut self, document_id: Uuid) -> Self {
+        self.document_id = Some(document_id);
+        self
+    }
+
+    /// Whether the lines account for the move from start to end balance
+    pub fn is_consistent(&self) -> bool {
+        self.start_balance + self.lines.iter().map(|l| l.amount).sum::<Decimal>() == self.end_balance
+    }
+
+    /// Reconciliation session against this statement
+    pub fn session(&self) -> ReconciliationSession {
+        ReconciliationSession::new(self.account_id, self.start_date, self.end_date, self.start_balance, self.end_balance)
+    }
+}
+
+/// How far a posting may be from a statement line and still match it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MatchTolerance {
+    /// Days between posting and booking date, e.g. for card payments that settle later
+    pub days: i64,
+    /// Absolute amount difference, e.g. for fees netted by the bank
+    pub amount: Decimal,
+}
+
+impl Default for MatchTolerance {
+    fn default() -> Self {
+        Self { days: 3, amount: Decimal::ZERO }
+    }
+}
+
+/// Statement line paired with a ledger posting
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StatementMatch {
+    /// Index into `Statement::lines`
+    pub line: usize,
+    pub posting: PostingRef,
+    /// Posting date minus line date, in days
+    pub date_difference: i64,
+    /// Posting amount minus line amount
+    pub amount_difference: Decimal,
+}
+
+impl StatementMatch {
+    pub fn is_exact(&self) -> bool {
+        self.date_difference == 0 && self.amount_difference.is_zero()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatementMatches {
+    pub matched: Vec<StatementMatch>,
+    /// Lines with no posting within tolerance, e.g. bank fees not yet booked
+    pub unmatched_lines: Vec<usize>,
+    /// Candidate postings no line claimed
+    pub unmatched_postings: Vec<PostingRef>,
+}
+
+/// Pair statement lines with candidate postings `(posting, date, amount)`. Each side is used at
+/// most once; closest amounts win, then closest dates, then statement order.
+pub fn match_statement(
+    statement: &Statement,
+    candidates: &[(PostingRef, NaiveDate, Decimal)],
+    tolerance: MatchTolerance,
+) -> StatementMatches {
+    let mut pairs = Vec::new();
+    for (line_index, line) in statement.lines.iter().enumerate() {
+        for (candidate, (posting, date, amount)) in candidates.iter().enumerate() {
+            let date_difference = (*date - line.date).num_days();
+            let amount_difference = *amount - line.amount;
+            if date_difference.abs() <= tolerance.days && amount_difference.abs() <= tolerance.amount {
+                pairs.push((amount_difference.abs(), date_difference.abs(), line_index, candidate, StatementMatch {
+                    line: line_index,
+                    posting: *posting,
+                    date_difference,
+                    amount_difference,
+                }));
+            }
+        }
+    }
+    pairs.sort_by_key(|a| (a.0, a.1, a.2, a.3));
+
+    let mut line_taken = vec![false; statement.lines.len()];
+    let mut candidate_taken = vec![false; candidates.len()];
+    let mut matched = Vec::new();
+    for (_, _, line, candidate, m) in pairs {
+        if line_taken[line] || candidate_taken[candidate] {
+            continue;
+        }
+        line_taken[line] = true;
+        candidate_taken[candidate] = true;
+        matched.push(m);
+    }
+    matched.sort_by_key(|m| m.line);
+
+    StatementMatches {
+        matched,
+        unmatched_lines: (0..statement.lines.len()).filter(|i| !line_taken[*i]).collect(),
+        unmatched_postings: candidates.iter()
+            .zip(&candidate_taken)
+            .filter(|(_, taken)| !**taken)
+            .map(|((posting, _, _), _)| *posting)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, d).unwrap()
+    }
+
+    fn line(d: u32, cents: i64) -> StatementLine {
+        StatementLine { date: date(d), amount: Decimal::new(cents, 2), description: String::new() }
+    }
+
+    fn posting(index: usize) -> PostingRef {
+        PostingRef { transaction_id: Uuid::nil(), index }
+    }
+
+    fn statement(lines: Vec<StatementLine>) -> Statement {
+        let end = lines.iter().map(|l| l.amount).sum();
+        Statement::new(Uuid::new_v4(), date(1), date(31), Decimal::ZERO, end).with_lines(lines)
+    }
+
+    #[test]
+    fn consistency_requires_the_lines_to_add_up() {
+        let mut statement = statement(vec![line(2, 5000), line(3, -1250)]);
+        assert!(statement.is_consistent());
+        statement.end_balance += Decimal::ONE;
+        assert!(!statement.is_consistent());
+        assert_eq!(statement.session().end_balance, statement.end_balance);
+    }
+
+    #[test]
+    fn closest_amount_then_closest_date_wins() {
+        let statement = statement(vec![line(10, -2000), line(10, -2000), line(12, 9900)]);
+        let candidates = vec![
+            (posting(0), date(12), Decimal::new(-2000, 2)),
+            (posting(1), date(10), Decimal::new(-2000, 2)),
+            (posting(2), date(11), Decimal::new(10000, 2)),
+            (posting(3), date(20), Decimal::new(-2000, 2)),
+        ];
+        let tolerance = MatchTolerance { days: 3, amount: Decimal::ONE };
+        let matches = match_statement(&statement, &candidates, tolerance);
+
+        assert_eq!(matches.matched.len(), 3);
+        assert!(matches.matched[0].is_exact());
+        assert_eq!(matches.matched[0].posting, posting(1));
+        assert_eq!(matches.matched[1].posting, posting(0));
+        assert_eq!(matches.matched[1].date_difference, 2);
+        assert_eq!(matches.matched[2].posting, posting(2));
+        assert_eq!(matches.matched[2].amount_difference, Decimal::ONE);
+        assert_eq!(matches.unmatched_lines, Vec::<usize>::new());
+        assert_eq!(matches.unmatched_postings, vec![posting(3)]);
+    }
+
+    #[test]
+    fn nothing_outside_the_tolerance_matches() {
+        let statement = statement(vec![line(10, -2000), line(10, -350)]);
+        let candidates = vec![(posting(0), date(14), Decimal::new(-2000, 2))];
+        let matches = match_statement(&statement, &candidates, MatchTolerance::default());
+        assert!(matches.matched.is_empty());
+        assert_eq!(matches.unmatched_lines, vec![0, 1]);
+        assert_eq!(matches.unmatched_postings, vec![posting(0)]);
+    }
+}
@@ -0,0 +1,243 @@
+//! Tax codes on postings and the VAT return built from them
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::RangeInclusive;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::ledger::Transaction;
+
+/// Whether tax under a code is collected on sales or reclaimable on purchases
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TaxDirection {
+    Output,
+    Input,
+}
+
+/// One tax code, e.g. "S20" standard-rated sales at 20%
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaxRate {
+    pub code: String,
+    pub description: String,
+    #[serde(with = "crate::canonical::serde_decimal")]
+    pub rate: Decimal,
+    pub direction: TaxDirection,
+    /// Account the tax itself is booked to; tagged postings to any other account are the net base
+    pub account_id: Uuid,
+}
+
+impl TaxRate {
+    pub fn new(code: impl Into<String>, rate: Decimal, direction: TaxDirection, account_id: Uuid) -> Self {
+        Self { code: code.into(), description: String::new(), rate, direction, account_id }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Tax due on a net amount at this rate
+    pub fn tax_on(&self, net: Decimal) -> Decimal {
+        (net * self.rate).round_dp(2)
+    }
+}
+
+/// Tax codes by code
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaxTable {
+    rates: BTreeMap<String, TaxRate>,
+}
+
+impl TaxTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_rate(mut self, rate: TaxRate) -> Self {
+        self.insert(rate);
+        self
+    }
+
+    /// Add or replace a code
+    pub fn insert(&mut self, rate: TaxRate) {
+        self.rates.insert(rate.code.clone(), rate);
+    }
+
+    pub fn remove(&mut self, code: &str) -> Option<TaxRate> {
+        self.rates.remove(code)
+    }
+
+    pub fn get(&self, code: &str) -> Option<&TaxRate> {
+        self.rates.get(code)
+    }
+
+    pub fn rates(&self) -> impl Iterator<Item = &TaxRate> {
+        self.rates.values()
+    }
+}
+
+/// Net and tax totals for one code; both positive for ordinary sales and purchases
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VatLine {
+    pub code: String,
+    pub direction: TaxDirection,
+    pub rate: Decimal,
+    pub net: Decimal,
+    /// Tax actually booked to the code's tax account
+    pub tax: Decimal,
+}
+
+impl VatLine {
+    /// Booked tax minus the tax the rate implies on the net, e.g. from rounding per invoice
+    pub fn difference(&self) -> Decimal {
+        self.tax - (self.net * self.rate).round_dp(2)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VatReport {
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    /// Output codes first, then input, each by code
+    pub lines: Vec<VatLine>,
+    /// Codes found on postings but missing from the tax table
+    pub unknown_codes: BTreeSet<String>,
+}
+
+impl VatReport {
+    pub fn output_tax(&self) -> Decimal {
+        self.total(TaxDirection::Output)
+    }
+
+    pub fn input_tax(&self) -> Decimal {
+        self.total(TaxDirection::Input)
+    }
+
+    /// Output minus input tax; negative when a refund is due
+    pub fn net_payable(&self) -> Decimal {
+        self.output_tax() - self.input_tax()
+    }
+
+    fn total(&self, direction: TaxDirection) -> Decimal {
+        self.lines.iter().filter(|l| l.direction == direction).map(|l| l.tax).sum()
+    }
+}
+
+/// Aggregate tagged postings dated within `period` per tax code
+pub fn vat_report<'a>(
+    transactions: impl IntoIterator<Item = &'a Transaction>,
+    table: &TaxTable,
+    period: RangeInclusive<NaiveDate>,
+) -> VatReport {
+    let mut totals: BTreeMap<(TaxDirection, &str), (Decimal, Decimal)> = BTreeMap::new();
+    let mut unknown_codes = BTreeSet::new();
+    let transactions = transactions.into_iter()
+        .filter(|t| period.contains(&t.date) && !t.is_closing_entry);
+    for tx in transactions {
+        for posting in &tx.postings {
+            let Some(code) = posting.tax_code.as_deref() else { continue };
+            let Some(rate) = table.get(code) else {
+                unknown_codes.insert(code.to_string());
+                continue;
+            };
+            // Sales are credits and purchases debits; flip output so both read positive
+            let amount = match rate.direction {
+                TaxDirection::Output => -posting.amount,
+                TaxDirection::Input => posting.amount,
+            };
+            let (net, tax) = totals.entry((rate.direction, rate.code.as_str())).or_default();
+            if posting.account_id == rate.account_id {
+                *tax += amount;
+            } else {
+                *net += amount;
+            }
+        }
+    }
+
+    let lines = totals.into_iter()
+        .map(|((direction, code), (net, tax))| VatLine {
+            code: code.to_string(),
+            direction,
+            rate: table.get(code).map_or(Decimal::ZERO, |r| r.rate),
+            net,
+            tax,
+        })
+        .collect();
+    VatReport {
+        period_start: *period.start(),
+        period_end: *period.end(),
+        lines,
+        unknown_codes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::Posting;
+
+    fn date(m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, m, d).unwrap()
+    }
+
+    fn cents(amount: i64) -> Decimal {
+        Decimal::new(amount, 2)
+    }
+
+    #[test]
+    fn vat_return_totals_net_and_booked_tax_per_code() {
+        let (bank, sales, expenses, vat_out, vat_in) =
+            (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let table = TaxTable::new()
+            .with_rate(TaxRate::new("S20", Decimal::new(20, 2), TaxDirection::Output, vat_out))
+            .with_rate(TaxRate::new("P20", Decimal::new(20, 2), TaxDirection::Input, vat_in));
+        let sale = |on: NaiveDate, net: i64, tax: i64, code: &str| Transaction::new(on, "Sale", vec![
+            Posting::new(bank, cents(net + tax)),
+            Posting::new(sales, cents(-net)).with_tax_code(code),
+            Posting::new(vat_out, cents(-tax)).with_tax_code(code),
+        ]);
+        let purchase = Transaction::new(date(3, 20), "Supplies", vec![
+            Posting::new(expenses, cents(5000)).with_tax_code("P20"),
+            Posting::new(vat_in, cents(1000)).with_tax_code("P20"),
+            Posting::new(bank, cents(-6000)),
+        ]);
+        let mut closing = sale(date(3, 31), 100000, 20000, "S20");
+        closing.is_closing_entry = true;
+        let transactions = vec![
+            sale(date(3, 5), 10000, 2000, "S20"),
+            // Rounded down on the invoice: 33.33 at 20% is 6.67
+            sale(date(3, 12), 3333, 666, "S20"),
+            purchase,
+            sale(date(3, 15), 1000, 0, "Z0"),
+            sale(date(4, 1), 10000, 2000, "S20"),
+            closing,
+        ];
+
+        let report = vat_report(&transactions, &table, date(3, 1)..=date(3, 31));
+        assert_eq!(report.lines, vec![
+            VatLine { code: "S20".to_string(), direction: TaxDirection::Output, rate: Decimal::new(20, 2), net: cents(13333), tax: cents(2666) },
+            VatLine { code: "P20".to_string(), direction: TaxDirection::Input, rate: Decimal::new(20, 2), net: cents(5000), tax: cents(1000) },
+        ]);
+        assert_eq!(report.lines[0].difference(), cents(-1));
+        assert_eq!(report.lines[1].difference(), Decimal::ZERO);
+        assert_eq!(report.output_tax(), cents(2666));
+        assert_eq!(report.input_tax(), cents(1000));
+        assert_eq!(report.net_payable(), cents(1666));
+        assert_eq!(report.unknown_codes, BTreeSet::from(["Z0".to_string()]));
+    }
+
+    #[test]
+    fn refund_is_due_when_input_tax_exceeds_output() {
+        let (bank, expenses, vat_in) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let table = TaxTable::new().with_rate(TaxRate::new("P5", Decimal::new(5, 2), TaxDirection::Input, vat_in));
+        let purchase = Transaction::new(date(3, 2), "Books", vec![
+            Posting::new(expenses, cents(2000)).with_tax_code("P5"),
+            Posting::new(vat_in, cents(100)).with_tax_code("P5"),
+            Posting::new(bank, cents(-2100)),
+        ]);
+        let report = vat_report([&purchase], &table, date(3, 1)..=date(3, 31));
+        assert_eq!(report.net_payable(), cents(-100));
+        assert_eq!(table.get("P5").unwrap().tax_on(cents(3333)), cents(167));
+    }
+}
@@ -1,29 +1,224 @@
 pub mod ledger;
+pub mod currency;
+pub mod canonical;
+pub mod locale;
 pub mod sync;
+pub mod storage;
+pub mod staging;
+pub mod receipts;
+pub mod close;
+pub mod inventory;
+pub mod payroll;
+pub mod activity;
+pub mod reports;
+pub mod dedup;
+pub mod protocol;
+pub mod control;
+pub mod diagnostics;
+pub mod numbering;
+pub mod changeset;
+pub mod demo;
+pub mod matching;
+pub mod conflict;
+pub mod splits;
+pub mod documents;
+pub mod contacts;
+pub mod projects;
+pub mod classes;
+pub mod antientropy;
+pub mod keyring;
+pub mod snapshot;
+pub mod qos;
+pub mod chunker;
+pub mod codec;
+pub mod views;
+pub mod chart;
+pub mod prices;
+pub mod amount;
+pub mod quickentry;
+pub mod recurring;
+pub mod pending;
+pub mod codes;
+pub mod jobs;
+pub mod config;
+pub mod invoicing;
+pub mod export;
+pub mod crypto;
+pub mod import;
+pub mod rules;
+pub mod workspace;
+pub mod delegation;
+pub mod rounding;
+pub mod recovery;
+pub mod attachments;
+pub mod blob_fetch;
+pub mod syncable;
+pub mod quorum;
+pub mod presence;
+pub mod mailbox;
+pub mod app_settings;
 
-pub use ledger::{Account, AccountType, Posting, Transaction, Ledger};
-pub use sync::{SyncDoc, SyncableLedger, SyncError};
+pub use ledger::{
+    Account, AccountDisplay, AccountType, AccountingEquation, BalanceChange, BalanceSample, Basis, Budget,
+    BudgetLine, CashFlowActivity, BudgetPeriod, Granularity, Ledger, LedgerError, Posting, RecordSummary, Simulation, Transaction,
+    TransactionStatus,
+};
+pub use ledger::depreciation::{DepreciationEntry, DepreciationMethod, DepreciationSchedule};
+pub use ledger::reconcile::{
+    ClearedState, MatchTolerance, PostingRef, ReconciliationReport, ReconciliationSession, SessionStatus, Statement,
+    StatementLine, StatementMatch, StatementMatches,
+};
+pub use ledger::tax::{TaxDirection, TaxRate, TaxTable, VatLine, VatReport};
+pub use ledger::lots::{realize, CostBasisMethod, Holdings, Lot, LotError, RealizedGain};
+pub use currency::{conversion_report, Commodity, ConversionCapture, ConversionLine, Retranslation};
+pub use locale::Locale;
+pub use sync::{CommodityMismatch, DocStats, MergeOutcome, PendingMerge, SyncDoc, SyncableLedger, SyncError};
+pub use storage::{
+    LocalStorage, PeerSyncStats, SnapshotInfo, StorageConfig, StorageReader, StorageStats, SyncLevel,
+    TransactionProvenance, VacuumPolicy,
+};
+pub use staging::{StagedTransaction, StagingArea};
+pub use receipts::{ReceiptCandidate, ReceiptExtractor};
+pub use close::{CloseChecklist, CloseStatus};
+pub use inventory::{Inventory, Item, StockMovement};
+pub use payroll::{PayrollComponent, PayrollTemplate};
+pub use activity::{ActivityEntry, ActivityKind};
+pub use reports::{ChartType, ReportChart, ReportDocument, ReportFormat};
+pub use dedup::DedupCache;
+pub use control::{SyncControl, SyncDirection};
+pub use diagnostics::{bisect_divergence, DivergenceReport};
+pub use changeset::Changeset;
+pub use matching::TransferCandidate;
+pub use conflict::{Conflict, ConflictHandler, ConflictPolicy, Resolution};
+pub use numbering::{NumberingScheme, ReferenceAllocator, Sequence};
+pub use protocol::{Capabilities, ChunkAssembler, Envelope, Session, SyncChunk};
+pub use splits::{SplitRule, SplitShare};
+pub use documents::{Document, DocumentKind, DocumentLink};
+pub use contacts::{Contact, ContactRole, Counterparty, PaymentTerms};
+pub use projects::Project;
+pub use classes::ReportingClass;
+pub use antientropy::{AntiEntropy, AntiEntropyConfig};
+pub use keyring::{BookKeyring, KeyringError, SealedPayload};
+pub use snapshot::{fetch_snapshot, SnapshotError, SnapshotServer};
+pub use qos::{OutboundQueue, Priority, QosConfig, RateLimit};
+pub use codec::{CodecError, Encoding};
+pub use views::{DerivedView, MonthlyAccountTotal, MonthlyAccountTotals};
+pub use chart::{ChartKind, ChartTemplate, TemplateAccount};
+pub use prices::{PriceDb, PriceQuote, RateBasis};
+pub use amount::{parse_amount_input, AmountError, ParsedAmount};
+pub use quickentry::{QuickEntry, QuickEntryError, QuickEntryRule};
+pub use recurring::{detect_recurring, DetectionOptions, Frequency, RecurringCandidate, RecurringTransaction};
+pub use pending::{PendingState, PendingTracker, PendingTransition};
+pub use codes::{Coded, CodedEvent, EventCode, Severity};
+pub use jobs::{JobRun, JobScheduler, JobState, RetryPolicy};
+pub use config::{Config, ConfigError, ConfigStore, SharedSettings};
+pub use invoicing::{Invoice, InvoiceDirection, InvoiceError, InvoiceLine, InvoicePayment, InvoiceStatus};
+pub use export::{export_transactions, ExportError, ExportFormat, TransactionExporter};
+pub use crypto::{CryptoSuite, Ed25519Signer, Ed25519Verifier, HashAlgorithm, Sha256Hash, Signature, Signer, Verifier};
+pub use import::{ImportReport, RowError, RowErrorKind, StatementColumns, StatementImporter};
+pub use rules::{apply_rules, CategorizationRule, DescriptionPattern};
+pub use workspace::{Entity, InterEntityTransfer, TransferLeg, TransferPair, TransferSide, Workspace, WorkspaceError};
+pub use delegation::{DelegationError, DelegationRegistry, DeviceTrust, Trust, WriteGrant, WriteToken};
+pub use rounding::{Allocation, RoundingMode, RoundingPolicy};
+pub use recovery::{RecoveryError, RecoveryKit};
+pub use attachments::AttachmentRef;
+pub use blob_fetch::BlobEvent;
+pub use syncable::{Syncable, SyncableError};
+pub use quorum::{Approval, DestructiveOp, Proposal, QuorumError, QuorumPolicy, QuorumStatus, QuorumTracker, Vote};
+pub use presence::{Presence, PresenceActivity, PresenceBoard};
+pub use mailbox::{AmazonParser, EmailReceipt, MailAttachment, MailMessage, MailboxConnector, MailboxError, PayPalParser, ReceiptParser};
+pub use app_settings::{AppSettings, AppSettingsError};
 
+use libp2p::futures::StreamExt;
 use libp2p::{
-    identity, noise, tcp, yamux, PeerId, Swarm, SwarmEvent,
-    Transport, NetworkBehaviour, gossipsub, mdns,
+    identity, noise, tcp, yamux, Multiaddr, PeerId, Swarm,
+    Transport, gossipsub, mdns,
+    swarm::{behaviour::toggle::Toggle, NetworkBehaviour, SwarmEvent},
 };
-use tokio::sync::mpsc;
+use std::collections::{BTreeMap, HashMap};
 use std::time::Duration;
 
+/// Gossip topic of the default book
+pub const SYNC_TOPIC: &str = "true-ledger-sync";
+
+/// Prefix of the per-book presence topics, kept apart from document traffic
+pub const PRESENCE_TOPIC: &str = "true-ledger-presence";
+
+/// Gossip topic for presence in one book, so only its members' devices subscribe
+pub fn presence_topic(book: &str) -> String {
+    format!("{}/{}", PRESENCE_TOPIC, book)
+}
+
+/// Gossip topic of a workspace entity, so peers only receive the books they join
+pub fn entity_topic(name: &str) -> String {
+    format!("{}/{}", SYNC_TOPIC, name)
+}
+
 #[derive(NetworkBehaviour)]
 struct LedgerBehaviour {
     gossipsub: gossipsub::Behaviour,
-    mdns: mdns::tokio::Behaviour,
+    mdns: Toggle<mdns::tokio::Behaviour>,
+    blobs: blob_fetch::BlobFetch,
+}
+
+/// Network settings for the sync client
+#[derive(Debug, Clone, Default)]
+pub struct NetworkConfig {
+    /// Disable mDNS discovery, only dial `peers`, and listen on a random port
+    pub privacy_mode: bool,
+    /// Peers dialed on startup (the only peers reachable in privacy mode)
+    pub peers: Vec<Multiaddr>,
+    /// Fixed TCP listen port; ignored in privacy mode
+    pub listen_port: Option<u16>,
+}
+
+/// Bytes and merges exchanged with one peer, and the error of its last failed merge
+#[derive(Debug, Default)]
+struct PeerTraffic {
+    sent: u64,
+    received: u64,
+    merges: u64,
+    error: Option<String>,
 }
 
 pub struct SyncClient {
     swarm: Swarm<LedgerBehaviour>,
-    event_rx: mpsc::UnboundedReceiver<SwarmEvent<LedgerBehaviour>>,
+    dedup: DedupCache,
+    sessions: HashMap<PeerId, Session>,
+    control: SyncControl,
+    /// Payloads held for review, with the helper key a signed one came with
+    held: Vec<(PeerId, Option<Vec<u8>>, Vec<u8>)>,
+    conflict_handler: Option<Box<dyn ConflictHandler>>,
+    conflict_policy: ConflictPolicy,
+    anti_entropy: AntiEntropy,
+    arrivals: Vec<TransactionProvenance>,
+    outbound: OutboundQueue,
+    /// Outbound queues of joined workspace entities, by entity name
+    entity_queues: BTreeMap<String, OutboundQueue>,
+    pending: PendingTracker,
+    /// Member and helper signing keys, saved so helpers stay limited across restarts
+    trust: DeviceTrust,
+    /// Attachment files requested and not answered yet, by hash
+    requested_blobs: std::collections::HashSet<String>,
+    /// Blob requests and responses waiting for `handle_blob_events`
+    blob_events: Vec<BlobEvent>,
+    /// Received postings in a commodity their account does not allow, until taken
+    mismatches: Vec<CommodityMismatch>,
+    /// Merges received since the last `save_activity`
+    activity: Vec<ActivityEntry>,
+    /// Traffic per peer since the last `save_peer_stats`
+    traffic: HashMap<PeerId, PeerTraffic>,
+    /// Who is viewing or editing what, per book whose presence topic we joined
+    presence: HashMap<String, PresenceBoard>,
 }
 
 impl SyncClient {
-    pub async fn new() -> Self {
+    /// Client with default network settings and the pause/mute state saved in `storage`
+    pub async fn new(storage: &LocalStorage) -> Self {
+        Self::with_config(NetworkConfig::default(), storage).await
+    }
+
+    pub async fn with_config(config: NetworkConfig, storage: &LocalStorage) -> Self {
         let local_key = identity::Keypair::generate_ed25519();
         let local_peer_id = PeerId::from(local_key.public());
 
@@ -33,35 +228,961 @@ impl SyncClient {
             .multiplex(yamux::Config::default())
             .boxed();
 
-        let mut mdns = mdns::tokio::Behaviour::new(mdns::Config::default(), local_peer_id).unwrap();
-        mdns.set_ttl(Duration::from_secs(30));
+        let mdns = if config.privacy_mode {
+            None
+        } else {
+            let mdns_config = mdns::Config {
+                ttl: Duration::from_secs(30),
+                ..Default::default()
+            };
+            Some(mdns::tokio::Behaviour::new(mdns_config, local_peer_id).unwrap())
+        };
 
         let gossipsub = gossipsub::Behaviour::new(
             gossipsub::MessageAuthenticity::Signed(local_key),
             gossipsub::Config::default(),
         ).unwrap();
 
-        let behaviour = LedgerBehaviour { gossipsub, mdns };
-        let mut swarm = Swarm::new(transport, behaviour, local_peer_id);
+        let behaviour = LedgerBehaviour { gossipsub, mdns: Toggle::from(mdns), blobs: blob_fetch::BlobFetch::default() };
+        let mut swarm = Swarm::new(
+            transport,
+            behaviour,
+            local_peer_id,
+            libp2p::swarm::Config::with_tokio_executor(),
+        );
 
-        let topic = gossipsub::IdentTopic::new("true-ledger-sync");
+        let topic = gossipsub::IdentTopic::new(SYNC_TOPIC);
         swarm.behaviour_mut().gossipsub.subscribe(&topic).unwrap();
 
-        let (event_tx, event_rx) = mpsc::unbounded_channel();
-        tokio::spawn(async move {
-            loop {
-                match swarm.select_next_some().await {
-                    SwarmEvent::Behaviour(event) => event_tx.send(SwarmEvent::Behaviour(event)).unwrap(),
-                    _ => {}
+        // Port 0 lets the OS pick a random port
+        let port = if config.privacy_mode { 0 } else { config.listen_port.unwrap_or(0) };
+        swarm.listen_on(format!("/ip4/0.0.0.0/tcp/{}", port).parse().unwrap()).unwrap();
+        for addr in config.peers {
+            let _ = swarm.dial(addr);
+        }
+
+        Self {
+            swarm,
+            dedup: DedupCache::default(),
+            sessions: HashMap::new(),
+            control: SyncControl::load(storage),
+            held: Vec::new(),
+            conflict_handler: None,
+            conflict_policy: ConflictPolicy::default(),
+            anti_entropy: AntiEntropy::default(),
+            arrivals: Vec::new(),
+            outbound: OutboundQueue::default(),
+            entity_queues: BTreeMap::new(),
+            pending: PendingTracker::new(),
+            trust: DeviceTrust::load(storage),
+            requested_blobs: std::collections::HashSet::new(),
+            blob_events: Vec::new(),
+            mismatches: Vec::new(),
+            activity: Vec::new(),
+            traffic: HashMap::new(),
+            presence: HashMap::new(),
+        }
+    }
+
+    /// Drive the network until the next gossip message arrives and return its sender and
+    /// payload, for `receive`. Peers found over mDNS are added along the way, and attachment
+    /// requests and responses are kept for `handle_blob_events`.
+    pub async fn next_message(&mut self) -> (PeerId, Vec<u8>) {
+        loop {
+            match self.swarm.select_next_some().await {
+                SwarmEvent::Behaviour(LedgerBehaviourEvent::Gossipsub(gossipsub::Event::Message {
+                    propagation_source,
+                    message,
+                    ..
+                })) => return (message.source.unwrap_or(propagation_source), message.data),
+                SwarmEvent::Behaviour(LedgerBehaviourEvent::Mdns(mdns::Event::Discovered(peers))) => {
+                    for (peer, _) in peers {
+                        self.swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer);
+                    }
                 }
+                SwarmEvent::Behaviour(LedgerBehaviourEvent::Blobs(event)) => self.blob_events.push(event),
+                _ => {}
             }
-        });
-
-        Self { swarm, event_rx }
+        }
     }
 
     pub async fn sync_with_peer(&mut self, data: Vec<u8>) {
-        let topic = gossipsub::IdentTopic::new("true-ledger-sync");
-        self.swarm.behaviour_mut().gossipsub.publish(topic, data).unwrap();
+        if self.control.paused {
+            return;
+        }
+        self.enqueue(Priority::Urgent, data);
+    }
+
+    /// Queue a payload in its service class and send whatever the rate limits allow
+    fn enqueue(&mut self, priority: Priority, data: Vec<u8>) {
+        self.outbound.push(priority, data);
+        self.flush_outbound();
+    }
+
+    /// Publish queued messages that are due, highest class first; returns how many went out.
+    /// Call periodically while bulk or background messages are pending.
+    pub fn flush_outbound(&mut self) -> usize {
+        let topic = gossipsub::IdentTopic::new(SYNC_TOPIC);
+        let mut sent = Self::drain(&mut self.swarm, &mut self.pending, &mut self.traffic, &mut self.outbound, &topic);
+        for (name, queue) in self.entity_queues.iter_mut() {
+            let topic = gossipsub::IdentTopic::new(entity_topic(name));
+            sent += Self::drain(&mut self.swarm, &mut self.pending, &mut self.traffic, queue, &topic);
+        }
+        sent
+    }
+
+    /// Publish due messages of one queue; each counts as sent to every mesh peer of the topic
+    fn drain(
+        swarm: &mut Swarm<LedgerBehaviour>,
+        pending: &mut PendingTracker,
+        traffic: &mut HashMap<PeerId, PeerTraffic>,
+        queue: &mut OutboundQueue,
+        topic: &gossipsub::IdentTopic,
+    ) -> usize {
+        let mut sent = 0;
+        while let Some((priority, data)) = queue.pop_ready(std::time::Instant::now()) {
+            match swarm.behaviour_mut().gossipsub.publish(topic.clone(), data.clone()) {
+                Ok(_) => {
+                    pending.payload_sent(&data);
+                    for peer in swarm.behaviour().gossipsub.mesh_peers(&topic.hash()) {
+                        traffic.entry(*peer).or_default().sent += data.len() as u64;
+                    }
+                    sent += 1;
+                }
+                // Mesh not ready: keep edits and backfill for the next flush, drop background traffic
+                Err(_) if priority != Priority::Background => {
+                    queue.requeue(priority, data);
+                    break;
+                }
+                Err(_) => {}
+            }
+        }
+        sent
+    }
+
+    /// Subscribe to a workspace entity's topic; returns false if already joined
+    pub fn join_entity(&mut self, name: &str) -> bool {
+        if self.entity_queues.contains_key(name) {
+            return false;
+        }
+        let topic = gossipsub::IdentTopic::new(entity_topic(name));
+        self.swarm.behaviour_mut().gossipsub.subscribe(&topic).unwrap();
+        let queue = OutboundQueue::new(*self.outbound.config());
+        self.entity_queues.insert(name.to_string(), queue);
+        true
+    }
+
+    /// Stop receiving an entity's updates; anything still queued for it is dropped
+    pub fn leave_entity(&mut self, name: &str) -> bool {
+        if self.entity_queues.remove(name).is_none() {
+            return false;
+        }
+        let topic = gossipsub::IdentTopic::new(entity_topic(name));
+        self.swarm.behaviour_mut().gossipsub.unsubscribe(&topic).is_ok()
+    }
+
+    /// Start exchanging presence with the other devices in `book`
+    pub fn join_presence(&mut self, book: &str) -> bool {
+        if self.presence.contains_key(book) {
+            return false;
+        }
+        let topic = gossipsub::IdentTopic::new(presence_topic(book));
+        self.swarm.behaviour_mut().gossipsub.subscribe(&topic).unwrap();
+        self.presence.insert(book.to_string(), PresenceBoard::new());
+        true
+    }
+
+    /// Stop exchanging presence in `book`; what its devices were doing is forgotten
+    pub fn leave_presence(&mut self, book: &str) -> bool {
+        if self.presence.remove(book).is_none() {
+            return false;
+        }
+        let topic = gossipsub::IdentTopic::new(presence_topic(book));
+        self.swarm.behaviour_mut().gossipsub.unsubscribe(&topic).is_ok()
+    }
+
+    /// Send this device's presence in `book` right away, signed by `signer` and sealed with the
+    /// book key. Not queued or retried: a lost update is replaced by the next one. Returns
+    /// whether it went out.
+    pub fn publish_presence(&mut self, book: &str, presence: &Presence, keyring: &BookKeyring, signer: &dyn Signer) -> bool {
+        if self.control.paused || !self.presence.contains_key(book) {
+            return false;
+        }
+        let Ok(data) = presence.seal(book, keyring, signer) else { return false };
+        let topic = gossipsub::IdentTopic::new(presence_topic(book));
+        self.swarm.behaviour_mut().gossipsub.publish(topic, data).is_ok()
+    }
+
+    /// Take a message received on a presence topic; updates that don't open with the book key
+    /// or verify are dropped. Returns whether a board changed.
+    pub fn receive_presence(&mut self, data: &[u8], keyring: &BookKeyring, suite: &CryptoSuite) -> bool {
+        let Some((book, presence)) = Presence::open(data, keyring, suite) else { return false };
+        match self.presence.get_mut(&book) {
+            Some(board) => board.update(presence, chrono::Utc::now()),
+            None => false,
+        }
+    }
+
+    /// Presence of other devices in `book`, while joined
+    pub fn presence(&self, book: &str) -> Option<&PresenceBoard> {
+        self.presence.get(book)
+    }
+
+    /// Broadcast an entity's document on its own topic; entities not joined are skipped
+    pub fn publish_entity(&mut self, entity: &Entity) {
+        if self.control.paused {
+            return;
+        }
+        let Some(queue) = self.entity_queues.get_mut(&entity.name) else { return };
+        queue.push(Priority::Urgent, entity.book.doc().to_bytes());
+        self.flush_outbound();
+    }
+
+    /// Show locally created transactions as provisional until `confirm_persisted` and `publish_local`
+    pub fn track_local(&mut self, ids: &[uuid::Uuid]) {
+        self.pending.track(ids);
+    }
+
+    /// The changeset holding the transactions was committed
+    pub fn confirm_persisted(&mut self, ids: &[uuid::Uuid]) {
+        self.pending.mark_persisted(ids);
+    }
+
+    /// The changeset holding the transactions was rolled back
+    pub fn local_failed(&mut self, ids: &[uuid::Uuid], reason: &str) {
+        self.pending.mark_failed(ids, reason);
+    }
+
+    /// Broadcast the document carrying new local transactions; they become confirmed once the
+    /// message actually leaves the outbound queue
+    pub fn publish_local(&mut self, ids: &[uuid::Uuid], doc: &SyncDoc) {
+        if self.control.paused {
+            return;
+        }
+        let data = doc.to_bytes();
+        self.pending.attach_payload(ids, &data);
+        self.enqueue(Priority::Urgent, data);
+    }
+
+    /// Pending state of a local transaction; None once confirmed or for remote transactions
+    pub fn pending_state(&self, id: &uuid::Uuid) -> Option<PendingState> {
+        self.pending.state(id)
+    }
+
+    /// Pending state changes since the last call, for UIs to update spinners and greyed entries
+    pub fn take_pending_transitions(&mut self) -> Vec<PendingTransition> {
+        self.pending.take_transitions()
+    }
+
+    /// Replace the per-class rate limits
+    pub fn set_qos_config(&mut self, config: QosConfig) {
+        for queue in self.entity_queues.values_mut() {
+            queue.set_config(config);
+        }
+        self.outbound.set_config(config);
+    }
+
+    /// Delay until the next queued message can go out
+    pub fn next_flush_in(&self) -> Option<Duration> {
+        self.outbound.next_ready_in(std::time::Instant::now())
+    }
+
+    /// Publish a book's document encrypted with that book's key
+    pub async fn sync_book(&mut self, keyring: &BookKeyring, book: &str, doc: &SyncDoc) -> Result<(), SyncError> {
+        if self.control.paused {
+            return Ok(());
+        }
+        let sealed = keyring.seal(book, &doc.to_bytes())?;
+        let data = Envelope::Sealed(sealed).to_bytes_as(self.broadcast_encoding())?;
+        self.enqueue(Priority::Urgent, data);
+        Ok(())
+    }
+
+    /// Decrypt and merge a sealed book document; payloads for books we don't hold are skipped
+    pub async fn receive_sealed(
+        &mut self,
+        peer: PeerId,
+        keyring: &BookKeyring,
+        sealed: &SealedPayload,
+        doc: &mut SyncDoc,
+    ) -> Result<bool, SyncError> {
+        match keyring.open(sealed) {
+            Ok(data) => self.receive(peer, doc, &data).await,
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Merge a received document broadcast, skipping payloads already merged. Once members or
+    /// helpers are enrolled, documents must be signed (see `receive_signed`) and unsigned ones
+    /// are refused. Returns whether a merge happened.
+    pub async fn receive(&mut self, peer: PeerId, doc: &mut SyncDoc, data: &[u8]) -> Result<bool, SyncError> {
+        if self.trust.trust(None) == Trust::Refused {
+            return Ok(false);
+        }
+        self.receive_from(peer, None, doc, data).await
+    }
+
+    /// Verify a `Signed` envelope around a full document and merge it. Changes of a helper device
+    /// are limited to its grant, looked up by the key that signed them; documents signed by a
+    /// dismissed helper, or by a key nobody enrolled once the book has members, are refused.
+    /// Returns whether a merge happened.
+    pub async fn receive_signed(
+        &mut self,
+        peer: PeerId,
+        envelope: Envelope,
+        suite: &CryptoSuite,
+        doc: &mut SyncDoc,
+    ) -> Result<bool, SyncError> {
+        let (Envelope::FullDoc(data), Some(signer)) = envelope.verify(suite)? else {
+            return Ok(false);
+        };
+        if self.trust.trust(Some(&signer)) == Trust::Refused {
+            return Ok(false);
+        }
+        self.receive_from(peer, Some(signer), doc, &data).await
+    }
+
+    async fn receive_from(&mut self, peer: PeerId, signer: Option<Vec<u8>>, doc: &mut SyncDoc, data: &[u8]) -> Result<bool, SyncError> {
+        if !self.control.direction(&peer.to_string()).allows_pull() {
+            return Ok(false);
+        }
+        if self.control.paused || self.control.is_muted(&peer.to_string()) {
+            self.held.push((peer, signer, data.to_vec()));
+            return Ok(false);
+        }
+        self.merge_payload(&peer, signer.as_deref(), doc, data).await
+    }
+
+    /// Merge one payload unless it was merged before; it only counts as seen once the merge
+    /// succeeded, so a payload that failed can arrive again and be retried
+    async fn merge_payload(&mut self, peer: &PeerId, signer: Option<&[u8]>, doc: &mut SyncDoc, data: &[u8]) -> Result<bool, SyncError> {
+        self.traffic.entry(*peer).or_default().received += data.len() as u64;
+        if self.dedup.contains(data) {
+            return Ok(false);
+        }
+        let merged = match SyncDoc::from_bytes(data) {
+            Ok(remote) => self.merge_remote(peer, signer, doc, &remote).await,
+            Err(e) => Err(e),
+        };
+        let traffic = self.traffic.entry(*peer).or_default();
+        let merged = match merged {
+            Ok(merged) => {
+                traffic.error = None;
+                merged
+            }
+            Err(e) => {
+                traffic.error = Some(e.to_string());
+                return Err(e);
+            }
+        };
+        let Some((arrived, mismatches)) = merged else {
+            return Ok(false);
+        };
+        traffic.merges += 1;
+        self.dedup.insert(data);
+        self.mismatches.extend(mismatches);
+        self.activity.push(ActivityEntry::new(
+            chrono::Utc::now(),
+            ActivityKind::MergeReceived { peer: peer.to_string(), changes: arrived.len() },
+        ));
+        self.note_arrivals(peer, data, arrived);
+        Ok(true)
+    }
+
+    /// Register the application's conflict UI
+    pub fn set_conflict_handler(&mut self, handler: Box<dyn ConflictHandler>) {
+        self.conflict_handler = Some(handler);
+    }
+
+    /// Timeout and fallback decision used when the handler is missing or slow
+    pub fn set_conflict_policy(&mut self, policy: ConflictPolicy) {
+        self.conflict_policy = policy;
+    }
+
+    /// Merge a peer document, asking the conflict handler about transactions edited on both sides.
+    /// Returns the transactions that were new to this device and their postings in commodities
+    /// the account does not allow; those are kept, only flagged. None when the signer, or an
+    /// unsigned payload, is not trusted and nothing was merged.
+    async fn merge_remote(
+        &self,
+        peer: &PeerId,
+        signer: Option<&[u8]>,
+        doc: &mut SyncDoc,
+        remote: &SyncDoc,
+    ) -> Result<Option<(Vec<Transaction>, Vec<CommodityMismatch>)>, SyncError> {
+        // Decided again here since trust may have changed while the payload was held
+        let grant = match self.trust.trust(signer) {
+            Trust::Full => None,
+            Trust::Limited(grant) => Some(grant),
+            Trust::Refused => return Ok(None),
+        };
+        let pending = doc.prepare_merge(remote, &peer.to_string())?;
+        let mut resolutions = Vec::with_capacity(pending.conflicts.len());
+        for c in &pending.conflicts {
+            resolutions.push(match &self.conflict_handler {
+                Some(handler) => tokio::time::timeout(self.conflict_policy.timeout, handler.resolve(c))
+                    .await
+                    .unwrap_or(self.conflict_policy.default),
+                None => self.conflict_policy.default,
+            });
+        }
+        let outcome = pending.apply(doc, &resolutions, grant, chrono::Utc::now())?;
+        Ok(Some((outcome.arrived, outcome.mismatches)))
+    }
+
+    fn note_arrivals(&mut self, peer: &PeerId, data: &[u8], arrived: Vec<Transaction>) {
+        let batch_id: String = dedup::content_hash(data).iter().map(|b| format!("{:02x}", b)).collect();
+        let received_at = chrono::Utc::now();
+        self.arrivals.extend(arrived.into_iter().map(|tx| TransactionProvenance {
+            transaction_id: tx.id.to_string(),
+            origin_device: tx.origin_device,
+            received_from: Some(peer.to_string()),
+            batch_id: Some(batch_id.clone()),
+            received_at,
+        }));
+    }
+
+    /// Received postings whose commodity their account does not allow, since the last call
+    pub fn take_commodity_mismatches(&mut self) -> Vec<CommodityMismatch> {
+        std::mem::take(&mut self.mismatches)
+    }
+
+    /// Persist provenance of transactions received since the last call; returns how many were written
+    pub fn save_provenance(&mut self, storage: &LocalStorage) -> usize {
+        let arrivals = std::mem::take(&mut self.arrivals);
+        for provenance in &arrivals {
+            storage.record_provenance(provenance);
+        }
+        arrivals.len()
+    }
+
+    /// Add the traffic since the last call to the per-peer sync statistics; returns how many peers were updated
+    pub fn save_peer_stats(&mut self, storage: &LocalStorage) -> usize {
+        let traffic = std::mem::take(&mut self.traffic);
+        for (peer, t) in &traffic {
+            storage.record_peer_sync(&peer.to_string(), t.sent, t.received, t.merges, t.error.as_deref());
+        }
+        traffic.len()
+    }
+
+    /// Add the merges received since the last call to the ledger's activity feed; returns how many were added
+    pub fn save_activity(&mut self, ledger: &mut Ledger) -> usize {
+        let entries = std::mem::take(&mut self.activity);
+        let count = entries.len();
+        for entry in entries {
+            ledger.log_activity_at(entry.at, entry.kind);
+        }
+        count
+    }
+
+    /// Publish a ledger to a newly joined device: recent history first, then older backfill chunks
+    pub async fn sync_recent_first(&mut self, ledger: &SyncableLedger, today: chrono::NaiveDate) -> Result<(), SyncError> {
+        if self.control.paused {
+            return Ok(());
+        }
+        for chunk in protocol::plan_chunks(ledger, today, protocol::RECENT_DAYS, protocol::BACKFILL_DAYS) {
+            let data = Envelope::Chunk(chunk).to_bytes_as(self.broadcast_encoding())?;
+            self.outbound.push(Priority::Bulk, data);
+        }
+        self.flush_outbound();
+        Ok(())
+    }
+
+    /// Announce our capabilities to peers
+    pub async fn send_hello(&mut self) -> Result<(), SyncError> {
+        let data = Envelope::Hello(Capabilities::local()).to_bytes()?;
+        self.enqueue(Priority::Urgent, data);
+        Ok(())
+    }
+
+    /// Record the negotiated session for a peer that sent its capabilities
+    pub fn handle_hello(&mut self, peer: PeerId, remote: &Capabilities) -> Result<&Session, SyncError> {
+        let session = protocol::negotiate(&Capabilities::local(), remote)?;
+        Ok(self.sessions.entry(peer).insert_entry(session).into_mut())
+    }
+
+    /// Trust documents signed with `device_key` as a full member's. Once anyone is enrolled,
+    /// every member has to be, and unsigned documents are refused.
+    pub fn add_member(&mut self, device_key: &[u8], storage: &LocalStorage) {
+        self.trust.add_member(device_key);
+        self.trust.save(storage);
+    }
+
+    /// Stop trusting a member's signing key
+    pub fn remove_member(&mut self, device_key: &[u8], storage: &LocalStorage) -> bool {
+        let removed = self.trust.remove_member(device_key);
+        self.trust.save(storage);
+        removed
     }
-}
\ No newline at end of file
+
+    /// Accept a helper device presenting a write token. `device_key` is the key the helper signs
+    /// its documents with; merges of documents it signed are limited to the token's scope.
+    pub fn admit_delegate(
+        &mut self,
+        device_key: &[u8],
+        token: &WriteToken,
+        registry: &mut DelegationRegistry,
+        suite: &CryptoSuite,
+        issuer_key: &[u8],
+        storage: &LocalStorage,
+    ) -> Result<(), DelegationError> {
+        let device = presence::device_id(device_key);
+        let grant = registry.redeem(token, &device, suite, issuer_key, chrono::Utc::now())?;
+        self.trust.admit(device_key, grant.clone());
+        self.trust.save(storage);
+        Ok(())
+    }
+
+    /// Stop accepting changes from a helper; its signed documents are refused from now on
+    pub fn dismiss_delegate(&mut self, device_key: &[u8], storage: &LocalStorage) -> bool {
+        let dismissed = self.trust.dismiss(device_key);
+        self.trust.save(storage);
+        dismissed
+    }
+
+    /// Revoke a token in the registry and dismiss the helper holding it
+    pub fn revoke_delegate(&mut self, grant_id: &uuid::Uuid, registry: &mut DelegationRegistry, storage: &LocalStorage) -> bool {
+        let revoked = registry.revoke(grant_id);
+        self.apply_revocations(registry, storage);
+        revoked
+    }
+
+    /// Dismiss helpers whose tokens the registry has revoked, e.g. after loading it from storage
+    pub fn apply_revocations(&mut self, registry: &DelegationRegistry, storage: &LocalStorage) {
+        if self.trust.apply_revocations(registry) {
+            self.trust.save(storage);
+        }
+    }
+
+    /// Negotiated session for a peer, if the handshake completed
+    pub fn session(&self, peer: &PeerId) -> Option<&Session> {
+        self.sessions.get(peer)
+    }
+
+    /// Encoding every peer we've shaken hands with agreed on; JSON otherwise, since gossip
+    /// reaches all of them
+    fn broadcast_encoding(&self) -> Encoding {
+        let mut encodings = self.sessions.values().map(|s| s.encoding);
+        match encodings.next() {
+            Some(first) if encodings.all(|e| e == first) => first,
+            _ => Encoding::Json,
+        }
+    }
+
+    /// Interval, jitter and on/off switch for background anti-entropy rounds
+    pub fn set_anti_entropy_config(&mut self, config: AntiEntropyConfig) {
+        self.anti_entropy.set_config(config);
+    }
+
+    /// Send our state hash to one random connected peer if a round is due.
+    /// Call from the app's idle loop; returns the probed peer.
+    pub async fn anti_entropy_round(&mut self, doc: &SyncDoc) -> Result<Option<PeerId>, SyncError> {
+        let now = std::time::Instant::now();
+        if self.control.paused || !self.anti_entropy.is_due(now) {
+            return Ok(None);
+        }
+        self.anti_entropy.schedule(now);
+
+        // Probed peers answer with their document, so only probe peers we may merge from
+        let peers: Vec<PeerId> = self.swarm.connected_peers()
+            .filter(|p| self.control.direction(&p.to_string()).allows_pull())
+            .copied()
+            .collect();
+        let Some(peer) = self.anti_entropy.pick(&peers).copied() else { return Ok(None) };
+        // Low priority: skip the round while earlier background traffic is still waiting
+        if self.outbound.pending(Priority::Background) > 0 {
+            return Ok(None);
+        }
+        let data = Envelope::StateHash { target: peer.to_string(), hash: doc.state_hash() }.to_bytes_as(self.broadcast_encoding())?;
+        self.enqueue(Priority::Background, data);
+        Ok(Some(peer))
+    }
+
+    /// Answer a state-hash probe addressed to us with our full document when the states differ.
+    /// Pull-only peers get no reply. Returns whether a reply was queued.
+    pub async fn handle_state_hash(&mut self, from: &PeerId, target: &str, hash: &str, doc: &SyncDoc) -> Result<bool, SyncError> {
+        if self.control.paused || !self.control.direction(&from.to_string()).allows_push() {
+            return Ok(false);
+        }
+        if target != self.swarm.local_peer_id().to_string() || hash == doc.state_hash() {
+            return Ok(false);
+        }
+        let data = Envelope::FullDoc(doc.to_bytes()).to_bytes_as(self.broadcast_encoding())?;
+        self.enqueue(Priority::Background, data);
+        Ok(true)
+    }
+
+    /// Ask trusted devices to vote on a destructive operation
+    pub fn propose(&mut self, proposal: &Proposal) -> Result<(), SyncError> {
+        let data = Envelope::QuorumProposal(proposal.clone()).to_bytes_as(self.broadcast_encoding())?;
+        self.enqueue(Priority::Urgent, data);
+        Ok(())
+    }
+
+    /// Send this device's vote on a proposal
+    pub fn send_vote(&mut self, vote: &Vote) -> Result<(), SyncError> {
+        let data = Envelope::QuorumVote(vote.clone()).to_bytes_as(self.broadcast_encoding())?;
+        self.enqueue(Priority::Urgent, data);
+        Ok(())
+    }
+
+    /// Ask a connected peer for the attached files missing locally, one direct stream per file;
+    /// returns how many were requested
+    pub fn request_attachments(&mut self, peer: &PeerId, ledger: &SyncableLedger, storage: &LocalStorage) -> usize {
+        if self.control.paused || !self.control.direction(&peer.to_string()).allows_pull() {
+            return 0;
+        }
+        let hashes: Vec<String> = ledger.attachment_hashes().into_iter()
+            .filter(|h| !storage.has_attachment(h) && !self.requested_blobs.contains(*h))
+            .map(String::from)
+            .collect();
+        for hash in &hashes {
+            self.requested_blobs.insert(hash.clone());
+            self.swarm.behaviour_mut().blobs.fetch(*peer, hash.clone());
+        }
+        hashes.len()
+    }
+
+    /// Answer file requests and store the files received since the last call. Pull-only peers
+    /// and requests while paused get no file; a received file is only stored if a transaction
+    /// references it and the bytes match the hash. Returns how many files were stored.
+    pub fn handle_blob_events(&mut self, ledger: &SyncableLedger, storage: &LocalStorage) -> usize {
+        let mut stored = 0;
+        for event in std::mem::take(&mut self.blob_events) {
+            match event {
+                BlobEvent::Request { peer, request_id, hash } => {
+                    let allowed = !self.control.paused && self.control.direction(&peer.to_string()).allows_push();
+                    let data = if allowed { storage.load_attachment(&hash) } else { None };
+                    self.swarm.behaviour_mut().blobs.respond(request_id, data);
+                }
+                BlobEvent::Response { hash, data, .. } => {
+                    self.requested_blobs.remove(&hash);
+                    let attachment = ledger.transactions.iter()
+                        .flat_map(|t| &t.attachments)
+                        .find(|a| a.hash == hash);
+                    if let (Some(attachment), Some(data)) = (attachment, data) {
+                        stored += usize::from(storage.store_attachment(attachment, &data));
+                    }
+                }
+                BlobEvent::Failed { hash, .. } => {
+                    self.requested_blobs.remove(&hash);
+                }
+            }
+        }
+        stored
+    }
+
+    pub fn control(&self) -> &SyncControl {
+        &self.control
+    }
+
+    /// Stop publishing and merging until resumed; received payloads are held meanwhile
+    pub fn pause(&mut self, storage: &LocalStorage) {
+        self.control.paused = true;
+        self.control.save(storage);
+    }
+
+    /// Publish and merge again; payloads held while paused wait for `accept_held`
+    pub fn resume(&mut self, storage: &LocalStorage) {
+        self.control.paused = false;
+        self.control.save(storage);
+    }
+
+    /// Hold a peer's changes for review instead of merging them
+    pub fn mute_peer(&mut self, peer: &PeerId, storage: &LocalStorage) {
+        self.control.muted_peers.insert(peer.to_string());
+        self.control.save(storage);
+    }
+
+    /// Accept changes from the peer again; held changes stay pending until accepted or discarded
+    pub fn unmute_peer(&mut self, peer: &PeerId, storage: &LocalStorage) {
+        self.control.muted_peers.remove(&peer.to_string());
+        self.control.save(storage);
+    }
+
+    /// Limit a peer to push-only or pull-only sync; `Both` restores two-way sync
+    pub fn set_peer_direction(&mut self, peer: &PeerId, direction: SyncDirection, storage: &LocalStorage) {
+        self.control.set_direction(&peer.to_string(), direction);
+        self.control.save(storage);
+    }
+
+    /// Whether an incoming envelope from the peer is allowed by its sync direction
+    pub fn accepts_envelope(&self, peer: &PeerId, envelope: &Envelope) -> bool {
+        self.control.accepts_envelope(&peer.to_string(), envelope)
+    }
+
+    /// Number of payloads held per peer, received while paused or from a muted peer
+    pub fn held_changes(&self) -> HashMap<PeerId, usize> {
+        let mut counts = HashMap::new();
+        for (peer, _, _) in &self.held {
+            *counts.entry(*peer).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Merge everything held from a peer after review. Payloads that fail to merge stay held so
+    /// the rest still go through; the first failure is returned and the held ones can be
+    /// accepted again or discarded.
+    pub async fn accept_held(&mut self, peer: &PeerId, doc: &mut SyncDoc) -> Result<usize, SyncError> {
+        // Held before the peer became push-only; nothing from it may be merged now
+        if !self.control.direction(&peer.to_string()).allows_pull() {
+            self.discard_held(peer);
+            return Ok(0);
+        }
+        let (accepted, rest): (Vec<_>, Vec<_>) = std::mem::take(&mut self.held)
+            .into_iter()
+            .partition(|(p, _, _)| p == peer);
+        self.held = rest;
+        let mut taken = 0;
+        let mut failure = None;
+        for (p, signer, data) in accepted {
+            match self.merge_payload(peer, signer.as_deref(), doc, &data).await {
+                Ok(_) => taken += 1,
+                Err(e) => {
+                    failure.get_or_insert(e);
+                    self.held.push((p, signer, data));
+                }
+            }
+        }
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(taken),
+        }
+    }
+
+    /// Drop everything held from a peer
+    pub fn discard_held(&mut self, peer: &PeerId) {
+        self.held.retain(|(p, _, _)| p != peer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn client() -> SyncClient {
+        SyncClient::with_config(NetworkConfig { privacy_mode: true, ..Default::default() }, &memory()).await
+    }
+
+    fn memory() -> LocalStorage {
+        LocalStorage::with_config(&StorageConfig { path: ":memory:".to_string(), ..Default::default() })
+    }
+
+    #[tokio::test]
+    async fn payload_is_deduplicated_only_after_it_merged() {
+        let mut client = client().await;
+        let peer = PeerId::random();
+        let mut doc = SyncDoc::new().unwrap();
+        let garbage = b"not a document".to_vec();
+
+        assert!(client.receive(peer, &mut doc, &garbage).await.is_err());
+        assert!(!client.dedup.contains(&garbage));
+
+        let remote = SyncDoc::new().unwrap().to_bytes();
+        assert!(client.receive(peer, &mut doc, &remote).await.unwrap());
+        assert!(!client.receive(peer, &mut doc, &remote).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn pause_holds_payloads_and_survives_a_restart() {
+        let storage = memory();
+        let config = NetworkConfig { privacy_mode: true, ..Default::default() };
+        let mut client = SyncClient::with_config(config.clone(), &storage).await;
+        client.pause(&storage);
+
+        let mut client = SyncClient::with_config(config, &storage).await;
+        assert!(client.control().paused);
+        let peer = PeerId::random();
+        let mut doc = SyncDoc::new().unwrap();
+        let remote = SyncDoc::new().unwrap().to_bytes();
+        assert!(!client.receive(peer, &mut doc, &remote).await.unwrap());
+        assert_eq!(client.held_changes().get(&peer), Some(&1));
+
+        client.resume(&storage);
+        assert_eq!(client.accept_held(&peer, &mut doc).await.unwrap(), 1);
+        assert!(client.dedup.contains(&remote));
+    }
+
+    #[tokio::test]
+    async fn accept_held_keeps_only_the_payloads_that_failed() {
+        let storage = memory();
+        let mut client = client().await;
+        client.pause(&storage);
+        let peer = PeerId::random();
+        let mut doc = SyncDoc::new().unwrap();
+        let garbage = b"not a document".to_vec();
+        let remote = SyncDoc::new().unwrap().to_bytes();
+        assert!(!client.receive(peer, &mut doc, &garbage).await.unwrap());
+        assert!(!client.receive(peer, &mut doc, &remote).await.unwrap());
+
+        client.resume(&storage);
+        assert!(client.accept_held(&peer, &mut doc).await.is_err());
+        assert!(client.dedup.contains(&remote));
+        assert_eq!(client.held_changes().get(&peer), Some(&1));
+
+        client.discard_held(&peer);
+        assert!(client.held_changes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unsigned_documents_are_refused_once_members_enroll_across_restarts() {
+        let storage = memory();
+        let config = NetworkConfig { privacy_mode: true, ..Default::default() };
+        let mut client = SyncClient::with_config(config.clone(), &storage).await;
+        let peer = PeerId::random();
+        let mut doc = SyncDoc::new().unwrap();
+        let held = SyncDoc::new().unwrap().to_bytes();
+        client.mute_peer(&peer, &storage);
+        assert!(!client.receive(peer, &mut doc, &held).await.unwrap());
+
+        let member = crypto::Ed25519Signer::generate();
+        client.add_member(&member.public_key(), &storage);
+        client.unmute_peer(&peer, &storage);
+        client.accept_held(&peer, &mut doc).await.unwrap();
+        assert!(!client.dedup.contains(&held));
+
+        let mut client = SyncClient::with_config(config, &storage).await;
+        let remote = SyncDoc::new().unwrap().to_bytes();
+        assert!(!client.receive(peer, &mut doc, &remote).await.unwrap());
+        let signed = Envelope::FullDoc(remote).sign(&member, Encoding::Json).unwrap();
+        assert!(client.receive_signed(peer, signed, &CryptoSuite::default(), &mut doc).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn merges_reach_the_activity_feed() {
+        let mut client = client().await;
+        let peer = PeerId::random();
+        let mut doc = SyncDoc::new().unwrap();
+        client.receive(peer, &mut doc, &SyncDoc::new().unwrap().to_bytes()).await.unwrap();
+
+        let mut ledger = Ledger::new();
+        assert_eq!(client.save_activity(&mut ledger), 1);
+        let feed = ledger.activity(chrono::DateTime::<chrono::Utc>::MIN_UTC);
+        assert_eq!(feed[0].kind, ActivityKind::MergeReceived { peer: peer.to_string(), changes: 0 });
+        assert_eq!(client.save_activity(&mut ledger), 0);
+    }
+
+    #[tokio::test]
+    async fn received_traffic_reaches_peer_stats() {
+        let storage = memory();
+        let mut client = client().await;
+        let peer = PeerId::random();
+        let mut doc = SyncDoc::new().unwrap();
+        let remote = SyncDoc::new().unwrap().to_bytes();
+        client.receive(peer, &mut doc, &remote).await.unwrap();
+        assert!(client.receive(peer, &mut doc, b"garbage").await.is_err());
+
+        assert_eq!(client.save_peer_stats(&storage), 1);
+        let stats = storage.peer_sync_stats(&peer.to_string()).unwrap();
+        assert_eq!(stats.bytes_received, (remote.len() + 7) as u64);
+        assert_eq!(stats.merges_applied, 1);
+        assert!(stats.last_error.is_some());
+    }
+
+    /// Drive both swarms until `done` holds, routing blob events to their clients
+    async fn pump(a: &mut SyncClient, b: &mut SyncClient, mut done: impl FnMut(&mut SyncClient, &mut SyncClient) -> bool) {
+        let run = async {
+            while !done(a, b) {
+                tokio::select! {
+                    event = a.swarm.select_next_some() => {
+                        if let SwarmEvent::Behaviour(LedgerBehaviourEvent::Blobs(event)) = event {
+                            a.blob_events.push(event);
+                        }
+                    }
+                    event = b.swarm.select_next_some() => {
+                        if let SwarmEvent::Behaviour(LedgerBehaviourEvent::Blobs(event)) = event {
+                            b.blob_events.push(event);
+                        }
+                    }
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(20), run).await.expect("peers did not finish in time");
+    }
+
+    #[tokio::test]
+    async fn attachments_are_fetched_directly_from_the_peer() {
+        let (mut holder, mut requester) = (client().await, client().await);
+        let (holder_storage, requester_storage) = (memory(), memory());
+        // Larger than a gossip message, so the old piecewise transfer would have split it
+        let file: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let attachment = AttachmentRef::for_bytes(&file, "application/pdf", "receipt.pdf");
+        assert!(holder_storage.store_attachment(&attachment, &file));
+        let mut ledger = SyncableLedger::new();
+        let mut tx = ledger::Transaction::new(chrono::NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(), "Receipt", Vec::new());
+        tx.attachments.push(attachment.clone());
+        ledger.transactions.push(tx);
+
+        let addr = loop {
+            if let SwarmEvent::NewListenAddr { address, .. } = holder.swarm.select_next_some().await {
+                break address;
+            }
+        };
+        requester.swarm.dial(addr).unwrap();
+        let holder_id = *holder.swarm.local_peer_id();
+        pump(&mut holder, &mut requester, |_, r| r.swarm.is_connected(&holder_id)).await;
+
+        assert_eq!(requester.request_attachments(&holder_id, &ledger, &requester_storage), 1);
+        let mut stored = 0;
+        pump(&mut holder, &mut requester, |h, r| {
+            h.handle_blob_events(&ledger, &holder_storage);
+            stored += r.handle_blob_events(&ledger, &requester_storage);
+            stored > 0
+        }).await;
+        assert_eq!(requester_storage.load_attachment(&attachment.hash), Some(file));
+        assert!(requester.requested_blobs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn pull_only_peers_get_no_files() {
+        let (mut holder, mut requester) = (client().await, client().await);
+        let (holder_storage, requester_storage) = (memory(), memory());
+        let file = b"statement".to_vec();
+        let attachment = AttachmentRef::for_bytes(&file, "text/plain", "statement.txt");
+        assert!(holder_storage.store_attachment(&attachment, &file));
+        let mut ledger = SyncableLedger::new();
+        let mut tx = ledger::Transaction::new(chrono::NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(), "Statement", Vec::new());
+        tx.attachments.push(attachment.clone());
+        ledger.transactions.push(tx);
+
+        let addr = loop {
+            if let SwarmEvent::NewListenAddr { address, .. } = holder.swarm.select_next_some().await {
+                break address;
+            }
+        };
+        requester.swarm.dial(addr).unwrap();
+        let (holder_id, requester_id) = (*holder.swarm.local_peer_id(), *requester.swarm.local_peer_id());
+        holder.set_peer_direction(&requester_id, SyncDirection::PullOnly, &holder_storage);
+        pump(&mut holder, &mut requester, |_, r| r.swarm.is_connected(&holder_id)).await;
+
+        requester.request_attachments(&holder_id, &ledger, &requester_storage);
+        pump(&mut holder, &mut requester, |h, r| {
+            h.handle_blob_events(&ledger, &holder_storage);
+            r.handle_blob_events(&ledger, &requester_storage);
+            r.requested_blobs.is_empty()
+        }).await;
+        assert!(!requester_storage.has_attachment(&attachment.hash));
+    }
+
+    #[tokio::test]
+    async fn presence_only_reaches_joined_books() {
+        let mut sync = client().await;
+        let mut keyring = BookKeyring::default();
+        keyring.create_book("family", std::collections::BTreeSet::new());
+        keyring.create_book("work", std::collections::BTreeSet::new());
+        let signer = Ed25519Signer::generate();
+        let suite = CryptoSuite::default();
+        assert!(sync.join_presence("family"));
+
+        let presence = Presence::new("laptop", "Anna", chrono::Utc::now());
+        let work = presence.seal("work", &keyring, &signer).unwrap();
+        assert!(!sync.receive_presence(&work, &keyring, &suite));
+        let family = presence.seal("family", &keyring, &signer).unwrap();
+        assert!(sync.receive_presence(&family, &keyring, &suite));
+
+        let board = sync.presence("family").unwrap();
+        let devices: Vec<&str> = board.active(chrono::Utc::now()).map(|p| p.device.as_str()).collect();
+        assert_eq!(devices, vec![presence::device_id(&signer.public_key()).as_str()]);
+        assert!(sync.presence("work").is_none());
+    }
+}
@@ -0,0 +1,183 @@
+//! Locale-aware number, currency and date formatting with parsing counterparts
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Serialize, Deserialize};
+
+use crate::amount::parse_amount_input;
+use crate::currency::Commodity;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SymbolPosition {
+    Before,
+    After,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LocaleError {
+    #[error("Invalid amount: {0}")]
+    InvalidAmount(String),
+    #[error("Invalid date: {0}")]
+    InvalidDate(String),
+    #[error("Unknown locale: {0}")]
+    UnknownLocale(String),
+}
+
+/// Formatting conventions for one locale
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Locale {
+    pub tag: String,
+    pub decimal_separator: char,
+    pub group_separator: Option<char>,
+    pub symbol_position: SymbolPosition,
+    pub symbol_spacing: bool,
+    /// chrono format string
+    pub date_format: String,
+}
+
+impl Locale {
+    pub fn en_us() -> Self {
+        Self {
+            tag: "en-US".to_string(),
+            decimal_separator: '.',
+            group_separator: Some(','),
+            symbol_position: SymbolPosition::Before,
+            symbol_spacing: false,
+            date_format: "%m/%d/%Y".to_string(),
+        }
+    }
+
+    pub fn en_gb() -> Self {
+        Self { tag: "en-GB".to_string(), date_format: "%d/%m/%Y".to_string(), ..Self::en_us() }
+    }
+
+    pub fn de_de() -> Self {
+        Self {
+            tag: "de-DE".to_string(),
+            decimal_separator: ',',
+            group_separator: Some('.'),
+            symbol_position: SymbolPosition::After,
+            symbol_spacing: true,
+            date_format: "%d.%m.%Y".to_string(),
+        }
+    }
+
+    pub fn fr_fr() -> Self {
+        Self {
+            tag: "fr-FR".to_string(),
+            group_separator: Some('\u{202f}'),
+            date_format: "%d/%m/%Y".to_string(),
+            ..Self::de_de()
+        }
+    }
+
+    /// ISO 8601 dates and plain decimals, for machine-readable output
+    pub fn iso() -> Self {
+        Self {
+            tag: "iso".to_string(),
+            decimal_separator: '.',
+            group_separator: None,
+            symbol_position: SymbolPosition::After,
+            symbol_spacing: true,
+            date_format: "%Y-%m-%d".to_string(),
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Result<Self, LocaleError> {
+        match tag.replace('_', "-").to_lowercase().as_str() {
+            "en-us" | "en" => Ok(Self::en_us()),
+            "en-gb" => Ok(Self::en_gb()),
+            "de-de" | "de" | "de-at" => Ok(Self::de_de()),
+            "fr-fr" | "fr" => Ok(Self::fr_fr()),
+            "iso" => Ok(Self::iso()),
+            _ => Err(LocaleError::UnknownLocale(tag.to_string())),
+        }
+    }
+
+    /// Format a number with locale separators and fixed decimals
+    pub fn format_amount(&self, amount: Decimal, decimals: u32) -> String {
+        let rounded = amount.round_dp(decimals).abs();
+        let text = format!("{:.*}", decimals as usize, rounded);
+        let (int_part, frac_part) = text.split_once('.').unwrap_or((&text, ""));
+
+        let mut grouped = String::new();
+        for (i, digit) in int_part.chars().enumerate() {
+            if i > 0 && (int_part.len() - i) % 3 == 0 {
+                if let Some(sep) = self.group_separator {
+                    grouped.push(sep);
+                }
+            }
+            grouped.push(digit);
+        }
+
+        let mut out = String::new();
+        if amount.is_sign_negative() && !rounded.is_zero() {
+            out.push('-');
+        }
+        out.push_str(&grouped);
+        if !frac_part.is_empty() {
+            out.push(self.decimal_separator);
+            out.push_str(frac_part);
+        }
+        out
+    }
+
+    /// Format an amount with its currency symbol
+    pub fn format_money(&self, amount: Decimal, commodity: &Commodity) -> String {
+        let decimals = minor_units(commodity);
+        let number = self.format_amount(amount, decimals);
+        let symbol = currency_symbol(commodity);
+        let space = if self.symbol_spacing { " " } else { "" };
+        match self.symbol_position {
+            SymbolPosition::Before => match number.strip_prefix('-') {
+                Some(abs) => format!("-{}{}{}", symbol, space, abs),
+                None => format!("{}{}{}", symbol, space, number),
+            },
+            SymbolPosition::After => format!("{}{}{}", number, space, symbol),
+        }
+    }
+
+    pub fn format_date(&self, date: NaiveDate) -> String {
+        date.format(&self.date_format).to_string()
+    }
+
+    /// Parse an amount written in this locale ("1.234,56" in de-DE), ignoring its currency;
+    /// see `parse_amount_input` for the accepted forms
+    pub fn parse_amount(&self, input: &str) -> Result<Decimal, LocaleError> {
+        parse_amount_input(input, self)
+            .map(|parsed| parsed.amount)
+            .map_err(|_| LocaleError::InvalidAmount(input.to_string()))
+    }
+
+    pub fn parse_date(&self, input: &str) -> Result<NaiveDate, LocaleError> {
+        NaiveDate::parse_from_str(input.trim(), &self.date_format)
+            .or_else(|_| NaiveDate::parse_from_str(input.trim(), "%Y-%m-%d"))
+            .map_err(|_| LocaleError::InvalidDate(input.to_string()))
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self::en_us()
+    }
+}
+
+/// Display symbol for common currencies, falling back to the code
+pub fn currency_symbol(commodity: &Commodity) -> &str {
+    match commodity.code() {
+        "USD" => "$",
+        "EUR" => "€",
+        "GBP" => "£",
+        "JPY" => "¥",
+        "BTC" => "₿",
+        code => code,
+    }
+}
+
+/// Decimal places conventionally shown for a currency
+pub fn minor_units(commodity: &Commodity) -> u32 {
+    match commodity.code() {
+        "JPY" | "KRW" => 0,
+        "BTC" => 8,
+        _ => 2,
+    }
+}
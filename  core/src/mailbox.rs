@@ -0,0 +1,312 @@
+//! Transactions from e-mailed receipts. Apps plug in a mailbox (typically IMAP) through
+//! `MailboxConnector`; parsers for known senders turn order confirmations into staged
+//! transactions with the mail's attachments, so online purchases only need approving.
+use std::future::Future;
+use chrono::{DateTime, NaiveDate, Utc};
+use regex::Regex;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+use crate::amount::parse_amount_input;
+use crate::currency::Commodity;
+use crate::ledger::Posting;
+use crate::locale::Locale;
+use crate::receipts::ReceiptDefaults;
+use crate::staging::{StagedAttachment, StagedTransaction, StagingArea, StagingSource};
+
+#[derive(Debug, Error)]
+pub enum MailboxError {
+    #[error("Mailbox connection failed: {0}")]
+    Connection(String),
+    #[error("Mailbox rejected the credentials")]
+    Authentication,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MailAttachment {
+    pub filename: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+/// One fetched message, already decoded to text
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MailMessage {
+    /// Mailbox-specific id (e.g. IMAP UID) used to mark the message processed
+    pub id: String,
+    pub from: String,
+    /// The receiving server's Authentication-Results header; parsers only trust senders whose
+    /// DKIM signature or DMARC check passed for the From domain
+    #[serde(default)]
+    pub authentication_results: Option<String>,
+    pub subject: String,
+    pub received_at: DateTime<Utc>,
+    /// Plain-text body; connectors strip HTML-only mails to text
+    pub body: String,
+    #[serde(default)]
+    pub attachments: Vec<MailAttachment>,
+}
+
+/// Implemented by apps for their mailbox, e.g. an IMAP folder the receipts are filtered into
+pub trait MailboxConnector {
+    /// Messages not yet marked processed, oldest first
+    fn fetch_unprocessed(&self) -> impl Future<Output = Result<Vec<MailMessage>, MailboxError>> + Send;
+
+    /// Keep a message from being fetched again, e.g. by setting an IMAP flag
+    fn mark_processed(&self, id: &str) -> impl Future<Output = Result<(), MailboxError>> + Send;
+}
+
+/// What a parser read from a receipt mail
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmailReceipt {
+    pub merchant: String,
+    pub date: NaiveDate,
+    pub total: Decimal,
+    pub commodity: Option<Commodity>,
+    /// Order or transaction number from the sender
+    pub order_id: Option<String>,
+}
+
+/// Reads receipts of one sender
+pub trait ReceiptParser: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn parse(&self, message: &MailMessage) -> Option<EmailReceipt>;
+}
+
+/// Amazon order confirmations
+pub struct AmazonParser {
+    order: Regex,
+    total: Regex,
+}
+
+impl Default for AmazonParser {
+    fn default() -> Self {
+        Self {
+            order: Regex::new(r"Order\s*#\s*(\d{3}-\d{7}-\d{7})").unwrap(),
+            total: Regex::new(r"(?i)(?:order|grand)\s+total:?\s*([^\n]+)").unwrap(),
+        }
+    }
+}
+
+impl ReceiptParser for AmazonParser {
+    fn name(&self) -> &'static str {
+        "Amazon"
+    }
+
+    fn parse(&self, message: &MailMessage) -> Option<EmailReceipt> {
+        if !sender_is(message, "amazon") {
+            return None;
+        }
+        let (total, commodity) = amount(self.total.captures(&message.body)?.get(1)?.as_str())?;
+        Some(EmailReceipt {
+            merchant: "Amazon".to_string(),
+            date: message.received_at.date_naive(),
+            total,
+            commodity,
+            order_id: self.order.captures(&message.body).map(|c| c[1].to_string()),
+        })
+    }
+}
+
+/// PayPal payment receipts ("You paid $12.34 USD to Example Shop")
+pub struct PayPalParser {
+    payment: Regex,
+    transaction: Regex,
+}
+
+impl Default for PayPalParser {
+    fn default() -> Self {
+        Self {
+            payment: Regex::new(r"You (?:paid|sent a payment of)\s+(.+?)\s+to\s+([^\n.]+)").unwrap(),
+            transaction: Regex::new(r"Transaction ID:?\s*([A-Z0-9]{17})").unwrap(),
+        }
+    }
+}
+
+impl ReceiptParser for PayPalParser {
+    fn name(&self) -> &'static str {
+        "PayPal"
+    }
+
+    fn parse(&self, message: &MailMessage) -> Option<EmailReceipt> {
+        if !sender_is(message, "paypal") {
+            return None;
+        }
+        let payment = self.payment.captures(&message.body)?;
+        let (total, commodity) = amount(&payment[1])?;
+        Some(EmailReceipt {
+            merchant: payment[2].trim().to_string(),
+            date: message.received_at.date_naive(),
+            total,
+            commodity,
+            order_id: self.transaction.captures(&message.body).map(|c| c[1].to_string()),
+        })
+    }
+}
+
+/// Parsers for the senders supported out of the box
+pub fn default_parsers() -> Vec<Box<dyn ReceiptParser>> {
+    vec![Box::new(AmazonParser::default()), Box::new(PayPalParser::default())]
+}
+
+/// Outcome of one mailbox run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MailImportReport {
+    pub staged: Vec<Uuid>,
+    /// Ids of messages no parser recognized; they stay unprocessed for other tools
+    pub unrecognized: Vec<String>,
+}
+
+/// Stage a transaction for every recognized receipt mail, attach the mail's files and mark it
+/// processed. Postings are pre-filled when `defaults` are given.
+pub async fn stage_email_receipts<C: MailboxConnector>(
+    connector: &C,
+    parsers: &[Box<dyn ReceiptParser>],
+    staging: &mut StagingArea,
+    defaults: Option<&ReceiptDefaults>,
+) -> Result<MailImportReport, MailboxError> {
+    let mut report = MailImportReport::default();
+    for message in connector.fetch_unprocessed().await? {
+        let Some((parser, receipt)) = parsers.iter().find_map(|p| Some((p.name(), p.parse(&message)?))) else {
+            report.unrecognized.push(message.id);
+            continue;
+        };
+
+        let mut entry = StagedTransaction::new(StagingSource::Email);
+        entry.date = Some(receipt.date);
+        entry.amount = Some(receipt.total);
+        entry.payee = Some(receipt.merchant.clone());
+        entry.description = match &receipt.order_id {
+            Some(order) => format!("{} {} ({})", parser, order, receipt.merchant),
+            None => format!("{} ({})", parser, receipt.merchant),
+        };
+        entry.attachments = message.attachments.into_iter()
+            .map(|a| StagedAttachment { filename: Some(a.filename), mime_type: Some(a.mime_type), data: a.data })
+            .collect();
+        if let Some(defaults) = defaults {
+            let posting = |account_id, amount| match &receipt.commodity {
+                Some(commodity) => Posting::in_commodity(account_id, amount, commodity.clone()),
+                None => Posting::new(account_id, amount),
+            };
+            entry.postings = vec![
+                posting(defaults.expense_account, receipt.total),
+                posting(defaults.payment_account, -receipt.total),
+            ];
+        }
+
+        report.staged.push(staging.stage(entry));
+        connector.mark_processed(&message.id).await?;
+    }
+    Ok(report)
+}
+
+/// Public suffixes with two labels that senders we parse mail from use; a single-label
+/// suffix (".com", ".de") is assumed otherwise
+const TWO_LABEL_SUFFIXES: &[&str] = &[
+    "co.uk", "org.uk", "com.au", "net.au", "co.nz", "co.jp", "co.in", "co.za", "com.br", "com.mx",
+    "com.tr", "com.sg", "com.hk", "com.cn", "com.ar", "co.kr", "com.tw", "com.my", "co.id", "com.sa",
+];
+
+/// Registrable domain ("amazon.co.uk" for "orders.amazon.co.uk") and its name label ("amazon")
+fn registrable(domain: &str) -> Option<(String, String)> {
+    let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+    let labels: Vec<&str> = domain.split('.').collect();
+    let suffix_len = match labels.len() {
+        n if n >= 3 && TWO_LABEL_SUFFIXES.contains(&labels[n - 2..].join(".").as_str()) => 2,
+        n if n >= 2 => 1,
+        _ => return None,
+    };
+    let start = labels.len() - suffix_len - 1;
+    let name = labels[start];
+    if name.is_empty() {
+        return None;
+    }
+    Some((labels[start..].join("."), name.to_string()))
+}
+
+fn address_domain(address: &str) -> Option<&str> {
+    let address = address.rsplit('<').next().unwrap_or(address).trim().trim_end_matches('>');
+    address.rsplit_once('@').map(|(_, domain)| domain)
+}
+
+/// Whether a message is from `name`'s own domain: "Name <orders@amazon.co.uk>" is from amazon,
+/// "amazon-lookalike.com" is not. The From domain must also be authenticated by a passing
+/// DKIM signature or DMARC check for the same registrable domain.
+fn sender_is(message: &MailMessage, name: &str) -> bool {
+    let Some((domain, label)) = address_domain(&message.from).and_then(registrable) else { return false };
+    label == name.to_ascii_lowercase() && authenticated_domains(message).any(|d| d == domain)
+}
+
+/// Registrable domains vouched for by passing DKIM (header.d / header.i) or DMARC (header.from)
+/// results
+fn authenticated_domains(message: &MailMessage) -> impl Iterator<Item = String> + '_ {
+    let results = message.authentication_results.as_deref().unwrap_or_default();
+    // The first element names the server that produced the results
+    results.split(';').skip(1).filter_map(|result| {
+        let mut tokens = result.split_whitespace();
+        let method = tokens.next()?.to_ascii_lowercase();
+        let property = match method.as_str() {
+            "dkim=pass" => ["header.d=", "header.i="].as_slice(),
+            "dmarc=pass" => ["header.from="].as_slice(),
+            _ => return None,
+        };
+        let value = tokens.find_map(|t| property.iter().find_map(|p| t.strip_prefix(p)))?;
+        let domain = value.rsplit('@').next()?;
+        registrable(domain).map(|(registrable, _)| registrable)
+    })
+}
+
+/// Amount and currency from text like "$1,234.56 USD" or "EUR 12,99"
+fn amount(text: &str) -> Option<(Decimal, Option<Commodity>)> {
+    let text = text.trim();
+    let (number, code) = match text.rsplit_once(' ') {
+        Some((number, code)) if code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase()) => (number, Some(code)),
+        _ => match text.split_once(' ') {
+            Some((code, number)) if code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase()) => (number, Some(code)),
+            _ => (text, None),
+        },
+    };
+    let parsed = parse_amount_input(number, &Locale::en_us()).ok()?;
+    Some((parsed.amount, code.map(Commodity::new).or(parsed.commodity)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mail(from: &str, results: Option<&str>) -> MailMessage {
+        MailMessage {
+            id: "1".to_string(),
+            from: from.to_string(),
+            authentication_results: results.map(String::from),
+            subject: "Your order".to_string(),
+            received_at: Utc::now(),
+            body: "Order # 123-1234567-1234567\nOrder Total: £12.34 GBP".to_string(),
+            attachments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn sender_check_knows_two_label_suffixes() {
+        let uk = "mx.example; dkim=pass header.i=@amazon.co.uk header.s=s1; spf=pass";
+        assert!(sender_is(&mail("Amazon <orders@amazon.co.uk>", Some(uk)), "amazon"));
+        let au = "mx.example; dmarc=pass (p=reject) header.from=amazon.com.au";
+        assert!(sender_is(&mail("auto-confirm@marketplace.amazon.com.au", Some(au)), "amazon"));
+        assert!(!sender_is(&mail("orders@amazon.co.uk.evil.com", Some(uk)), "amazon"));
+        assert!(!sender_is(&mail("orders@amazon-lookalike.com", None), "amazon"));
+    }
+
+    #[test]
+    fn unauthenticated_sender_is_not_trusted() {
+        let parser = AmazonParser::default();
+        assert!(parser.parse(&mail("orders@amazon.co.uk", None)).is_none());
+        let failed = "mx.example; dkim=fail header.d=amazon.co.uk; dmarc=pass header.from=evil.com";
+        assert!(parser.parse(&mail("orders@amazon.co.uk", Some(failed))).is_none());
+        let passed = "mx.example; dkim=pass header.d=amazon.co.uk";
+        let receipt = parser.parse(&mail("orders@amazon.co.uk", Some(passed))).unwrap();
+        assert_eq!(receipt.total, Decimal::new(1234, 2));
+    }
+}
@@ -0,0 +1,157 @@
+//! Detection of imported outflow/inflow pairs that are really one transfer
+use std::collections::HashSet;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::ledger::{Posting, Transaction};
+
+/// Proposed consolidation of two one-sided imports into a single transfer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferCandidate {
+    pub outflow_id: Uuid,
+    pub inflow_id: Uuid,
+    pub from_account: Uuid,
+    pub to_account: Uuid,
+    pub amount: Decimal,
+    pub days_apart: i64,
+}
+
+/// Real-account side of an import booked against the suspense account
+fn real_side(tx: &Transaction, suspense_account: Uuid) -> Option<&Posting> {
+    if tx.postings.len() != 2 || !tx.postings.iter().any(|p| p.account_id == suspense_account) {
+        return None;
+    }
+    tx.postings.iter().find(|p| p.account_id != suspense_account)
+}
+
+/// Pair imports of equal amount leaving one account and arriving in another within `max_days`.
+/// Imports still sitting in `suspense_account` are considered; closest dates are matched first.
+pub fn find_transfers(transactions: &[Transaction], suspense_account: Uuid, max_days: i64) -> Vec<TransferCandidate> {
+    let mut pairs = Vec::new();
+    for out_tx in transactions {
+        let Some(out) = real_side(out_tx, suspense_account) else { continue };
+        if out.amount >= Decimal::ZERO {
+            continue;
+        }
+        for in_tx in transactions {
+            let Some(inc) = real_side(in_tx, suspense_account) else { continue };
+            let days_apart = (in_tx.date - out_tx.date).num_days();
+            if inc.amount == -out.amount
+                && inc.commodity == out.commodity
+                && inc.account_id != out.account_id
+                && (0..=max_days).contains(&days_apart)
+            {
+                pairs.push(TransferCandidate {
+                    outflow_id: out_tx.id,
+                    inflow_id: in_tx.id,
+                    from_account: out.account_id,
+                    to_account: inc.account_id,
+                    amount: inc.amount,
+                    days_apart,
+                });
+            }
+        }
+    }
+
+    pairs.sort_by_key(|c| c.days_apart);
+    let mut used = HashSet::new();
+    pairs.retain(|c| {
+        if used.contains(&c.outflow_id) || used.contains(&c.inflow_id) {
+            return false;
+        }
+        used.insert(c.outflow_id);
+        used.insert(c.inflow_id);
+        true
+    });
+    pairs
+}
+
+/// Build the single transfer transaction replacing both imports (dated on the outflow, in the
+/// outflow's commodity)
+pub fn consolidate(candidate: &TransferCandidate, transactions: &[Transaction]) -> Option<Transaction> {
+    let outflow = transactions.iter().find(|t| t.id == candidate.outflow_id)?;
+    let commodity = &outflow.postings.iter().find(|p| p.account_id == candidate.from_account)?.commodity;
+    Some(Transaction::new(
+        outflow.date,
+        format!("Transfer: {}", outflow.description),
+        vec![
+            Posting::in_commodity(candidate.to_account, candidate.amount, commodity.clone()),
+            Posting::in_commodity(candidate.from_account, -candidate.amount, commodity.clone()),
+        ],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use crate::currency::Commodity;
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, d).unwrap()
+    }
+
+    /// One-sided import of `amount` into `account`, balanced against suspense
+    fn import(d: u32, account: Uuid, amount: i64, commodity: &str, suspense: Uuid) -> Transaction {
+        let commodity = Commodity::new(commodity);
+        Transaction::new(date(d), "Bank import", vec![
+            Posting::in_commodity(account, Decimal::from(amount), commodity.clone()),
+            Posting::in_commodity(suspense, Decimal::from(-amount), commodity),
+        ])
+    }
+
+    #[test]
+    fn closest_inflow_in_the_same_commodity_is_paired() {
+        let (suspense, checking, savings, wallet) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let transactions = vec![
+            import(10, checking, -100, "USD", suspense),
+            import(13, savings, 100, "USD", suspense),
+            import(11, savings, 100, "USD", suspense),
+            // Wrong commodity, before the outflow, or back into the same account
+            import(10, wallet, 100, "EUR", suspense),
+            import(9, wallet, 100, "USD", suspense),
+            import(10, checking, 100, "USD", suspense),
+        ];
+        let candidates = find_transfers(&transactions, suspense, 5);
+        assert_eq!(candidates, vec![TransferCandidate {
+            outflow_id: transactions[0].id,
+            inflow_id: transactions[2].id,
+            from_account: checking,
+            to_account: savings,
+            amount: Decimal::from(100),
+            days_apart: 1,
+        }]);
+        assert!(find_transfers(&transactions[..2], suspense, 2).is_empty());
+    }
+
+    #[test]
+    fn only_two_posting_suspense_imports_are_considered() {
+        let (suspense, checking, savings, fees) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let split = Transaction::new(date(10), "Transfer and fee", vec![
+            Posting::new(checking, Decimal::from(-101)),
+            Posting::new(fees, Decimal::ONE),
+            Posting::new(suspense, Decimal::from(100)),
+        ]);
+        let categorised = Transaction::new(date(10), "Rent", vec![
+            Posting::new(checking, Decimal::from(-100)),
+            Posting::new(fees, Decimal::from(100)),
+        ]);
+        let transactions = vec![split, categorised, import(10, savings, 100, "USD", suspense)];
+        assert!(find_transfers(&transactions, suspense, 5).is_empty());
+    }
+
+    #[test]
+    fn consolidated_transfer_is_dated_on_the_outflow() {
+        let (suspense, checking, savings) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let transactions = vec![import(10, checking, -250, "GBP", suspense), import(12, savings, 250, "GBP", suspense)];
+        let candidate = find_transfers(&transactions, suspense, 5).pop().unwrap();
+        let transfer = consolidate(&candidate, &transactions).unwrap();
+        assert_eq!(transfer.date, date(10));
+        assert_eq!(transfer.description, "Transfer: Bank import");
+        assert_eq!(transfer.postings.iter().map(|p| p.amount).sum::<Decimal>(), Decimal::ZERO);
+        assert!(transfer.postings.iter().all(|p| p.commodity == Commodity::new("GBP")));
+        assert_eq!((transfer.postings[0].account_id, transfer.postings[0].amount), (savings, Decimal::from(250)));
+        assert!(consolidate(&candidate, &transactions[1..]).is_none());
+    }
+}
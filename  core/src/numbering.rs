@@ -0,0 +1,190 @@
+//! Per-book reference sequences (journal numbers, invoice numbers) that stay unique across devices
+use std::collections::HashMap;
+use chrono::{Datelike, NaiveDate};
+use serde::{Serialize, Deserialize};
+
+use crate::ledger::Transaction;
+use crate::storage::LocalStorage;
+
+/// How numbers stay unique when several devices issue them offline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NumberingScheme {
+    /// Each device issues from its own prefix: "JE-A-0001", "JE-B-0001"
+    DevicePrefixed,
+    /// Devices issue provisional numbers; a post-merge pass assigns the final gapless sequence
+    RenumberAfterMerge,
+}
+
+/// Definition of a reference sequence
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sequence {
+    pub name: String,
+    pub prefix: String,
+    pub width: usize,
+    pub scheme: NumberingScheme,
+    /// Final numbers restart every calendar year as "JE-2024-000001"; applies to the numbers
+    /// assigned by `renumber`
+    #[serde(default)]
+    pub yearly: bool,
+}
+
+impl Sequence {
+    pub fn new(name: &str, prefix: &str, scheme: NumberingScheme) -> Self {
+        Self {
+            name: name.to_string(),
+            prefix: prefix.to_string(),
+            width: 6,
+            scheme,
+            yearly: false,
+        }
+    }
+
+    pub fn with_yearly_reset(mut self) -> Self {
+        self.yearly = true;
+        self
+    }
+
+    /// Numbering period a transaction dated `date` falls in
+    fn period(&self, date: NaiveDate) -> Option<i32> {
+        self.yearly.then(|| date.year())
+    }
+
+    fn provisional_prefix(&self) -> String {
+        format!("{}-TMP-", self.prefix)
+    }
+
+    /// Whether a reference was issued from this sequence
+    pub fn owns(&self, reference: &str) -> bool {
+        reference.starts_with(&format!("{}-", self.prefix))
+    }
+
+    fn format(&self, number: u64, period: Option<i32>) -> String {
+        match period {
+            Some(year) => format!("{}-{}-{:0width$}", self.prefix, year, number, width = self.width),
+            None => format!("{}-{:0width$}", self.prefix, number, width = self.width),
+        }
+    }
+
+    /// Number of a final reference of this sequence in `period`; None for provisional,
+    /// device-prefixed or other periods' references
+    fn final_number(&self, reference: &str, period: Option<i32>) -> Option<u64> {
+        let mut rest = reference.strip_prefix(&self.prefix)?.strip_prefix('-')?;
+        if let Some(year) = period {
+            rest = rest.strip_prefix(&year.to_string())?.strip_prefix('-')?;
+        }
+        if rest.is_empty() || !rest.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        rest.parse().ok()
+    }
+}
+
+/// Issues references on this device; counters are persisted in storage settings
+#[derive(Debug, Clone)]
+pub struct ReferenceAllocator {
+    device_prefix: String,
+    counters: HashMap<String, u64>,
+}
+
+impl ReferenceAllocator {
+    pub fn new(device_prefix: &str) -> Self {
+        Self {
+            device_prefix: device_prefix.to_string(),
+            counters: HashMap::new(),
+        }
+    }
+
+    /// Restore counters saved by a previous run
+    pub fn load(storage: &LocalStorage, device_prefix: &str) -> Self {
+        let counters = storage.get_setting("reference_counters")
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self { device_prefix: device_prefix.to_string(), counters }
+    }
+
+    pub fn save(&self, storage: &LocalStorage) {
+        storage.set_setting("reference_counters", &serde_json::to_string(&self.counters).unwrap());
+    }
+
+    /// Issue the next reference from a sequence
+    pub fn allocate(&mut self, sequence: &Sequence) -> String {
+        let counter = self.counters.entry(sequence.name.clone()).or_insert(0);
+        *counter += 1;
+        let number = format!("{:0width$}", counter, width = sequence.width);
+        match sequence.scheme {
+            NumberingScheme::DevicePrefixed => format!("{}-{}-{}", sequence.prefix, self.device_prefix, number),
+            NumberingScheme::RenumberAfterMerge => format!("{}{}-{}", sequence.provisional_prefix(), self.device_prefix, number),
+        }
+    }
+}
+
+/// Assign final gapless numbers after a merge, ordered by date then provisional reference.
+/// Already-final numbers are kept and each period continues after its highest final number;
+/// returns the (transaction id, new reference) pairs applied.
+pub fn renumber(sequence: &Sequence, transactions: &mut [Transaction]) -> Vec<(uuid::Uuid, String)> {
+    let provisional = sequence.provisional_prefix();
+    let mut last: HashMap<Option<i32>, u64> = HashMap::new();
+    for tx in transactions.iter() {
+        let period = sequence.period(tx.date);
+        if let Some(number) = tx.reference.as_deref().and_then(|r| sequence.final_number(r, period)) {
+            let max = last.entry(period).or_insert(0);
+            *max = (*max).max(number);
+        }
+    }
+
+    let mut pending: Vec<&mut Transaction> = transactions.iter_mut()
+        .filter(|t| t.reference.as_deref().is_some_and(|r| r.starts_with(&provisional)))
+        .collect();
+    pending.sort_by(|a, b| (a.date, &a.reference, a.id).cmp(&(b.date, &b.reference, b.id)));
+
+    let mut assigned = Vec::new();
+    for tx in pending {
+        let period = sequence.period(tx.date);
+        let number = last.entry(period).or_insert(0);
+        *number += 1;
+        let reference = sequence.format(*number, period);
+        tx.reference = Some(reference.clone());
+        assigned.push((tx.id, reference));
+    }
+    assigned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(date: NaiveDate, reference: &str) -> Transaction {
+        let mut tx = Transaction::new(date, "Entry", Vec::new());
+        tx.reference = Some(reference.to_string());
+        tx
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn renumber_ignores_device_prefixed_numbers() {
+        let sequence = Sequence::new("journal", "JE", NumberingScheme::RenumberAfterMerge);
+        let mut transactions = vec![
+            entry(date(2024, 1, 1), "JE-000002"),
+            entry(date(2024, 1, 2), "JE-B-000090"),
+            entry(date(2024, 1, 3), "JE-TMP-A-1"),
+        ];
+        let assigned = renumber(&sequence, &mut transactions);
+        assert_eq!(assigned, vec![(transactions[2].id, "JE-000003".to_string())]);
+    }
+
+    #[test]
+    fn yearly_sequence_restarts_each_year() {
+        let sequence = Sequence::new("journal", "JE", NumberingScheme::RenumberAfterMerge).with_yearly_reset();
+        let mut transactions = vec![
+            entry(date(2023, 12, 30), "JE-2023-000041"),
+            entry(date(2023, 12, 31), "JE-TMP-A-1"),
+            entry(date(2024, 1, 2), "JE-TMP-A-2"),
+        ];
+        renumber(&sequence, &mut transactions);
+        assert_eq!(transactions[1].reference.as_deref(), Some("JE-2023-000042"));
+        assert_eq!(transactions[2].reference.as_deref(), Some("JE-2024-000001"));
+    }
+}
@@ -0,0 +1,154 @@
+//! Payroll templates generating compound payroll transactions
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::currency::Commodity;
+use crate::ledger::{Posting, Transaction};
+use crate::rounding::RoundingPolicy;
+
+/// How a component amount is derived from gross pay
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ComponentAmount {
+    /// Fraction of gross (0.062 = 6.2%)
+    Rate(Decimal),
+    Fixed(Decimal),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ComponentKind {
+    /// Withheld from the employee, owed to a third party
+    Withholding { liability_account: Uuid },
+    /// Paid by the employer on top of gross
+    EmployerContribution { expense_account: Uuid, liability_account: Uuid },
+}
+
+/// One configurable payroll line (income tax, social security, pension...)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayrollComponent {
+    pub name: String,
+    pub kind: ComponentKind,
+    pub amount: ComponentAmount,
+}
+
+impl PayrollComponent {
+    /// Rate-based amounts are rounded to `commodity` by the ledger's policy
+    pub fn amount_for(&self, gross: Decimal, commodity: &Commodity, policy: &RoundingPolicy) -> Decimal {
+        match self.amount {
+            ComponentAmount::Rate(rate) => policy.round(gross * rate, commodity),
+            ComponentAmount::Fixed(amount) => amount,
+        }
+    }
+}
+
+/// Payroll setup for one jurisdiction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayrollTemplate {
+    pub jurisdiction: String,
+    pub wage_expense_account: Uuid,
+    pub net_pay_account: Uuid,
+    pub components: Vec<PayrollComponent>,
+    /// Currency wages are paid in
+    #[serde(default)]
+    pub commodity: Commodity,
+}
+
+/// Breakdown of a generated payroll run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayrollRun {
+    pub gross: Decimal,
+    pub withheld: Decimal,
+    pub employer_cost: Decimal,
+    pub net: Decimal,
+    pub transaction: Transaction,
+}
+
+impl PayrollTemplate {
+    /// Template paying wages in `commodity`, usually the ledger's base currency
+    pub fn new(jurisdiction: &str, wage_expense_account: Uuid, net_pay_account: Uuid, commodity: Commodity) -> Self {
+        Self {
+            jurisdiction: jurisdiction.to_string(),
+            wage_expense_account,
+            net_pay_account,
+            components: Vec::new(),
+            commodity,
+        }
+    }
+
+    pub fn with_component(mut self, component: PayrollComponent) -> Self {
+        self.components.push(component);
+        self
+    }
+
+    /// Generate the compound payroll transaction for a gross amount, rounding rate-based
+    /// components by the ledger's policy
+    pub fn generate(
+        &self,
+        gross: Decimal,
+        date: NaiveDate,
+        description: &str,
+        policy: &RoundingPolicy,
+    ) -> Result<PayrollRun, &'static str> {
+        if gross <= Decimal::ZERO {
+            return Err("Gross pay must be positive");
+        }
+
+        let mut postings = vec![Posting::in_commodity(self.wage_expense_account, gross, self.commodity.clone())];
+        let mut withheld = Decimal::ZERO;
+        let mut employer_cost = Decimal::ZERO;
+
+        for component in &self.components {
+            let amount = component.amount_for(gross, &self.commodity, policy);
+            if amount.is_zero() {
+                continue;
+            }
+            match &component.kind {
+                ComponentKind::Withholding { liability_account } => {
+                    withheld += amount;
+                    postings.push(Posting::in_commodity(*liability_account, -amount, self.commodity.clone()));
+                }
+                ComponentKind::EmployerContribution { expense_account, liability_account } => {
+                    employer_cost += amount;
+                    postings.push(Posting::in_commodity(*expense_account, amount, self.commodity.clone()));
+                    postings.push(Posting::in_commodity(*liability_account, -amount, self.commodity.clone()));
+                }
+            }
+        }
+
+        let net = gross - withheld;
+        if net < Decimal::ZERO {
+            return Err("Withholdings exceed gross pay");
+        }
+        postings.push(Posting::in_commodity(self.net_pay_account, -net, self.commodity.clone()));
+
+        Ok(PayrollRun {
+            gross,
+            withheld,
+            employer_cost,
+            net,
+            transaction: Transaction::new(date, description, postings),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn components_round_to_the_payroll_currency() {
+        let (wages, net, tax) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let template = PayrollTemplate::new("JP", wages, net, Commodity::new("JPY"))
+            .with_component(PayrollComponent {
+                name: "Income tax".to_string(),
+                kind: ComponentKind::Withholding { liability_account: tax },
+                amount: ComponentAmount::Rate(Decimal::new(1023, 4)),
+            });
+        let date = NaiveDate::from_ymd_opt(2024, 4, 25).unwrap();
+        let run = template.generate(Decimal::from(300_001), date, "April payroll", &RoundingPolicy::default()).unwrap();
+
+        assert_eq!(run.withheld, Decimal::from(30_690));
+        assert!(run.transaction.postings.iter().all(|p| p.commodity == Commodity::new("JPY")));
+    }
+}
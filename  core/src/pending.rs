@@ -0,0 +1,136 @@
+//! Provisional state of locally created transactions, for optimistic UIs that grey out
+//! entries until they are safely stored and sent to peers
+use std::collections::HashMap;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::dedup::{content_hash, ContentHash};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PendingState {
+    /// Applied in memory only
+    Provisional,
+    /// Committed to local storage, not yet published to peers
+    Persisted,
+    /// Persisted and published; the transaction is no longer tracked
+    Confirmed,
+    /// The commit was rolled back; the UI should offer retry or discard
+    Failed(String),
+}
+
+/// One state change, for UIs that poll instead of re-reading every entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingTransition {
+    pub transaction_id: Uuid,
+    /// None when the transaction just started being tracked
+    pub from: Option<PendingState>,
+    pub to: PendingState,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Entry {
+    persisted: bool,
+    published: bool,
+    failed: Option<String>,
+}
+
+impl Entry {
+    fn state(&self) -> PendingState {
+        match (&self.failed, self.persisted, self.published) {
+            (Some(reason), _, _) => PendingState::Failed(reason.clone()),
+            (None, true, true) => PendingState::Confirmed,
+            (None, true, false) => PendingState::Persisted,
+            (None, false, _) => PendingState::Provisional,
+        }
+    }
+}
+
+/// Tracks local transactions from creation until they are both persisted and published
+#[derive(Debug, Clone, Default)]
+pub struct PendingTracker {
+    entries: HashMap<Uuid, Entry>,
+    /// Queued outgoing payload -> transactions it carries
+    payloads: HashMap<ContentHash, Vec<Uuid>>,
+    transitions: Vec<PendingTransition>,
+}
+
+impl PendingTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start tracking locally created transactions as provisional; retracking a failed one resets it
+    pub fn track(&mut self, ids: &[Uuid]) {
+        for id in ids {
+            let from = self.entries.get(id).map(Entry::state);
+            self.entries.insert(*id, Entry::default());
+            self.push_transition(*id, from, PendingState::Provisional);
+        }
+    }
+
+    /// The changeset holding these transactions was committed to storage
+    pub fn mark_persisted(&mut self, ids: &[Uuid]) {
+        for id in ids {
+            self.update(*id, |e| e.persisted = true);
+        }
+    }
+
+    /// The changeset holding these transactions was rolled back
+    pub fn mark_failed(&mut self, ids: &[Uuid], reason: &str) {
+        for id in ids {
+            self.update(*id, |e| e.failed = Some(reason.to_string()));
+        }
+    }
+
+    /// Remember which queued payload carries the transactions, so sending it publishes them
+    pub fn attach_payload(&mut self, ids: &[Uuid], data: &[u8]) {
+        self.payloads.entry(content_hash(data)).or_default().extend_from_slice(ids);
+    }
+
+    /// A payload went out; transactions it carried count as published
+    pub fn payload_sent(&mut self, data: &[u8]) {
+        if let Some(ids) = self.payloads.remove(&content_hash(data)) {
+            for id in ids {
+                self.update(id, |e| e.published = true);
+            }
+        }
+    }
+
+    /// Current state; None for transactions that aren't pending (remote or already confirmed)
+    pub fn state(&self, id: &Uuid) -> Option<PendingState> {
+        self.entries.get(id).map(Entry::state)
+    }
+
+    /// Whether the UI should still show the transaction as unconfirmed
+    pub fn is_pending(&self, id: &Uuid) -> bool {
+        self.entries.contains_key(id)
+    }
+
+    /// All tracked transactions and their states
+    pub fn pending(&self) -> Vec<(Uuid, PendingState)> {
+        self.entries.iter().map(|(id, e)| (*id, e.state())).collect()
+    }
+
+    /// State changes since the last call, oldest first
+    pub fn take_transitions(&mut self) -> Vec<PendingTransition> {
+        std::mem::take(&mut self.transitions)
+    }
+
+    /// Apply a change to a tracked entry and record the transition; confirmed entries are dropped
+    fn update(&mut self, id: Uuid, change: impl FnOnce(&mut Entry)) {
+        let Some(entry) = self.entries.get_mut(&id) else { return };
+        let from = entry.state();
+        change(entry);
+        let to = entry.state();
+        if to == PendingState::Confirmed {
+            self.entries.remove(&id);
+        }
+        if from != to {
+            self.push_transition(id, Some(from), to);
+        }
+    }
+
+    fn push_transition(&mut self, transaction_id: Uuid, from: Option<PendingState>, to: PendingState) {
+        self.transitions.push(PendingTransition { transaction_id, from, to });
+    }
+}
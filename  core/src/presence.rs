@@ -0,0 +1,202 @@
+//! Ephemeral presence for shared books: which device is viewing or editing which transaction.
+//! Sent on each book's own gossip topic and never stored or merged into the document, so it
+//! costs nothing once peers stop sending; entries fade out when their sender goes quiet.
+//! Updates are signed by the device and sealed with the book key, so only members read them
+//! and nobody can speak for another device.
+use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+use crate::crypto::{CryptoSuite, Signature, Signer};
+use crate::keyring::{BookKeyring, KeyringError, SealedPayload};
+
+/// How long a presence entry counts without a refresh
+pub const PRESENCE_TTL_SECS: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PresenceActivity {
+    /// Has the book open, nothing selected
+    Idle,
+    Viewing,
+    Editing,
+}
+
+/// What one device is doing right now
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Presence {
+    /// Hex of the signing key on received updates, whatever the sender put here
+    pub device: String,
+    /// Shown to other users, e.g. "Anna"
+    pub display_name: String,
+    #[serde(default)]
+    pub transaction_id: Option<Uuid>,
+    pub activity: PresenceActivity,
+    pub sent_at: DateTime<Utc>,
+}
+
+impl Presence {
+    pub fn new(device: impl Into<String>, display_name: impl Into<String>, now: DateTime<Utc>) -> Self {
+        Self {
+            device: device.into(),
+            display_name: display_name.into(),
+            transaction_id: None,
+            activity: PresenceActivity::Idle,
+            sent_at: now,
+        }
+    }
+
+    pub fn viewing(mut self, transaction_id: Uuid) -> Self {
+        self.transaction_id = Some(transaction_id);
+        self.activity = PresenceActivity::Viewing;
+        self
+    }
+
+    pub fn editing(mut self, transaction_id: Uuid) -> Self {
+        self.transaction_id = Some(transaction_id);
+        self.activity = PresenceActivity::Editing;
+        self
+    }
+
+    /// Sign with the device key and seal for `book`, ready to publish on its presence topic
+    pub fn seal(&self, book: &str, keyring: &BookKeyring, signer: &dyn Signer) -> Result<Vec<u8>, KeyringError> {
+        let payload = serde_json::to_vec(self).unwrap();
+        let signed = SignedPresence { signature: Signature::create(signer, &payload), payload };
+        let sealed = keyring.seal(book, &serde_json::to_vec(&signed).unwrap())?;
+        Ok(serde_json::to_vec(&sealed).unwrap())
+    }
+
+    /// Open a sealed update and check its signature; returns the book it belongs to and the
+    /// presence with `device` set to the signing key. None for books we don't hold, bad
+    /// signatures and anything malformed.
+    pub fn open(data: &[u8], keyring: &BookKeyring, suite: &CryptoSuite) -> Option<(String, Self)> {
+        let sealed: SealedPayload = serde_json::from_slice(data).ok()?;
+        let signed: SignedPresence = serde_json::from_slice(&keyring.open(&sealed).ok()?).ok()?;
+        if !suite.verify(&signed.payload, &signed.signature) {
+            return None;
+        }
+        let mut presence: Self = serde_json::from_slice(&signed.payload).ok()?;
+        presence.device = device_id(&signed.signature.public_key);
+        Some((sealed.book, presence))
+    }
+}
+
+/// Presence bytes with the sending device's signature over them
+#[derive(Debug, Serialize, Deserialize)]
+struct SignedPresence {
+    payload: Vec<u8>,
+    signature: Signature,
+}
+
+/// Device id shown on the board: the hex of its signing key
+pub fn device_id(public_key: &[u8]) -> String {
+    public_key.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Latest presence of every other device, by device id
+#[derive(Debug, Clone)]
+pub struct PresenceBoard {
+    entries: HashMap<String, (Presence, DateTime<Utc>)>,
+    ttl: Duration,
+}
+
+impl Default for PresenceBoard {
+    fn default() -> Self {
+        Self { entries: HashMap::new(), ttl: Duration::seconds(PRESENCE_TTL_SECS) }
+    }
+}
+
+impl PresenceBoard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self { ttl, ..Self::default() }
+    }
+
+    /// Take a received update; older ones than what we have are ignored. Returns whether it changed the board.
+    pub fn update(&mut self, presence: Presence, now: DateTime<Utc>) -> bool {
+        if self.entries.get(&presence.device).is_some_and(|(p, _)| p.sent_at >= presence.sent_at) {
+            return false;
+        }
+        self.entries.insert(presence.device.clone(), (presence, now));
+        true
+    }
+
+    /// Forget a device, e.g. when its peer disconnects
+    pub fn remove(&mut self, device: &str) -> bool {
+        self.entries.remove(device).is_some()
+    }
+
+    /// Devices heard from within the TTL
+    pub fn active(&self, now: DateTime<Utc>) -> impl Iterator<Item = &Presence> {
+        let ttl = self.ttl;
+        self.entries.values()
+            .filter(move |(_, received)| now - *received < ttl)
+            .map(|(p, _)| p)
+    }
+
+    /// Devices currently viewing or editing a transaction
+    pub fn on_transaction(&self, transaction_id: Uuid, now: DateTime<Utc>) -> Vec<&Presence> {
+        self.active(now).filter(|p| p.transaction_id == Some(transaction_id)).collect()
+    }
+
+    /// Devices editing a transaction, for "Anna is editing this entry"
+    pub fn editors(&self, transaction_id: Uuid, now: DateTime<Utc>) -> Vec<&Presence> {
+        self.on_transaction(transaction_id, now)
+            .into_iter()
+            .filter(|p| p.activity == PresenceActivity::Editing)
+            .collect()
+    }
+
+    /// Drop entries past the TTL; returns how many were dropped
+    pub fn prune(&mut self, now: DateTime<Utc>) -> usize {
+        let before = self.entries.len();
+        let ttl = self.ttl;
+        self.entries.retain(|_, (_, received)| now - *received < ttl);
+        before - self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use crate::crypto::Ed25519Signer;
+
+    #[test]
+    fn device_comes_from_the_signature() {
+        let mut keyring = BookKeyring::default();
+        keyring.create_book("family", BTreeSet::new());
+        let signer = Ed25519Signer::generate();
+        let now = Utc::now();
+        let claimed = Presence::new("someone-else", "Anna", now).editing(Uuid::new_v4());
+
+        let data = claimed.seal("family", &keyring, &signer).unwrap();
+        let (book, presence) = Presence::open(&data, &keyring, &CryptoSuite::default()).unwrap();
+        assert_eq!(book, "family");
+        assert_eq!(presence.device, device_id(&signer.public_key()));
+        assert_eq!(presence.display_name, "Anna");
+    }
+
+    #[test]
+    fn other_books_and_tampering_are_rejected() {
+        let mut keyring = BookKeyring::default();
+        keyring.create_book("family", BTreeSet::new());
+        let signer = Ed25519Signer::generate();
+        let data = Presence::new("laptop", "Anna", Utc::now()).seal("family", &keyring, &signer).unwrap();
+
+        let mut outsider = BookKeyring::default();
+        outsider.create_book("family", BTreeSet::new());
+        assert!(Presence::open(&data, &outsider, &CryptoSuite::default()).is_none());
+
+        let forged = SignedPresence {
+            payload: serde_json::to_vec(&Presence::new("laptop", "Mallory", Utc::now())).unwrap(),
+            signature: Signature::create(&signer, b"something else"),
+        };
+        let sealed = keyring.seal("family", &serde_json::to_vec(&forged).unwrap()).unwrap();
+        let forged = serde_json::to_vec(&sealed).unwrap();
+        assert!(Presence::open(&forged, &keyring, &CryptoSuite::default()).is_none());
+    }
+}
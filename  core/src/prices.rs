@@ -0,0 +1,97 @@
+//! Dated exchange rates shared between devices
+use std::collections::{BTreeMap, HashMap};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::currency::Commodity;
+
+/// 1 `from` = `rate` `to` on `date`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceQuote {
+    pub id: Uuid,
+    pub from: Commodity,
+    pub to: Commodity,
+    pub date: NaiveDate,
+    #[serde(with = "crate::canonical::serde_decimal")]
+    pub rate: Decimal,
+    /// Where the rate came from (bank, ECB feed, manual entry)
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+impl PriceQuote {
+    pub fn new(from: Commodity, to: Commodity, date: NaiveDate, rate: Decimal) -> Self {
+        Self { id: Uuid::new_v4(), from, to, date, rate, source: None }
+    }
+}
+
+/// Which rate converts an amount into the presentation currency
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RateBasis {
+    /// Closing rate at the end of the report period
+    #[default]
+    PeriodEnd,
+    /// Historical rate on each transaction's date
+    TransactionDate,
+}
+
+/// Rate lookup by currency pair and date
+#[derive(Debug, Clone, Default)]
+pub struct PriceDb {
+    /// (from, to) -> date -> (quote id, rate)
+    rates: HashMap<(Commodity, Commodity), BTreeMap<NaiveDate, (Uuid, Decimal)>>,
+}
+
+impl PriceDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_quotes<'a>(quotes: impl IntoIterator<Item = &'a PriceQuote>) -> Self {
+        let mut db = Self::new();
+        for quote in quotes {
+            db.insert(quote);
+        }
+        db
+    }
+
+    /// Add a quote; when two devices entered the same pair and date, the higher id wins on every device
+    pub fn insert(&mut self, quote: &PriceQuote) {
+        if quote.rate <= Decimal::ZERO {
+            return;
+        }
+        let by_date = self.rates.entry((quote.from.clone(), quote.to.clone())).or_default();
+        match by_date.get(&quote.date) {
+            Some((id, _)) if *id > quote.id => {}
+            _ => {
+                by_date.insert(quote.date, (quote.id, quote.rate));
+            }
+        }
+    }
+
+    /// Most recent rate on or before `date`, falling back to the inverse of the reverse pair
+    pub fn rate(&self, from: &Commodity, to: &Commodity, date: NaiveDate) -> Option<Decimal> {
+        if from == to {
+            return Some(Decimal::ONE);
+        }
+        let latest = |a: &Commodity, b: &Commodity| {
+            self.rates.get(&(a.clone(), b.clone()))?
+                .range(..=date)
+                .next_back()
+                .map(|(d, (_, r))| (*d, *r))
+        };
+        match (latest(from, to), latest(to, from)) {
+            // Prefer whichever direction was quoted more recently
+            (Some((d, _)), Some((r, inverse))) if r > d => Some(Decimal::ONE / inverse),
+            (Some((_, direct)), _) => Some(direct),
+            (None, Some((_, inverse))) => Some(Decimal::ONE / inverse),
+            (None, None) => None,
+        }
+    }
+
+    pub fn convert(&self, amount: Decimal, from: &Commodity, to: &Commodity, date: NaiveDate) -> Option<Decimal> {
+        Some(amount * self.rate(from, to, date)?)
+    }
+}
@@ -0,0 +1,35 @@
+//! Project/job registry used as a costing dimension on postings
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+/// Client engagement or job that postings can be tagged with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub id: Uuid,
+    pub name: String,
+    /// Client the work is done for
+    #[serde(default)]
+    pub contact_id: Option<Uuid>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+impl Project {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            contact_id: None,
+            active: true,
+        }
+    }
+
+    pub fn for_contact(mut self, contact_id: Uuid) -> Self {
+        self.contact_id = Some(contact_id);
+        self
+    }
+}
@@ -0,0 +1,289 @@
+//! Sync wire protocol: envelopes and date-range chunked transfer
+use std::collections::BTreeSet;
+use chrono::{Duration, NaiveDate};
+use rust_decimal::Decimal;
+use serde::{Serialize, Deserialize};
+
+use crate::codec::{self, Encoding};
+use crate::crypto::{CryptoSuite, Signature, Signer};
+use crate::ledger::{Account, Transaction};
+use crate::sync::{SyncError, SyncableLedger};
+
+/// Days of history sent first to a newly joined device
+pub const RECENT_DAYS: i64 = 90;
+/// Width of each backfill chunk
+pub const BACKFILL_DAYS: i64 = 180;
+
+/// Inclusive date range; `from: None` means since the beginning
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DateRange {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl DateRange {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.from.is_none_or(|f| date >= f) && self.to.is_none_or(|t| date <= t)
+    }
+}
+
+/// Slice of ledger history covering one date range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncChunk {
+    pub seq: u32,
+    pub total: u32,
+    pub range: DateRange,
+    pub accounts: Vec<Account>,
+    pub transactions: Vec<Transaction>,
+}
+
+/// Schema version written by this build
+pub const SCHEMA_VERSION: u32 = 1;
+/// Oldest schema version this build can still read
+pub const MIN_SCHEMA_VERSION: u32 = 1;
+
+/// Features a peer supports, exchanged in the handshake
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub schema_version: u32,
+    pub min_schema_version: u32,
+    /// Codecs in preference order
+    pub compression: Vec<String>,
+    pub incremental_sync: bool,
+    /// Encryption suites in preference order
+    pub encryption_suites: Vec<String>,
+    /// Envelope encodings in preference order; older peers only speak JSON
+    #[serde(default = "default_encodings")]
+    pub encodings: Vec<String>,
+}
+
+fn default_encodings() -> Vec<String> {
+    vec![Encoding::Json.name().to_string()]
+}
+
+impl Capabilities {
+    /// Capabilities of this build
+    pub fn local() -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            min_schema_version: MIN_SCHEMA_VERSION,
+            compression: vec!["none".to_string()],
+            incremental_sync: false,
+            encryption_suites: vec!["noise".to_string()],
+            encodings: Encoding::available().iter().map(|e| e.name().to_string()).collect(),
+        }
+    }
+}
+
+/// Agreed feature set for talking to one peer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    pub schema_version: u32,
+    pub compression: String,
+    pub incremental_sync: bool,
+    pub encryption_suite: Option<String>,
+    pub encoding: Encoding,
+}
+
+/// Pick the best common feature set, falling back to the most basic options
+pub fn negotiate(local: &Capabilities, remote: &Capabilities) -> Result<Session, SyncError> {
+    let schema_version = local.schema_version.min(remote.schema_version);
+    if schema_version < local.min_schema_version || schema_version < remote.min_schema_version {
+        return Err(SyncError::IncompatiblePeer(format!(
+            "schema {}..={} vs {}..={}",
+            local.min_schema_version, local.schema_version,
+            remote.min_schema_version, remote.schema_version,
+        )));
+    }
+    let compression = local.compression.iter()
+        .find(|c| remote.compression.contains(c))
+        .cloned()
+        .unwrap_or_else(|| "none".to_string());
+    let encryption_suite = local.encryption_suites.iter()
+        .find(|s| remote.encryption_suites.contains(s))
+        .cloned();
+    let encoding = local.encodings.iter()
+        .find(|e| remote.encodings.contains(e))
+        .and_then(|e| Encoding::from_name(e))
+        .unwrap_or(Encoding::Json);
+    Ok(Session {
+        schema_version,
+        compression,
+        incremental_sync: local.incremental_sync && remote.incremental_sync,
+        encryption_suite,
+        encoding,
+    })
+}
+
+/// Message exchanged between peers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "body")]
+pub enum Envelope {
+    Hello(Capabilities),
+    FullDoc(Vec<u8>),
+    Chunk(SyncChunk),
+    ChunkRequest { seqs: Vec<u32> },
+    /// Anti-entropy probe: `target` replies with its full document if its state hash differs
+    StateHash { target: String, hash: String },
+    /// Full document encrypted with a book key; peers without the key ignore it
+    Sealed(crate::keyring::SealedPayload),
+    /// Encoded inner envelope with the sender's signature over those bytes
+    Signed { payload: Vec<u8>, signature: Signature },
+    /// Destructive operation waiting for a quorum of trusted devices
+    QuorumProposal(crate::quorum::Proposal),
+    /// A trusted device's vote on a proposal
+    QuorumVote(crate::quorum::Vote),
+    /// Envelope type from a newer peer; ignored instead of failing
+    #[serde(other)]
+    Unknown,
+}
+
+impl Envelope {
+    /// JSON encoding, readable by every peer (used for `Hello`)
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SyncError> {
+        self.to_bytes_as(Encoding::Json)
+    }
+
+    pub fn to_bytes_as(&self, encoding: Encoding) -> Result<Vec<u8>, SyncError> {
+        Ok(encoding.encode(self)?)
+    }
+
+    /// Decode an envelope in whichever encoding the sender used
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SyncError> {
+        Ok(codec::decode(data)?)
+    }
+
+    /// Wrap this envelope in a `Signed` one
+    pub fn sign(&self, signer: &dyn Signer, encoding: Encoding) -> Result<Envelope, SyncError> {
+        let payload = self.to_bytes_as(encoding)?;
+        let signature = Signature::create(signer, &payload);
+        Ok(Envelope::Signed { payload, signature })
+    }
+
+    /// Verify a `Signed` envelope and return the inner one with the signer's public key;
+    /// other envelopes pass through unsigned
+    pub fn verify(self, suite: &CryptoSuite) -> Result<(Envelope, Option<Vec<u8>>), SyncError> {
+        match self {
+            Envelope::Signed { payload, signature } => {
+                if !suite.verify(&payload, &signature) {
+                    return Err(SyncError::BadSignature);
+                }
+                Ok((Envelope::from_bytes(&payload)?, Some(signature.public_key)))
+            }
+            other => Ok((other, None)),
+        }
+    }
+}
+
+/// Split a ledger into chunks: the most recent `recent_days` first (with all accounts),
+/// then older history backwards in `backfill_days` windows. Both are at least one day.
+pub fn plan_chunks(ledger: &SyncableLedger, today: NaiveDate, recent_days: i64, backfill_days: i64) -> Vec<SyncChunk> {
+    let (recent_days, backfill_days) = (recent_days.max(1), backfill_days.max(1));
+    let recent_start = today - Duration::days(recent_days);
+    let mut ranges = vec![DateRange { from: Some(recent_start), to: None }];
+
+    if let Some(earliest) = ledger.transactions.iter().map(|t| t.date).min() {
+        let mut end = recent_start - Duration::days(1);
+        while end >= earliest {
+            let start = end - Duration::days(backfill_days - 1);
+            let from = if start <= earliest { None } else { Some(start) };
+            ranges.push(DateRange { from, to: Some(end) });
+            end = start - Duration::days(1);
+        }
+    }
+
+    let total = ranges.len() as u32;
+    ranges
+        .into_iter()
+        .enumerate()
+        .map(|(seq, range)| SyncChunk {
+            seq: seq as u32,
+            total,
+            accounts: if seq == 0 { ledger.accounts.values().cloned().collect() } else { Vec::new() },
+            transactions: ledger.transactions.iter()
+                .filter(|t| range.contains(t.date))
+                .cloned()
+                .collect(),
+            range,
+        })
+        .collect()
+}
+
+/// Rebuilds a ledger from chunks arriving in any order
+#[derive(Debug, Clone, Default)]
+pub struct ChunkAssembler {
+    ledger: SyncableLedger,
+    received: BTreeSet<u32>,
+    total: Option<u32>,
+}
+
+impl ChunkAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a chunk; repeated chunks and already-known transactions are ignored
+    pub fn apply(&mut self, chunk: SyncChunk) {
+        if !self.received.insert(chunk.seq) {
+            return;
+        }
+        self.total = Some(chunk.total);
+        for account in chunk.accounts {
+            self.ledger.balances.entry(account.id).or_insert(Decimal::ZERO);
+            self.ledger.accounts.insert(account.id, account);
+        }
+        for tx in chunk.transactions {
+            if !self.ledger.transactions.iter().any(|t| t.id == tx.id) {
+                self.ledger.push_transaction(tx);
+            }
+        }
+    }
+
+    /// Recent chunk arrived, the ledger can be shown to the user
+    pub fn is_usable(&self) -> bool {
+        self.received.contains(&0)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.total == Some(self.received.len() as u32)
+    }
+
+    /// Chunks still outstanding, to re-request from a peer
+    pub fn missing(&self) -> Vec<u32> {
+        match self.total {
+            Some(total) => (0..total).filter(|s| !self.received.contains(s)).collect(),
+            None => vec![0],
+        }
+    }
+
+    pub fn ledger(&self) -> &SyncableLedger {
+        &self.ledger
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::{AccountType, Posting};
+
+    #[test]
+    fn chunk_windows_are_at_least_a_day() {
+        let mut ledger = SyncableLedger::new();
+        let cash = Account::new("Cash", AccountType::Asset);
+        let sales = Account::new("Sales", AccountType::Revenue);
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        for days in 0..5 {
+            let amount = Decimal::from(days + 1);
+            ledger.transactions.push(Transaction::new(today - Duration::days(days), "Sale", vec![
+                Posting::new(cash.id, amount),
+                Posting::new(sales.id, -amount),
+            ]));
+        }
+
+        for (recent, backfill) in [(0, 0), (-3, -1), (1, 1)] {
+            let chunks = plan_chunks(&ledger, today, recent, backfill);
+            let sent: usize = chunks.iter().map(|c| c.transactions.len()).sum();
+            assert_eq!(sent, ledger.transactions.len(), "recent {} backfill {}", recent, backfill);
+        }
+    }
+}
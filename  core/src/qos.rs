@@ -0,0 +1,164 @@
+//! Outgoing sync message classes with per-class priority and rate limits
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use serde::{Serialize, Deserialize};
+
+/// Service class of an outgoing message, highest priority first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Priority {
+    /// New local edits and handshakes
+    Urgent,
+    /// History backfill for newly joined devices
+    Bulk,
+    /// Anti-entropy probes and replies
+    Background,
+}
+
+impl Priority {
+    const ALL: [Priority; 3] = [Priority::Urgent, Priority::Bulk, Priority::Background];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Token bucket: `per_second` messages sustained, bursts up to `burst`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: f64,
+}
+
+impl RateLimit {
+    pub const UNLIMITED: RateLimit = RateLimit { per_second: f64::INFINITY, burst: f64::INFINITY };
+}
+
+/// Rate limit per class
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QosConfig {
+    pub urgent: RateLimit,
+    pub bulk: RateLimit,
+    pub background: RateLimit,
+}
+
+impl Default for QosConfig {
+    fn default() -> Self {
+        Self {
+            urgent: RateLimit::UNLIMITED,
+            bulk: RateLimit { per_second: 4.0, burst: 8.0 },
+            background: RateLimit { per_second: 0.2, burst: 1.0 },
+        }
+    }
+}
+
+impl QosConfig {
+    fn limit(&self, priority: Priority) -> RateLimit {
+        match priority {
+            Priority::Urgent => self.urgent,
+            Priority::Bulk => self.bulk,
+            Priority::Background => self.background,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_second).min(limit.burst);
+        self.refilled_at = now;
+    }
+}
+
+/// Priority queue of outgoing payloads; lower classes only go out when higher ones are empty
+#[derive(Debug, Clone)]
+pub struct OutboundQueue {
+    config: QosConfig,
+    queues: [VecDeque<Vec<u8>>; 3],
+    buckets: [Bucket; 3],
+}
+
+impl OutboundQueue {
+    pub fn new(config: QosConfig) -> Self {
+        let now = Instant::now();
+        let bucket = |p: Priority| Bucket { tokens: config.limit(p).burst, refilled_at: now };
+        Self {
+            config,
+            queues: Default::default(),
+            buckets: Priority::ALL.map(bucket),
+        }
+    }
+
+    pub fn config(&self) -> &QosConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: QosConfig) {
+        self.config = config;
+    }
+
+    pub fn push(&mut self, priority: Priority, data: Vec<u8>) {
+        self.queues[priority.index()].push_back(data);
+    }
+
+    /// Put a message that failed to send back at the head of its class
+    pub fn requeue(&mut self, priority: Priority, data: Vec<u8>) {
+        self.queues[priority.index()].push_front(data);
+    }
+
+    /// Queued message count per class
+    pub fn pending(&self, priority: Priority) -> usize {
+        self.queues[priority.index()].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queues.iter().all(|q| q.is_empty())
+    }
+
+    /// Next message allowed to go out now, highest class first
+    pub fn pop_ready(&mut self, now: Instant) -> Option<(Priority, Vec<u8>)> {
+        for priority in Priority::ALL {
+            let i = priority.index();
+            if self.queues[i].is_empty() {
+                continue;
+            }
+            let limit = self.config.limit(priority);
+            self.buckets[i].refill(limit, now);
+            if self.buckets[i].tokens >= 1.0 {
+                self.buckets[i].tokens -= 1.0;
+                return self.queues[i].pop_front().map(|data| (priority, data));
+            }
+            // A waiting higher class blocks lower ones so backfill never delays edits
+            if priority == Priority::Urgent {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// Time until the next queued message may be sent, if any are waiting
+    pub fn next_ready_in(&self, now: Instant) -> Option<Duration> {
+        Priority::ALL.iter()
+            .filter(|p| !self.queues[p.index()].is_empty())
+            .map(|p| {
+                let limit = self.config.limit(*p);
+                let mut bucket = self.buckets[p.index()].clone();
+                bucket.refill(limit, now);
+                let missing = (1.0 - bucket.tokens).max(0.0);
+                Duration::from_secs_f64(missing / limit.per_second)
+            })
+            .min()
+    }
+}
+
+impl Default for OutboundQueue {
+    fn default() -> Self {
+        Self::new(QosConfig::default())
+    }
+}
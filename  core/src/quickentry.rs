@@ -0,0 +1,272 @@
+//! One-line quick entry ("12.50 coffee @Starbucks from Checking #work") for fast mobile capture
+use chrono::{Duration, NaiveDate};
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::amount::parse_amount_input;
+use crate::currency::Commodity;
+use crate::ledger::{Account, AccountType, Posting};
+use crate::locale::Locale;
+use crate::staging::{StagedTransaction, StagingSource};
+use crate::sync::SyncableLedger;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum QuickEntryError {
+    #[error("No amount in quick entry")]
+    MissingAmount,
+    #[error("Unknown account: {0}")]
+    UnknownAccount(String),
+}
+
+/// Counter account to use when the text mentions a keyword (e.g. "coffee" -> Expenses:Dining)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuickEntryRule {
+    pub keyword: String,
+    pub account_id: Uuid,
+}
+
+impl QuickEntryRule {
+    fn matches(&self, words: &[String], payee: Option<&str>) -> bool {
+        let keyword = self.keyword.trim();
+        words.iter().any(|w| w.eq_ignore_ascii_case(keyword))
+            || payee.is_some_and(|p| p.eq_ignore_ascii_case(keyword))
+    }
+}
+
+/// Parser settings: keyword rules and the account paid from when "from" is omitted
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QuickEntry {
+    pub rules: Vec<QuickEntryRule>,
+    pub default_account: Option<Uuid>,
+}
+
+const MARKERS: &[&str] = &["from", "to", "on"];
+
+impl QuickEntry {
+    /// Turn a quick-entry line into a staged transaction for review.
+    ///
+    /// - the first token that parses as an amount (`12.50`, `€12`, `3*4.99`) is the spend
+    /// - `@Payee` names the payee (`_` for spaces: `@Blue_Bottle`)
+    /// - `from <account>` / `to <account>` pick the paying and counter accounts
+    /// - `#tag` tags the counter posting with a matching project or class
+    /// - `today`, `yesterday` or `on <date>` set the date (default `today`)
+    /// - remaining words become the memo
+    ///
+    /// Without `to`, the counter account comes from keyword rules, then from the latest
+    /// transaction with the same payee.
+    pub fn parse(
+        &self,
+        input: &str,
+        ledger: &SyncableLedger,
+        locale: &Locale,
+        today: NaiveDate,
+    ) -> Result<StagedTransaction, QuickEntryError> {
+        let tokens: Vec<&str> = input.split_whitespace().collect();
+        let mut amount = None;
+        let mut commodity: Option<Commodity> = None;
+        let mut payee: Option<String> = None;
+        let mut from: Option<&Account> = None;
+        let mut to: Option<&Account> = None;
+        let mut date = today;
+        let mut tags = Vec::new();
+        let mut memo: Vec<String> = Vec::new();
+
+        let mut i = 0;
+        while i < tokens.len() {
+            let token = tokens[i];
+            let lower = token.to_lowercase();
+            if let Some(name) = token.strip_prefix('@').filter(|n| !n.is_empty()) {
+                payee = Some(name.replace('_', " "));
+            } else if let Some(tag) = token.strip_prefix('#').filter(|t| !t.is_empty()) {
+                tags.push(tag.to_string());
+            } else if (lower == "from" || lower == "to") && i + 1 < tokens.len() {
+                let end = phrase_end(&tokens, i + 1);
+                let name = tokens[i + 1..end].join(" ");
+                let account = find_account(ledger, &name).ok_or(QuickEntryError::UnknownAccount(name))?;
+                if lower == "from" {
+                    from = Some(account);
+                } else {
+                    to = Some(account);
+                }
+                i = end;
+                continue;
+            } else if lower == "on" && i + 1 < tokens.len() && locale.parse_date(tokens[i + 1]).is_ok() {
+                date = locale.parse_date(tokens[i + 1]).unwrap();
+                i += 2;
+                continue;
+            } else if lower == "today" {
+                date = today;
+            } else if lower == "yesterday" {
+                date = today - Duration::days(1);
+            } else if amount.is_none() && token.chars().any(|c| c.is_ascii_digit()) {
+                match parse_amount_input(token, locale) {
+                    Ok(parsed) => {
+                        amount = Some(parsed.amount);
+                        commodity = parsed.commodity;
+                    }
+                    Err(_) => memo.push(token.to_string()),
+                }
+            } else {
+                memo.push(token.to_string());
+            }
+            i += 1;
+        }
+
+        let amount = amount.ok_or(QuickEntryError::MissingAmount)?;
+        let from = from.map(|a| a.id).or(self.default_account);
+        let to = to.map(|a| a.id)
+            .or_else(|| self.rules.iter().find(|r| r.matches(&memo, payee.as_deref())).map(|r| r.account_id))
+            .or_else(|| payee.as_deref().and_then(|p| counter_from_history(ledger, p, from)));
+        let project_id = tags.iter()
+            .find_map(|t| ledger.projects.values().find(|p| p.active && p.name.eq_ignore_ascii_case(t)))
+            .map(|p| p.id);
+        let class_id = tags.iter()
+            .find_map(|t| ledger.classes.values().find(|c| c.name.eq_ignore_ascii_case(t)))
+            .map(|c| c.id);
+
+        let mut entry = StagedTransaction::new(StagingSource::Manual);
+        entry.date = Some(date);
+        entry.amount = Some(amount);
+        entry.description = match (&payee, memo.is_empty()) {
+            (Some(p), true) => p.clone(),
+            (Some(p), false) => format!("{}: {}", p, memo.join(" ")),
+            (None, _) => memo.join(" "),
+        };
+        entry.payee = payee;
+        let commodity = commodity.unwrap_or_default();
+        if let Some(to) = to {
+            let mut counter = Posting::in_commodity(to, amount, commodity.clone());
+            counter.project_id = project_id;
+            counter.class_id = class_id;
+            entry.postings.push(counter);
+        }
+        if let Some(from) = from {
+            entry.postings.push(Posting::in_commodity(from, -amount, commodity));
+        }
+        Ok(entry)
+    }
+}
+
+/// Index just past an account name that starts at `start`
+fn phrase_end(tokens: &[&str], start: usize) -> usize {
+    let mut end = start + 1;
+    while end < tokens.len()
+        && !MARKERS.contains(&tokens[end].to_lowercase().as_str())
+        && !tokens[end].starts_with(['@', '#'])
+        && !tokens[end].chars().any(|c| c.is_ascii_digit())
+    {
+        end += 1;
+    }
+    end
+}
+
+/// Active account by full name or last segment ("Checking" for "Assets:Checking"); the shortest name wins
+fn find_account<'a>(ledger: &'a SyncableLedger, name: &str) -> Option<&'a Account> {
+    let name = name.trim();
+    let matches = |a: &&Account| {
+        a.active
+            && (a.name.eq_ignore_ascii_case(name)
+                || a.name.rsplit(':').next().is_some_and(|leaf| leaf.trim().eq_ignore_ascii_case(name)))
+    };
+    ledger.accounts.values().filter(matches).min_by_key(|a| (a.name.len(), a.id))
+}
+
+/// Counter account of the latest transaction for the same payee
+fn counter_from_history(ledger: &SyncableLedger, payee: &str, from: Option<Uuid>) -> Option<Uuid> {
+    let payee = payee.to_lowercase();
+    ledger.transactions.iter()
+        .filter(|tx| match &tx.payee {
+            Some(p) => p.to_lowercase() == payee,
+            None => tx.description.to_lowercase().starts_with(&payee),
+        })
+        .max_by_key(|tx| tx.date)?
+        .postings.iter()
+        .filter(|p| Some(p.account_id) != from)
+        .filter(|p| {
+            ledger.accounts.get(&p.account_id)
+                .is_some_and(|a| matches!(a.r#type, AccountType::Expense | AccountType::Revenue))
+        })
+        .map(|p| p.account_id)
+        .next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use crate::ledger::Transaction;
+    use crate::projects::Project;
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, d).unwrap()
+    }
+
+    fn add(ledger: &mut SyncableLedger, name: &str, r#type: AccountType) -> Uuid {
+        let account = Account::new(name, r#type);
+        let id = account.id;
+        ledger.accounts.insert(id, account);
+        id
+    }
+
+    #[test]
+    fn every_marker_lands_in_its_field() {
+        let mut ledger = SyncableLedger::default();
+        let checking = add(&mut ledger, "Assets:Checking", AccountType::Asset);
+        let dining = add(&mut ledger, "Expenses:Dining", AccountType::Expense);
+        let work = Project::new("Work");
+        let work_id = work.id;
+        ledger.projects.insert(work_id, work);
+
+        let entry = QuickEntry::default()
+            .parse("12.50 flat white @Blue_Bottle from checking to Dining #work yesterday", &ledger, &Locale::en_us(), date(10))
+            .unwrap();
+        assert_eq!(entry.amount, Some(Decimal::new(1250, 2)));
+        assert_eq!(entry.date, Some(date(9)));
+        assert_eq!(entry.payee.as_deref(), Some("Blue Bottle"));
+        assert_eq!(entry.description, "Blue Bottle: flat white");
+        assert_eq!(entry.postings.len(), 2);
+        assert_eq!((entry.postings[0].account_id, entry.postings[0].amount), (dining, Decimal::new(1250, 2)));
+        assert_eq!(entry.postings[0].project_id, Some(work_id));
+        assert_eq!((entry.postings[1].account_id, entry.postings[1].amount), (checking, Decimal::new(-1250, 2)));
+    }
+
+    #[test]
+    fn counter_account_falls_back_to_rules_then_history() {
+        let mut ledger = SyncableLedger::default();
+        let card = add(&mut ledger, "Liabilities:Credit Card", AccountType::Liability);
+        let dining = add(&mut ledger, "Expenses:Dining", AccountType::Expense);
+        let groceries = add(&mut ledger, "Expenses:Groceries", AccountType::Expense);
+        ledger.transactions.push(Transaction::new(date(1), "Weekly shop", vec![
+            Posting::new(groceries, Decimal::from(40)),
+            Posting::new(card, Decimal::from(-40)),
+        ]).with_payee("Corner Shop"));
+        let quick = QuickEntry {
+            rules: vec![QuickEntryRule { keyword: "coffee".to_string(), account_id: dining }],
+            default_account: None,
+        };
+
+        let coffee = quick.parse("coffee 3.20 from credit card", &ledger, &Locale::en_us(), date(10)).unwrap();
+        assert_eq!(coffee.postings[0].account_id, dining);
+        assert_eq!(coffee.postings[1].account_id, card);
+
+        let shop = quick.parse("€8 @Corner_Shop from Credit Card on 2024-05-03", &ledger, &Locale::en_us(), date(10)).unwrap();
+        assert_eq!(shop.date, Some(date(3)));
+        assert_eq!(shop.postings[0].account_id, groceries);
+        assert_eq!(shop.postings[0].commodity, Commodity::new("EUR"));
+    }
+
+    #[test]
+    fn amount_and_accounts_must_be_known() {
+        let ledger = SyncableLedger::default();
+        let quick = QuickEntry::default();
+        assert_eq!(quick.parse("coffee @Cafe", &ledger, &Locale::en_us(), date(10)).unwrap_err(), QuickEntryError::MissingAmount);
+        assert_eq!(
+            quick.parse("5 from Petty Cash", &ledger, &Locale::en_us(), date(10)).unwrap_err(),
+            QuickEntryError::UnknownAccount("Petty Cash".to_string()),
+        );
+        // No accounts named: staged with the amount only, for review
+        let bare = quick.parse("5 snacks", &ledger, &Locale::en_us(), date(10)).unwrap();
+        assert_eq!(bare.amount, Some(Decimal::from(5)));
+        assert!(bare.postings.is_empty());
+    }
+}
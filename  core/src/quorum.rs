@@ -0,0 +1,311 @@
+//! Quorum of trusted devices for destructive operations: purging history, deleting a book and
+//! rotating its key. The initiating device broadcasts a proposal, trusted devices answer with
+//! signed votes over the sync channel, and the operation runs only once the approving devices'
+//! weights reach the threshold.
+use std::collections::{BTreeMap, BTreeSet};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::crypto::{CryptoSuite, Signature, Signer};
+use crate::keyring::{BookKeyring, KeyringError};
+use crate::storage::LocalStorage;
+use crate::workspace::Workspace;
+
+const SETTINGS_KEY: &str = "quorum_policy";
+
+#[derive(Debug, Error)]
+pub enum QuorumError {
+    #[error("Unknown proposal {0}")]
+    UnknownProposal(Uuid),
+    #[error("Device is not trusted to vote")]
+    NotTrusted,
+    #[error("Vote signature is invalid")]
+    BadSignature,
+    #[error("Proposal expired")]
+    Expired,
+    #[error("Quorum not reached: {weight} of {threshold}")]
+    NotApproved { weight: u32, threshold: u32 },
+    #[error("Proposal was declined")]
+    Declined,
+    #[error("Approval is for a different operation")]
+    WrongOperation,
+    #[error(transparent)]
+    Keyring(#[from] KeyringError),
+}
+
+/// Operation that cannot be undone once it took effect
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DestructiveOp {
+    /// Drop local snapshots taken before the date
+    PurgeHistory { before: NaiveDate },
+    DeleteBook { book: String },
+    RotateKey { book: String },
+}
+
+/// Trusted devices by public key (hex) with their vote weights, and the weight needed to approve.
+/// The threshold is at least 1, so a policy without trusted devices approves nothing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuorumPolicy {
+    pub devices: BTreeMap<String, u32>,
+    pub threshold: u32,
+}
+
+impl Default for QuorumPolicy {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl QuorumPolicy {
+    pub fn new(threshold: u32) -> Self {
+        Self { devices: BTreeMap::new(), threshold: threshold.max(1) }
+    }
+
+    /// Weight needed to approve; a stored threshold of 0 still needs one vote
+    pub fn required_weight(&self) -> u32 {
+        self.threshold.max(1)
+    }
+
+    pub fn with_device(mut self, public_key: &[u8], weight: u32) -> Self {
+        self.devices.insert(to_hex(public_key), weight);
+        self
+    }
+
+    pub fn weight_of(&self, public_key: &[u8]) -> Option<u32> {
+        self.devices.get(&to_hex(public_key)).copied()
+    }
+
+    /// Largest weight the trusted devices can reach together
+    pub fn total_weight(&self) -> u32 {
+        self.devices.values().sum()
+    }
+
+    pub fn load(storage: &LocalStorage) -> Self {
+        storage.get_setting(SETTINGS_KEY)
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &LocalStorage) {
+        storage.set_setting(SETTINGS_KEY, &serde_json::to_string(self).unwrap());
+    }
+}
+
+/// Request to run a destructive operation, broadcast to every trusted device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proposal {
+    pub id: Uuid,
+    pub op: DestructiveOp,
+    /// Device id of the initiator
+    pub proposer: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Proposal {
+    pub fn new(op: DestructiveOp, proposer: impl Into<String>, now: DateTime<Utc>, ttl: Duration) -> Self {
+        Self { id: Uuid::new_v4(), op, proposer: proposer.into(), created_at: now, expires_at: now + ttl }
+    }
+
+    fn signing_bytes(&self, approve: bool) -> Vec<u8> {
+        serde_json::to_vec(&(self, approve)).unwrap()
+    }
+}
+
+/// A trusted device's answer to a proposal; the signature covers the whole proposal
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vote {
+    pub proposal_id: Uuid,
+    pub approve: bool,
+    pub signature: Signature,
+}
+
+impl Vote {
+    pub fn cast(proposal: &Proposal, approve: bool, signer: &dyn Signer) -> Self {
+        let signature = Signature::create(signer, &proposal.signing_bytes(approve));
+        Self { proposal_id: proposal.id, approve, signature }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuorumStatus {
+    Pending { weight: u32, threshold: u32 },
+    Approved,
+    /// Too many devices declined for the rest to reach the threshold
+    Rejected,
+}
+
+/// Proof that a quorum approved an operation; consumed by running it
+#[derive(Debug)]
+pub struct Approval {
+    proposal: Proposal,
+}
+
+impl Approval {
+    pub fn proposal(&self) -> &Proposal {
+        &self.proposal
+    }
+
+    /// Delete local snapshots taken before the approved date; returns how many were deleted
+    pub fn purge_history(self, storage: &LocalStorage) -> Result<usize, QuorumError> {
+        let DestructiveOp::PurgeHistory { before } = &self.proposal.op else {
+            return Err(QuorumError::WrongOperation);
+        };
+        let old: Vec<String> = storage.snapshots().into_iter()
+            .filter(|s| s.taken_at.date_naive() < *before)
+            .map(|s| s.id)
+            .collect();
+        Ok(old.iter().filter(|id| storage.delete_snapshot(id)).count())
+    }
+
+    /// Remove the book from the workspace and delete its stored document
+    pub fn delete_book(self, workspace: &mut Workspace, storage: &LocalStorage) -> Result<bool, QuorumError> {
+        let DestructiveOp::DeleteBook { book } = &self.proposal.op else {
+            return Err(QuorumError::WrongOperation);
+        };
+        Ok(workspace.forget(book, storage))
+    }
+
+    /// Rotate the book's key for `members`; returns the new generation
+    pub fn rotate_key(self, keyring: &mut BookKeyring, members: BTreeSet<String>) -> Result<u32, QuorumError> {
+        let DestructiveOp::RotateKey { book } = &self.proposal.op else {
+            return Err(QuorumError::WrongOperation);
+        };
+        Ok(keyring.rotate(book, members)?)
+    }
+}
+
+/// Open proposals and the votes received for them
+#[derive(Debug, Default)]
+pub struct QuorumTracker {
+    policy: QuorumPolicy,
+    open: BTreeMap<Uuid, (Proposal, BTreeMap<String, bool>)>,
+}
+
+impl QuorumTracker {
+    pub fn new(policy: QuorumPolicy) -> Self {
+        Self { policy, open: BTreeMap::new() }
+    }
+
+    pub fn policy(&self) -> &QuorumPolicy {
+        &self.policy
+    }
+
+    /// Track a proposal made here or received from a peer; repeats are ignored
+    pub fn open(&mut self, proposal: Proposal) {
+        self.open.entry(proposal.id).or_insert((proposal, BTreeMap::new()));
+    }
+
+    pub fn proposals(&self) -> impl Iterator<Item = &Proposal> {
+        self.open.values().map(|(p, _)| p)
+    }
+
+    /// Count a vote after checking its signature and that the device is trusted. A device's
+    /// later vote replaces its earlier one.
+    pub fn record_vote(&mut self, vote: &Vote, suite: &CryptoSuite, now: DateTime<Utc>) -> Result<QuorumStatus, QuorumError> {
+        let (proposal, votes) = self.open.get_mut(&vote.proposal_id).ok_or(QuorumError::UnknownProposal(vote.proposal_id))?;
+        if now >= proposal.expires_at {
+            return Err(QuorumError::Expired);
+        }
+        if self.policy.weight_of(&vote.signature.public_key).is_none() {
+            return Err(QuorumError::NotTrusted);
+        }
+        if !suite.verify(&proposal.signing_bytes(vote.approve), &vote.signature) {
+            return Err(QuorumError::BadSignature);
+        }
+        votes.insert(to_hex(&vote.signature.public_key), vote.approve);
+        self.status(&vote.proposal_id)
+    }
+
+    pub fn status(&self, id: &Uuid) -> Result<QuorumStatus, QuorumError> {
+        let (_, votes) = self.open.get(id).ok_or(QuorumError::UnknownProposal(*id))?;
+        let weight_where = |approve: bool| -> u32 {
+            votes.iter()
+                .filter(|(_, v)| **v == approve)
+                .filter_map(|(key, _)| self.policy.devices.get(key))
+                .sum()
+        };
+        let (approved, declined) = (weight_where(true), weight_where(false));
+        let threshold = self.policy.required_weight();
+        Ok(if approved >= threshold {
+            QuorumStatus::Approved
+        } else if self.policy.total_weight().saturating_sub(declined) < threshold {
+            QuorumStatus::Rejected
+        } else {
+            QuorumStatus::Pending { weight: approved, threshold }
+        })
+    }
+
+    /// Close an approved proposal and hand out the approval to run it
+    pub fn take_approval(&mut self, id: &Uuid, now: DateTime<Utc>) -> Result<Approval, QuorumError> {
+        let status = self.status(id)?;
+        let (proposal, _) = &self.open[id];
+        if now >= proposal.expires_at {
+            self.open.remove(id);
+            return Err(QuorumError::Expired);
+        }
+        match status {
+            QuorumStatus::Approved => {
+                let (proposal, _) = self.open.remove(id).ok_or(QuorumError::UnknownProposal(*id))?;
+                Ok(Approval { proposal })
+            }
+            QuorumStatus::Pending { weight, threshold } => Err(QuorumError::NotApproved { weight, threshold }),
+            QuorumStatus::Rejected => {
+                self.open.remove(id);
+                Err(QuorumError::Declined)
+            }
+        }
+    }
+
+    /// Drop expired proposals; returns how many were dropped
+    pub fn expire(&mut self, now: DateTime<Utc>) -> usize {
+        let before = self.open.len();
+        self.open.retain(|_, (p, _)| now < p.expires_at);
+        before - self.open.len()
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Ed25519Signer;
+
+    fn purge(now: DateTime<Utc>) -> Proposal {
+        let before = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        Proposal::new(DestructiveOp::PurgeHistory { before }, "device", now, Duration::hours(1))
+    }
+
+    #[test]
+    fn default_policy_approves_nothing() {
+        let now = Utc::now();
+        let mut tracker = QuorumTracker::default();
+        let proposal = purge(now);
+        let id = proposal.id;
+        tracker.open(proposal);
+        assert_eq!(tracker.status(&id).unwrap(), QuorumStatus::Rejected);
+        assert!(matches!(tracker.take_approval(&id, now), Err(QuorumError::Declined)));
+    }
+
+    #[test]
+    fn zero_threshold_still_needs_a_vote() {
+        let now = Utc::now();
+        let signer = Ed25519Signer::generate();
+        let mut policy = QuorumPolicy::new(0).with_device(&signer.public_key(), 1);
+        policy.threshold = 0;
+        let mut tracker = QuorumTracker::new(policy);
+        let proposal = purge(now);
+        let id = proposal.id;
+        tracker.open(proposal.clone());
+        assert_eq!(tracker.status(&id).unwrap(), QuorumStatus::Pending { weight: 0, threshold: 1 });
+
+        let vote = Vote::cast(&proposal, true, &signer);
+        assert_eq!(tracker.record_vote(&vote, &CryptoSuite::default(), now).unwrap(), QuorumStatus::Approved);
+        assert!(tracker.take_approval(&id, now).is_ok());
+    }
+}
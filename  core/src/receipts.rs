@@ -0,0 +1,69 @@
+//! Receipt OCR integration hook
+use std::future::Future;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::ledger::Posting;
+use crate::staging::{StagedAttachment, StagedTransaction, StagingArea, StagingSource};
+
+/// Values an OCR engine managed to read from a receipt
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReceiptCandidate {
+    pub date: Option<NaiveDate>,
+    pub amount: Option<Decimal>,
+    pub payee: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReceiptError {
+    #[error("Unsupported image format")]
+    UnsupportedFormat,
+    #[error("Extraction failed: {0}")]
+    Extraction(String),
+}
+
+/// Implemented by apps to plug in their OCR engine of choice
+pub trait ReceiptExtractor {
+    fn extract(&self, image: &[u8]) -> impl Future<Output = Result<ReceiptCandidate, ReceiptError>> + Send;
+}
+
+/// Accounts used to pre-fill postings of receipt transactions
+#[derive(Debug, Clone)]
+pub struct ReceiptDefaults {
+    pub expense_account: Uuid,
+    pub payment_account: Uuid,
+}
+
+/// Run OCR on an image and stage a pending transaction with the image attached
+pub async fn stage_receipt<E: ReceiptExtractor>(
+    extractor: &E,
+    staging: &mut StagingArea,
+    image: Vec<u8>,
+    mime_type: Option<String>,
+    defaults: Option<&ReceiptDefaults>,
+) -> Result<Uuid, ReceiptError> {
+    let candidate = extractor.extract(&image).await?;
+
+    let mut entry = StagedTransaction::new(StagingSource::Receipt);
+    entry.date = candidate.date;
+    entry.amount = candidate.amount;
+    entry.payee = candidate.payee.clone();
+    entry.description = candidate.payee.unwrap_or_default();
+    entry.attachments.push(StagedAttachment {
+        filename: None,
+        mime_type,
+        data: image,
+    });
+
+    // Pre-fill expense/payment postings when both amount and accounts are known
+    if let (Some(amount), Some(defaults)) = (candidate.amount, defaults) {
+        entry.postings = vec![
+            Posting::new(defaults.expense_account, amount),
+            Posting::new(defaults.payment_account, -amount),
+        ];
+    }
+
+    Ok(staging.stage(entry))
+}
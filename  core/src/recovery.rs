@@ -0,0 +1,183 @@
+//! Printable recovery kit: a book's sync key plus a checkpoint of its ledger state, so a user who
+//! lost every device can bootstrap a new one from a server snapshot and confirm it is their book
+use std::collections::BTreeSet;
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::net::ToSocketAddrs;
+
+use crate::keyring::BookKeyring;
+use crate::snapshot::{fetch_snapshot, SnapshotError};
+use crate::sync::SyncDoc;
+
+/// Hex digits per printed group
+const GROUP: usize = 4;
+
+#[derive(Debug, Error)]
+pub enum RecoveryError {
+    #[error("No key for book {0}")]
+    UnknownBook(String),
+    #[error("Malformed recovery kit: {0}")]
+    Malformed(&'static str),
+    #[error("Recovery key checksum does not match; check for typos")]
+    Checksum,
+    #[error("Snapshot does not contain the ledger state recorded in the kit")]
+    StateMismatch,
+    #[error(transparent)]
+    Snapshot(#[from] SnapshotError),
+}
+
+/// Everything needed to restore one book, meant to be printed and kept offline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryKit {
+    pub book: String,
+    pub generation: u32,
+    /// Book key in groups of four hex digits, ending with a checksum group
+    pub key_code: String,
+    /// Document heads when the kit was made; a recovered snapshot must contain them
+    pub checkpoint: Vec<String>,
+    /// Short state hash for comparing by eye with what a device shows
+    pub fingerprint: String,
+    pub created_at: DateTime<Utc>,
+    /// Where the snapshot server was reachable when the kit was made
+    #[serde(default)]
+    pub server: Option<String>,
+}
+
+impl RecoveryKit {
+    pub fn create(keyring: &BookKeyring, book: &str, doc: &SyncDoc) -> Result<Self, RecoveryError> {
+        let (generation, key) = keyring.export_key(book).ok_or_else(|| RecoveryError::UnknownBook(book.to_string()))?;
+        Ok(Self {
+            book: book.to_string(),
+            generation,
+            key_code: encode_key(&key),
+            checkpoint: doc.heads_hex(),
+            fingerprint: group(&doc.state_hash()[..16]),
+            created_at: Utc::now(),
+            server: None,
+        })
+    }
+
+    pub fn with_server(mut self, server: impl Into<String>) -> Self {
+        self.server = Some(server.into());
+        self
+    }
+
+    /// Book key from the code, after checking its checksum
+    pub fn key(&self) -> Result<[u8; 32], RecoveryError> {
+        decode_key(&self.key_code)
+    }
+
+    /// Plain-text sheet; `from_printable` reads it back, including retyped copies
+    pub fn to_printable(&self) -> String {
+        let mut out = format!(
+            "TRUE LEDGER RECOVERY KIT\nBook: {}\nCreated: {}\nKey generation: {}\nKey: {}\nFingerprint: {}\n",
+            self.book,
+            self.created_at.to_rfc3339(),
+            self.generation,
+            self.key_code,
+            self.fingerprint,
+        );
+        if let Some(server) = &self.server {
+            out.push_str(&format!("Server: {}\n", server));
+        }
+        for head in &self.checkpoint {
+            out.push_str(&format!("Checkpoint: {}\n", group(head)));
+        }
+        out
+    }
+
+    pub fn from_printable(text: &str) -> Result<Self, RecoveryError> {
+        let mut book = None;
+        let mut created_at = None;
+        let mut generation = None;
+        let mut key_code = None;
+        let mut fingerprint = None;
+        let mut server = None;
+        let mut checkpoint = Vec::new();
+        for line in text.lines() {
+            let Some((label, value)) = line.split_once(':') else { continue };
+            let value = value.trim();
+            match label.trim() {
+                "Book" => book = Some(value.to_string()),
+                "Created" => created_at = DateTime::parse_from_rfc3339(value).ok().map(|d| d.with_timezone(&Utc)),
+                "Key generation" => generation = value.parse().ok(),
+                "Key" => key_code = Some(value.to_uppercase()),
+                "Fingerprint" => fingerprint = Some(value.to_uppercase()),
+                "Server" => server = Some(value.to_string()),
+                "Checkpoint" => checkpoint.push(value.replace(['-', ' '], "").to_lowercase()),
+                _ => {}
+            }
+        }
+        let kit = Self {
+            book: book.ok_or(RecoveryError::Malformed("missing book"))?,
+            generation: generation.ok_or(RecoveryError::Malformed("missing key generation"))?,
+            key_code: key_code.ok_or(RecoveryError::Malformed("missing key"))?,
+            checkpoint,
+            fingerprint: fingerprint.ok_or(RecoveryError::Malformed("missing fingerprint"))?,
+            created_at: created_at.ok_or(RecoveryError::Malformed("missing creation date"))?,
+            server,
+        };
+        kit.key()?;
+        Ok(kit)
+    }
+
+    /// Check a recovered document has seen at least the state the kit was made at
+    pub fn verify(&self, doc: &SyncDoc) -> Result<(), RecoveryError> {
+        if self.checkpoint.is_empty() || !doc.contains_heads(&self.checkpoint) {
+            return Err(RecoveryError::StateMismatch);
+        }
+        Ok(())
+    }
+
+    /// Fetch the book from a snapshot server, verify it against the checkpoint and install the
+    /// key; nothing is installed if verification fails
+    pub async fn restore(
+        &self,
+        addr: impl ToSocketAddrs,
+        token: &str,
+        keyring: &mut BookKeyring,
+        members: BTreeSet<String>,
+    ) -> Result<SyncDoc, RecoveryError> {
+        let key = self.key()?;
+        let mut kit_keyring = BookKeyring::default();
+        kit_keyring.import_key(&self.book, self.generation, key, members.clone());
+        let doc = fetch_snapshot(addr, token, &self.book, &kit_keyring).await?;
+        self.verify(&doc)?;
+        keyring.import_key(&self.book, self.generation, key, members);
+        Ok(doc)
+    }
+}
+
+fn group(hex: &str) -> String {
+    hex.as_bytes()
+        .chunks(GROUP)
+        .map(|c| String::from_utf8_lossy(c).to_uppercase())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn checksum(key: &[u8; 32]) -> String {
+    Sha256::digest(key)[..2].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn encode_key(key: &[u8; 32]) -> String {
+    let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+    group(&format!("{}{}", hex, checksum(key)))
+}
+
+fn decode_key(code: &str) -> Result<[u8; 32], RecoveryError> {
+    let hex: String = code.chars().filter(|c| c.is_ascii_hexdigit()).collect::<String>().to_lowercase();
+    if hex.len() != 68 {
+        return Err(RecoveryError::Malformed("key must have 17 groups"));
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| RecoveryError::Malformed("key is not hex"))?;
+    }
+    if checksum(&key) != hex[64..] {
+        return Err(RecoveryError::Checksum);
+    }
+    Ok(key)
+}
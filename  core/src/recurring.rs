@@ -0,0 +1,327 @@
+//! Recurring transaction definitions and detection of recurring patterns in history
+use std::collections::HashMap;
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::ledger::{Posting, Transaction, TransactionStatus};
+use crate::ledger::reconcile::ClearedState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Frequency {
+    Weekly,
+    Biweekly,
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl Frequency {
+    /// Occurrence after `date`; monthly steps keep `anchor_day`, clamped in short months
+    pub fn next(&self, date: NaiveDate, anchor_day: u32) -> NaiveDate {
+        let add_months = |months: u32| {
+            let first = date.with_day(1).unwrap() + Months::new(months);
+            (1..=anchor_day.max(1)).rev().find_map(|day| first.with_day(day)).unwrap_or(first)
+        };
+        match self {
+            Frequency::Weekly => date + Duration::days(7),
+            Frequency::Biweekly => date + Duration::days(14),
+            Frequency::Monthly => add_months(1),
+            Frequency::Quarterly => add_months(3),
+            Frequency::Yearly => add_months(12),
+        }
+    }
+
+    /// Nominal days between occurrences
+    pub fn days(&self) -> i64 {
+        match self {
+            Frequency::Weekly => 7,
+            Frequency::Biweekly => 14,
+            Frequency::Monthly => 30,
+            Frequency::Quarterly => 91,
+            Frequency::Yearly => 365,
+        }
+    }
+
+    /// Occurrences per year, for annualizing amounts
+    pub fn per_year(&self) -> Decimal {
+        Decimal::from(match self {
+            Frequency::Weekly => 52,
+            Frequency::Biweekly => 26,
+            Frequency::Monthly => 12,
+            Frequency::Quarterly => 4,
+            Frequency::Yearly => 1,
+        })
+    }
+
+    fn from_interval(days: i64) -> Option<Self> {
+        match days {
+            6..=8 => Some(Frequency::Weekly),
+            13..=15 => Some(Frequency::Biweekly),
+            27..=33 => Some(Frequency::Monthly),
+            86..=95 => Some(Frequency::Quarterly),
+            355..=375 => Some(Frequency::Yearly),
+            _ => None,
+        }
+    }
+}
+
+/// Transaction posted on a fixed schedule (rent, salary, subscriptions)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringTransaction {
+    pub id: Uuid,
+    pub description: String,
+    pub postings: Vec<Posting>,
+    pub frequency: Frequency,
+    /// Next date a transaction is due
+    pub next_date: NaiveDate,
+    /// Day of month monthly/quarterly/yearly occurrences fall on
+    pub anchor_day: u32,
+    #[serde(default)]
+    pub end_date: Option<NaiveDate>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+impl RecurringTransaction {
+    pub fn new(description: impl Into<String>, postings: Vec<Posting>, frequency: Frequency, next_date: NaiveDate) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            description: description.into(),
+            postings,
+            frequency,
+            next_date,
+            anchor_day: next_date.day(),
+            end_date: None,
+            active: true,
+        }
+    }
+
+    /// Debit-side total of one occurrence
+    pub fn amount(&self) -> Decimal {
+        self.postings.iter().map(|p| p.amount).filter(|a| a.is_sign_positive()).sum()
+    }
+
+    /// Occurrence dates from `next_date` through `until`
+    pub fn occurrences_through(&self, until: NaiveDate) -> Vec<NaiveDate> {
+        let mut dates = Vec::new();
+        if !self.active {
+            return dates;
+        }
+        let mut date = self.next_date;
+        while date <= until && self.end_date.is_none_or(|end| date <= end) {
+            dates.push(date);
+            date = self.frequency.next(date, self.anchor_day);
+        }
+        dates
+    }
+
+    /// Concrete transaction for one occurrence
+    pub fn instantiate(&self, date: NaiveDate) -> Transaction {
+        Transaction::new(date, self.description.clone(), self.postings.clone())
+    }
+
+    /// Transactions due through `today`; advances `next_date` past them
+    pub fn take_due(&mut self, today: NaiveDate) -> Vec<Transaction> {
+        let due = self.occurrences_through(today);
+        let transactions = due.iter().map(|d| self.instantiate(*d)).collect();
+        if let Some(last) = due.last() {
+            self.next_date = self.frequency.next(*last, self.anchor_day);
+        }
+        transactions
+    }
+}
+
+/// Detection thresholds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectionOptions {
+    /// Fewest past occurrences for a pattern (yearly patterns need one fewer)
+    pub min_occurrences: usize,
+    /// Allowed deviation of each amount from the latest one (0.1 = 10%)
+    pub amount_tolerance: Decimal,
+    /// Share of intervals that must match the frequency
+    pub min_regularity: f64,
+}
+
+impl Default for DetectionOptions {
+    fn default() -> Self {
+        Self { min_occurrences: 3, amount_tolerance: Decimal::new(10, 2), min_regularity: 0.75 }
+    }
+}
+
+/// Proposed recurring definition and the history it was derived from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringCandidate {
+    pub definition: RecurringTransaction,
+    pub source_transactions: Vec<Uuid>,
+    /// 0..1, from interval regularity and amount stability
+    pub confidence: f64,
+}
+
+/// Grouping key: description without digits (invoice numbers, dates) plus the accounts touched
+fn pattern_key(tx: &Transaction) -> (String, Vec<Uuid>) {
+    let description: String = tx.description.to_lowercase()
+        .chars()
+        .filter(|c| !c.is_ascii_digit())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut accounts: Vec<Uuid> = tx.postings.iter().map(|p| p.account_id).collect();
+    accounts.sort();
+    accounts.dedup();
+    (description, accounts)
+}
+
+/// Find recurring patterns in posted history that aren't covered by an existing definition.
+/// Patterns whose last occurrence is more than two periods before `today` are treated as ended.
+pub fn detect_recurring(
+    transactions: &[Transaction],
+    existing: &[RecurringTransaction],
+    today: NaiveDate,
+    options: &DetectionOptions,
+) -> Vec<RecurringCandidate> {
+    let mut groups: HashMap<(String, Vec<Uuid>), Vec<&Transaction>> = HashMap::new();
+    for tx in transactions.iter().filter(|t| t.status == TransactionStatus::Posted && !t.is_closing_entry && !t.is_opening_balance) {
+        groups.entry(pattern_key(tx)).or_default().push(tx);
+    }
+
+    let mut candidates = Vec::new();
+    for ((_, accounts), mut txs) in groups {
+        txs.sort_by_key(|t| t.date);
+        let intervals: Vec<i64> = txs.windows(2).map(|w| (w[1].date - w[0].date).num_days()).collect();
+        let Some(frequency) = median(&intervals).and_then(Frequency::from_interval) else { continue };
+        let needed = if frequency == Frequency::Yearly { options.min_occurrences - 1 } else { options.min_occurrences };
+        if txs.len() < needed.max(2) {
+            continue;
+        }
+        let regular = intervals.iter().filter(|d| Frequency::from_interval(**d) == Some(frequency)).count();
+        let regularity = regular as f64 / intervals.len() as f64;
+        if regularity < options.min_regularity {
+            continue;
+        }
+
+        let last = txs[txs.len() - 1];
+        let latest_amount = debit_total(last);
+        let stable = txs.iter()
+            .filter(|t| (debit_total(t) - latest_amount).abs() <= latest_amount * options.amount_tolerance)
+            .count();
+        let stability = stable as f64 / txs.len() as f64;
+        if stability < options.min_regularity {
+            continue;
+        }
+        if (today - last.date).num_days() > 2 * frequency.days() {
+            continue;
+        }
+        let covered = existing.iter().any(|r| {
+            r.frequency == frequency && {
+                let mut ids: Vec<Uuid> = r.postings.iter().map(|p| p.account_id).collect();
+                ids.sort();
+                ids.dedup();
+                ids == accounts
+            }
+        });
+        if covered {
+            continue;
+        }
+
+        let anchor_day = last.date.day();
+        let mut next_date = frequency.next(last.date, anchor_day);
+        while next_date < today {
+            next_date = frequency.next(next_date, anchor_day);
+        }
+        let postings = last.postings.iter()
+            .map(|p| Posting { assert_balance: None, lot: None, cleared: ClearedState::Uncleared, ..p.clone() })
+            .collect();
+        let mut definition = RecurringTransaction::new(last.description.clone(), postings, frequency, next_date);
+        definition.anchor_day = anchor_day;
+        candidates.push(RecurringCandidate {
+            definition,
+            source_transactions: txs.iter().map(|t| t.id).collect(),
+            confidence: regularity * stability,
+        });
+    }
+    candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence).then_with(|| a.definition.description.cmp(&b.definition.description)));
+    candidates
+}
+
+fn debit_total(tx: &Transaction) -> Decimal {
+    tx.postings.iter().map(|p| p.amount).filter(|a| a.is_sign_positive()).sum()
+}
+
+fn median(values: &[i64]) -> Option<i64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    sorted.get(sorted.len() / 2).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn rent(on: NaiveDate, accounts: (Uuid, Uuid), amount: i64) -> Transaction {
+        Transaction::new(on, format!("Rent {}", on.format("%m/%Y")), vec![
+            Posting::new(accounts.0, Decimal::from(amount)),
+            Posting::new(accounts.1, Decimal::from(-amount)),
+        ])
+    }
+
+    #[test]
+    fn monthly_steps_keep_the_anchor_day() {
+        let feb = Frequency::Monthly.next(date(2024, 1, 31), 31);
+        assert_eq!(feb, date(2024, 2, 29));
+        assert_eq!(Frequency::Monthly.next(feb, 31), date(2024, 3, 31));
+        assert_eq!(Frequency::Quarterly.next(date(2024, 11, 30), 30), date(2025, 2, 28));
+        assert_eq!(Frequency::Yearly.next(date(2024, 2, 29), 29), date(2025, 2, 28));
+        assert_eq!(Frequency::Biweekly.next(date(2024, 12, 25), 25), date(2025, 1, 8));
+    }
+
+    #[test]
+    fn take_due_advances_past_the_posted_occurrences() {
+        let accounts = (Uuid::new_v4(), Uuid::new_v4());
+        let template = rent(date(2024, 1, 31), accounts, 900);
+        let mut recurring = RecurringTransaction::new("Rent", template.postings, Frequency::Monthly, date(2024, 1, 31));
+        recurring.end_date = Some(date(2024, 4, 15));
+
+        let due = recurring.take_due(date(2024, 3, 31));
+        assert_eq!(due.iter().map(|t| t.date).collect::<Vec<_>>(), vec![date(2024, 1, 31), date(2024, 2, 29), date(2024, 3, 31)]);
+        assert_eq!(recurring.next_date, date(2024, 4, 30));
+        assert_eq!(recurring.amount(), Decimal::from(900));
+        // Past the end date nothing more is due
+        assert!(recurring.take_due(date(2024, 12, 31)).is_empty());
+
+        recurring.end_date = None;
+        recurring.active = false;
+        assert!(recurring.occurrences_through(date(2024, 12, 31)).is_empty());
+    }
+
+    #[test]
+    fn detects_a_live_monthly_pattern_not_yet_defined() {
+        let accounts = (Uuid::new_v4(), Uuid::new_v4());
+        let history: Vec<Transaction> = (1..=4).map(|m| rent(date(2024, m, 1), accounts, 1000)).collect();
+        let today = date(2024, 5, 10);
+
+        let candidates = detect_recurring(&history, &[], today, &DetectionOptions::default());
+        assert_eq!(candidates.len(), 1);
+        let candidate = &candidates[0];
+        assert_eq!(candidate.definition.frequency, Frequency::Monthly);
+        assert_eq!(candidate.definition.next_date, date(2024, 6, 1));
+        assert_eq!(candidate.definition.amount(), Decimal::from(1000));
+        assert_eq!(candidate.source_transactions.len(), 4);
+        assert_eq!(candidate.confidence, 1.0);
+
+        // Already defined, or ended more than two periods ago
+        assert!(detect_recurring(&history, std::slice::from_ref(&candidate.definition), today, &DetectionOptions::default()).is_empty());
+        assert!(detect_recurring(&history, &[], date(2024, 7, 1), &DetectionOptions::default()).is_empty());
+        assert!(detect_recurring(&history[..2], &[], today, &DetectionOptions::default()).is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+//! Structured report documents shared by all report generators and renderers
+pub mod aging;
+pub mod cash_flow;
+pub mod comparison;
+pub mod delivery;
+pub mod dimensions;
+pub mod drill;
+#[cfg(feature = "html")]
+pub mod html;
+pub mod projects;
+pub mod register;
+pub mod schedule;
+pub mod statements;
+pub mod subscriptions;
+pub mod translation;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::ledger::{Account, BalanceSample, Basis, Transaction};
+use crate::locale::Locale;
+use crate::sync::SyncableLedger;
+
+pub use aging::{aging, AgedInvoice, AgingBucket, AgingReport, AgingRow, AgingSide};
+pub use cash_flow::cash_flow;
+pub use comparison::{comparative_income_statement, variance_flags, VarianceFlag, VarianceThresholds};
+pub use delivery::{DeliveryError, FileDelivery, ReportDelivery};
+pub use dimensions::{dimension_report, Dimension};
+pub use drill::{CellQuery, CellRef};
+pub use projects::project_pnl;
+pub use register::{register, Register, RegisterLine};
+pub use schedule::{Cadence, ReportSchedule, ReportScheduler};
+pub use statements::{balance_sheet, income_statement};
+pub use subscriptions::{subscription_report, subscriptions, Subscription};
+pub use translation::{presentation_balances, TranslationError};
+
+/// Output format for rendered reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReportFormat {
+    Text,
+    Csv,
+    Json,
+    /// Self-contained page with inline SVG charts
+    #[cfg(feature = "html")]
+    Html,
+}
+
+impl ReportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Text => "txt",
+            ReportFormat::Csv => "csv",
+            ReportFormat::Json => "json",
+            #[cfg(feature = "html")]
+            ReportFormat::Html => "html",
+        }
+    }
+}
+
+/// Options shared by report generators
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportOptions {
+    /// Skip accounts closed before the report period starts
+    pub hide_closed_accounts: bool,
+    /// Skip archived accounts
+    #[serde(default)]
+    pub hide_archived_accounts: bool,
+    /// Cash basis counts only transactions touching a cash-equivalent account
+    #[serde(default)]
+    pub basis: Basis,
+    /// Leave out the opening entry of a ledger started mid-life, e.g. for activity reports
+    #[serde(default)]
+    pub exclude_opening_balances: bool,
+}
+
+impl ReportOptions {
+    /// Whether an account should appear in a report starting on `period_start`
+    pub fn includes(&self, account: &Account, period_start: NaiveDate) -> bool {
+        !(self.hide_closed_accounts && account.closed_before(period_start))
+            && (account.active || !self.hide_archived_accounts)
+    }
+
+    /// Whether a transaction contributes to a report on the selected basis
+    pub fn includes_transaction(&self, tx: &Transaction, ledger: &SyncableLedger) -> bool {
+        if self.exclude_opening_balances && tx.is_opening_balance {
+            return false;
+        }
+        match self.basis {
+            Basis::Accrual => true,
+            Basis::Cash => tx.postings.iter()
+                .any(|p| ledger.accounts.get(&p.account_id).is_some_and(|a| a.cash_equivalent)),
+        }
+    }
+}
+
+/// One labelled line of figures
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportRow {
+    pub label: String,
+    pub account_id: Option<Uuid>,
+    pub depth: usize,
+    pub values: Vec<Decimal>,
+    /// Query behind each value, parallel to `values`
+    #[serde(default)]
+    pub queries: Vec<Option<CellQuery>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportSection {
+    pub title: String,
+    pub rows: Vec<ReportRow>,
+    pub total: Option<Vec<Decimal>>,
+    #[serde(default)]
+    pub total_queries: Vec<Option<CellQuery>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChartType {
+    /// Values over time, e.g. a balance history
+    Line,
+    /// Shares of a whole, e.g. spending by category
+    Pie,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChartPoint {
+    pub label: String,
+    pub value: Decimal,
+}
+
+/// Chart data carried with a report; renderers that can't draw it ignore it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportChart {
+    pub title: String,
+    pub chart_type: ChartType,
+    pub points: Vec<ChartPoint>,
+}
+
+impl ReportChart {
+    /// Line chart of samples from `Ledger::balance_history`
+    pub fn balance_history(title: &str, samples: &[BalanceSample]) -> Self {
+        Self {
+            title: title.to_string(),
+            chart_type: ChartType::Line,
+            points: samples.iter()
+                .map(|s| ChartPoint { label: s.date.to_string(), value: s.balance })
+                .collect(),
+        }
+    }
+
+    /// Pie of a section's rows in one column; rows at or below zero are left out
+    pub fn category_pie(title: &str, section: &ReportSection, column: usize) -> Self {
+        Self {
+            title: title.to_string(),
+            chart_type: ChartType::Pie,
+            points: section.rows.iter()
+                .filter_map(|row| {
+                    let value = *row.values.get(column)?;
+                    (value > Decimal::ZERO).then(|| ChartPoint { label: row.label.clone(), value })
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Renderer-independent report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportDocument {
+    pub title: String,
+    pub period_start: Option<NaiveDate>,
+    pub period_end: NaiveDate,
+    pub generated_at: DateTime<Utc>,
+    pub columns: Vec<String>,
+    pub sections: Vec<ReportSection>,
+    #[serde(default)]
+    pub charts: Vec<ReportChart>,
+}
+
+impl ReportDocument {
+    pub fn new(title: &str, period_start: Option<NaiveDate>, period_end: NaiveDate, columns: Vec<String>) -> Self {
+        Self {
+            title: title.to_string(),
+            period_start,
+            period_end,
+            generated_at: Utc::now(),
+            columns,
+            sections: Vec::new(),
+            charts: Vec::new(),
+        }
+    }
+
+    pub fn with_chart(mut self, chart: ReportChart) -> Self {
+        self.charts.push(chart);
+        self
+    }
+
+    /// Query behind a cell, if the generator recorded one
+    pub fn cell_query(&self, cell: &CellRef) -> Option<&CellQuery> {
+        let section = self.sections.get(cell.section)?;
+        let queries = match cell.row {
+            Some(row) => &section.rows.get(row)?.queries,
+            None => &section.total_queries,
+        };
+        queries.get(cell.column)?.as_ref()
+    }
+
+    /// Transactions contributing to a cell
+    pub fn drill_down<'a>(&self, ledger: &'a SyncableLedger, cell: &CellRef) -> Vec<&'a Transaction> {
+        self.cell_query(cell).map(|q| q.transactions(ledger)).unwrap_or_default()
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.to_text(),
+            ReportFormat::Csv => self.to_csv(),
+            ReportFormat::Json => self.to_json(),
+            #[cfg(feature = "html")]
+            ReportFormat::Html => self.to_html(),
+        }
+    }
+
+    /// Plain-text rendering suitable for terminals and email bodies
+    pub fn to_text(&self) -> String {
+        self.to_text_with(&Locale::default())
+    }
+
+    /// Plain-text rendering using the locale's number and date conventions
+    pub fn to_text_with(&self, locale: &Locale) -> String {
+        let mut out = format!("{}\n", self.title);
+        match self.period_start {
+            Some(start) => out.push_str(&format!(
+                "{} to {}\n\n",
+                locale.format_date(start),
+                locale.format_date(self.period_end),
+            )),
+            None => out.push_str(&format!("As of {}\n\n", locale.format_date(self.period_end))),
+        }
+        for section in &self.sections {
+            out.push_str(&format!("{}\n", section.title));
+            for row in &section.rows {
+                let label = format!("{}{}", "  ".repeat(row.depth + 1), row.label);
+                out.push_str(&format!("{:<40}{}\n", label, format_values(&row.values, locale)));
+            }
+            if let Some(total) = &section.total {
+                out.push_str(&format!("{:<40}{}\n", format!("Total {}", section.title), format_values(total, locale)));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("section,account");
+        for column in &self.columns {
+            out.push(',');
+            out.push_str(&csv_field(column));
+        }
+        out.push('\n');
+        for section in &self.sections {
+            for row in &section.rows {
+                out.push_str(&format!("{},{}", csv_field(&section.title), csv_field(&row.label)));
+                for value in &row.values {
+                    out.push_str(&format!(",{}", value));
+                }
+                out.push('\n');
+            }
+            if let Some(total) = &section.total {
+                out.push_str(&format!("{},Total", csv_field(&section.title)));
+                for value in total {
+                    out.push_str(&format!(",{}", value));
+                }
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+fn format_values(values: &[Decimal], locale: &Locale) -> String {
+    values.iter().map(|v| format!("{:>15}", locale.format_amount(*v, 2))).collect()
+}
+
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
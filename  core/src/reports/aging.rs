@@ -0,0 +1,327 @@
+//! Aged receivables and payables: open invoice balances per contact, bucketed by days past due
+use std::collections::{BTreeMap, BTreeSet};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::currency::Commodity;
+use crate::invoicing::{Invoice, InvoiceDirection, InvoiceStatus};
+use crate::sync::SyncableLedger;
+use super::{csv_field, CellQuery, ReportDocument, ReportRow, ReportSection};
+
+/// Which side of the books to age: sales invoices owed to us or bills we owe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AgingSide {
+    Receivables,
+    Payables,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AgingBucket {
+    /// Not yet due or up to 30 days past due
+    Days0To30,
+    Days31To60,
+    Days61To90,
+    Over90,
+}
+
+impl AgingBucket {
+    pub const ALL: [AgingBucket; 4] = [AgingBucket::Days0To30, AgingBucket::Days31To60, AgingBucket::Days61To90, AgingBucket::Over90];
+
+    pub fn for_days(days_overdue: i64) -> Self {
+        match days_overdue {
+            ..=30 => AgingBucket::Days0To30,
+            31..=60 => AgingBucket::Days31To60,
+            61..=90 => AgingBucket::Days61To90,
+            _ => AgingBucket::Over90,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AgingBucket::Days0To30 => "0-30",
+            AgingBucket::Days31To60 => "31-60",
+            AgingBucket::Days61To90 => "61-90",
+            AgingBucket::Over90 => "90+",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// One invoice still open on the report date
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgedInvoice {
+    pub invoice_id: Uuid,
+    #[serde(default)]
+    pub number: Option<String>,
+    pub contact_id: Uuid,
+    pub due_date: NaiveDate,
+    /// Negative while not yet due
+    pub days_overdue: i64,
+    pub outstanding: Decimal,
+    pub commodity: Commodity,
+    pub bucket: AgingBucket,
+}
+
+/// Open balance of one contact in one commodity, by bucket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgingRow {
+    pub contact_id: Uuid,
+    pub contact_name: String,
+    pub commodity: Commodity,
+    /// Indexed like `AgingBucket::ALL`
+    pub buckets: [Decimal; 4],
+}
+
+impl AgingRow {
+    pub fn total(&self) -> Decimal {
+        self.buckets.iter().sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgingReport {
+    pub side: AgingSide,
+    pub as_of: NaiveDate,
+    /// Sorted by contact name
+    pub rows: Vec<AgingRow>,
+    /// Oldest due date first
+    pub invoices: Vec<AgedInvoice>,
+}
+
+/// Bucket amounts followed by their total
+fn with_total(buckets: [Decimal; 4]) -> Vec<Decimal> {
+    let mut values = buckets.to_vec();
+    values.push(buckets.iter().sum());
+    values
+}
+
+impl AgingReport {
+    /// Bucket totals over all rows in `commodity`
+    pub fn totals(&self, commodity: &Commodity) -> [Decimal; 4] {
+        let mut totals = [Decimal::ZERO; 4];
+        for row in self.rows.iter().filter(|r| &r.commodity == commodity) {
+            for (total, amount) in totals.iter_mut().zip(row.buckets) {
+                *total += amount;
+            }
+        }
+        totals
+    }
+
+    /// As a report document with one section per commodity
+    pub fn to_document(&self, ledger: &SyncableLedger) -> ReportDocument {
+        let title = match self.side {
+            AgingSide::Receivables => "Aged Receivables",
+            AgingSide::Payables => "Aged Payables",
+        };
+        let mut columns: Vec<String> = AgingBucket::ALL.iter().map(|b| b.label().to_string()).collect();
+        columns.push("Total".to_string());
+        let mut doc = ReportDocument::new(title, None, self.as_of, columns);
+
+        let commodities: BTreeSet<&Commodity> = self.rows.iter().map(|r| &r.commodity).collect();
+        for commodity in commodities {
+            let rows = self.rows.iter()
+                .filter(|r| &r.commodity == commodity)
+                .map(|row| {
+                    let account = ledger.contacts.get(&row.contact_id).and_then(|c| c.account_id);
+                    ReportRow {
+                        label: row.contact_name.clone(),
+                        account_id: account,
+                        depth: 0,
+                        values: with_total(row.buckets),
+                        queries: vec![None, None, None, None, Some(self.contact_query(ledger, row))],
+                    }
+                })
+                .collect();
+            doc.sections.push(ReportSection {
+                title: commodity.code().to_string(),
+                rows,
+                total: Some(with_total(self.totals(commodity))),
+                total_queries: Vec::new(),
+            });
+        }
+        doc
+    }
+
+    /// The contact's entries on the accounts of its aged invoices in the row's commodity
+    fn contact_query(&self, ledger: &SyncableLedger, row: &AgingRow) -> CellQuery {
+        let accounts: BTreeSet<Uuid> = self.invoices.iter()
+            .filter(|i| i.contact_id == row.contact_id && i.commodity == row.commodity)
+            .filter_map(|i| ledger.invoices.get(&i.invoice_id).map(|invoice| invoice.receivable_account))
+            .collect();
+        accounts.into_iter().fold(CellQuery::period(None, self.as_of).contact(row.contact_id), |q, id| q.account(id))
+    }
+
+    /// One line per contact and commodity, then a totals line per commodity
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("contact,commodity,0-30,31-60,61-90,90+,total\n");
+        let mut push = |label: &str, commodity: &Commodity, buckets: [Decimal; 4]| {
+            let values = with_total(buckets);
+            out.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                csv_field(label),
+                commodity.code(),
+                values[0],
+                values[1],
+                values[2],
+                values[3],
+                values[4],
+            ));
+        };
+        for row in &self.rows {
+            push(&row.contact_name, &row.commodity, row.buckets);
+        }
+        let commodities: BTreeSet<&Commodity> = self.rows.iter().map(|r| &r.commodity).collect();
+        for commodity in commodities {
+            push("Total", commodity, self.totals(commodity));
+        }
+        out
+    }
+}
+
+/// Age every invoice on `side` that is open on `as_of`: issued by then and not fully paid by
+/// payments dated up to then. Days are counted from the due date, or the issue date when
+/// there is none.
+pub fn aging(ledger: &SyncableLedger, side: AgingSide, as_of: NaiveDate) -> AgingReport {
+    let on_side = |invoice: &Invoice| match side {
+        AgingSide::Receivables => invoice.direction == InvoiceDirection::Sales,
+        AgingSide::Payables => invoice.direction == InvoiceDirection::Purchase,
+    };
+
+    let mut invoices: Vec<AgedInvoice> = ledger.invoices.values()
+        .filter(|i| matches!(i.status, InvoiceStatus::Issued | InvoiceStatus::PartiallyPaid | InvoiceStatus::Paid))
+        .filter(|i| on_side(i))
+        .filter_map(|invoice| {
+            let issued = invoice.issue_date.filter(|d| *d <= as_of)?;
+            let paid: Decimal = invoice.payments.iter().filter(|p| p.date <= as_of).map(|p| p.amount).sum();
+            let outstanding = invoice.total() - paid;
+            if outstanding <= Decimal::ZERO {
+                return None;
+            }
+            let due_date = invoice.due_date.unwrap_or(issued);
+            let days_overdue = (as_of - due_date).num_days();
+            Some(AgedInvoice {
+                invoice_id: invoice.id,
+                number: invoice.number.clone(),
+                contact_id: invoice.contact_id,
+                due_date,
+                days_overdue,
+                outstanding,
+                commodity: invoice.commodity.clone(),
+                bucket: AgingBucket::for_days(days_overdue),
+            })
+        })
+        .collect();
+    invoices.sort_by_key(|i| (i.due_date, i.invoice_id));
+
+    let mut grouped: BTreeMap<(String, Uuid, Commodity), AgingRow> = BTreeMap::new();
+    for invoice in &invoices {
+        let contact_name = ledger.contacts.get(&invoice.contact_id).map(|c| c.name.clone()).unwrap_or_default();
+        let row = grouped
+            .entry((contact_name.clone(), invoice.contact_id, invoice.commodity.clone()))
+            .or_insert_with(|| AgingRow {
+                contact_id: invoice.contact_id,
+                contact_name,
+                commodity: invoice.commodity.clone(),
+                buckets: [Decimal::ZERO; 4],
+            });
+        row.buckets[invoice.bucket.index()] += invoice.outstanding;
+    }
+
+    AgingReport { side, as_of, rows: grouped.into_values().collect(), invoices }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contacts::{Contact, ContactRole};
+    use crate::invoicing::InvoiceLine;
+    use crate::ledger::{Account, AccountType};
+
+    struct Books {
+        ledger: SyncableLedger,
+        vendor: Uuid,
+        customer: Uuid,
+        bill: Uuid,
+        payable: Uuid,
+        supplies: Uuid,
+        bank: Uuid,
+    }
+
+    fn books() -> Books {
+        let mut ledger = SyncableLedger::new();
+        let accounts = [
+            Account::new("Payables", AccountType::Liability),
+            Account::new("Receivables", AccountType::Asset),
+            Account::new("Supplies", AccountType::Expense),
+            Account::new("Sales", AccountType::Revenue),
+            Account::new("Bank", AccountType::Asset),
+        ];
+        let [payable, receivable, supplies, sales, bank] = accounts.each_ref().map(|a| a.id);
+        for account in accounts {
+            ledger.accounts.insert(account.id, account);
+        }
+        let vendor = Contact::new("Paper Co", ContactRole::Vendor);
+        let customer = Contact::new("Acme", ContactRole::Customer);
+        let (vendor_id, customer_id) = (vendor.id, customer.id);
+        ledger.contacts.insert(vendor.id, vendor);
+        ledger.contacts.insert(customer.id, customer);
+
+        let bill = Invoice::bill(vendor_id, payable, Commodity::default())
+            .with_line(InvoiceLine::new("Paper", Decimal::ONE, Decimal::from(80), supplies));
+        let invoice = Invoice::new(customer_id, receivable, Commodity::default())
+            .with_line(InvoiceLine::new("Consulting", Decimal::ONE, Decimal::from(200), sales));
+        let bill_id = bill.id;
+        let invoice_id = invoice.id;
+        ledger.invoices.insert(bill.id, bill);
+        ledger.invoices.insert(invoice.id, invoice);
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        ledger.issue_invoice(bill_id, date, Some("B-1".to_string())).unwrap();
+        ledger.issue_invoice(invoice_id, date, Some("1".to_string())).unwrap();
+        Books { ledger, vendor: vendor_id, customer: customer_id, bill: bill_id, payable, supplies, bank }
+    }
+
+    #[test]
+    fn bills_credit_the_payable_and_age_as_payables() {
+        let mut books = books();
+        let issue = books.ledger.transactions.iter().find(|t| t.reference.as_deref() == Some("B-1")).unwrap();
+        let amount_on = |account: Uuid| issue.postings.iter().find(|p| p.account_id == account).unwrap().amount;
+        assert_eq!(amount_on(books.payable), Decimal::from(-80));
+        assert_eq!(amount_on(books.supplies), Decimal::from(80));
+
+        let date = NaiveDate::from_ymd_opt(2024, 1, 10).unwrap();
+        let payment = books.ledger.record_invoice_payment(books.bill, date, Decimal::from(30), books.bank).unwrap();
+        let payment = books.ledger.transactions.iter().find(|t| t.id == payment).unwrap();
+        assert_eq!(payment.postings[0].amount, Decimal::from(-30));
+        assert_eq!(payment.postings[1].amount, Decimal::from(30));
+
+        let as_of = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let payables = aging(&books.ledger, AgingSide::Payables, as_of);
+        assert_eq!(payables.rows.len(), 1);
+        assert_eq!(payables.rows[0].contact_id, books.vendor);
+        assert_eq!(payables.rows[0].total(), Decimal::from(50));
+        let receivables = aging(&books.ledger, AgingSide::Receivables, as_of);
+        assert_eq!(receivables.rows.len(), 1);
+        assert_eq!(receivables.rows[0].contact_id, books.customer);
+    }
+
+    #[test]
+    fn total_drills_into_the_contact_and_csv_has_totals() {
+        let books = books();
+        let as_of = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let report = aging(&books.ledger, AgingSide::Receivables, as_of);
+        let doc = report.to_document(&books.ledger);
+        let query = doc.sections[0].rows[0].queries[4].clone().unwrap();
+        let transactions = query.transactions(&books.ledger);
+        assert_eq!(transactions.len(), 1);
+        assert!(transactions.iter().all(|t| t.contact_id == Some(books.customer)));
+
+        let csv = report.to_csv();
+        assert!(csv.lines().last().unwrap().starts_with("Total,"), "{}", csv);
+        assert!(csv.lines().last().unwrap().ends_with(",200"), "{}", csv);
+    }
+}
@@ -0,0 +1,181 @@
+//! Cash flow statement by the indirect method: net income adjusted by the change in every
+//! non-cash balance sheet account, grouped into operating, investing and financing activities
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::currency::Commodity;
+use crate::ledger::{AccountType, CashFlowActivity};
+use crate::sync::SyncableLedger;
+use super::{CellQuery, ReportDocument, ReportRow, ReportSection};
+
+/// Cash flows between `from` and `to` (inclusive). Closing entries are left out since they only
+/// move net income into equity. Cash is every cash-equivalent account; the sections add up to
+/// its change over the period. Each commodity is reported in its own column.
+pub fn cash_flow(ledger: &SyncableLedger, from: NaiveDate, to: NaiveDate) -> ReportDocument {
+    let is_cash = |id: &Uuid| ledger.accounts.get(id).is_some_and(|a| a.cash_equivalent);
+    let transactions = || ledger.transactions.iter().filter(|t| t.date <= to && !t.is_closing_entry);
+    let commodities: Vec<Commodity> = transactions()
+        .flat_map(|t| &t.postings)
+        .map(|p| &p.commodity)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .cloned()
+        .collect();
+    let width = commodities.len().max(1);
+    let column = |commodity: &Commodity| commodities.iter().position(|c| c == commodity).unwrap_or(0);
+
+    let mut opening_cash = vec![Decimal::ZERO; width];
+    // Debit-positive change per account and commodity column within the period
+    let mut changes: BTreeMap<Uuid, Vec<Decimal>> = BTreeMap::new();
+    for tx in transactions() {
+        for posting in &tx.postings {
+            let index = column(&posting.commodity);
+            if tx.date < from {
+                if is_cash(&posting.account_id) {
+                    opening_cash[index] += posting.amount;
+                }
+            } else {
+                changes.entry(posting.account_id).or_insert_with(|| vec![Decimal::ZERO; width])[index] += posting.amount;
+            }
+        }
+    }
+
+    let base = CellQuery::period(Some(from), to);
+    let mut net_income = vec![Decimal::ZERO; width];
+    let mut cash_change = vec![Decimal::ZERO; width];
+    let mut rows: HashMap<CashFlowActivity, Vec<ReportRow>> = HashMap::new();
+    for (account_id, change) in &changes {
+        let Some(account) = ledger.accounts.get(account_id) else { continue };
+        match account.r#type {
+            AccountType::Revenue | AccountType::Expense => add(&mut net_income, change, -Decimal::ONE),
+            _ if account.cash_equivalent => add(&mut cash_change, change, Decimal::ONE),
+            // More of a non-cash asset used cash; more of a liability or equity brought it in
+            _ if change.iter().any(|c| !c.is_zero()) => rows.entry(account.cash_flow_activity()).or_default().push(ReportRow {
+                label: account.name.clone(),
+                account_id: Some(*account_id),
+                depth: 0,
+                values: change.iter().map(|c| -*c).collect(),
+                queries: vec![Some(base.clone().account(*account_id)); width],
+            }),
+            _ => {}
+        }
+    }
+
+    let columns = if commodities.len() > 1 {
+        commodities.iter().map(|c| c.code().to_string()).collect()
+    } else {
+        vec!["Amount".to_string()]
+    };
+    let mut doc = ReportDocument::new("Cash Flow Statement", Some(from), to, columns);
+    for (title, activity) in [
+        ("Operating Activities", CashFlowActivity::Operating),
+        ("Investing Activities", CashFlowActivity::Investing),
+        ("Financing Activities", CashFlowActivity::Financing),
+    ] {
+        let mut section_rows = Vec::new();
+        if activity == CashFlowActivity::Operating {
+            section_rows.push(ReportRow {
+                label: "Net income".to_string(),
+                account_id: None,
+                depth: 0,
+                values: net_income.clone(),
+                queries: vec![Some(base.clone().account_types(&[AccountType::Revenue, AccountType::Expense])); width],
+            });
+        }
+        let mut adjustments = rows.remove(&activity).unwrap_or_default();
+        adjustments.sort_by(|a, b| a.label.cmp(&b.label));
+        section_rows.extend(adjustments);
+        let mut total = vec![Decimal::ZERO; width];
+        for row in &section_rows {
+            add(&mut total, &row.values, Decimal::ONE);
+        }
+        doc.sections.push(ReportSection {
+            title: title.to_string(),
+            rows: section_rows,
+            total: Some(total),
+            total_queries: Vec::new(),
+        });
+    }
+
+    let cash_query = |start: Option<NaiveDate>, end: NaiveDate| {
+        ledger.accounts.values()
+            .filter(|a| a.cash_equivalent)
+            .fold(CellQuery::period(start, end), |q, a| q.account(a.id))
+    };
+    doc.sections.push(ReportSection {
+        title: "Net Change in Cash".to_string(),
+        rows: vec![
+            ReportRow {
+                label: "Cash at beginning of period".to_string(),
+                account_id: None,
+                depth: 0,
+                values: opening_cash.clone(),
+                queries: vec![from.pred_opt().map(|end| cash_query(None, end)); width],
+            },
+            ReportRow {
+                label: "Cash at end of period".to_string(),
+                account_id: None,
+                depth: 0,
+                values: opening_cash.iter().zip(&cash_change).map(|(o, c)| o + c).collect(),
+                queries: vec![Some(cash_query(None, to)); width],
+            },
+        ],
+        total: Some(cash_change),
+        total_queries: vec![Some(cash_query(Some(from), to)); width],
+    });
+    doc
+}
+
+/// Add `amounts * sign` column by column
+fn add(target: &mut [Decimal], amounts: &[Decimal], sign: Decimal) {
+    for (t, a) in target.iter_mut().zip(amounts) {
+        *t += *a * sign;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::{Account, Posting, Transaction};
+
+    fn posting(account_id: Uuid, amount: i64, commodity: &str) -> Posting {
+        let mut posting = Posting::new(account_id, Decimal::from(amount));
+        posting.commodity = Commodity::new(commodity);
+        posting
+    }
+
+    #[test]
+    fn commodities_are_not_summed_together() {
+        let mut ledger = SyncableLedger::new();
+        let mut cash = Account::new("Cash", AccountType::Asset);
+        cash.cash_equivalent = true;
+        let sales = Account::new("Sales", AccountType::Revenue);
+        let (cash_id, sales_id) = (cash.id, sales.id);
+        ledger.accounts.insert(cash.id, cash);
+        ledger.accounts.insert(sales.id, sales);
+        let opening = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        ledger.transactions.push(Transaction::new(opening, "Earlier sale", vec![
+            posting(cash_id, 30, "USD"),
+            posting(sales_id, -30, "USD"),
+        ]));
+        ledger.transactions.push(Transaction::new(date, "USD sale", vec![
+            posting(cash_id, 100, "USD"),
+            posting(sales_id, -100, "USD"),
+        ]));
+        ledger.transactions.push(Transaction::new(date, "EUR sale", vec![
+            posting(cash_id, 50, "EUR"),
+            posting(sales_id, -50, "EUR"),
+        ]));
+
+        let doc = cash_flow(&ledger, NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(), date);
+        assert_eq!(doc.columns, vec!["EUR".to_string(), "USD".to_string()]);
+        assert_eq!(doc.sections[0].rows[0].values, vec![Decimal::from(50), Decimal::from(100)]);
+        let cash = doc.sections.last().unwrap();
+        assert_eq!(cash.rows[0].values, vec![Decimal::ZERO, Decimal::from(30)]);
+        assert_eq!(cash.rows[1].values, vec![Decimal::from(50), Decimal::from(130)]);
+        assert_eq!(cash.total, Some(vec![Decimal::from(50), Decimal::from(100)]));
+    }
+}
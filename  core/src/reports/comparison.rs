@@ -0,0 +1,178 @@
+//! Period-over-period comparison with variance flags for line items that moved unusually
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::ledger::AccountType;
+use crate::sync::SyncableLedger;
+use super::{CellQuery, CellRef, ReportDocument, ReportOptions, ReportRow, ReportSection};
+
+/// Column indexes of a comparative report
+pub const CURRENT: usize = 0;
+pub const PRIOR: usize = 1;
+pub const CHANGE: usize = 2;
+
+/// When a change is worth flagging; unset limits are ignored and with none set nothing is flagged
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VarianceThresholds {
+    /// Relative change in percent, e.g. 25 for ±25%
+    pub percent: Option<Decimal>,
+    /// Absolute change in the report currency
+    pub amount: Option<Decimal>,
+    /// Flag only when both limits are exceeded, so small lines with big swings stay quiet
+    pub require_both: bool,
+}
+
+impl VarianceThresholds {
+    fn exceeded(&self, change: Decimal, percent: Option<Decimal>) -> bool {
+        // A line that appeared from nothing counts as exceeding any percentage
+        let by_percent = self.percent.map(|limit| percent.map_or(!change.is_zero(), |p| p.abs() >= limit));
+        let by_amount = self.amount.map(|limit| change.abs() >= limit);
+        match (by_percent, by_amount) {
+            (None, None) => false,
+            (Some(p), None) => p,
+            (None, Some(a)) => a,
+            (Some(p), Some(a)) if self.require_both => p && a,
+            (Some(p), Some(a)) => p || a,
+        }
+    }
+}
+
+/// A line item that moved beyond the thresholds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VarianceFlag {
+    /// The change cell, for highlighting
+    pub cell: CellRef,
+    pub label: String,
+    pub account_id: Option<Uuid>,
+    pub current: Decimal,
+    pub prior: Decimal,
+    pub change: Decimal,
+    /// Change relative to the prior period; None when the prior period was zero
+    pub percent: Option<Decimal>,
+}
+
+impl VarianceFlag {
+    /// Short label such as "Utilities +240%" (or "Consulting +1200" without a prior figure)
+    pub fn describe(&self) -> String {
+        match self.percent {
+            Some(percent) => format!("{} {:+}%", self.label, percent.round_dp(0)),
+            None => format!("{} {:+}", self.label, self.change),
+        }
+    }
+}
+
+fn percent_change(current: Decimal, prior: Decimal) -> Option<Decimal> {
+    (!prior.is_zero()).then(|| (current - prior) / prior.abs() * Decimal::ONE_HUNDRED)
+}
+
+/// Rows of a comparative report (current, prior and change columns) that exceed the thresholds,
+/// largest relative changes first
+pub fn variance_flags(doc: &ReportDocument, thresholds: &VarianceThresholds) -> Vec<VarianceFlag> {
+    let mut flags: Vec<VarianceFlag> = doc.sections.iter()
+        .enumerate()
+        .flat_map(|(s, section)| section.rows.iter().enumerate().map(move |(r, row)| (s, r, row)))
+        .filter_map(|(section, row_index, row)| {
+            let (current, prior) = (*row.values.get(CURRENT)?, *row.values.get(PRIOR)?);
+            let change = current - prior;
+            let percent = percent_change(current, prior);
+            thresholds.exceeded(change, percent).then(|| VarianceFlag {
+                cell: CellRef { section, row: Some(row_index), column: CHANGE },
+                label: row.label.clone(),
+                account_id: row.account_id,
+                current,
+                prior,
+                change,
+                percent,
+            })
+        })
+        .collect();
+    flags.sort_by(|a, b| {
+        let key = |f: &VarianceFlag| f.percent.map(|p| p.abs());
+        // New lines (no percentage) sort ahead of everything else
+        match (key(a), key(b)) {
+            (None, None) => b.change.abs().cmp(&a.change.abs()),
+            (None, Some(_)) => std::cmp::Ordering::Less,
+            (Some(_), None) => std::cmp::Ordering::Greater,
+            (Some(x), Some(y)) => y.cmp(&x),
+        }
+    });
+    flags
+}
+
+/// Revenue and expenses per account for two periods side by side, both shown positive
+pub fn comparative_income_statement(
+    ledger: &SyncableLedger,
+    options: &ReportOptions,
+    current: RangeInclusive<NaiveDate>,
+    prior: RangeInclusive<NaiveDate>,
+) -> ReportDocument {
+    // account -> (current, prior), debit-positive
+    let mut totals: BTreeMap<Uuid, (Decimal, Decimal)> = BTreeMap::new();
+    for tx in ledger.transactions.iter().filter(|t| options.includes_transaction(t, ledger)) {
+        let in_current = current.contains(&tx.date);
+        if !in_current && !prior.contains(&tx.date) {
+            continue;
+        }
+        for posting in &tx.postings {
+            let entry = totals.entry(posting.account_id).or_default();
+            if in_current {
+                entry.0 += posting.amount;
+            } else {
+                entry.1 += posting.amount;
+            }
+        }
+    }
+
+    let mut doc = ReportDocument::new(
+        "Income Statement Comparison",
+        Some(*current.start()),
+        *current.end(),
+        vec![
+            format!("{} to {}", current.start(), current.end()),
+            format!("{} to {}", prior.start(), prior.end()),
+            "Change".to_string(),
+        ],
+    );
+    for (title, account_type) in [("Revenue", AccountType::Revenue), ("Expenses", AccountType::Expense)] {
+        // Revenue is credit-normal; flip it so growth reads positive in both sections
+        let sign = if account_type == AccountType::Revenue { -Decimal::ONE } else { Decimal::ONE };
+        let mut rows: Vec<ReportRow> = totals.iter()
+            .filter_map(|(account_id, (cur, pri))| {
+                let account = ledger.accounts.get(account_id)?;
+                if account.r#type != account_type || !options.includes(account, *prior.start()) {
+                    return None;
+                }
+                let (cur, pri) = (*cur * sign, *pri * sign);
+                Some(ReportRow {
+                    label: account.name.clone(),
+                    account_id: Some(*account_id),
+                    depth: 0,
+                    values: vec![cur, pri, cur - pri],
+                    queries: vec![
+                        Some(CellQuery::period(Some(*current.start()), *current.end()).account(*account_id)),
+                        Some(CellQuery::period(Some(*prior.start()), *prior.end()).account(*account_id)),
+                        None,
+                    ],
+                })
+            })
+            .collect();
+        rows.sort_by(|a, b| a.label.cmp(&b.label));
+        let total = (0..3).map(|i| rows.iter().map(|r| r.values[i]).sum()).collect();
+        doc.sections.push(ReportSection {
+            title: title.to_string(),
+            rows,
+            total: Some(total),
+            total_queries: vec![
+                Some(CellQuery::period(Some(*current.start()), *current.end()).account_types(&[account_type])),
+                Some(CellQuery::period(Some(*prior.start()), *prior.end()).account_types(&[account_type])),
+                None,
+            ],
+        });
+    }
+    doc
+}
@@ -0,0 +1,131 @@
+//! Report delivery targets (file, webhook, SMTP)
+use std::path::PathBuf;
+
+use super::{ReportDocument, ReportFormat};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeliveryError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Transport error: {0}")]
+    Transport(String),
+}
+
+/// Destination a rendered report is handed to
+pub trait ReportDelivery: Send + Sync {
+    fn deliver(&self, report: &ReportDocument) -> Result<(), DeliveryError>;
+}
+
+/// Writes reports into a directory, one file per run
+pub struct FileDelivery {
+    pub dir: PathBuf,
+    pub format: ReportFormat,
+}
+
+impl ReportDelivery for FileDelivery {
+    fn deliver(&self, report: &ReportDocument) -> Result<(), DeliveryError> {
+        std::fs::create_dir_all(&self.dir)?;
+        let name = format!("{}-{}.{}", file_stem(&report.title), report.period_end, self.format.extension());
+        std::fs::write(self.dir.join(name), report.render(self.format))?;
+        Ok(())
+    }
+}
+
+/// File name part for a report title: lowercase ASCII letters and digits joined by single
+/// dashes, so a title can never name a path outside the target directory
+fn file_stem(title: &str) -> String {
+    let stem = title
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if stem.is_empty() { "report".to_string() } else { stem }
+}
+
+/// POSTs the JSON report to a URL
+#[cfg(feature = "webhook")]
+pub struct WebhookDelivery {
+    pub url: String,
+}
+
+#[cfg(feature = "webhook")]
+impl ReportDelivery for WebhookDelivery {
+    fn deliver(&self, report: &ReportDocument) -> Result<(), DeliveryError> {
+        ureq::post(&self.url)
+            .set("Content-Type", "application/json")
+            .send_string(&report.to_json())
+            .map_err(|e| DeliveryError::Transport(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Emails the text report with the CSV attached (and the HTML page when the `html` feature is on)
+#[cfg(feature = "smtp")]
+pub struct SmtpDelivery {
+    pub relay: String,
+    pub username: String,
+    pub password: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+#[cfg(feature = "smtp")]
+impl ReportDelivery for SmtpDelivery {
+    fn deliver(&self, report: &ReportDocument) -> Result<(), DeliveryError> {
+        use lettre::message::{header::ContentType, Attachment, MultiPart, SinglePart};
+        use lettre::transport::smtp::authentication::Credentials;
+        use lettre::{Message, SmtpTransport, Transport};
+
+        let transport_err = |e: &dyn std::fmt::Display| DeliveryError::Transport(e.to_string());
+
+        let mut builder = Message::builder()
+            .from(self.from.parse().map_err(|e| transport_err(&e))?)
+            .subject(report.title.clone());
+        for to in &self.to {
+            builder = builder.to(to.parse().map_err(|e| transport_err(&e))?);
+        }
+        let stem = file_stem(&report.title);
+        let parts = MultiPart::mixed()
+            .singlepart(SinglePart::plain(report.to_text()))
+            .singlepart(Attachment::new(format!("{}.csv", stem)).body(report.to_csv(), ContentType::parse("text/csv").unwrap()));
+        #[cfg(feature = "html")]
+        let parts = parts.singlepart(Attachment::new(format!("{}.html", stem)).body(report.to_html(), ContentType::TEXT_HTML));
+        let email = builder.multipart(parts).map_err(|e| transport_err(&e))?;
+
+        let mailer = SmtpTransport::relay(&self.relay)
+            .map_err(|e| transport_err(&e))?
+            .credentials(Credentials::new(self.username.clone(), self.password.clone()))
+            .build();
+        mailer.send(&email).map_err(|e| transport_err(&e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use crate::reports::{ReportRow, ReportSection};
+
+    #[test]
+    fn file_name_stays_in_the_directory() {
+        assert_eq!(file_stem("../../etc/Profit & Loss"), "etc-profit-loss");
+        assert_eq!(file_stem("/.."), "report");
+
+        let dir = std::env::temp_dir().join(format!("delivery-{}", uuid::Uuid::new_v4()));
+        let mut report = ReportDocument::new("../escape", None, NaiveDate::from_ymd_opt(2024, 5, 31).unwrap(), vec!["Amount".to_string()]);
+        report.sections.push(ReportSection {
+            title: "Income".to_string(),
+            rows: vec![ReportRow { label: "Sales".to_string(), account_id: None, depth: 0, values: vec![Decimal::from(5)], queries: Vec::new() }],
+            total: Some(vec![Decimal::from(5)]),
+            total_queries: Vec::new(),
+        });
+        FileDelivery { dir: dir.clone(), format: ReportFormat::Csv }.deliver(&report).unwrap();
+
+        let written = std::fs::read_to_string(dir.join("escape-2024-05-31.csv")).unwrap();
+        assert!(written.ends_with("Income,Total,5\n"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
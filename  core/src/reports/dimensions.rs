@@ -0,0 +1,128 @@
+//! Income statement cross-tab over any two reporting dimensions
+use std::collections::BTreeMap;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::ledger::{AccountType, Posting};
+use crate::sync::SyncableLedger;
+use super::{CellQuery, ReportDocument, ReportRow, ReportSection};
+
+/// Axis a report can be broken down by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Dimension {
+    Account,
+    Project,
+    Class,
+}
+
+impl Dimension {
+    pub(crate) fn key(&self, posting: &Posting) -> Option<Uuid> {
+        match self {
+            Dimension::Account => Some(posting.account_id),
+            Dimension::Project => posting.project_id,
+            Dimension::Class => posting.class_id,
+        }
+    }
+
+    fn label(&self, ledger: &SyncableLedger, key: Option<Uuid>) -> String {
+        let Some(id) = key else { return "Unassigned".to_string() };
+        let name = match self {
+            Dimension::Account => ledger.accounts.get(&id).map(|a| a.name.clone()),
+            Dimension::Project => ledger.projects.get(&id).map(|p| p.name.clone()),
+            Dimension::Class => ledger.classes.get(&id).map(|c| c.name.clone()),
+        };
+        name.unwrap_or_else(|| id.to_string())
+    }
+}
+
+/// Net income contribution (revenue positive, expenses negative) with `rows` down the side and
+/// optionally `columns` across the top; a trailing Total column is always included
+pub fn dimension_report(
+    ledger: &SyncableLedger,
+    start: NaiveDate,
+    end: NaiveDate,
+    rows: Dimension,
+    columns: Option<Dimension>,
+) -> ReportDocument {
+    let mut cells: BTreeMap<(String, String), Decimal> = BTreeMap::new();
+    let mut row_keys: BTreeMap<String, Option<Uuid>> = BTreeMap::new();
+    let mut column_keys: BTreeMap<String, Option<Uuid>> = BTreeMap::new();
+
+    for tx in ledger.transactions.iter().filter(|t| start <= t.date && t.date <= end) {
+        for posting in &tx.postings {
+            let is_pnl = ledger.accounts.get(&posting.account_id)
+                .is_some_and(|a| matches!(a.r#type, AccountType::Revenue | AccountType::Expense));
+            if !is_pnl {
+                continue;
+            }
+            let row_key = rows.key(posting);
+            let row = rows.label(ledger, row_key);
+            let column = match columns {
+                Some(c) => {
+                    let key = c.key(posting);
+                    let label = c.label(ledger, key);
+                    column_keys.insert(label.clone(), key);
+                    label
+                }
+                None => String::new(),
+            };
+            row_keys.insert(row.clone(), row_key);
+            *cells.entry((row, column)).or_insert(Decimal::ZERO) -= posting.amount;
+        }
+    }
+    let column_labels: Vec<String> = column_keys.keys().cloned().collect();
+
+    let mut headers = column_labels.clone();
+    headers.push("Total".to_string());
+    let mut doc = ReportDocument::new("Income by Dimension", Some(start), end, headers);
+
+    let base = CellQuery::period(Some(start), end)
+        .account_types(&[AccountType::Revenue, AccountType::Expense]);
+    let column_query = |q: CellQuery, label: &String| match columns {
+        Some(c) => q.dimension(c, column_keys[label]),
+        None => q,
+    };
+
+    let mut grand = vec![Decimal::ZERO; column_labels.len() + 1];
+    let report_rows = row_keys.into_iter()
+        .map(|(label, key)| {
+            let mut values: Vec<Decimal> = column_labels.iter()
+                .map(|c| cells.get(&(label.clone(), c.clone())).copied().unwrap_or(Decimal::ZERO))
+                .collect();
+            let total = if columns.is_some() {
+                values.iter().copied().sum()
+            } else {
+                cells.get(&(label.clone(), String::new())).copied().unwrap_or(Decimal::ZERO)
+            };
+            values.push(total);
+            let row_query = base.clone().dimension(rows, key);
+            let mut queries: Vec<Option<CellQuery>> = column_labels.iter()
+                .map(|c| Some(column_query(row_query.clone(), c)))
+                .collect();
+            queries.push(Some(row_query));
+            for (g, v) in grand.iter_mut().zip(&values) {
+                *g += *v;
+            }
+            ReportRow {
+                label,
+                account_id: if rows == Dimension::Account { key } else { None },
+                depth: 0,
+                values,
+                queries,
+            }
+        })
+        .collect();
+
+    doc.sections.push(ReportSection {
+        title: "Net Income".to_string(),
+        rows: report_rows,
+        total: Some(grand),
+        total_queries: column_labels.iter()
+            .map(|c| Some(column_query(base.clone(), c)))
+            .chain(std::iter::once(Some(base.clone())))
+            .collect(),
+    });
+    doc
+}
@@ -0,0 +1,82 @@
+//! Drill-down from a report cell to the transactions behind it
+use chrono::NaiveDate;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::ledger::{AccountType, Posting, Transaction};
+use crate::sync::SyncableLedger;
+use super::dimensions::Dimension;
+
+/// Query that produced a report cell; empty lists match anything
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CellQuery {
+    pub start: Option<NaiveDate>,
+    pub end: NaiveDate,
+    #[serde(default)]
+    pub accounts: Vec<Uuid>,
+    #[serde(default)]
+    pub account_types: Vec<AccountType>,
+    /// Required dimension values (None = posting has no value for that dimension)
+    #[serde(default)]
+    pub dimensions: Vec<(Dimension, Option<Uuid>)>,
+    /// Only transactions with this counterparty
+    #[serde(default)]
+    pub contact_id: Option<Uuid>,
+}
+
+impl CellQuery {
+    pub fn period(start: Option<NaiveDate>, end: NaiveDate) -> Self {
+        Self {
+            start,
+            end,
+            accounts: Vec::new(),
+            account_types: Vec::new(),
+            dimensions: Vec::new(),
+            contact_id: None,
+        }
+    }
+
+    pub fn account(mut self, account_id: Uuid) -> Self {
+        self.accounts.push(account_id);
+        self
+    }
+
+    pub fn account_types(mut self, types: &[AccountType]) -> Self {
+        self.account_types.extend_from_slice(types);
+        self
+    }
+
+    pub fn dimension(mut self, dimension: Dimension, value: Option<Uuid>) -> Self {
+        self.dimensions.push((dimension, value));
+        self
+    }
+
+    pub fn contact(mut self, contact_id: Uuid) -> Self {
+        self.contact_id = Some(contact_id);
+        self
+    }
+
+    fn matches_posting(&self, ledger: &SyncableLedger, posting: &Posting) -> bool {
+        (self.accounts.is_empty() || self.accounts.contains(&posting.account_id))
+            && (self.account_types.is_empty()
+                || ledger.accounts.get(&posting.account_id).is_some_and(|a| self.account_types.contains(&a.r#type)))
+            && self.dimensions.iter().all(|(d, value)| d.key(posting) == *value)
+    }
+
+    /// Transactions with at least one posting contributing to the cell
+    pub fn transactions<'a>(&self, ledger: &'a SyncableLedger) -> Vec<&'a Transaction> {
+        ledger.transactions.iter()
+            .filter(|t| self.start.is_none_or(|s| s <= t.date) && t.date <= self.end)
+            .filter(|t| self.contact_id.is_none_or(|c| t.contact_id == Some(c)))
+            .filter(|t| t.postings.iter().any(|p| self.matches_posting(ledger, p)))
+            .collect()
+    }
+}
+
+/// Address of one figure in a report; `row: None` is the section total
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellRef {
+    pub section: usize,
+    pub row: Option<usize>,
+    pub column: usize,
+}
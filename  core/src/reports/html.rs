@@ -0,0 +1,191 @@
+//! Self-contained HTML rendering with inline SVG charts: no scripts, stylesheets or images
+//! to fetch, so the page can be served by a headless node or sent as an email attachment
+use std::fmt::Write;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+
+use super::{ChartType, ReportChart, ReportDocument};
+use crate::locale::Locale;
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;margin-bottom:1.5em}\
+th,td{padding:2px 12px}td.num{text-align:right;font-variant-numeric:tabular-nums}\
+tr.total td{border-top:1px solid #888;font-weight:bold}\
+figure{display:inline-block;margin:0 2em 2em 0}";
+
+const PALETTE: [&str; 8] = ["#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#9c755f"];
+
+const WIDTH: f64 = 480.0;
+const HEIGHT: f64 = 240.0;
+const MARGIN: f64 = 24.0;
+
+impl ReportDocument {
+    pub fn to_html(&self) -> String {
+        self.to_html_with(&Locale::default())
+    }
+
+    /// Full HTML page using the locale's number and date conventions
+    pub fn to_html_with(&self, locale: &Locale) -> String {
+        let mut out = String::from("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">");
+        let _ = writeln!(out, "<title>{}</title><style>{}</style></head><body>", escape(&self.title), STYLE);
+        let _ = writeln!(out, "<h1>{}</h1>", escape(&self.title));
+        let period = match self.period_start {
+            Some(start) => format!("{} to {}", locale.format_date(start), locale.format_date(self.period_end)),
+            None => format!("As of {}", locale.format_date(self.period_end)),
+        };
+        let _ = writeln!(out, "<p>{}</p>", escape(&period));
+
+        for chart in &self.charts {
+            let _ = writeln!(out, "<figure>{}<figcaption>{}</figcaption></figure>", chart_svg(chart, locale), escape(&chart.title));
+        }
+
+        for section in &self.sections {
+            let _ = write!(out, "<h2>{}</h2>\n<table><tr><th></th>", escape(&section.title));
+            for column in &self.columns {
+                let _ = write!(out, "<th>{}</th>", escape(column));
+            }
+            out.push_str("</tr>\n");
+            for row in &section.rows {
+                let _ = write!(out, "<tr><td style=\"padding-left:{}em\">{}</td>", row.depth + 1, escape(&row.label));
+                push_values(&mut out, &row.values, locale);
+                out.push_str("</tr>\n");
+            }
+            if let Some(total) = &section.total {
+                let _ = write!(out, "<tr class=\"total\"><td>Total {}</td>", escape(&section.title));
+                push_values(&mut out, total, locale);
+                out.push_str("</tr>\n");
+            }
+            out.push_str("</table>\n");
+        }
+        out.push_str("</body></html>\n");
+        out
+    }
+}
+
+fn push_values(out: &mut String, values: &[Decimal], locale: &Locale) {
+    for value in values {
+        let _ = write!(out, "<td class=\"num\">{}</td>", escape(&locale.format_amount(*value, 2)));
+    }
+}
+
+/// Inline SVG for one chart; empty charts render as an empty frame
+pub fn chart_svg(chart: &ReportChart, locale: &Locale) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" role=\"img\">",
+        w = WIDTH,
+        h = HEIGHT,
+    );
+    let _ = write!(svg, "<title>{}</title>", escape(&chart.title));
+    match chart.chart_type {
+        ChartType::Line => line_chart(&mut svg, chart, locale),
+        ChartType::Pie => pie_chart(&mut svg, chart),
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn line_chart(svg: &mut String, chart: &ReportChart, locale: &Locale) {
+    let values: Vec<f64> = chart.points.iter().map(|p| p.value.to_f64().unwrap_or(0.0)).collect();
+    if values.is_empty() {
+        return;
+    }
+    // Keep zero in range so the baseline is meaningful
+    let min = values.iter().copied().fold(0.0, f64::min);
+    let max = values.iter().copied().fold(0.0, f64::max);
+    let span = if max > min { max - min } else { 1.0 };
+    let step = if values.len() > 1 { (WIDTH - 2.0 * MARGIN) / (values.len() - 1) as f64 } else { 0.0 };
+    let y = |v: f64| HEIGHT - MARGIN - (v - min) / span * (HEIGHT - 2.0 * MARGIN);
+
+    let _ = write!(
+        svg,
+        "<line x1=\"{m}\" y1=\"{z:.1}\" x2=\"{x}\" y2=\"{z:.1}\" stroke=\"#bbb\"/>",
+        m = MARGIN,
+        x = WIDTH - MARGIN,
+        z = y(0.0),
+    );
+    let points: Vec<String> = values.iter()
+        .enumerate()
+        .map(|(i, v)| format!("{:.1},{:.1}", MARGIN + step * i as f64, y(*v)))
+        .collect();
+    let _ = write!(svg, "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/>", PALETTE[0], points.join(" "));
+
+    // Label the first and last points with their dates and values
+    let last = chart.points.len() - 1;
+    for (i, anchor) in [(0, "start"), (last, "end")] {
+        let point = &chart.points[i];
+        let _ = write!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{}\" font-size=\"11\" text-anchor=\"{}\">{} {}</text>",
+            MARGIN + step * i as f64,
+            HEIGHT - 6.0,
+            anchor,
+            escape(&point.label),
+            escape(&locale.format_amount(point.value, 2)),
+        );
+        if last == 0 {
+            break;
+        }
+    }
+}
+
+fn pie_chart(svg: &mut String, chart: &ReportChart) {
+    let values: Vec<f64> = chart.points.iter().map(|p| p.value.to_f64().unwrap_or(0.0).max(0.0)).collect();
+    let total: f64 = values.iter().sum();
+    if total <= 0.0 {
+        return;
+    }
+    let radius = HEIGHT / 2.0 - MARGIN;
+    let (cx, cy) = (HEIGHT / 2.0, HEIGHT / 2.0);
+    let mut angle = -std::f64::consts::FRAC_PI_2;
+    for (i, (point, value)) in chart.points.iter().zip(&values).enumerate() {
+        let color = PALETTE[i % PALETTE.len()];
+        let share = value / total;
+        if share >= 1.0 {
+            let _ = write!(svg, "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>", cx, cy, radius, color);
+        } else if share > 0.0 {
+            let end = angle + share * std::f64::consts::TAU;
+            let _ = write!(
+                svg,
+                "<path d=\"M{cx},{cy} L{:.2},{:.2} A{r},{r} 0 {} 1 {:.2},{:.2} Z\" fill=\"{}\"/>",
+                cx + radius * angle.cos(),
+                cy + radius * angle.sin(),
+                u8::from(share > 0.5),
+                cx + radius * end.cos(),
+                cy + radius * end.sin(),
+                color,
+                cx = cx,
+                cy = cy,
+                r = radius,
+            );
+            angle = end;
+        }
+        // Legend to the right of the pie
+        let ly = MARGIN + 16.0 * i as f64;
+        let _ = write!(
+            svg,
+            "<rect x=\"{x}\" y=\"{y}\" width=\"10\" height=\"10\" fill=\"{c}\"/><text x=\"{tx}\" y=\"{ty}\" font-size=\"11\">{l} ({p:.1}%)</text>",
+            x = HEIGHT,
+            y = ly,
+            c = color,
+            tx = HEIGHT + 16.0,
+            ty = ly + 9.0,
+            l = escape(&point.label),
+            p = share * 100.0,
+        );
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
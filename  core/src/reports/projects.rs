@@ -0,0 +1,69 @@
+//! Profit and loss per project
+use std::collections::BTreeMap;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::ledger::AccountType;
+use crate::sync::SyncableLedger;
+use super::{CellQuery, Dimension, ReportDocument, ReportRow, ReportSection};
+
+/// Revenue, expenses and net for each project over the period; untagged postings are ignored
+pub fn project_pnl(ledger: &SyncableLedger, start: NaiveDate, end: NaiveDate) -> ReportDocument {
+    // project -> account -> amount
+    let mut totals: BTreeMap<Uuid, BTreeMap<Uuid, Decimal>> = BTreeMap::new();
+    for tx in ledger.transactions.iter().filter(|t| start <= t.date && t.date <= end) {
+        for posting in &tx.postings {
+            let Some(project_id) = posting.project_id else { continue };
+            *totals.entry(project_id).or_default().entry(posting.account_id).or_insert(Decimal::ZERO) += posting.amount;
+        }
+    }
+
+    let mut doc = ReportDocument::new(
+        "Project Profit and Loss",
+        Some(start),
+        end,
+        vec!["Revenue".to_string(), "Expenses".to_string(), "Net".to_string()],
+    );
+
+    let mut sections: Vec<ReportSection> = totals.into_iter()
+        .map(|(project_id, by_account)| {
+            let title = ledger.projects.get(&project_id)
+                .map(|p| p.name.clone())
+                .unwrap_or_else(|| project_id.to_string());
+            let base = CellQuery::period(Some(start), end).dimension(Dimension::Project, Some(project_id));
+            let mut rows = Vec::new();
+            let (mut revenue, mut expenses) = (Decimal::ZERO, Decimal::ZERO);
+            for (account_id, amount) in by_account {
+                let Some(account) = ledger.accounts.get(&account_id) else { continue };
+                let query = Some(base.clone().account(account_id));
+                let (values, queries) = match account.r#type {
+                    AccountType::Revenue => {
+                        revenue -= amount;
+                        (vec![-amount, Decimal::ZERO, -amount], vec![query.clone(), None, query])
+                    }
+                    AccountType::Expense => {
+                        expenses += amount;
+                        (vec![Decimal::ZERO, amount, -amount], vec![None, query.clone(), query])
+                    }
+                    _ => continue,
+                };
+                rows.push(ReportRow { label: account.name.clone(), account_id: Some(account_id), depth: 0, values, queries });
+            }
+            rows.sort_by(|a, b| a.label.cmp(&b.label));
+            ReportSection {
+                title,
+                rows,
+                total: Some(vec![revenue, expenses, revenue - expenses]),
+                total_queries: vec![
+                    Some(base.clone().account_types(&[AccountType::Revenue])),
+                    Some(base.clone().account_types(&[AccountType::Expense])),
+                    Some(base.account_types(&[AccountType::Revenue, AccountType::Expense])),
+                ],
+            }
+        })
+        .collect();
+    sections.sort_by(|a, b| a.title.cmp(&b.title));
+    doc.sections = sections;
+    doc
+}
@@ -0,0 +1,247 @@
+//! Account register: every transaction touching one account with a running balance, for printing
+//! and reconciling against a statement
+use std::collections::{BTreeMap, BTreeSet};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::currency::Commodity;
+use crate::ledger::{AccountKind, Transaction};
+use crate::sync::SyncableLedger;
+use super::{csv_field, CellQuery, ReportDocument, ReportRow, ReportSection};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegisterLine {
+    pub date: NaiveDate,
+    pub transaction_id: Uuid,
+    #[serde(default)]
+    pub reference: Option<String>,
+    pub description: String,
+    #[serde(default)]
+    pub commodity: Commodity,
+    pub debit: Decimal,
+    pub credit: Decimal,
+    /// Balance in `commodity` after this line, positive on the account's normal side
+    pub balance: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Register {
+    pub account_id: Uuid,
+    pub account_name: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Balances carried in from before `from`, per commodity
+    pub opening_balances: BTreeMap<Commodity, Decimal>,
+    pub lines: Vec<RegisterLine>,
+}
+
+impl Register {
+    /// Balance per commodity after the last line
+    pub fn closing_balances(&self) -> BTreeMap<Commodity, Decimal> {
+        let mut balances = self.opening_balances.clone();
+        for line in &self.lines {
+            balances.insert(line.commodity.clone(), line.balance);
+        }
+        balances
+    }
+
+    fn commodities(&self) -> BTreeSet<&Commodity> {
+        self.opening_balances.keys().chain(self.lines.iter().map(|l| &l.commodity)).collect()
+    }
+
+    /// As a report document with a section per commodity, one row per line labelled with date
+    /// and description
+    pub fn to_document(&self) -> ReportDocument {
+        let mut doc = ReportDocument::new(
+            &format!("Register: {}", self.account_name),
+            Some(self.from),
+            self.to,
+            vec!["Debit".to_string(), "Credit".to_string(), "Balance".to_string()],
+        );
+        let commodities = self.commodities();
+        let closing = self.closing_balances();
+        for commodity in &commodities {
+            let opening = self.opening_balances.get(*commodity).copied().unwrap_or_default();
+            let lines: Vec<&RegisterLine> = self.lines.iter().filter(|l| &l.commodity == *commodity).collect();
+            let mut rows = vec![ReportRow {
+                label: "Opening balance".to_string(),
+                account_id: Some(self.account_id),
+                depth: 0,
+                values: vec![Decimal::ZERO, Decimal::ZERO, opening],
+                queries: Vec::new(),
+            }];
+            rows.extend(lines.iter().map(|line| {
+                let query = Some(CellQuery::period(Some(line.date), line.date).account(self.account_id));
+                ReportRow {
+                    label: format!("{} {}", line.date, line.description),
+                    account_id: Some(self.account_id),
+                    depth: 0,
+                    values: vec![line.debit, line.credit, line.balance],
+                    queries: vec![query.clone(), query, None],
+                }
+            }));
+            let debits = lines.iter().map(|l| l.debit).sum();
+            let credits = lines.iter().map(|l| l.credit).sum();
+            let title = if commodities.len() > 1 {
+                format!("{} ({})", self.account_name, commodity)
+            } else {
+                self.account_name.clone()
+            };
+            doc.sections.push(ReportSection {
+                title,
+                rows,
+                total: Some(vec![debits, credits, closing.get(*commodity).copied().unwrap_or_default()]),
+                total_queries: Vec::new(),
+            });
+        }
+        doc
+    }
+
+    /// One line per transaction and commodity with separate date and reference columns
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("date,reference,description,commodity,debit,credit,balance\n");
+        for (commodity, balance) in &self.opening_balances {
+            out.push_str(&format!("{},,Opening balance,{},,,{}\n", self.from, csv_field(commodity.code()), balance));
+        }
+        for line in &self.lines {
+            out.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                line.date,
+                csv_field(line.reference.as_deref().unwrap_or("")),
+                csv_field(&line.description),
+                csv_field(line.commodity.code()),
+                line.debit,
+                line.credit,
+                line.balance,
+            ));
+        }
+        out
+    }
+}
+
+/// Register of one account between `from` and `to` (inclusive); None for an unknown account.
+/// Each commodity keeps its own running balance. Transactions on the same day keep a stable
+/// order on every replica: opening balances first, corrections after what they correct and
+/// closing entries last, then by transaction id.
+pub fn register(ledger: &SyncableLedger, account_id: Uuid, from: NaiveDate, to: NaiveDate) -> Option<Register> {
+    let account = ledger.accounts.get(&account_id)?;
+    let sign = match account.r#type.natural_balance() {
+        AccountKind::Debit => Decimal::ONE,
+        AccountKind::Credit => -Decimal::ONE,
+    };
+    // Net amount per commodity the transaction posts to the account
+    let amounts_in = |tx: &Transaction| -> BTreeMap<Commodity, Decimal> {
+        let mut amounts = BTreeMap::new();
+        for posting in tx.postings.iter().filter(|p| p.account_id == account_id) {
+            *amounts.entry(posting.commodity.clone()).or_insert(Decimal::ZERO) += posting.amount;
+        }
+        amounts
+    };
+
+    let mut touching: Vec<&Transaction> = ledger.transactions.iter()
+        .filter(|t| t.date <= to && t.postings.iter().any(|p| p.account_id == account_id))
+        .collect();
+    touching.sort_by_key(|t| (t.date, !t.is_opening_balance, t.is_closing_entry, t.corrects.is_some(), t.id));
+
+    let mut opening_balances: BTreeMap<Commodity, Decimal> = BTreeMap::new();
+    for tx in touching.iter().filter(|t| t.date < from) {
+        for (commodity, amount) in amounts_in(tx) {
+            *opening_balances.entry(commodity).or_insert(Decimal::ZERO) += amount * sign;
+        }
+    }
+    let mut balances = opening_balances.clone();
+    let mut lines = Vec::new();
+    for tx in touching.into_iter().filter(|t| t.date >= from) {
+        for (commodity, amount) in amounts_in(tx) {
+            let balance = balances.entry(commodity.clone()).or_insert(Decimal::ZERO);
+            *balance += amount * sign;
+            lines.push(RegisterLine {
+                date: tx.date,
+                transaction_id: tx.id,
+                reference: tx.reference.clone(),
+                description: tx.description.clone(),
+                commodity,
+                debit: amount.max(Decimal::ZERO),
+                credit: (-amount).max(Decimal::ZERO),
+                balance: *balance,
+            });
+        }
+    }
+
+    Some(Register {
+        account_id,
+        account_name: account.name.clone(),
+        from,
+        to,
+        opening_balances,
+        lines,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::{Account, AccountType, Posting};
+
+    fn posting(account_id: Uuid, amount: i64, commodity: &str) -> Posting {
+        let mut posting = Posting::new(account_id, Decimal::from(amount));
+        posting.commodity = Commodity::new(commodity);
+        posting
+    }
+
+    #[test]
+    fn running_balance_is_kept_per_commodity() {
+        let mut ledger = SyncableLedger::new();
+        let cash = Account::new("Cash", AccountType::Asset);
+        let sales = Account::new("Sales", AccountType::Revenue);
+        let (cash_id, sales_id) = (cash.id, sales.id);
+        ledger.accounts.insert(cash.id, cash);
+        ledger.accounts.insert(sales.id, sales);
+        let january = NaiveDate::from_ymd_opt(2024, 1, 10).unwrap();
+        let march = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        ledger.transactions.push(Transaction::new(january, "Earlier", vec![
+            posting(cash_id, 30, "USD"),
+            posting(sales_id, -30, "USD"),
+        ]));
+        ledger.transactions.push(Transaction::new(march, "USD sale", vec![
+            posting(cash_id, 100, "USD"),
+            posting(sales_id, -100, "USD"),
+        ]));
+        ledger.transactions.push(Transaction::new(march, "EUR sale", vec![
+            posting(cash_id, 50, "EUR"),
+            posting(sales_id, -50, "EUR"),
+        ]));
+
+        let register = register(&ledger, cash_id, NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(), march).unwrap();
+        assert_eq!(register.opening_balances.get(&Commodity::new("USD")), Some(&Decimal::from(30)));
+        for line in &register.lines {
+            let expected = if line.commodity == Commodity::new("USD") { 130 } else { 50 };
+            assert_eq!(line.balance, Decimal::from(expected));
+        }
+        let closing = register.closing_balances();
+        assert_eq!(closing.get(&Commodity::new("EUR")), Some(&Decimal::from(50)));
+        assert_eq!(closing.get(&Commodity::new("USD")), Some(&Decimal::from(130)));
+        assert_eq!(register.to_document().sections.len(), 2);
+    }
+
+    #[test]
+    fn same_day_order_follows_transaction_ids() {
+        let mut ledger = SyncableLedger::new();
+        let cash = Account::new("Cash", AccountType::Asset);
+        let cash_id = cash.id;
+        ledger.accounts.insert(cash.id, cash);
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let first = Transaction::new(date, "A", vec![Posting::new(cash_id, Decimal::from(1))]);
+        let second = Transaction::new(date, "B", vec![Posting::new(cash_id, Decimal::from(2))]);
+        let mut reversed = ledger.clone();
+        ledger.transactions.extend([first.clone(), second.clone()]);
+        reversed.transactions.extend([second, first]);
+
+        let ids = |ledger: &SyncableLedger| -> Vec<Uuid> {
+            register(ledger, cash_id, date, date).unwrap().lines.iter().map(|l| l.transaction_id).collect()
+        };
+        assert_eq!(ids(&ledger), ids(&reversed));
+    }
+}
@@ -0,0 +1,246 @@
+//! Report scheduling (e.g. monthly P&L on the 1st) for the headless server node
+use std::collections::BTreeMap;
+use chrono::{Datelike, NaiveDate, Weekday};
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::ledger::Ledger;
+use crate::storage::LocalStorage;
+use super::delivery::{DeliveryError, ReportDelivery};
+use super::ReportDocument;
+
+/// How often a scheduled report runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Cadence {
+    Daily,
+    Weekly(Weekday),
+    /// Day of month, clamped to the last day in short months
+    Monthly(u32),
+}
+
+impl Cadence {
+    pub fn occurs_on(&self, date: NaiveDate) -> bool {
+        match *self {
+            Cadence::Daily => true,
+            Cadence::Weekly(weekday) => date.weekday() == weekday,
+            Cadence::Monthly(day) => date.day() == day.clamp(1, days_in_month(date)),
+        }
+    }
+}
+
+fn days_in_month(date: NaiveDate) -> u32 {
+    let (year, month) = if date.month() == 12 { (date.year() + 1, 1) } else { (date.year(), date.month() + 1) };
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|d| d.pred_opt())
+        .map(|d| d.day())
+        .unwrap_or(31)
+}
+
+const SETTINGS_KEY: &str = "report_schedule_state";
+
+/// Builds a report for the run date
+pub type ReportGenerator = Box<dyn Fn(&Ledger, NaiveDate) -> ReportDocument + Send + Sync>;
+
+pub struct ReportSchedule {
+    pub id: Uuid,
+    pub name: String,
+    pub cadence: Cadence,
+    pub last_run: Option<NaiveDate>,
+    generator: ReportGenerator,
+}
+
+impl ReportSchedule {
+    pub fn new(name: &str, cadence: Cadence, generator: ReportGenerator) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            cadence,
+            last_run: None,
+            generator,
+        }
+    }
+
+    /// Due if an occurrence fell after the last run (catching up missed days), or today when never run
+    pub fn is_due(&self, today: NaiveDate) -> bool {
+        match self.last_run {
+            None => self.cadence.occurs_on(today),
+            Some(last) if last >= today => false,
+            Some(last) => last
+                .iter_days()
+                .skip(1)
+                .take_while(|d| *d <= today)
+                .any(|d| self.cadence.occurs_on(d)),
+        }
+    }
+}
+
+/// Outcome of one scheduled report run
+#[derive(Debug)]
+pub struct ScheduledRun {
+    pub schedule_id: Uuid,
+    pub name: String,
+    pub results: Vec<Result<(), DeliveryError>>,
+}
+
+impl ScheduledRun {
+    /// At least one target took the report
+    pub fn delivered(&self) -> bool {
+        self.results.iter().any(Result::is_ok)
+    }
+}
+
+/// Runs due report schedules and hands the output to every delivery target. Last runs are
+/// kept by schedule name, since schedules are registered again with their generators on start.
+#[derive(Default)]
+pub struct ReportScheduler {
+    schedules: Vec<ReportSchedule>,
+    deliveries: Vec<Box<dyn ReportDelivery>>,
+    /// Saved last runs of schedules not registered yet
+    restored: BTreeMap<String, NaiveDate>,
+}
+
+impl ReportScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_schedule(&mut self, mut schedule: ReportSchedule) -> Uuid {
+        if let Some(last_run) = self.restored.remove(&schedule.name) {
+            schedule.last_run = schedule.last_run.max(Some(last_run));
+        }
+        let id = schedule.id;
+        self.schedules.push(schedule);
+        id
+    }
+
+    pub fn remove_schedule(&mut self, id: &Uuid) {
+        self.schedules.retain(|s| &s.id != id);
+    }
+
+    pub fn add_delivery(&mut self, delivery: Box<dyn ReportDelivery>) {
+        self.deliveries.push(delivery);
+    }
+
+    pub fn schedules(&self) -> &[ReportSchedule] {
+        &self.schedules
+    }
+
+    /// Render and deliver every schedule due on `today`. A run no target took stays due and is
+    /// retried on the next call.
+    pub fn run_due(&mut self, ledger: &Ledger, today: NaiveDate) -> Vec<ScheduledRun> {
+        let mut runs = Vec::new();
+        for schedule in self.schedules.iter_mut().filter(|s| s.is_due(today)) {
+            let report = (schedule.generator)(ledger, today);
+            let run = ScheduledRun {
+                schedule_id: schedule.id,
+                name: schedule.name.clone(),
+                results: self.deliveries.iter().map(|d| d.deliver(&report)).collect(),
+            };
+            if run.delivered() {
+                schedule.last_run = Some(today);
+            }
+            runs.push(run);
+        }
+        runs
+    }
+
+    /// Restore last runs saved by a previous run; schedules added later pick theirs up on
+    /// `add_schedule`
+    pub fn load(&mut self, storage: &LocalStorage) {
+        let mut saved: BTreeMap<String, NaiveDate> = storage.get_setting(SETTINGS_KEY)
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        for schedule in &mut self.schedules {
+            if let Some(last_run) = saved.remove(&schedule.name) {
+                schedule.last_run = schedule.last_run.max(Some(last_run));
+            }
+        }
+        self.restored = saved;
+    }
+
+    pub fn save(&self, storage: &LocalStorage) {
+        let mut last_runs: BTreeMap<&str, NaiveDate> = self.restored.iter().map(|(n, d)| (n.as_str(), *d)).collect();
+        last_runs.extend(self.schedules.iter().filter_map(|s| Some((s.name.as_str(), s.last_run?))));
+        storage.set_setting(SETTINGS_KEY, &serde_json::to_string(&last_runs).unwrap());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use crate::storage::StorageConfig;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn schedule(name: &str, cadence: Cadence) -> ReportSchedule {
+        ReportSchedule::new(name, cadence, Box::new(|_, today| ReportDocument::new("P&L", None, today, Vec::new())))
+    }
+
+    /// Fails until `up` is set
+    struct Flaky {
+        up: Arc<AtomicBool>,
+    }
+
+    impl ReportDelivery for Flaky {
+        fn deliver(&self, _report: &ReportDocument) -> Result<(), DeliveryError> {
+            if self.up.load(Ordering::SeqCst) {
+                Ok(())
+            } else {
+                Err(DeliveryError::Transport("unreachable".to_string()))
+            }
+        }
+    }
+
+    #[test]
+    fn monthly_on_the_31st_runs_on_the_last_day_of_short_months() {
+        let cadence = Cadence::Monthly(31);
+        assert!(cadence.occurs_on(date(2024, 2, 29)));
+        assert!(cadence.occurs_on(date(2023, 2, 28)));
+        assert!(cadence.occurs_on(date(2024, 4, 30)));
+        assert!(!cadence.occurs_on(date(2024, 5, 30)));
+        assert!(cadence.occurs_on(date(2024, 5, 31)));
+        assert!(Cadence::Monthly(0).occurs_on(date(2024, 5, 1)));
+    }
+
+    #[test]
+    fn due_catches_up_missed_occurrences() {
+        let mut weekly = schedule("Weekly", Cadence::Weekly(Weekday::Mon));
+        assert!(weekly.is_due(date(2024, 5, 6)));
+        assert!(!weekly.is_due(date(2024, 5, 7)));
+
+        weekly.last_run = Some(date(2024, 5, 6));
+        assert!(!weekly.is_due(date(2024, 5, 6)));
+        assert!(!weekly.is_due(date(2024, 5, 12)));
+        // Nothing ran on the 13th, so Wednesday catches it up
+        assert!(weekly.is_due(date(2024, 5, 15)));
+    }
+
+    #[test]
+    fn failed_runs_stay_due_and_last_runs_survive_a_restart() {
+        let up = Arc::new(AtomicBool::new(false));
+        let storage = LocalStorage::with_config(&StorageConfig { path: ":memory:".to_string(), ..Default::default() });
+        let mut scheduler = ReportScheduler::new();
+        scheduler.add_schedule(schedule("Daily", Cadence::Daily));
+        scheduler.add_delivery(Box::new(Flaky { up: up.clone() }));
+        let ledger = Ledger::new();
+
+        let runs = scheduler.run_due(&ledger, date(2024, 5, 1));
+        assert!(!runs[0].delivered());
+        assert_eq!(scheduler.schedules()[0].last_run, None);
+
+        up.store(true, Ordering::SeqCst);
+        assert!(scheduler.run_due(&ledger, date(2024, 5, 1))[0].delivered());
+        assert!(scheduler.run_due(&ledger, date(2024, 5, 1)).is_empty());
+        scheduler.save(&storage);
+
+        let mut restarted = ReportScheduler::new();
+        restarted.load(&storage);
+        restarted.add_schedule(schedule("Daily", Cadence::Daily));
+        assert_eq!(restarted.schedules()[0].last_run, Some(date(2024, 5, 1)));
+        assert!(!restarted.schedules()[0].is_due(date(2024, 5, 1)));
+    }
+}
@@ -0,0 +1,397 @@
+//! Balance sheet and income statement, grouped by account type along the account hierarchy
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::currency::Commodity;
+use crate::ledger::{Account, AccountType};
+use crate::sync::SyncableLedger;
+use super::{CellQuery, ReportDocument, ReportOptions, ReportRow, ReportSection};
+
+/// Net postings per account and commodity, and the commodities that make up the columns
+struct Totals {
+    accounts: HashMap<Uuid, HashMap<Commodity, Decimal>>,
+    commodities: Vec<Commodity>,
+}
+
+impl Totals {
+    fn has(&self, account_id: &Uuid) -> bool {
+        self.accounts.contains_key(account_id)
+    }
+
+    fn width(&self) -> usize {
+        self.commodities.len().max(1)
+    }
+
+    /// The single `label` column for a one-commodity book, otherwise one column per commodity
+    fn columns(&self, label: &str) -> Vec<String> {
+        if self.commodities.len() <= 1 {
+            vec![label.to_string()]
+        } else {
+            self.commodities.iter().map(|c| c.code().to_string()).collect()
+        }
+    }
+
+    /// One amount per column for a set of accounts
+    fn amounts<'a>(&self, ids: impl Iterator<Item = &'a Uuid> + Clone) -> Vec<Decimal> {
+        if self.commodities.is_empty() {
+            return vec![Decimal::ZERO];
+        }
+        self.commodities.iter()
+            .map(|c| ids.clone().filter_map(|id| self.accounts.get(id)?.get(c)).sum())
+            .collect()
+    }
+}
+
+/// Assets, liabilities and equity as of a date. Revenue and expenses not yet closed into equity
+/// show as current earnings, so the sections balance. A book holding several commodities gets
+/// one column per commodity rather than a sum across them. A book stocking items gets a stock
+/// section valuing each inventory account from its movements, with any difference to the ledger.
+pub fn balance_sheet(ledger: &SyncableLedger, as_of: NaiveDate, options: &ReportOptions) -> ReportDocument {
+    let totals = account_totals(ledger, options, None, as_of);
+    let base = CellQuery::period(None, as_of);
+    let mut doc = ReportDocument::new("Balance Sheet", None, as_of, totals.columns("Balance"));
+
+    for (title, account_type) in [
+        ("Assets", AccountType::Asset),
+        ("Liabilities", AccountType::Liability),
+        ("Equity", AccountType::Equity),
+    ] {
+        let sign = natural_sign(account_type);
+        let mut rows = tree_rows(ledger, options, as_of, account_type, &totals, sign, &base);
+        let mut total = column_totals(&rows, totals.width());
+        if account_type == AccountType::Equity {
+            let earnings: Vec<Decimal> = type_total(ledger, &totals, &[AccountType::Revenue, AccountType::Expense])
+                .into_iter()
+                .map(|amount| -amount)
+                .collect();
+            if earnings.iter().any(|amount| !amount.is_zero()) {
+                let query = base.clone().account_types(&[AccountType::Revenue, AccountType::Expense]);
+                rows.push(ReportRow {
+                    label: "Current earnings".to_string(),
+                    account_id: None,
+                    depth: 0,
+                    queries: vec![Some(query); earnings.len()],
+                    values: earnings.clone(),
+                });
+                total.iter_mut().zip(&earnings).for_each(|(t, e)| *t += e);
+            }
+        }
+        doc.sections.push(ReportSection {
+            title: title.to_string(),
+            rows,
+            total_queries: vec![Some(base.clone().account_types(&[account_type])); total.len()],
+            total: Some(total),
+        });
+    }
+    if let Some(section) = stock_section(ledger, as_of, &totals) {
+        doc.sections.push(section);
+    }
+    doc
+}
+
+/// Stock value per inventory account in the base currency column, and a difference row when the
+/// inventory accounts hold something else (e.g. a purchase booked without a stock movement)
+fn stock_section(ledger: &SyncableLedger, as_of: NaiveDate, totals: &Totals) -> Option<ReportSection> {
+    let stock = ledger.inventory().value_by_account(as_of);
+    if stock.is_empty() {
+        return None;
+    }
+    let base = ledger.settings.base_currency.clone().unwrap_or_default();
+    let column = match totals.commodities.len() {
+        0 | 1 => 0,
+        _ => totals.commodities.iter().position(|c| *c == base)?,
+    };
+    let in_column = |amount: Decimal| {
+        let mut values = vec![Decimal::ZERO; totals.width()];
+        values[column] = amount;
+        values
+    };
+
+    let mut accounts: Vec<(&Uuid, &Decimal)> = stock.iter().collect();
+    accounts.sort_by_key(|(id, _)| ledger.accounts.get(id).map(|a| a.name.as_str()));
+    let mut rows: Vec<ReportRow> = accounts.iter()
+        .map(|(id, value)| ReportRow {
+            label: ledger.accounts.get(id).map_or_else(|| id.to_string(), |a| a.name.clone()),
+            account_id: Some(**id),
+            depth: 0,
+            queries: vec![None; totals.width()],
+            values: in_column(**value),
+        })
+        .collect();
+    let value: Decimal = stock.values().sum();
+    let booked: Decimal = stock.keys()
+        .filter_map(|id| totals.accounts.get(id)?.get(&base))
+        .sum();
+    if value != booked {
+        rows.push(ReportRow {
+            label: "Difference from ledger".to_string(),
+            account_id: None,
+            depth: 1,
+            queries: vec![None; totals.width()],
+            values: in_column(value - booked),
+        });
+    }
+    Some(ReportSection {
+        title: "Stock on hand".to_string(),
+        rows,
+        total_queries: vec![None; totals.width()],
+        total: Some(in_column(value)),
+    })
+}
+
+/// Revenue and expenses between `from` and `to` (inclusive), both shown positive, and the net income
+pub fn income_statement(ledger: &SyncableLedger, from: NaiveDate, to: NaiveDate, options: &ReportOptions) -> ReportDocument {
+    let totals = account_totals(ledger, options, Some(from), to);
+    let base = CellQuery::period(Some(from), to);
+    let mut doc = ReportDocument::new("Income Statement", Some(from), to, totals.columns("Amount"));
+
+    let mut net = vec![Decimal::ZERO; totals.width()];
+    for (title, account_type) in [("Revenue", AccountType::Revenue), ("Expenses", AccountType::Expense)] {
+        let sign = natural_sign(account_type);
+        let rows = tree_rows(ledger, options, from, account_type, &totals, sign, &base);
+        let total = column_totals(&rows, totals.width());
+        for (n, t) in net.iter_mut().zip(&total) {
+            *n += if account_type == AccountType::Revenue { *t } else { -*t };
+        }
+        doc.sections.push(ReportSection {
+            title: title.to_string(),
+            rows,
+            total_queries: vec![Some(base.clone().account_types(&[account_type])); total.len()],
+            total: Some(total),
+        });
+    }
+    doc.sections.push(ReportSection {
+        title: "Net Income".to_string(),
+        rows: Vec::new(),
+        total_queries: vec![Some(base.account_types(&[AccountType::Revenue, AccountType::Expense])); net.len()],
+        total: Some(net),
+    });
+    doc
+}
+
+/// Debit-positive net postings per account and commodity within the period
+fn account_totals(
+    ledger: &SyncableLedger,
+    options: &ReportOptions,
+    start: Option<NaiveDate>,
+    end: NaiveDate,
+) -> Totals {
+    let mut totals: HashMap<Uuid, HashMap<Commodity, Decimal>> = HashMap::new();
+    let transactions = ledger.transactions.iter()
+        .filter(|t| start.is_none_or(|s| s <= t.date) && t.date <= end)
+        .filter(|t| options.includes_transaction(t, ledger));
+    for tx in transactions {
+        for posting in &tx.postings {
+            *totals.entry(posting.account_id).or_default()
+                .entry(posting.commodity.clone())
+                .or_insert(Decimal::ZERO) += posting.amount;
+        }
+    }
+    let commodities: BTreeSet<&Commodity> = totals.values().flat_map(|amounts| amounts.keys()).collect();
+    let commodities = commodities.into_iter().cloned().collect();
+    Totals { accounts: totals, commodities }
+}
+
+/// Sums of the top-level rows, per column
+fn column_totals(rows: &[ReportRow], width: usize) -> Vec<Decimal> {
+    let mut total = vec![Decimal::ZERO; width];
+    for row in rows.iter().filter(|r| r.depth == 0) {
+        total.iter_mut().zip(&row.values).for_each(|(t, v)| *t += v);
+    }
+    total
+}
+
+/// Credit-normal types are flipped so each section reads positive
+fn natural_sign(account_type: AccountType) -> Decimal {
+    match account_type {
+        AccountType::Asset | AccountType::Expense => Decimal::ONE,
+        _ => -Decimal::ONE,
+    }
+}
+
+fn type_total(ledger: &SyncableLedger, totals: &Totals, types: &[AccountType]) -> Vec<Decimal> {
+    let ids = totals.accounts.keys().filter(|id| ledger.accounts.get(id).is_some_and(|a| types.contains(&a.r#type)));
+    totals.amounts(ids)
+}
+
+/// Rows for one account type in chart order, children indented under their parent. A parent's
+/// figure includes its descendants; subtrees without postings are left out.
+fn tree_rows(
+    ledger: &SyncableLedger,
+    options: &ReportOptions,
+    period_start: NaiveDate,
+    account_type: AccountType,
+    totals: &Totals,
+    sign: Decimal,
+    base: &CellQuery,
+) -> Vec<ReportRow> {
+    let accounts: BTreeMap<Uuid, &Account> = ledger.accounts.values()
+        .filter(|a| a.r#type == account_type && options.includes(a, period_start))
+        .map(|a| (a.id, a))
+        .collect();
+    // Accounts whose parent is of another type or hidden start their own subtree
+    let mut children: BTreeMap<Option<Uuid>, Vec<&Account>> = BTreeMap::new();
+    for account in accounts.values() {
+        let parent = account.parent_id.filter(|p| accounts.contains_key(p));
+        children.entry(parent).or_default().push(account);
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by(|a, b| {
+            a.display.sort_order.cmp(&b.display.sort_order).then_with(|| a.name.cmp(&b.name))
+        });
+    }
+
+    let mut rows = Vec::new();
+    for root in children.get(&None).into_iter().flatten() {
+        push_subtree(root, 0, &children, totals, sign, base, &mut rows);
+    }
+    rows
+}
+
+/// Append an account and its descendants; returns the subtree's ids, or nothing if it has no postings
+fn push_subtree(
+    account: &Account,
+    depth: usize,
+    children: &BTreeMap<Option<Uuid>, Vec<&Account>>,
+    totals: &Totals,
+    sign: Decimal,
+    base: &CellQuery,
+    rows: &mut Vec<ReportRow>,
+) -> Vec<Uuid> {
+    let index = rows.len();
+    let mut ids = vec![account.id];
+    let mut active = totals.has(&account.id);
+    for child in children.get(&Some(account.id)).into_iter().flatten() {
+        let child_ids = push_subtree(child, depth + 1, children, totals, sign, base, rows);
+        active |= !child_ids.is_empty();
+        ids.extend(child_ids);
+    }
+    if !active {
+        return Vec::new();
+    }
+    let values: Vec<Decimal> = totals.amounts(ids.iter()).into_iter().map(|a| a * sign).collect();
+    let mut query = base.clone();
+    query.accounts = ids.clone();
+    rows.insert(index, ReportRow {
+        label: account.name.clone(),
+        account_id: Some(account.id),
+        depth,
+        queries: vec![Some(query); values.len()],
+        values,
+    });
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::{Posting, Transaction};
+
+    fn posting(account_id: Uuid, amount: i64, commodity: &str) -> Posting {
+        let mut posting = Posting::new(account_id, Decimal::from(amount));
+        posting.commodity = Commodity::new(commodity);
+        posting
+    }
+
+    #[test]
+    fn commodities_get_their_own_columns() {
+        let mut ledger = SyncableLedger::new();
+        let cash = Account::new("Cash", AccountType::Asset);
+        let sales = Account::new("Sales", AccountType::Revenue);
+        let (cash_id, sales_id) = (cash.id, sales.id);
+        ledger.accounts.insert(cash.id, cash);
+        ledger.accounts.insert(sales.id, sales);
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        ledger.transactions.push(Transaction::new(date, "USD sale", vec![
+            posting(cash_id, 100, "USD"),
+            posting(sales_id, -100, "USD"),
+        ]));
+        ledger.transactions.push(Transaction::new(date, "EUR sale", vec![
+            posting(cash_id, 50, "EUR"),
+            posting(sales_id, -50, "EUR"),
+        ]));
+
+        let doc = balance_sheet(&ledger, date, &ReportOptions::default());
+        assert_eq!(doc.columns, vec!["EUR".to_string(), "USD".to_string()]);
+        let assets = &doc.sections[0];
+        assert_eq!(assets.rows[0].values, vec![Decimal::from(50), Decimal::from(100)]);
+        assert_eq!(assets.total, Some(vec![Decimal::from(50), Decimal::from(100)]));
+        let equity = &doc.sections[2];
+        assert_eq!(equity.total, Some(vec![Decimal::from(50), Decimal::from(100)]));
+
+        let statement = income_statement(&ledger, date, date, &ReportOptions::default());
+        assert_eq!(statement.sections.last().unwrap().total, Some(vec![Decimal::from(50), Decimal::from(100)]));
+    }
+
+    #[test]
+    fn single_commodity_keeps_one_column() {
+        let mut ledger = SyncableLedger::new();
+        let cash = Account::new("Cash", AccountType::Asset);
+        let sales = Account::new("Sales", AccountType::Revenue);
+        let (cash_id, sales_id) = (cash.id, sales.id);
+        ledger.accounts.insert(cash.id, cash);
+        ledger.accounts.insert(sales.id, sales);
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        ledger.transactions.push(Transaction::new(date, "Sale", vec![
+            Posting::new(cash_id, Decimal::from(20)),
+            Posting::new(sales_id, Decimal::from(-20)),
+        ]));
+
+        let doc = balance_sheet(&ledger, date, &ReportOptions::default());
+        assert_eq!(doc.columns, vec!["Balance".to_string()]);
+        assert_eq!(doc.sections[0].total, Some(vec![Decimal::from(20)]));
+    }
+
+    #[test]
+    fn stock_on_hand_ties_into_the_inventory_accounts() {
+        use crate::inventory::{Inventory, Item};
+        use crate::ledger::Ledger;
+
+        let mut book = Ledger::new();
+        let mut add = |name: &str, r#type| {
+            let account = book.new_account(name, r#type);
+            let id = account.id;
+            book.add_account(account).unwrap();
+            id
+        };
+        let cash = add("Cash", AccountType::Asset);
+        let stock = add("Inventory", AccountType::Asset);
+        let item = Item {
+            id: Uuid::new_v4(),
+            sku: "W-1".to_string(),
+            name: "Widget".to_string(),
+            inventory_account: stock,
+            cogs_account: add("Cost of goods sold", AccountType::Expense),
+            revenue_account: add("Sales", AccountType::Revenue),
+        };
+        let item_id = item.id;
+        let mut inventory = Inventory::new();
+        inventory.add_item(item).unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        inventory.purchase(&mut book, item_id, Decimal::from(4), Decimal::from(5), date, cash).unwrap();
+
+        let mut ledger = SyncableLedger::new();
+        for (_, account) in book.account_tree() {
+            ledger.accounts.insert(account.id, account.clone());
+        }
+        ledger.transactions.extend(book.transactions().cloned());
+        ledger.set_inventory(&inventory);
+
+        let doc = balance_sheet(&ledger, date, &ReportOptions::default());
+        let section = doc.sections.last().unwrap();
+        assert_eq!(section.title, "Stock on hand");
+        assert_eq!(section.rows.len(), 1);
+        assert_eq!(section.total, Some(vec![Decimal::from(20)]));
+
+        // A purchase booked straight to the account shows up as a difference
+        ledger.transactions.push(Transaction::new(date, "Untracked stock", vec![
+            Posting::new(stock, Decimal::from(7)),
+            Posting::new(cash, Decimal::from(-7)),
+        ]));
+        let doc = balance_sheet(&ledger, date, &ReportOptions::default());
+        let section = doc.sections.last().unwrap();
+        assert_eq!(section.rows[1].values, vec![Decimal::from(-7)]);
+    }
+}
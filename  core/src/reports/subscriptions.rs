@@ -0,0 +1,105 @@
+//! Subscriptions: recurring expenses with their monthly and annualized cost
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::ledger::AccountType;
+use crate::recurring::{DetectionOptions, Frequency, RecurringTransaction};
+use crate::sync::SyncableLedger;
+use super::{CellQuery, ReportDocument, ReportRow, ReportSection};
+
+/// A recurring expense, either declared (stored definition) or detected from history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subscription {
+    pub name: String,
+    /// Expense account charged
+    pub account_id: Uuid,
+    pub frequency: Frequency,
+    pub amount: Decimal,
+    pub next_renewal: NaiveDate,
+    /// False for patterns found by detection that haven't been saved yet
+    pub declared: bool,
+    /// Stored definition for declared subscriptions, proposed one for detected
+    pub definition: RecurringTransaction,
+}
+
+impl Subscription {
+    fn from_definition(definition: &RecurringTransaction, ledger: &SyncableLedger, declared: bool) -> Option<Self> {
+        let expense = definition.postings.iter().find(|p| {
+            p.amount.is_sign_positive()
+                && ledger.accounts.get(&p.account_id).is_some_and(|a| a.r#type == AccountType::Expense)
+        })?;
+        Some(Self {
+            name: definition.description.clone(),
+            account_id: expense.account_id,
+            frequency: definition.frequency,
+            amount: expense.amount,
+            next_renewal: definition.next_date,
+            declared,
+            definition: definition.clone(),
+        })
+    }
+
+    pub fn annual_cost(&self) -> Decimal {
+        (self.amount * self.frequency.per_year()).round_dp(2)
+    }
+
+    pub fn monthly_cost(&self) -> Decimal {
+        (self.amount * self.frequency.per_year() / Decimal::from(12)).round_dp(2)
+    }
+}
+
+/// Active declared subscriptions followed by detected ones, each ordered by next renewal
+pub fn subscriptions(ledger: &SyncableLedger, today: NaiveDate) -> Vec<Subscription> {
+    let mut declared: Vec<Subscription> = ledger.recurring.values()
+        .filter(|r| r.active && r.end_date.is_none_or(|end| end >= today))
+        .filter_map(|r| Subscription::from_definition(r, ledger, true))
+        .collect();
+    let mut detected: Vec<Subscription> = ledger.detect_recurring(today, &DetectionOptions::default())
+        .iter()
+        .filter_map(|c| Subscription::from_definition(&c.definition, ledger, false))
+        .collect();
+    declared.sort_by(|a, b| a.next_renewal.cmp(&b.next_renewal).then_with(|| a.name.cmp(&b.name)));
+    detected.sort_by(|a, b| a.next_renewal.cmp(&b.next_renewal).then_with(|| a.name.cmp(&b.name)));
+    declared.extend(detected);
+    declared
+}
+
+/// Subscription list with monthly and annualized cost; the renewal date is part of each label
+pub fn subscription_report(ledger: &SyncableLedger, today: NaiveDate) -> ReportDocument {
+    let mut doc = ReportDocument::new(
+        "Subscriptions",
+        None,
+        today,
+        vec!["Monthly".to_string(), "Annual".to_string()],
+    );
+    let all = subscriptions(ledger, today);
+    for (declared, title) in [(true, "Subscriptions"), (false, "Detected (not yet confirmed)")] {
+        let rows: Vec<ReportRow> = all.iter()
+            .filter(|s| s.declared == declared)
+            .map(|s| {
+                let query = Some(CellQuery::period(None, today).account(s.account_id));
+                ReportRow {
+                    label: format!("{} (renews {})", s.name, s.next_renewal),
+                    account_id: Some(s.account_id),
+                    depth: 0,
+                    values: vec![s.monthly_cost(), s.annual_cost()],
+                    queries: vec![query.clone(), query],
+                }
+            })
+            .collect();
+        if rows.is_empty() {
+            continue;
+        }
+        let monthly = rows.iter().map(|r| r.values[0]).sum();
+        let annual = rows.iter().map(|r| r.values[1]).sum();
+        doc.sections.push(ReportSection {
+            title: title.to_string(),
+            rows,
+            total: Some(vec![monthly, annual]),
+            total_queries: Vec::new(),
+        });
+    }
+    doc
+}
@@ -0,0 +1,105 @@
+//! Balances translated into a single presentation currency
+use std::collections::BTreeMap;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::currency::Commodity;
+use crate::ledger::AccountType;
+use crate::prices::{PriceDb, RateBasis};
+use crate::rounding::RoundingPolicy;
+use crate::sync::SyncableLedger;
+use super::{CellQuery, ReportDocument, ReportRow, ReportSection};
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum TranslationError {
+    #[error("No {from}/{to} rate on or before {date}")]
+    MissingRate { from: Commodity, to: Commodity, date: NaiveDate },
+}
+
+const SECTIONS: [(AccountType, &str); 5] = [
+    (AccountType::Asset, "Assets"),
+    (AccountType::Liability, "Liabilities"),
+    (AccountType::Equity, "Equity"),
+    (AccountType::Revenue, "Revenue"),
+    (AccountType::Expense, "Expenses"),
+];
+
+/// Account balances at `end`, one row per account and commodity, converted into `presentation`
+/// and rounded with the book's `rounding` policy
+pub fn presentation_balances(
+    ledger: &SyncableLedger,
+    prices: &PriceDb,
+    presentation: &Commodity,
+    end: NaiveDate,
+    basis: RateBasis,
+    rounding: &RoundingPolicy,
+) -> Result<ReportDocument, TranslationError> {
+    let convert = |amount: Decimal, from: &Commodity, date: NaiveDate| {
+        prices.convert(amount, from, presentation, date).ok_or_else(|| TranslationError::MissingRate {
+            from: from.clone(),
+            to: presentation.clone(),
+            date,
+        })
+    };
+
+    // (account, commodity) -> (native, converted)
+    let mut totals: BTreeMap<(Uuid, Commodity), (Decimal, Decimal)> = BTreeMap::new();
+    for tx in ledger.transactions.iter().filter(|t| t.date <= end) {
+        for posting in &tx.postings {
+            let entry = totals.entry((posting.account_id, posting.commodity.clone())).or_default();
+            entry.0 += posting.amount;
+            if basis == RateBasis::TransactionDate {
+                entry.1 += convert(posting.amount, &posting.commodity, tx.date)?;
+            }
+        }
+    }
+    if basis == RateBasis::PeriodEnd {
+        for ((_, commodity), (native, converted)) in totals.iter_mut() {
+            *converted = convert(*native, commodity, end)?;
+        }
+    }
+
+    let basis_label = match basis {
+        RateBasis::PeriodEnd => "period-end rates",
+        RateBasis::TransactionDate => "transaction-date rates",
+    };
+    let mut doc = ReportDocument::new(
+        &format!("Balances in {} ({})", presentation, basis_label),
+        None,
+        end,
+        vec![presentation.code().to_string()],
+    );
+    for (account_type, title) in SECTIONS {
+        let mut rows: Vec<ReportRow> = totals.iter()
+            .filter(|(_, (native, _))| !native.is_zero())
+            .filter_map(|((account_id, commodity), (_, converted))| {
+                let account = ledger.accounts.get(account_id).filter(|a| a.r#type == account_type)?;
+                let label = if commodity == presentation {
+                    account.name.clone()
+                } else {
+                    format!("{} ({})", account.name, commodity)
+                };
+                Some(ReportRow {
+                    label,
+                    account_id: Some(*account_id),
+                    depth: 0,
+                    values: vec![rounding.round(*converted, presentation)],
+                    queries: vec![Some(CellQuery::period(None, end).account(*account_id))],
+                })
+            })
+            .collect();
+        if rows.is_empty() {
+            continue;
+        }
+        rows.sort_by(|a, b| a.label.cmp(&b.label));
+        let total = rows.iter().map(|r| r.values[0]).sum();
+        doc.sections.push(ReportSection {
+            title: title.to_string(),
+            rows,
+            total: Some(vec![total]),
+            total_queries: vec![Some(CellQuery::period(None, end).account_types(&[account_type]))],
+        });
+    }
+    Ok(doc)
+}
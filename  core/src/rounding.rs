@@ -0,0 +1,152 @@
+//! Rounding policy for generated amounts: the precision and rounding mode, and where the
+//! leftover of a split goes, so expanded templates never carry sub-cent residue
+use rust_decimal::{Decimal, RoundingStrategy};
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::currency::Commodity;
+use crate::locale::minor_units;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoundingMode {
+    /// Ties to the even digit (banker's rounding)
+    #[default]
+    HalfEven,
+    /// Ties away from zero, as on most invoices
+    HalfUp,
+    /// Truncate toward zero
+    Down,
+    /// Always away from zero
+    Up,
+}
+
+impl RoundingMode {
+    fn strategy(self) -> RoundingStrategy {
+        match self {
+            RoundingMode::HalfEven => RoundingStrategy::MidpointNearestEven,
+            RoundingMode::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            RoundingMode::Down => RoundingStrategy::ToZero,
+            RoundingMode::Up => RoundingStrategy::AwayFromZero,
+        }
+    }
+}
+
+/// How a ledger rounds amounts it generates; the default matches the commodity's minor units
+/// with banker's rounding and spreads leftovers over the parts
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoundingPolicy {
+    /// Decimal places; None uses the commodity's minor units
+    pub scale: Option<u32>,
+    pub mode: RoundingMode,
+    /// Account booking what is left after rounding each part of a split; without one the
+    /// leftover units go to the parts that lost the most to rounding
+    pub residual_account: Option<Uuid>,
+}
+
+/// Parts of a split amount and the leftover for the residual account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allocation {
+    pub parts: Vec<Decimal>,
+    /// Zero unless the policy has a residual account
+    pub residual: Decimal,
+}
+
+impl RoundingPolicy {
+    pub fn new(mode: RoundingMode) -> Self {
+        Self { mode, ..Self::default() }
+    }
+
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    pub fn with_residual_account(mut self, account_id: Uuid) -> Self {
+        self.residual_account = Some(account_id);
+        self
+    }
+
+    pub fn scale_for(&self, commodity: &Commodity) -> u32 {
+        self.scale.unwrap_or_else(|| minor_units(commodity))
+    }
+
+    pub fn round(&self, amount: Decimal, commodity: &Commodity) -> Decimal {
+        amount.round_dp_with_strategy(self.scale_for(commodity), self.mode.strategy())
+    }
+
+    /// Split `total` by `ratios` into rounded parts. With a residual account each part is rounded
+    /// on its own and the difference is returned as the residual; otherwise the parts sum to
+    /// `total` exactly (100.00 three ways is 33.34, 33.33, 33.33).
+    pub fn allocate(&self, total: Decimal, ratios: &[Decimal], commodity: &Commodity) -> Allocation {
+        let total = self.round(total, commodity);
+        let exact: Vec<Decimal> = ratios.iter().map(|r| total * r).collect();
+        if self.residual_account.is_some() {
+            let parts: Vec<Decimal> = exact.iter().map(|e| self.round(*e, commodity)).collect();
+            let residual = total - parts.iter().sum::<Decimal>();
+            return Allocation { parts, residual };
+        }
+
+        // Largest remainder: truncate, then hand out the missing units by rounding loss
+        let scale = self.scale_for(commodity);
+        let mut parts: Vec<Decimal> = exact.iter()
+            .map(|e| e.round_dp_with_strategy(scale, RoundingStrategy::ToZero))
+            .collect();
+        let mut order: Vec<usize> = (0..parts.len()).collect();
+        order.sort_by(|&a, &b| (exact[b] - parts[b]).abs().cmp(&(exact[a] - parts[a]).abs()));
+        let unit = Decimal::new(1, scale);
+        let mut leftover = total - parts.iter().sum::<Decimal>();
+        for i in order.into_iter().cycle() {
+            if leftover.abs() < unit {
+                break;
+            }
+            let step = if leftover.is_sign_negative() { -unit } else { unit };
+            parts[i] += step;
+            leftover -= step;
+        }
+        Allocation { parts, residual: Decimal::ZERO }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thirds() -> Vec<Decimal> {
+        vec![Decimal::ONE / Decimal::from(3); 3]
+    }
+
+    #[test]
+    fn a_hundred_three_ways_sums_exactly() {
+        let split = RoundingPolicy::default().allocate(Decimal::from(100), &thirds(), &Commodity::default());
+        assert_eq!(split.parts, vec![Decimal::new(3334, 2), Decimal::new(3333, 2), Decimal::new(3333, 2)]);
+        assert_eq!(split.residual, Decimal::ZERO);
+    }
+
+    #[test]
+    fn negative_totals_hand_out_negative_units() {
+        let split = RoundingPolicy::default().allocate(Decimal::from(-100), &thirds(), &Commodity::default());
+        assert_eq!(split.parts, vec![Decimal::new(-3334, 2), Decimal::new(-3333, 2), Decimal::new(-3333, 2)]);
+        assert_eq!(split.parts.iter().sum::<Decimal>(), Decimal::from(-100));
+    }
+
+    #[test]
+    fn residual_account_takes_the_leftover() {
+        let policy = RoundingPolicy::default().with_residual_account(Uuid::new_v4());
+        let split = policy.allocate(Decimal::from(100), &thirds(), &Commodity::default());
+        assert_eq!(split.parts, vec![Decimal::new(3333, 2); 3]);
+        assert_eq!(split.residual, Decimal::new(1, 2));
+    }
+
+    #[test]
+    fn scale_follows_the_commodity_unless_set() {
+        let yen = Commodity::new("JPY");
+        let split = RoundingPolicy::default().allocate(Decimal::from(1000), &thirds(), &yen);
+        assert_eq!(split.parts, vec![Decimal::from(334), Decimal::from(333), Decimal::from(333)]);
+
+        let half = Decimal::new(2345, 3);
+        assert_eq!(RoundingPolicy::default().round(half, &Commodity::default()), Decimal::new(234, 2));
+        assert_eq!(RoundingPolicy::new(RoundingMode::HalfUp).round(half, &Commodity::default()), Decimal::new(235, 2));
+        assert_eq!(RoundingPolicy::default().with_scale(1).round(half, &yen), Decimal::new(23, 1));
+    }
+}
@@ -0,0 +1,251 @@
+//! Automatic categorization: user-defined rules matching description, amount and counterparty
+//! that pick the counter account (and project, class or tax code) for imported entries
+use regex::{Regex, RegexBuilder};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::ledger::{Posting, Transaction};
+use crate::staging::StagedTransaction;
+
+/// Match conditions and what to book when they hold; unset conditions match anything
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CategorizationRule {
+    pub id: Uuid,
+    pub name: String,
+    /// Lower runs first; the first matching rule wins
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Case-insensitive regex searched in the description and payee
+    #[serde(default)]
+    pub description_pattern: Option<DescriptionPattern>,
+    /// Bounds on the bank-side amount (money out is negative), inclusive
+    #[serde(default)]
+    pub min_amount: Option<Decimal>,
+    #[serde(default)]
+    pub max_amount: Option<Decimal>,
+    /// Counterparty the transaction must be linked to
+    #[serde(default)]
+    pub contact_id: Option<Uuid>,
+    /// Counter account booked by the rule
+    pub target_account: Uuid,
+    #[serde(default)]
+    pub project_id: Option<Uuid>,
+    #[serde(default)]
+    pub class_id: Option<Uuid>,
+    #[serde(default)]
+    pub tax_code: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Description regex with its source text, compiled once when the rule is built or loaded
+#[derive(Debug, Clone)]
+pub struct DescriptionPattern {
+    source: String,
+    /// None when the source is not a valid regex; such a pattern never matches
+    regex: Option<Regex>,
+}
+
+impl DescriptionPattern {
+    pub fn new(source: impl Into<String>) -> Self {
+        let source = source.into();
+        let regex = RegexBuilder::new(&source).case_insensitive(true).build().ok();
+        Self { source, regex }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.regex.is_some()
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        self.regex.as_ref().is_some_and(|re| re.is_match(text))
+    }
+}
+
+impl PartialEq for DescriptionPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Serialize for DescriptionPattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.source.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DescriptionPattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+impl CategorizationRule {
+    pub fn new(name: impl Into<String>, target_account: Uuid) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            priority: 0,
+            enabled: true,
+            description_pattern: None,
+            min_amount: None,
+            max_amount: None,
+            contact_id: None,
+            target_account,
+            project_id: None,
+            class_id: None,
+            tax_code: None,
+        }
+    }
+
+    pub fn with_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.description_pattern = Some(DescriptionPattern::new(pattern));
+        self
+    }
+
+    pub fn with_amount_range(mut self, min: Option<Decimal>, max: Option<Decimal>) -> Self {
+        self.min_amount = min;
+        self.max_amount = max;
+        self
+    }
+
+    pub fn with_contact(mut self, contact_id: Uuid) -> Self {
+        self.contact_id = Some(contact_id);
+        self
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Check the pattern compiles and the amount range isn't empty
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.description_pattern.as_ref().is_some_and(|p| !p.is_valid()) {
+            return Err("Invalid description pattern");
+        }
+        if let (Some(min), Some(max)) = (self.min_amount, self.max_amount) {
+            if min > max {
+                return Err("Minimum amount exceeds maximum");
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether an entry with these fields matches; invalid patterns never match
+    pub fn matches(&self, description: &str, payee: Option<&str>, contact_id: Option<Uuid>, amount: Decimal) -> bool {
+        if !self.enabled
+            || self.min_amount.is_some_and(|min| amount < min)
+            || self.max_amount.is_some_and(|max| amount > max)
+            || self.contact_id.is_some_and(|c| contact_id != Some(c))
+        {
+            return false;
+        }
+        match &self.description_pattern {
+            None => true,
+            Some(pattern) => pattern.is_match(description) || payee.is_some_and(|p| pattern.is_match(p)),
+        }
+    }
+
+    /// Book the counter side of the bank-side `amount`: add it when no counter posting exists
+    /// yet, re-point it when there is exactly one. Split entries are left alone; returns
+    /// whether anything changed.
+    fn apply_postings(&self, postings: &mut Vec<Posting>, amount: Decimal) -> bool {
+        match postings.len() {
+            // Staged without postings, e.g. a receipt; the bank side is chosen on approval
+            0 => {
+                postings.push(self.tag(Posting::new(self.target_account, -amount)));
+                true
+            }
+            1 => {
+                let bank = &postings[0];
+                let counter = Posting::in_commodity(self.target_account, -bank.amount, bank.commodity.clone());
+                postings.push(self.tag(counter));
+                true
+            }
+            2 => {
+                let retargeted = Posting { account_id: self.target_account, ..postings[1].clone() };
+                postings[1] = self.tag(retargeted);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn tag(&self, mut posting: Posting) -> Posting {
+        posting.project_id = self.project_id.or(posting.project_id);
+        posting.class_id = self.class_id.or(posting.class_id);
+        posting.tax_code = self.tax_code.clone().or(posting.tax_code);
+        posting
+    }
+}
+
+/// Enabled rules in the order they are tried
+fn ordered<'a>(rules: impl IntoIterator<Item = &'a CategorizationRule>) -> Vec<&'a CategorizationRule> {
+    let mut rules: Vec<_> = rules.into_iter().filter(|r| r.enabled).collect();
+    rules.sort_by(|a, b| a.priority.cmp(&b.priority).then_with(|| a.name.cmp(&b.name)));
+    rules
+}
+
+/// Apply the first matching rule to a transaction; returns the rule's id.
+/// The bank-side amount is the first posting's.
+pub fn apply_rules<'a>(rules: impl IntoIterator<Item = &'a CategorizationRule>, tx: &mut Transaction) -> Option<Uuid> {
+    let amount = tx.postings.first()?.amount;
+    let rule = ordered(rules).into_iter()
+        .find(|r| r.matches(&tx.description, tx.payee.as_deref(), tx.contact_id, amount))?;
+    rule.apply_postings(&mut tx.postings, amount).then_some(rule.id)
+}
+
+/// Same as `apply_rules` for an entry still in staging, e.g. straight after import
+pub fn apply_rules_staged<'a>(
+    rules: impl IntoIterator<Item = &'a CategorizationRule>,
+    entry: &mut StagedTransaction,
+) -> Option<Uuid> {
+    let amount = entry.postings.first().map(|p| p.amount).or(entry.amount)?;
+    let rule = ordered(rules).into_iter()
+        .find(|r| r.matches(&entry.description, entry.payee.as_deref(), entry.contact_id, amount))?;
+    rule.apply_postings(&mut entry.postings, amount).then_some(rule.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::staging::StagingSource;
+
+    #[test]
+    fn pattern_is_compiled_once_and_survives_serde() {
+        let rule = CategorizationRule::new("Coffee", Uuid::new_v4()).with_pattern("^coffee");
+        let json = serde_json::to_string(&rule).unwrap();
+        assert!(json.contains(r#""description_pattern":"^coffee""#));
+        let loaded: CategorizationRule = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, rule);
+        assert!(loaded.matches("COFFEE shop", None, None, Decimal::from(-4)));
+
+        let broken = CategorizationRule::new("Broken", Uuid::new_v4()).with_pattern("(");
+        assert!(broken.validate().is_err());
+        assert!(!broken.matches("(", None, None, Decimal::ONE));
+    }
+
+    #[test]
+    fn staged_entry_without_postings_is_categorized() {
+        let target = Uuid::new_v4();
+        let rule = CategorizationRule::new("Groceries", target).with_pattern("market");
+        let mut entry = StagedTransaction::new(StagingSource::Email);
+        entry.description = "Corner Market".to_string();
+        entry.amount = Some(Decimal::new(-1250, 2));
+
+        assert_eq!(apply_rules_staged([&rule], &mut entry), Some(rule.id));
+        assert_eq!(entry.postings.len(), 1);
+        assert_eq!(entry.postings[0].account_id, target);
+        assert_eq!(entry.postings[0].amount, Decimal::new(1250, 2));
+    }
+}
@@ -0,0 +1,239 @@
+//! Snapshot bootstrap for the headless server node: a token-protected TCP stream that hands new
+//! devices a compacted document plus the changes made since it was taken. Request and response
+//! are sealed with the book key, so neither the token nor the ledger crosses the wire in clear.
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use automerge::{Change, ChangeHash};
+use serde::{Serialize, Deserialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::Mutex;
+
+use crate::dedup::{content_hash, ContentHash};
+use crate::keyring::{BookKeyring, KeyringError, SealedPayload};
+use crate::sync::{SyncDoc, SyncError};
+
+/// Largest snapshot response either side accepts
+const MAX_FRAME: u32 = 256 * 1024 * 1024;
+/// Largest request the server reads before the token is checked
+const MAX_REQUEST_FRAME: u32 = 4 * 1024;
+/// How long either side waits for a whole frame
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Sync(#[from] SyncError),
+    #[error("Serialization error: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[error("Access denied")]
+    Denied,
+    #[error("Frame too large: {0} bytes")]
+    TooLarge(u32),
+    #[error("Invalid change in snapshot stream")]
+    InvalidChange,
+    #[error("Timed out waiting for the peer")]
+    Timeout,
+    #[error(transparent)]
+    Keyring(#[from] KeyringError),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotRequest {
+    token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum SnapshotResponse {
+    Ok { snapshot: Vec<u8>, changes: Vec<Vec<u8>> },
+    Denied,
+}
+
+struct CachedSnapshot {
+    bytes: Vec<u8>,
+    heads: Vec<ChangeHash>,
+    taken_at: Instant,
+}
+
+/// Serves snapshots of a shared document; the compacted snapshot is rebuilt at most every `max_age`
+pub struct SnapshotServer {
+    doc: Arc<Mutex<SyncDoc>>,
+    token_hash: ContentHash,
+    book: String,
+    keyring: BookKeyring,
+    max_age: Duration,
+    timeout: Duration,
+    cache: Mutex<Option<CachedSnapshot>>,
+}
+
+impl SnapshotServer {
+    /// Serve `book`, sealing traffic with its key from `keyring`
+    pub fn new(doc: Arc<Mutex<SyncDoc>>, token: &str, book: impl Into<String>, keyring: BookKeyring) -> Self {
+        Self {
+            doc,
+            token_hash: content_hash(token.as_bytes()),
+            book: book.into(),
+            keyring,
+            max_age: Duration::from_secs(3600),
+            timeout: READ_TIMEOUT,
+            cache: Mutex::new(None),
+        }
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// How long a client may take to send its request
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Accept connections until the listener fails
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) -> Result<(), SnapshotError> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let server = Arc::clone(&self);
+            tokio::spawn(async move {
+                let _ = server.handle(stream).await;
+            });
+        }
+    }
+
+    /// Requests not sealed with this book's key are dropped without an answer
+    async fn handle(&self, mut stream: TcpStream) -> Result<(), SnapshotError> {
+        let frame = read_frame(&mut stream, MAX_REQUEST_FRAME, self.timeout).await?;
+        let sealed: SealedPayload = serde_json::from_slice(&frame)?;
+        if sealed.book != self.book {
+            return Err(SnapshotError::Denied);
+        }
+        let request: SnapshotRequest = serde_json::from_slice(&self.keyring.open(&sealed)?)?;
+        let response = if content_hash(request.token.as_bytes()) == self.token_hash {
+            let (snapshot, changes) = self.current().await?;
+            SnapshotResponse::Ok { snapshot, changes }
+        } else {
+            SnapshotResponse::Denied
+        };
+        let sealed = self.keyring.seal(&self.book, &serde_json::to_vec(&response)?)?;
+        write_frame(&mut stream, &serde_json::to_vec(&sealed)?).await
+    }
+
+    /// Cached compacted snapshot plus raw changes made after it
+    async fn current(&self) -> Result<(Vec<u8>, Vec<Vec<u8>>), SnapshotError> {
+        let mut doc = self.doc.lock().await;
+        let mut cache = self.cache.lock().await;
+        if cache.as_ref().is_none_or(|c| c.taken_at.elapsed() > self.max_age) {
+            *cache = Some(CachedSnapshot {
+                bytes: doc.to_bytes(),
+                heads: doc.doc.get_heads(),
+                taken_at: Instant::now(),
+            });
+        }
+        let cached = cache.as_ref().expect("snapshot cached above");
+        let changes = doc.doc
+            .get_changes(&cached.heads)
+            .into_iter()
+            .map(|c| c.raw_bytes().to_vec())
+            .collect();
+        Ok((cached.bytes.clone(), changes))
+    }
+}
+
+/// Download a snapshot of `book` from a server node and apply the trailing changes; `keyring`
+/// must hold the book's current key
+pub async fn fetch_snapshot(addr: impl ToSocketAddrs, token: &str, book: &str, keyring: &BookKeyring) -> Result<SyncDoc, SnapshotError> {
+    let mut stream = TcpStream::connect(addr).await?;
+    let request = SnapshotRequest { token: token.to_string() };
+    let sealed = keyring.seal(book, &serde_json::to_vec(&request)?)?;
+    write_frame(&mut stream, &serde_json::to_vec(&sealed)?).await?;
+
+    let sealed: SealedPayload = serde_json::from_slice(&read_frame(&mut stream, MAX_FRAME, READ_TIMEOUT).await?)?;
+    if sealed.book != book {
+        return Err(SnapshotError::InvalidChange);
+    }
+    match serde_json::from_slice(&keyring.open(&sealed)?)? {
+        SnapshotResponse::Denied => Err(SnapshotError::Denied),
+        SnapshotResponse::Ok { snapshot, changes } => {
+            let mut doc = SyncDoc::from_bytes(&snapshot)?;
+            let changes = changes.into_iter()
+                .map(|raw| Change::from_bytes(raw).map_err(|_| SnapshotError::InvalidChange))
+                .collect::<Result<Vec<_>, _>>()?;
+            doc.doc.apply_changes(changes).map_err(SyncError::from)?;
+            Ok(doc)
+        }
+    }
+}
+
+/// Length-prefixed (u32, big endian) frame
+async fn write_frame(stream: &mut TcpStream, data: &[u8]) -> Result<(), SnapshotError> {
+    let len = u32::try_from(data.len()).map_err(|_| SnapshotError::TooLarge(u32::MAX))?;
+    if len > MAX_FRAME {
+        return Err(SnapshotError::TooLarge(len));
+    }
+    stream.write_all(&len.to_be_bytes()).await?;
+    stream.write_all(data).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Read one frame of at most `max` bytes, giving up after `timeout` for the whole frame
+async fn read_frame(stream: &mut TcpStream, max: u32, timeout: Duration) -> Result<Vec<u8>, SnapshotError> {
+    let read = async {
+        let len = stream.read_u32().await?;
+        if len > max {
+            return Err(SnapshotError::TooLarge(len));
+        }
+        let mut data = vec![0; len as usize];
+        stream.read_exact(&mut data).await?;
+        Ok(data)
+    };
+    tokio::time::timeout(timeout, read).await.map_err(|_| SnapshotError::Timeout)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    async fn start(keyring: &BookKeyring, timeout: Duration) -> std::net::SocketAddr {
+        let doc = Arc::new(Mutex::new(SyncDoc::new().unwrap()));
+        let server = SnapshotServer::new(doc, "secret", "book", keyring.clone()).with_timeout(timeout);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(Arc::new(server).serve(listener));
+        addr
+    }
+
+    fn keyring() -> BookKeyring {
+        let mut keyring = BookKeyring::default();
+        keyring.create_book("book", BTreeSet::new());
+        keyring
+    }
+
+    #[tokio::test]
+    async fn fetch_needs_token_and_book_key() {
+        let keyring = keyring();
+        let addr = start(&keyring, READ_TIMEOUT).await;
+        assert!(fetch_snapshot(addr, "secret", "book", &keyring).await.is_ok());
+        assert!(matches!(fetch_snapshot(addr, "wrong", "book", &keyring).await, Err(SnapshotError::Denied)));
+        // A client with another key gets no answer at all
+        assert!(fetch_snapshot(addr, "secret", "book", &self::keyring()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn oversized_and_stalled_requests_are_dropped() {
+        let addr = start(&keyring(), Duration::from_millis(50)).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(&(MAX_REQUEST_FRAME + 1).to_be_bytes()).await.unwrap();
+        assert_eq!(stream.read(&mut [0; 1]).await.unwrap(), 0);
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(&16u32.to_be_bytes()).await.unwrap();
+        assert_eq!(stream.read(&mut [0; 1]).await.unwrap(), 0);
+    }
+}
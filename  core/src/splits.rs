@@ -0,0 +1,91 @@
+//! Per-account default split rules applied to imported transactions
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::currency::Commodity;
+use crate::ledger::Posting;
+use crate::rounding::RoundingPolicy;
+use crate::staging::{StagedTransaction, StagingSource};
+
+/// Counterparty account and its share of the amount
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplitShare {
+    pub account_id: Uuid,
+    /// Fraction of the amount (0.5 = half); shares of a rule must sum to 1
+    pub ratio: Decimal,
+}
+
+/// Default split for activity on one account (e.g. joint card split 50/50 between two IOU accounts)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitRule {
+    pub id: Uuid,
+    /// Account whose imported activity gets split
+    pub account_id: Uuid,
+    pub shares: Vec<SplitShare>,
+}
+
+impl SplitRule {
+    pub fn new(account_id: Uuid, shares: Vec<SplitShare>) -> Result<Self, &'static str> {
+        if shares.is_empty() {
+            return Err("Split rule needs at least one share");
+        }
+        if shares.iter().any(|s| s.ratio <= Decimal::ZERO) {
+            return Err("Split ratios must be positive");
+        }
+        if shares.iter().map(|s| s.ratio).sum::<Decimal>() != Decimal::ONE {
+            return Err("Split ratios must sum to 1");
+        }
+        Ok(Self { id: Uuid::new_v4(), account_id, shares })
+    }
+
+    /// Even split across the given accounts
+    pub fn equal(account_id: Uuid, counterparties: &[Uuid]) -> Result<Self, &'static str> {
+        if counterparties.is_empty() {
+            return Err("Split rule needs at least one share");
+        }
+        let ratio = Decimal::ONE / Decimal::from(counterparties.len());
+        let mut shares: Vec<SplitShare> = counterparties.iter()
+            .map(|id| SplitShare { account_id: *id, ratio })
+            .collect();
+        // Put the division remainder on the last share so ratios sum to exactly 1
+        let remainder = Decimal::ONE - ratio * Decimal::from(counterparties.len());
+        if let Some(last) = shares.last_mut() {
+            last.ratio += remainder;
+        }
+        Self::new(account_id, shares)
+    }
+
+    /// Counter postings offsetting `amount` posted on the rule account, rounded by the ledger's
+    /// policy; a rounding residual is booked on the policy's residual account
+    pub fn counter_postings(&self, amount: Decimal, commodity: &Commodity, policy: &RoundingPolicy) -> Vec<Posting> {
+        let ratios: Vec<Decimal> = self.shares.iter().map(|s| s.ratio).collect();
+        let allocation = policy.allocate(-amount, &ratios, commodity);
+        let mut postings: Vec<Posting> = self.shares.iter()
+            .zip(allocation.parts)
+            .map(|(share, part)| Posting::in_commodity(share.account_id, part, commodity.clone()))
+            .collect();
+        if let (Some(account_id), false) = (policy.residual_account, allocation.residual.is_zero()) {
+            postings.push(Posting::in_commodity(account_id, allocation.residual, commodity.clone()));
+        }
+        postings
+    }
+}
+
+/// Fill in the counter side of an imported entry that only has its bank-side posting.
+/// Returns true when a rule was applied.
+pub fn apply_split_rules<'a>(
+    rules: impl IntoIterator<Item = &'a SplitRule>,
+    entry: &mut StagedTransaction,
+    policy: &RoundingPolicy,
+) -> bool {
+    if entry.source != StagingSource::Import || entry.postings.len() != 1 {
+        return false;
+    }
+    let posting = entry.postings[0].clone();
+    let Some(rule) = rules.into_iter().find(|r| r.account_id == posting.account_id) else {
+        return false;
+    };
+    entry.postings.extend(rule.counter_postings(posting.amount, &posting.commodity, policy));
+    true
+}
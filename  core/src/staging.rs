@@ -0,0 +1,178 @@
+//! Staging area for transactions awaiting review before they hit the ledger
+use std::collections::HashMap;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+use crate::attachments::AttachmentRef;
+use crate::ledger::{Ledger, LedgerError, Posting, RecordSummary, Simulation, Transaction, TransactionStatus};
+use crate::storage::LocalStorage;
+
+/// Where a staged transaction came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StagingSource {
+    Manual,
+    Receipt,
+    Import,
+    /// Parsed from a receipt mail
+    Email,
+}
+
+/// File attached to a staged transaction (e.g. receipt photo)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StagedAttachment {
+    pub filename: Option<String>,
+    pub mime_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+impl StagedAttachment {
+    /// Reference the approved transaction carries
+    pub fn to_ref(&self) -> AttachmentRef {
+        AttachmentRef::for_bytes(
+            &self.data,
+            self.mime_type.as_deref().unwrap_or("application/octet-stream"),
+            self.filename.clone().unwrap_or_default(),
+        )
+    }
+}
+
+/// Pre-filled transaction the user still has to confirm
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StagedTransaction {
+    pub id: Uuid,
+    pub source: StagingSource,
+    pub date: Option<NaiveDate>,
+    pub amount: Option<Decimal>,
+    pub payee: Option<String>,
+    /// Contact matched from the counterparty account or payee
+    #[serde(default)]
+    pub contact_id: Option<Uuid>,
+    /// IBAN or account number of the other side, when the statement has one
+    #[serde(default)]
+    pub counterparty_account: Option<String>,
+    pub description: String,
+    pub postings: Vec<Posting>,
+    pub attachments: Vec<StagedAttachment>,
+}
+
+impl StagedTransaction {
+    /// Create empty staged entry
+    pub fn new(source: StagingSource) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            source,
+            date: None,
+            amount: None,
+            payee: None,
+            contact_id: None,
+            counterparty_account: None,
+            description: String::new(),
+            postings: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    /// Build the final transaction (date and balanced postings required)
+    pub fn to_transaction(&self) -> Result<Transaction, &'static str> {
+        let date = self.date.ok_or("Missing date")?;
+        if self.postings.is_empty() {
+            return Err("Missing postings");
+        }
+        let tx = Transaction {
+            id: self.id,
+            date,
+            description: self.description.clone(),
+            postings: self.postings.clone(),
+            payee: self.payee.clone(),
+            contact_id: self.contact_id,
+            reference: None,
+            origin_device: None,
+            is_closing_entry: false,
+            is_reversing_entry: false,
+            is_opening_balance: false,
+            status: TransactionStatus::Posted,
+            corrects: None,
+            void_reason: None,
+            transfer_id: None,
+            attachments: self.attachments.iter().map(StagedAttachment::to_ref).collect(),
+        };
+        if !tx.is_balanced() {
+            return Err("Unbalanced transaction");
+        }
+        Ok(tx)
+    }
+}
+
+/// Holds staged transactions until approved or discarded
+#[derive(Debug, Clone, Default)]
+pub struct StagingArea {
+    entries: HashMap<Uuid, StagedTransaction>,
+}
+
+impl StagingArea {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add entry to staging, returns its id
+    pub fn stage(&mut self, entry: StagedTransaction) -> Uuid {
+        let id = entry.id;
+        self.entries.insert(id, entry);
+        id
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<&StagedTransaction> {
+        self.entries.get(id)
+    }
+
+    pub fn get_mut(&mut self, id: &Uuid) -> Option<&mut StagedTransaction> {
+        self.entries.get_mut(id)
+    }
+
+    /// All pending entries
+    pub fn pending(&self) -> impl Iterator<Item = &StagedTransaction> {
+        self.entries.values()
+    }
+
+    /// Drop entry without posting
+    pub fn discard(&mut self, id: &Uuid) -> Option<StagedTransaction> {
+        self.entries.remove(id)
+    }
+
+    /// Turn staged entry into a transaction and remove it from staging
+    pub fn approve(&mut self, id: &Uuid) -> Result<Transaction, &'static str> {
+        let tx = self.entries.get(id).ok_or("Staged transaction not found")?.to_transaction()?;
+        self.entries.remove(id);
+        Ok(tx)
+    }
+
+    /// Like `approve`, also saving the attachment files so the transaction's references resolve
+    pub fn approve_storing(&mut self, id: &Uuid, storage: &LocalStorage) -> Result<Transaction, &'static str> {
+        let entry = self.entries.get(id).ok_or("Staged transaction not found")?;
+        let tx = entry.to_transaction()?;
+        for attachment in &entry.attachments {
+            storage.store_attachment(&attachment.to_ref(), &attachment.data);
+        }
+        self.entries.remove(id);
+        Ok(tx)
+    }
+
+    /// Dry run of approving every pending entry; incomplete entries show up as rejected
+    pub fn preview(&self, ledger: &Ledger) -> Simulation<RecordSummary> {
+        let mut incomplete = Vec::new();
+        let transactions: Vec<Transaction> = self.entries.values()
+            .filter_map(|entry| match entry.to_transaction() {
+                Ok(tx) => Some(tx),
+                Err(e) => {
+                    incomplete.push((entry.id, LedgerError::Rejected(e)));
+                    None
+                }
+            })
+            .collect();
+        let mut simulation = ledger.simulate_transactions(transactions);
+        simulation.result.rejected.extend(incomplete);
+        simulation
+    }
+}
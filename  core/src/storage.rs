@@ -1,19 +1,191 @@
-use rusqlite::{Connection, params};
+use rusqlite::{Connection, OptionalExtension, Params, params};
+use rusqlite::types::ValueRef;
 use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+use crate::attachments::AttachmentRef;
+use crate::chunker::{self, ChunkerConfig};
+use crate::codec::{self, CodecError, Encoding};
+use crate::dedup::content_hash;
+use crate::documents::Document;
+use crate::ledger::Transaction;
+use crate::views::{self, DerivedView};
 
 #[derive(Serialize, Deserialize)]
 pub struct StoredTransaction {
     pub id: String,
-    pub data: String, // JSON-serialized Transaction
+    pub data: Vec<u8>, // Transaction serialized with any `Encoding`
+}
+
+impl StoredTransaction {
+    pub fn encode(tx: &Transaction, encoding: Encoding) -> Result<Self, CodecError> {
+        Ok(Self { id: tx.id.to_string(), data: encoding.encode(tx)? })
+    }
+
+    pub fn decode(&self) -> Result<Transaction, CodecError> {
+        codec::decode(&self.data)
+    }
+}
+
+/// Cumulative sync traffic and outcome for one peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerSyncStats {
+    pub peer_id: String,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub merges_applied: u64,
+    pub last_error: Option<String>,
+    pub last_sync_at: Option<DateTime<Utc>>,
+}
+
+/// Where a transaction came from and how it reached this device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionProvenance {
+    pub transaction_id: String,
+    /// Device the transaction was entered on, when known
+    pub origin_device: Option<String>,
+    /// Peer whose sync batch delivered it; None for local entries
+    pub received_from: Option<String>,
+    /// Content hash of the sync payload that delivered it
+    pub batch_id: Option<String>,
+    pub received_at: DateTime<Utc>,
+}
+
+/// On-disk footprint of one table or index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableSize {
+    pub name: String,
+    pub bytes: u64,
+}
+
+/// Database size breakdown for storage settings screens
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageStats {
+    pub file_bytes: u64,
+    /// Space held by deleted rows, reclaimable by `vacuum`
+    pub free_bytes: u64,
+    /// Largest first
+    pub tables: Vec<TableSize>,
+}
+
+impl StorageStats {
+    pub fn free_ratio(&self) -> f64 {
+        if self.file_bytes == 0 { 0.0 } else { self.free_bytes as f64 / self.file_bytes as f64 }
+    }
+}
+
+/// When `vacuum_if_needed` actually rewrites the file
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct VacuumPolicy {
+    pub min_free_bytes: u64,
+    pub min_free_ratio: f64,
+}
+
+impl Default for VacuumPolicy {
+    fn default() -> Self {
+        Self { min_free_bytes: 4 * 1024 * 1024, min_free_ratio: 0.25 }
+    }
+}
+
+/// SQLite `synchronous` level: durability vs. write latency
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncLevel {
+    Off,
+    Normal,
+    Full,
+}
+
+impl SyncLevel {
+    fn pragma(&self) -> &'static str {
+        match self {
+            SyncLevel::Off => "OFF",
+            SyncLevel::Normal => "NORMAL",
+            SyncLevel::Full => "FULL",
+        }
+    }
+}
+
+/// Database location and connection tuning knobs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub path: String,
+    /// Page cache size in KiB
+    pub cache_size_kib: u32,
+    /// Memory-mapped I/O limit in bytes; 0 disables mmap
+    pub mmap_size: u64,
+    pub synchronous: SyncLevel,
+    /// Write-ahead logging, so readers don't block the writer
+    pub wal: bool,
+    /// Prepared statements kept per connection
+    pub statement_cache: usize,
+    /// Format new transaction rows are written in; older rows stay readable
+    #[serde(default)]
+    pub encoding: Encoding,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            path: "ledger.db".to_string(),
+            cache_size_kib: 8 * 1024,
+            mmap_size: 0,
+            synchronous: SyncLevel::Full,
+            wal: false,
+            statement_cache: 32,
+            encoding: Encoding::Json,
+        }
+    }
+}
+
+impl StorageConfig {
+    /// Small cache, mmap off and WAL with NORMAL sync for low-end phones
+    pub fn low_end_device() -> Self {
+        Self {
+            cache_size_kib: 2 * 1024,
+            synchronous: SyncLevel::Normal,
+            wal: true,
+            statement_cache: 16,
+            ..Self::default()
+        }
+    }
 }
 
 pub struct LocalStorage {
     conn: Connection,
+    config: StorageConfig,
+}
+
+/// Stored snapshot and how much new data it added
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub id: String,
+    pub taken_at: DateTime<Utc>,
+    pub size: u64,
+    pub chunk_count: u64,
+    /// Bytes of chunks not already present from earlier snapshots
+    pub new_bytes: u64,
+}
+
+/// Read-only connection for long report queries; with WAL it never blocks the writer
+pub struct StorageReader {
+    conn: Connection,
+}
+
+impl Default for LocalStorage {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl LocalStorage {
     pub fn new() -> Self {
-        let conn = Connection::open("ledger.db").unwrap();
+        Self::with_config(&StorageConfig::default())
+    }
+
+    pub fn with_config(config: &StorageConfig) -> Self {
+        let conn = Connection::open(&config.path).unwrap();
+        Self::apply_tuning(&conn, config);
         conn.execute(
             "CREATE TABLE IF NOT EXISTS transactions (
                 id TEXT PRIMARY KEY,
@@ -21,24 +193,667 @@ impl LocalStorage {
             )",
             [],
         ).unwrap();
-        Self { conn }
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS peer_sync_stats (
+                peer_id TEXT PRIMARY KEY,
+                bytes_sent INTEGER NOT NULL DEFAULT 0,
+                bytes_received INTEGER NOT NULL DEFAULT 0,
+                merges_applied INTEGER NOT NULL DEFAULT 0,
+                last_error TEXT,
+                last_sync_at TEXT
+            )",
+            [],
+        ).unwrap();
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            )",
+            [],
+        ).unwrap();
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS transaction_provenance (
+                transaction_id TEXT PRIMARY KEY,
+                origin_device TEXT,
+                received_from TEXT,
+                batch_id TEXT,
+                received_at TEXT NOT NULL
+            )",
+            [],
+        ).unwrap();
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS blob_chunks (
+                hash TEXT PRIMARY KEY,
+                data BLOB NOT NULL,
+                refs INTEGER NOT NULL DEFAULT 0
+            )",
+            [],
+        ).unwrap();
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS snapshots (
+                id TEXT PRIMARY KEY,
+                taken_at TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                new_bytes INTEGER NOT NULL,
+                chunks TEXT NOT NULL
+            )",
+            [],
+        ).unwrap();
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS derived_views (
+                name TEXT PRIMARY KEY,
+                stale INTEGER NOT NULL DEFAULT 0
+            )",
+            [],
+        ).unwrap();
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS attachments (
+                hash TEXT PRIMARY KEY,
+                mime_type TEXT NOT NULL,
+                data BLOB NOT NULL
+            )",
+            [],
+        ).unwrap();
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS entity_docs (
+                name TEXT PRIMARY KEY,
+                doc BLOB NOT NULL
+            )",
+            [],
+        ).unwrap();
+        conn.execute(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS documents_fts USING fts5(
+                id UNINDEXED,
+                title,
+                body
+            )",
+            [],
+        ).unwrap();
+        Self { conn, config: config.clone() }
     }
 
-    pub fn save_transaction(&self, tx: &StoredTransaction) {
-        self.conn.execute(
-            "INSERT OR REPLACE INTO transactions (id, data) VALUES (?, ?)",
-            params![tx.id, tx.data],
+    /// Open an extra read-only connection to the same database (e.g. one per report worker).
+    /// Readers only run concurrently with writes when the database uses WAL.
+    pub fn reader(&self) -> StorageReader {
+        let conn = Connection::open_with_flags(
+            &self.config.path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
         ).unwrap();
+        conn.execute_batch(&format!(
+            "PRAGMA cache_size = -{}; PRAGMA mmap_size = {};",
+            self.config.cache_size_kib, self.config.mmap_size,
+        )).unwrap();
+        conn.set_prepared_statement_cache_capacity(self.config.statement_cache);
+        StorageReader { conn }
+    }
+
+    /// Change tuning on the open connection (the path is ignored)
+    pub fn tune(&mut self, config: &StorageConfig) {
+        Self::apply_tuning(&self.conn, config);
+        self.config = StorageConfig { path: self.config.path.clone(), ..config.clone() };
+    }
+
+    fn apply_tuning(conn: &Connection, config: &StorageConfig) {
+        // Negative cache_size is in KiB rather than pages
+        conn.execute_batch(&format!(
+            "PRAGMA cache_size = -{};
+             PRAGMA mmap_size = {};
+             PRAGMA synchronous = {};
+             PRAGMA journal_mode = {};",
+            config.cache_size_kib,
+            config.mmap_size,
+            config.synchronous.pragma(),
+            if config.wal { "WAL" } else { "DELETE" },
+        )).unwrap();
+        conn.set_prepared_statement_cache_capacity(config.statement_cache);
+    }
+
+    pub fn save_transaction(&self, tx: &StoredTransaction) {
+        let mut stmt = self.conn
+            .prepare_cached("INSERT OR REPLACE INTO transactions (id, data) VALUES (?, ?)")
+            .unwrap();
+        // Keep JSON rows as TEXT so SQL json functions keep working on them
+        match std::str::from_utf8(&tx.data) {
+            Ok(json) if Encoding::detect(&tx.data) == Encoding::Json => stmt.execute(params![tx.id, json]),
+            _ => stmt.execute(params![tx.id, tx.data]),
+        }
+        .unwrap();
+        self.invalidate_views();
+    }
+
+    /// Create (or recreate, if its SQL changed between versions) a derived view
+    pub fn register_view<V: DerivedView>(&self) {
+        let table = views::table_name::<V>();
+        let tx = self.conn.unchecked_transaction().unwrap();
+        tx.execute_batch(&format!("DROP VIEW IF EXISTS {0}; DROP TABLE IF EXISTS {0};", table)).unwrap();
+        if V::MATERIALIZED {
+            tx.execute_batch(&format!("CREATE TABLE {} AS {}", table, V::select())).unwrap();
+            tx.execute(
+                "INSERT OR REPLACE INTO derived_views (name, stale) VALUES (?, 0)",
+                params![V::NAME],
+            ).unwrap();
+        } else {
+            tx.execute_batch(&format!("CREATE VIEW {} AS {}", table, V::select())).unwrap();
+            tx.execute("DELETE FROM derived_views WHERE name = ?", params![V::NAME]).unwrap();
+        }
+        tx.commit().unwrap();
+    }
+
+    /// Rows of a registered view, refreshing it first if writes made it stale
+    pub fn query_view<V: DerivedView>(&self) -> Vec<V::Row> {
+        self.query_view_where::<V, _>("1", [])
+    }
+
+    /// Rows of a registered view matching a SQL condition over its columns
+    pub fn query_view_where<V: DerivedView, P: Params>(&self, condition: &str, params: P) -> Vec<V::Row> {
+        if V::MATERIALIZED && view_is_stale(&self.conn, V::NAME) {
+            self.refresh_view::<V>();
+        }
+        query_view::<V, P>(&self.conn, &views::table_name::<V>(), condition, params)
+    }
+
+    /// Recompute a materialized view now instead of on its next query
+    pub fn refresh_view<V: DerivedView>(&self) {
+        let table = views::table_name::<V>();
+        let tx = self.conn.unchecked_transaction().unwrap();
+        tx.execute(&format!("DELETE FROM {}", table), []).unwrap();
+        tx.execute(&format!("INSERT INTO {} {}", table, V::select()), []).unwrap();
+        tx.execute("UPDATE derived_views SET stale = 0 WHERE name = ?", params![V::NAME]).unwrap();
+        tx.commit().unwrap();
+    }
+
+    fn invalidate_views(&self) {
+        self.conn
+            .prepare_cached("UPDATE derived_views SET stale = 1 WHERE stale = 0")
+            .unwrap()
+            .execute([])
+            .unwrap();
+    }
+
+    /// Serialize with the configured encoding and save
+    pub fn store_transaction(&self, tx: &Transaction) -> Result<(), CodecError> {
+        self.save_transaction(&StoredTransaction::encode(tx, self.config.encoding)?);
+        Ok(())
     }
 
     pub fn get_all_transactions(&self) -> Vec<StoredTransaction> {
-        let mut stmt = self.conn.prepare("SELECT id, data FROM transactions").unwrap();
-        let tx_iter = stmt.query_map([], |row| {
-            Ok(StoredTransaction {
+        load_transactions(&self.conn)
+    }
+
+    /// Visit stored transactions one row at a time, in insertion order, without loading them all
+    pub fn for_each_transaction<E>(&self, f: impl FnMut(StoredTransaction) -> Result<(), E>) -> Result<(), E> {
+        for_each_transaction(&self.conn, f)
+    }
+
+    /// File size, reclaimable space and per-table usage
+    pub fn stats(&self) -> StorageStats {
+        let pragma = |name: &str| -> u64 {
+            self.conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get::<_, i64>(0)).unwrap() as u64
+        };
+        let page_size = pragma("page_size");
+        let mut stmt = self.conn
+            .prepare("SELECT name, SUM(pgsize) FROM dbstat GROUP BY name ORDER BY SUM(pgsize) DESC")
+            .unwrap();
+        let tables = stmt.query_map([], |row| {
+            Ok(TableSize { name: row.get(0)?, bytes: row.get::<_, i64>(1)? as u64 })
+        }).unwrap();
+        StorageStats {
+            file_bytes: pragma("page_count") * page_size,
+            free_bytes: pragma("freelist_count") * page_size,
+            tables: tables.collect::<Result<Vec<_>, _>>().unwrap(),
+        }
+    }
+
+    /// Rewrite the database file, returning freed pages to the OS; returns bytes reclaimed
+    pub fn vacuum(&self) -> u64 {
+        let before = self.stats().file_bytes;
+        self.conn.execute_batch("VACUUM").unwrap();
+        before.saturating_sub(self.stats().file_bytes)
+    }
+
+    /// Vacuum when deleted space crosses the policy thresholds (call after pruning or compaction).
+    /// Skipped inside an open savepoint, where SQLite refuses to vacuum.
+    pub fn vacuum_if_needed(&self, policy: &VacuumPolicy) -> Option<u64> {
+        if !self.conn.is_autocommit() {
+            return None;
+        }
+        let stats = self.stats();
+        (stats.free_bytes >= policy.min_free_bytes && stats.free_ratio() >= policy.min_free_ratio)
+            .then(|| self.vacuum())
+    }
+
+    pub fn savepoint(&self, name: &str) {
+        self.conn.execute_batch(&format!("SAVEPOINT {}", name)).unwrap();
+    }
+
+    pub fn rollback_to_savepoint(&self, name: &str) {
+        self.conn.execute_batch(&format!("ROLLBACK TO {}", name)).unwrap();
+    }
+
+    pub fn release_savepoint(&self, name: &str) {
+        self.conn.execute_batch(&format!("RELEASE {}", name)).unwrap();
+    }
+
+    pub fn get_setting(&self, key: &str) -> Option<String> {
+        self.conn
+            .prepare_cached("SELECT value FROM settings WHERE key = ?")
+            .unwrap()
+            .query_row(params![key], |row| row.get(0))
+            .optional()
+            .unwrap()
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) {
+        self.conn
+            .prepare_cached("INSERT OR REPLACE INTO settings (key, value) VALUES (?, ?)")
+            .unwrap()
+            .execute(params![key, value])
+            .unwrap();
+    }
+
+    /// Store an attachment's bytes under its hash; returns false if they don't match the reference
+    pub fn store_attachment(&self, attachment: &AttachmentRef, data: &[u8]) -> bool {
+        if !attachment.matches(data) {
+            return false;
+        }
+        self.conn
+            .prepare_cached("INSERT OR IGNORE INTO attachments (hash, mime_type, data) VALUES (?, ?, ?)")
+            .unwrap()
+            .execute(params![attachment.hash, attachment.mime_type, data])
+            .unwrap();
+        true
+    }
+
+    pub fn load_attachment(&self, hash: &str) -> Option<Vec<u8>> {
+        self.conn
+            .prepare_cached("SELECT data FROM attachments WHERE hash = ?")
+            .unwrap()
+            .query_row(params![hash], |row| row.get(0))
+            .optional()
+            .unwrap()
+    }
+
+    pub fn has_attachment(&self, hash: &str) -> bool {
+        self.conn
+            .prepare_cached("SELECT 1 FROM attachments WHERE hash = ?")
+            .unwrap()
+            .exists(params![hash])
+            .unwrap()
+    }
+
+    /// Store the CRDT document of a workspace entity
+    pub fn save_entity_doc(&self, name: &str, doc: &[u8]) {
+        self.conn
+            .prepare_cached("INSERT OR REPLACE INTO entity_docs (name, doc) VALUES (?, ?)")
+            .unwrap()
+            .execute(params![name, doc])
+            .unwrap();
+    }
+
+    pub fn load_entity_doc(&self, name: &str) -> Option<Vec<u8>> {
+        self.conn
+            .prepare_cached("SELECT doc FROM entity_docs WHERE name = ?")
+            .unwrap()
+            .query_row(params![name], |row| row.get(0))
+            .optional()
+            .unwrap()
+    }
+
+    /// Names of stored workspace entities, sorted
+    pub fn entity_names(&self) -> Vec<String> {
+        let mut stmt = self.conn.prepare_cached("SELECT name FROM entity_docs ORDER BY name").unwrap();
+        let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+        rows.collect::<Result<Vec<_>, _>>().unwrap()
+    }
+
+    pub fn delete_entity_doc(&self, name: &str) -> bool {
+        self.conn.execute("DELETE FROM entity_docs WHERE name = ?", params![name]).unwrap() > 0
+    }
+
+    /// Add or refresh a document in the full-text index
+    pub fn index_document(&self, document: &Document) {
+        self.conn
+            .prepare_cached("DELETE FROM documents_fts WHERE id = ?")
+            .unwrap()
+            .execute(params![document.id.to_string()])
+            .unwrap();
+        self.conn
+            .prepare_cached("INSERT INTO documents_fts (id, title, body) VALUES (?, ?, ?)")
+            .unwrap()
+            .execute(params![document.id.to_string(), document.title, document.body])
+            .unwrap();
+    }
+
+    pub fn remove_document_index(&self, id: &str) {
+        self.conn.execute("DELETE FROM documents_fts WHERE id = ?", params![id]).unwrap();
+    }
+
+    /// Rebuild the index from the synced collection (e.g. after a merge)
+    pub fn reindex_documents<'a>(&self, documents: impl IntoIterator<Item = &'a Document>) {
+        self.conn.execute("DELETE FROM documents_fts", []).unwrap();
+        for document in documents {
+            self.index_document(document);
+        }
+        self.vacuum_if_needed(&VacuumPolicy::default());
+    }
+
+    /// Ids of documents matching an FTS5 query, best match first
+    pub fn search_documents(&self, query: &str) -> Vec<String> {
+        search_documents(&self.conn, query)
+    }
+
+    /// Store a CRDT snapshot as content-defined chunks, sharing chunks with earlier snapshots
+    pub fn store_snapshot(&self, id: &str, data: &[u8]) -> SnapshotInfo {
+        let tx = self.conn.unchecked_transaction().unwrap();
+        let mut hashes = Vec::new();
+        let mut new_bytes = 0u64;
+        for chunk in chunker::chunks(data, &ChunkerConfig::default()) {
+            let hash: String = content_hash(chunk).iter().map(|b| format!("{:02x}", b)).collect();
+            let inserted = tx
+                .prepare_cached("INSERT OR IGNORE INTO blob_chunks (hash, data, refs) VALUES (?, ?, 0)")
+                .unwrap()
+                .execute(params![hash, chunk])
+                .unwrap();
+            if inserted > 0 {
+                new_bytes += chunk.len() as u64;
+            }
+            tx.prepare_cached("UPDATE blob_chunks SET refs = refs + 1 WHERE hash = ?")
+                .unwrap()
+                .execute(params![hash])
+                .unwrap();
+            hashes.push(hash);
+        }
+        let info = SnapshotInfo {
+            id: id.to_string(),
+            taken_at: Utc::now(),
+            size: data.len() as u64,
+            chunk_count: hashes.len() as u64,
+            new_bytes,
+        };
+        tx.execute(
+            "INSERT INTO snapshots (id, taken_at, size, new_bytes, chunks) VALUES (?, ?, ?, ?, ?)",
+            params![
+                info.id,
+                info.taken_at.to_rfc3339(),
+                info.size as i64,
+                info.new_bytes as i64,
+                serde_json::to_string(&hashes).unwrap(),
+            ],
+        ).unwrap();
+        tx.commit().unwrap();
+        info
+    }
+
+    /// Reassemble a stored snapshot
+    pub fn load_snapshot(&self, id: &str) -> Option<Vec<u8>> {
+        let chunks: String = self.conn
+            .query_row("SELECT chunks FROM snapshots WHERE id = ?", params![id], |row| row.get(0))
+            .optional()
+            .unwrap()?;
+        let hashes: Vec<String> = serde_json::from_str(&chunks).unwrap();
+        let mut stmt = self.conn.prepare_cached("SELECT data FROM blob_chunks WHERE hash = ?").unwrap();
+        let mut data = Vec::new();
+        for hash in hashes {
+            let chunk: Vec<u8> = stmt.query_row(params![hash], |row| row.get(0)).unwrap();
+            data.extend_from_slice(&chunk);
+        }
+        Some(data)
+    }
+
+    /// Stored snapshots, newest first
+    pub fn snapshots(&self) -> Vec<SnapshotInfo> {
+        let mut stmt = self.conn
+            .prepare("SELECT id, taken_at, size, json_array_length(chunks), new_bytes FROM snapshots ORDER BY taken_at DESC")
+            .unwrap();
+        let rows = stmt.query_map([], |row| {
+            let taken_at: String = row.get(1)?;
+            Ok(SnapshotInfo {
                 id: row.get(0)?,
-                data: row.get(1)?,
+                taken_at: DateTime::parse_from_rfc3339(&taken_at)
+                    .map(|d| d.with_timezone(&Utc))
+                    .unwrap_or_else(|_| Utc::now()),
+                size: row.get::<_, i64>(2)? as u64,
+                chunk_count: row.get::<_, i64>(3)? as u64,
+                new_bytes: row.get::<_, i64>(4)? as u64,
             })
         }).unwrap();
-        tx_iter.collect::<Result<Vec<_>, _>>().unwrap()
+        rows.collect::<Result<Vec<_>, _>>().unwrap()
     }
-}
\ No newline at end of file
+
+    /// Drop a snapshot and any chunks no other snapshot uses; only reachable through a quorum `Approval`
+    pub(crate) fn delete_snapshot(&self, id: &str) -> bool {
+        let Some(chunks) = self.conn
+            .query_row("SELECT chunks FROM snapshots WHERE id = ?", params![id], |row| row.get::<_, String>(0))
+            .optional()
+            .unwrap()
+        else {
+            return false;
+        };
+        let hashes: Vec<String> = serde_json::from_str(&chunks).unwrap();
+        let tx = self.conn.unchecked_transaction().unwrap();
+        for hash in &hashes {
+            tx.execute("UPDATE blob_chunks SET refs = refs - 1 WHERE hash = ?", params![hash]).unwrap();
+        }
+        tx.execute("DELETE FROM blob_chunks WHERE refs <= 0", []).unwrap();
+        tx.execute("DELETE FROM snapshots WHERE id = ?", params![id]).unwrap();
+        tx.commit().unwrap();
+        self.vacuum_if_needed(&VacuumPolicy::default());
+        true
+    }
+
+    /// Stable id of this device, generated on first use
+    pub fn device_id(&self) -> String {
+        if let Some(id) = self.get_setting("device_id") {
+            return id;
+        }
+        let id = uuid::Uuid::new_v4().to_string();
+        self.set_setting("device_id", &id);
+        id
+    }
+
+    /// Remember how a transaction arrived; the first record wins so later re-deliveries don't overwrite it
+    pub fn record_provenance(&self, provenance: &TransactionProvenance) {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO transaction_provenance
+                (transaction_id, origin_device, received_from, batch_id, received_at)
+             VALUES (?, ?, ?, ?, ?)",
+            params![
+                provenance.transaction_id,
+                provenance.origin_device,
+                provenance.received_from,
+                provenance.batch_id,
+                provenance.received_at.to_rfc3339(),
+            ],
+        ).unwrap();
+    }
+
+    pub fn provenance(&self, transaction_id: &str) -> Option<TransactionProvenance> {
+        self.query_provenance("WHERE transaction_id = ?1", params![transaction_id]).into_iter().next()
+    }
+
+    /// Everything delivered by one sync batch
+    pub fn provenance_for_batch(&self, batch_id: &str) -> Vec<TransactionProvenance> {
+        self.query_provenance("WHERE batch_id = ?1 ORDER BY transaction_id", params![batch_id])
+    }
+
+    fn query_provenance(&self, clause: &str, args: &[&dyn rusqlite::ToSql]) -> Vec<TransactionProvenance> {
+        let sql = format!(
+            "SELECT transaction_id, origin_device, received_from, batch_id, received_at
+             FROM transaction_provenance {}",
+            clause
+        );
+        let mut stmt = self.conn.prepare(&sql).unwrap();
+        let rows = stmt.query_map(args, |row| {
+            let received_at: String = row.get(4)?;
+            Ok(TransactionProvenance {
+                transaction_id: row.get(0)?,
+                origin_device: row.get(1)?,
+                received_from: row.get(2)?,
+                batch_id: row.get(3)?,
+                received_at: DateTime::parse_from_rfc3339(&received_at)
+                    .map(|d| d.with_timezone(&Utc))
+                    .unwrap_or_else(|_| Utc::now()),
+            })
+        }).unwrap();
+        rows.collect::<Result<Vec<_>, _>>().unwrap()
+    }
+
+    /// Add one sync exchange to a peer's totals; `error` replaces the last error (None clears it)
+    pub fn record_peer_sync(
+        &self,
+        peer_id: &str,
+        bytes_sent: u64,
+        bytes_received: u64,
+        merges_applied: u64,
+        error: Option<&str>,
+    ) {
+        self.conn
+            .prepare_cached(
+                "INSERT INTO peer_sync_stats (peer_id, bytes_sent, bytes_received, merges_applied, last_error, last_sync_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT(peer_id) DO UPDATE SET
+                    bytes_sent = bytes_sent + excluded.bytes_sent,
+                    bytes_received = bytes_received + excluded.bytes_received,
+                    merges_applied = merges_applied + excluded.merges_applied,
+                    last_error = excluded.last_error,
+                    last_sync_at = excluded.last_sync_at",
+            )
+            .unwrap()
+            .execute(params![
+                peer_id,
+                bytes_sent as i64,
+                bytes_received as i64,
+                merges_applied as i64,
+                error,
+                Utc::now().to_rfc3339(),
+            ])
+            .unwrap();
+    }
+
+    pub fn peer_sync_stats(&self, peer_id: &str) -> Option<PeerSyncStats> {
+        self.query_peer_sync_stats("WHERE peer_id = ?1", params![peer_id]).into_iter().next()
+    }
+
+    /// All peers, heaviest data consumers first
+    pub fn all_peer_sync_stats(&self) -> Vec<PeerSyncStats> {
+        self.query_peer_sync_stats("ORDER BY bytes_sent + bytes_received DESC", params![])
+    }
+
+    pub fn reset_peer_sync_stats(&self, peer_id: &str) {
+        self.conn.execute("DELETE FROM peer_sync_stats WHERE peer_id = ?", params![peer_id]).unwrap();
+    }
+
+    fn query_peer_sync_stats(&self, clause: &str, args: &[&dyn rusqlite::ToSql]) -> Vec<PeerSyncStats> {
+        let sql = format!(
+            "SELECT peer_id, bytes_sent, bytes_received, merges_applied, last_error, last_sync_at
+             FROM peer_sync_stats {}",
+            clause
+        );
+        let mut stmt = self.conn.prepare(&sql).unwrap();
+        let rows = stmt.query_map(args, |row| {
+            let last_sync_at: Option<String> = row.get(5)?;
+            Ok(PeerSyncStats {
+                peer_id: row.get(0)?,
+                bytes_sent: row.get::<_, i64>(1)? as u64,
+                bytes_received: row.get::<_, i64>(2)? as u64,
+                merges_applied: row.get::<_, i64>(3)? as u64,
+                last_error: row.get(4)?,
+                last_sync_at: last_sync_at
+                    .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                    .map(|d| d.with_timezone(&Utc)),
+            })
+        }).unwrap();
+        rows.collect::<Result<Vec<_>, _>>().unwrap()
+    }
+}
+
+impl StorageReader {
+    pub fn get_all_transactions(&self) -> Vec<StoredTransaction> {
+        load_transactions(&self.conn)
+    }
+
+    pub fn for_each_transaction<E>(&self, f: impl FnMut(StoredTransaction) -> Result<(), E>) -> Result<(), E> {
+        for_each_transaction(&self.conn, f)
+    }
+
+    pub fn search_documents(&self, query: &str) -> Vec<String> {
+        search_documents(&self.conn, query)
+    }
+
+    /// Rows of a registered view; a stale materialized view is computed live since readers can't refresh it
+    pub fn query_view<V: DerivedView>(&self) -> Vec<V::Row> {
+        self.query_view_where::<V, _>("1", [])
+    }
+
+    pub fn query_view_where<V: DerivedView, P: Params>(&self, condition: &str, params: P) -> Vec<V::Row> {
+        let source = if V::MATERIALIZED && view_is_stale(&self.conn, V::NAME) {
+            format!("({})", V::select())
+        } else {
+            views::table_name::<V>()
+        };
+        query_view::<V, P>(&self.conn, &source, condition, params)
+    }
+
+    /// Run several queries against one consistent snapshot, unaffected by concurrent merges
+    pub fn snapshot<T>(&mut self, f: impl FnOnce(&Connection) -> T) -> T {
+        let tx = self.conn.transaction_with_behavior(rusqlite::TransactionBehavior::Deferred).unwrap();
+        let result = f(&tx);
+        tx.finish().unwrap();
+        result
+    }
+
+    /// Raw connection for ad-hoc report SQL
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+fn stored_transaction(row: &rusqlite::Row) -> rusqlite::Result<StoredTransaction> {
+    // Rows written before binary encodings were added are JSON TEXT
+    let data = match row.get_ref(1)? {
+        ValueRef::Text(bytes) | ValueRef::Blob(bytes) => bytes.to_vec(),
+        _ => Vec::new(),
+    };
+    Ok(StoredTransaction {
+        id: row.get(0)?,
+        data,
+    })
+}
+
+fn load_transactions(conn: &Connection) -> Vec<StoredTransaction> {
+    let mut stmt = conn.prepare_cached("SELECT id, data FROM transactions").unwrap();
+    let tx_iter = stmt.query_map([], stored_transaction).unwrap();
+    tx_iter.collect::<Result<Vec<_>, _>>().unwrap()
+}
+
+fn for_each_transaction<E>(conn: &Connection, mut f: impl FnMut(StoredTransaction) -> Result<(), E>) -> Result<(), E> {
+    let mut stmt = conn.prepare_cached("SELECT id, data FROM transactions ORDER BY rowid").unwrap();
+    let mut rows = stmt.query([]).unwrap();
+    while let Some(row) = rows.next().unwrap() {
+        f(stored_transaction(row).unwrap())?;
+    }
+    Ok(())
+}
+
+fn view_is_stale(conn: &Connection, name: &str) -> bool {
+    conn.prepare_cached("SELECT stale FROM derived_views WHERE name = ?")
+        .unwrap()
+        .query_row(params![name], |row| row.get::<_, bool>(0))
+        .optional()
+        .unwrap()
+        .unwrap_or(false)
+}
+
+fn query_view<V: DerivedView, P: Params>(conn: &Connection, source: &str, condition: &str, params: P) -> Vec<V::Row> {
+    let mut stmt = conn.prepare(&format!("SELECT * FROM {} WHERE {}", source, condition)).unwrap();
+    let rows = stmt.query_map(params, |row| V::map_row(row)).unwrap();
+    rows.collect::<Result<Vec<_>, _>>().unwrap()
+}
+
+fn search_documents(conn: &Connection, query: &str) -> Vec<String> {
+    let mut stmt = conn
+        .prepare_cached("SELECT id FROM documents_fts WHERE documents_fts MATCH ? ORDER BY rank")
+        .unwrap();
+    let ids = stmt.query_map(params![query], |row| row.get(0)).unwrap();
+    ids.collect::<Result<Vec<_>, _>>().unwrap()
+}
@@ -1,18 +1,78 @@
 //! CRDT-based synchronization layer for offline-first ledger sync
 use std::collections::HashMap;
+use std::str::FromStr;
+use automerge::transaction::Transactable;
 use automerge::{AutoCommit, ObjId, ObjType, ReadDoc, Value};
 use rust_decimal::Decimal;
 use uuid::Uuid;
-use serde::{Serialize, Deserialize};
+use serde::{de::DeserializeOwned, Serialize, Deserialize};
 
-use crate::ledger::{Account, AccountType, Transaction};
+use crate::activity::ActivityEntry;
+use crate::app_settings::AppSettings;
+use crate::attachments::AttachmentRef;
+use crate::canonical::{CanonicalDecimal, OutOfRange};
+use crate::classes::ReportingClass;
+use crate::close::CloseChecklist;
+use crate::config::SharedSettings;
+use crate::conflict::{self, Conflict, Resolution};
+use crate::currency::Commodity;
+use crate::delegation::WriteGrant;
+use crate::contacts::Contact;
+use crate::documents::{Document, DocumentLink};
+use crate::inventory::{Inventory, Item, StockMovement};
+use crate::invoicing::{Invoice, InvoiceError};
+use crate::ledger::reconcile::{ClearedState, PostingRef, ReconciliationSession, Statement};
+use crate::ledger::{Account, AccountDisplay, AccountType, Budget, CashFlowActivity, Transaction, TransactionStatus};
+use crate::prices::{PriceDb, PriceQuote};
+use crate::projects::Project;
+use crate::recurring::{detect_recurring, DetectionOptions, RecurringCandidate, RecurringTransaction};
+use crate::rules::{self, CategorizationRule};
+use crate::splits::SplitRule;
+
+/// Posting in a commodity its account does not take, e.g. from a misconfigured importer on a peer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommodityMismatch {
+    pub transaction_id: Uuid,
+    pub account_id: Uuid,
+    pub commodity: Commodity,
+}
 
 /// Represents a syncable ledger state
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SyncableLedger {
     pub accounts: HashMap<Uuid, Account>,
     pub transactions: Vec<Transaction>,
     pub balances: HashMap<Uuid, Decimal>,
+    pub close_checklists: HashMap<Uuid, CloseChecklist>,
+    pub split_rules: HashMap<Uuid, SplitRule>,
+    pub documents: HashMap<Uuid, Document>,
+    pub contacts: HashMap<Uuid, Contact>,
+    pub projects: HashMap<Uuid, Project>,
+    pub classes: HashMap<Uuid, ReportingClass>,
+    pub budgets: HashMap<Uuid, Budget>,
+    pub prices: HashMap<Uuid, PriceQuote>,
+    pub recurring: HashMap<Uuid, RecurringTransaction>,
+    pub settings: SharedSettings,
+    pub invoices: HashMap<Uuid, Invoice>,
+    pub reconciliations: HashMap<Uuid, ReconciliationSession>,
+    /// Shared period lock; merges keep the later of two dates
+    pub locked_through: Option<chrono::NaiveDate>,
+    /// Last closed period end; merges keep the later of two dates
+    #[serde(default)]
+    pub closed_through: Option<chrono::NaiveDate>,
+    pub rules: HashMap<Uuid, CategorizationRule>,
+    pub statements: HashMap<Uuid, Statement>,
+    /// Frontend preferences shared across devices
+    #[serde(default)]
+    pub app_settings: AppSettings,
+    /// Stocked items; stock levels are summed from the movements
+    #[serde(default)]
+    pub stock_items: HashMap<Uuid, Item>,
+    #[serde(default)]
+    pub stock_movements: HashMap<Uuid, StockMovement>,
+    /// Activity feed entries from every device
+    #[serde(default)]
+    pub activity: HashMap<Uuid, ActivityEntry>,
 }
 
 impl SyncableLedger {
@@ -22,22 +82,440 @@ impl SyncableLedger {
             accounts: HashMap::new(),
             transactions: Vec::new(),
             balances: HashMap::new(),
+            close_checklists: HashMap::new(),
+            split_rules: HashMap::new(),
+            documents: HashMap::new(),
+            contacts: HashMap::new(),
+            projects: HashMap::new(),
+            classes: HashMap::new(),
+            budgets: HashMap::new(),
+            prices: HashMap::new(),
+            recurring: HashMap::new(),
+            settings: SharedSettings::default(),
+            invoices: HashMap::new(),
+            reconciliations: HashMap::new(),
+            locked_through: None,
+            closed_through: None,
+            rules: HashMap::new(),
+            statements: HashMap::new(),
+            app_settings: AppSettings::default(),
+            stock_items: HashMap::new(),
+            stock_movements: HashMap::new(),
+            activity: HashMap::new(),
         }
     }
 
-    /// Add account to ledger
-    pub fn add_account(&mut self, account: Account) {
-        self.accounts.insert(account.id, account);
-        self.balances.entry(account.id).or_insert(Decimal::ZERO);
-    }
-
-    /// Record transaction (assumes already validated)
-    pub fn record_transaction(&mut self, tx: Transaction) {
+    /// Append a transaction and update balances; validation is the `Ledger`'s job
+    pub(crate) fn push_transaction(&mut self, tx: Transaction) {
         for posting in &tx.postings {
             *self.balances.entry(posting.account_id).or_insert(Decimal::ZERO) += posting.amount;
         }
         self.transactions.push(tx);
     }
+
+    /// Postings of `transactions` in a commodity their account does not allow
+    pub fn commodity_mismatches(&self, transactions: &[Transaction]) -> Vec<CommodityMismatch> {
+        transactions.iter()
+            .flat_map(|tx| tx.postings.iter().map(move |p| (tx.id, p)))
+            .filter(|(_, p)| self.accounts.get(&p.account_id).is_some_and(|a| !a.accepts(&p.commodity)))
+            .map(|(transaction_id, p)| CommodityMismatch {
+                transaction_id,
+                account_id: p.account_id,
+                commodity: p.commodity.clone(),
+            })
+            .collect()
+    }
+
+    /// Attach a file reference to a stored transaction; attaching the same file again is a no-op
+    pub fn attach(&mut self, id: Uuid, attachment: AttachmentRef) -> Result<(), &'static str> {
+        let tx = self.transactions.iter_mut().find(|t| t.id == id).ok_or("Transaction not found")?;
+        if !tx.attachments.iter().any(|a| a.hash == attachment.hash) {
+            tx.attachments.push(attachment);
+        }
+        Ok(())
+    }
+
+    /// Hashes of every attached file, for fetching the ones missing locally
+    pub fn attachment_hashes(&self) -> std::collections::BTreeSet<&str> {
+        self.transactions.iter().flat_map(|t| &t.attachments).map(|a| a.hash.as_str()).collect()
+    }
+
+    /// Undo merged edits that touch the locked period: transactions dated in it (before or after
+    /// the edit) go back to their `local` version, new ones are dropped and deleted ones restored.
+    /// Cleared state may still change so locked periods can be reconciled. Returns the ids that
+    /// were reverted, dropped or restored.
+    pub fn enforce_lock(&mut self, local: &SyncableLedger) -> Vec<Uuid> {
+        let Some(lock) = self.locked_through else {
+            return Vec::new();
+        };
+        let previous: HashMap<Uuid, &Transaction> = local.transactions.iter().map(|t| (t.id, t)).collect();
+        let mut reverted = Vec::new();
+        self.transactions.retain_mut(|tx| match previous.get(&tx.id) {
+            Some(old) if old.date <= lock || tx.date <= lock => {
+                let mut kept = (*old).clone();
+                for (posting, merged) in kept.postings.iter_mut().zip(&tx.postings) {
+                    posting.cleared = merged.cleared;
+                }
+                if kept != *tx {
+                    *tx = kept;
+                    reverted.push(tx.id);
+                }
+                true
+            }
+            None if tx.date <= lock => {
+                reverted.push(tx.id);
+                false
+            }
+            _ => true,
+        });
+        let kept: std::collections::HashSet<Uuid> = self.transactions.iter().map(|t| t.id).collect();
+        for old in local.transactions.iter().filter(|t| t.date <= lock && !kept.contains(&t.id)) {
+            reverted.push(old.id);
+            self.transactions.push(old.clone());
+        }
+        reverted
+    }
+
+    /// Undo merged changes a delegated helper may not make: transaction edits and deletions go
+    /// back to their `local` version, new transactions outside the grant are dropped and every
+    /// other collection, setting and period date is restored. Returns the ids of transactions and
+    /// records that were reverted, restored or dropped.
+    pub fn enforce_delegation(
+        &mut self,
+        local: &SyncableLedger,
+        grant: &WriteGrant,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Vec<Uuid> {
+        let previous: HashMap<Uuid, &Transaction> = local.transactions.iter().map(|t| (t.id, t)).collect();
+        let mut reverted = Vec::new();
+        self.transactions.retain_mut(|tx| match previous.get(&tx.id) {
+            Some(old) => {
+                if **old != *tx {
+                    *tx = (*old).clone();
+                    reverted.push(tx.id);
+                }
+                true
+            }
+            None if grant.permits(tx, now).is_err() => {
+                reverted.push(tx.id);
+                false
+            }
+            None => true,
+        });
+        let kept: std::collections::HashSet<Uuid> = self.transactions.iter().map(|t| t.id).collect();
+        for old in local.transactions.iter().filter(|t| !kept.contains(&t.id)) {
+            reverted.push(old.id);
+            self.transactions.push(old.clone());
+        }
+
+        // Listed in full so a new collection stays closed to helpers until decided otherwise
+        let SyncableLedger {
+            accounts,
+            transactions: _,
+            // Derived from the transactions
+            balances: _,
+            close_checklists,
+            split_rules,
+            documents,
+            contacts,
+            projects,
+            classes,
+            budgets,
+            prices,
+            recurring,
+            settings,
+            invoices,
+            reconciliations,
+            locked_through,
+            closed_through,
+            rules,
+            statements,
+            app_settings,
+            stock_items,
+            stock_movements,
+            activity,
+        } = local;
+        restore_records(&mut self.accounts, accounts, &mut reverted);
+        restore_records(&mut self.close_checklists, close_checklists, &mut reverted);
+        restore_records(&mut self.split_rules, split_rules, &mut reverted);
+        restore_records(&mut self.documents, documents, &mut reverted);
+        restore_records(&mut self.contacts, contacts, &mut reverted);
+        restore_records(&mut self.projects, projects, &mut reverted);
+        restore_records(&mut self.classes, classes, &mut reverted);
+        restore_records(&mut self.budgets, budgets, &mut reverted);
+        restore_records(&mut self.prices, prices, &mut reverted);
+        restore_records(&mut self.recurring, recurring, &mut reverted);
+        restore_records(&mut self.invoices, invoices, &mut reverted);
+        restore_records(&mut self.reconciliations, reconciliations, &mut reverted);
+        restore_records(&mut self.rules, rules, &mut reverted);
+        restore_records(&mut self.statements, statements, &mut reverted);
+        restore_records(&mut self.stock_items, stock_items, &mut reverted);
+        restore_records(&mut self.stock_movements, stock_movements, &mut reverted);
+        restore_records(&mut self.activity, activity, &mut reverted);
+        self.settings = settings.clone();
+        self.locked_through = *locked_through;
+        self.closed_through = *closed_through;
+        self.app_settings = app_settings.clone();
+        reverted
+    }
+
+    /// Rebuild balances from the transaction list
+    pub fn recompute_balances(&mut self) {
+        for balance in self.balances.values_mut() {
+            *balance = Decimal::ZERO;
+        }
+        for tx in &self.transactions {
+            for posting in &tx.postings {
+                *self.balances.entry(posting.account_id).or_insert(Decimal::ZERO) += posting.amount;
+            }
+        }
+    }
+
+    /// Rebuild balances of the given (dirty) accounts in a single pass over the transactions
+    pub fn recompute_balances_for(&mut self, dirty: &std::collections::HashSet<Uuid>) {
+        for id in dirty {
+            self.balances.insert(*id, Decimal::ZERO);
+        }
+        for tx in &self.transactions {
+            for posting in tx.postings.iter().filter(|p| dirty.contains(&p.account_id)) {
+                *self.balances.get_mut(&posting.account_id).unwrap() += posting.amount;
+            }
+        }
+    }
+
+    /// Add or replace a period close checklist
+    pub fn upsert_checklist(&mut self, checklist: CloseChecklist) {
+        self.close_checklists.insert(checklist.id, checklist);
+    }
+
+    /// Checklist covering the given date, if any
+    pub fn checklist_for(&self, date: chrono::NaiveDate) -> Option<&CloseChecklist> {
+        self.close_checklists.values()
+            .find(|c| c.period_start <= date && date <= c.period_end)
+    }
+
+    /// Add or replace an account's default split rule
+    pub fn upsert_split_rule(&mut self, rule: SplitRule) {
+        self.split_rules.retain(|_, r| r.account_id != rule.account_id || r.id == rule.id);
+        self.split_rules.insert(rule.id, rule);
+    }
+
+    /// Split rule configured for the account, if any
+    pub fn split_rule_for(&self, account_id: &Uuid) -> Option<&SplitRule> {
+        self.split_rules.values().find(|r| r.account_id == *account_id)
+    }
+
+    /// Add or replace a stored document
+    pub fn upsert_document(&mut self, document: Document) {
+        self.documents.insert(document.id, document);
+    }
+
+    /// Documents linked to an account or transaction
+    pub fn documents_for(&self, link: &DocumentLink) -> Vec<&Document> {
+        self.documents.values().filter(|d| d.is_linked_to(link)).collect()
+    }
+
+    /// Add or replace a contact
+    pub fn upsert_contact(&mut self, contact: Contact) {
+        self.contacts.insert(contact.id, contact);
+    }
+
+    /// Transactions linked to a contact, in stored order
+    pub fn transactions_for_contact(&self, contact_id: Uuid) -> impl Iterator<Item = &Transaction> {
+        self.transactions.iter().filter(move |t| t.contact_id == Some(contact_id))
+    }
+
+    /// Add or replace a project
+    pub fn upsert_project(&mut self, project: Project) {
+        self.projects.insert(project.id, project);
+    }
+
+    /// Add or replace a class/department
+    pub fn upsert_class(&mut self, class: ReportingClass) {
+        self.classes.insert(class.id, class);
+    }
+
+    /// Add or replace a budget
+    pub fn upsert_budget(&mut self, budget: Budget) {
+        self.budgets.insert(budget.id, budget);
+    }
+
+    /// Add or replace an exchange rate quote
+    pub fn upsert_price(&mut self, quote: PriceQuote) {
+        self.prices.insert(quote.id, quote);
+    }
+
+    /// Rate table built from the synced quotes
+    pub fn price_db(&self) -> PriceDb {
+        PriceDb::from_quotes(self.prices.values())
+    }
+
+    /// Add or replace a recurring transaction definition
+    pub fn upsert_recurring(&mut self, recurring: RecurringTransaction) {
+        self.recurring.insert(recurring.id, recurring);
+    }
+
+    /// Recurring patterns in the history that no stored definition covers yet
+    pub fn detect_recurring(&self, today: chrono::NaiveDate, options: &DetectionOptions) -> Vec<RecurringCandidate> {
+        let existing: Vec<RecurringTransaction> = self.recurring.values().cloned().collect();
+        detect_recurring(&self.transactions, &existing, today, options)
+    }
+
+    /// Cancel a stored subscription: no occurrences are posted from `effective` on.
+    /// Detected subscriptions must be saved with `upsert_recurring` first.
+    pub fn cancel_subscription(&mut self, id: Uuid, effective: chrono::NaiveDate) -> Result<(), &'static str> {
+        let recurring = self.recurring.get_mut(&id).ok_or("Subscription not found")?;
+        recurring.active = false;
+        recurring.end_date = Some(effective.pred_opt().unwrap_or(effective));
+        Ok(())
+    }
+
+    /// Record every occurrence due through `today` and advance the definitions
+    pub fn post_due_recurring(&mut self, today: chrono::NaiveDate) -> Vec<Uuid> {
+        let mut ids: Vec<Uuid> = self.recurring.keys().copied().collect();
+        ids.sort();
+        let mut posted = Vec::new();
+        for id in ids {
+            let due = self.recurring.get_mut(&id).unwrap().take_due(today);
+            for tx in due {
+                posted.push(tx.id);
+                self.push_transaction(tx);
+            }
+        }
+        posted
+    }
+
+    /// Items and stock movements as an inventory
+    pub fn inventory(&self) -> Inventory {
+        Inventory::from_records(self.stock_items.values().cloned(), self.stock_movements.values().cloned())
+    }
+
+    /// Store an inventory's items and movements
+    pub fn set_inventory(&mut self, inventory: &Inventory) {
+        self.stock_items = inventory.items().map(|i| (i.id, i.clone())).collect();
+        self.stock_movements = inventory.movements().map(|m| (m.id, m.clone())).collect();
+    }
+
+    /// Add or replace an invoice
+    pub fn upsert_invoice(&mut self, invoice: Invoice) {
+        self.invoices.insert(invoice.id, invoice);
+    }
+
+    /// Issue a draft invoice on `date` with the customer's payment terms and record its receivable entry
+    pub fn issue_invoice(&mut self, id: Uuid, date: chrono::NaiveDate, number: Option<String>) -> Result<Uuid, InvoiceError> {
+        let invoice = self.invoices.get_mut(&id).ok_or(InvoiceError::NotFound)?;
+        let terms = self.contacts.get(&invoice.contact_id).map(|c| c.terms).unwrap_or_default();
+        let tx = invoice.issue(date, terms, number)?;
+        let tx_id = tx.id;
+        self.push_transaction(tx);
+        Ok(tx_id)
+    }
+
+    /// Record a payment against an issued invoice and its settlement entry
+    pub fn record_invoice_payment(
+        &mut self,
+        id: Uuid,
+        date: chrono::NaiveDate,
+        amount: Decimal,
+        deposit_account: Uuid,
+    ) -> Result<Uuid, InvoiceError> {
+        let invoice = self.invoices.get_mut(&id).ok_or(InvoiceError::NotFound)?;
+        let tx = invoice.record_payment(date, amount, deposit_account)?;
+        let tx_id = tx.id;
+        self.push_transaction(tx);
+        Ok(tx_id)
+    }
+
+    /// Issued invoices with an outstanding balance, oldest due date first
+    pub fn open_invoices(&self) -> Vec<&Invoice> {
+        let mut open: Vec<&Invoice> = self.invoices.values()
+            .filter(|i| !i.outstanding().is_zero())
+            .collect();
+        open.sort_by_key(|i| (i.due_date, i.id));
+        open
+    }
+
+    /// Add or replace a reconciliation session
+    pub fn upsert_reconciliation(&mut self, session: ReconciliationSession) {
+        self.reconciliations.insert(session.id, session);
+    }
+
+    /// Mirror a posting's cleared state; reconciled postings are locked
+    pub fn set_cleared(&mut self, posting: PostingRef, state: ClearedState) -> Result<(), &'static str> {
+        let tx = self.transactions.iter_mut()
+            .find(|t| t.id == posting.transaction_id)
+            .ok_or("Transaction not found")?;
+        let target = tx.postings.get_mut(posting.index).ok_or("Posting not found")?;
+        if target.cleared == ClearedState::Reconciled && state != ClearedState::Reconciled {
+            return Err("Posting is already reconciled");
+        }
+        target.cleared = state;
+        Ok(())
+    }
+
+    /// Store a completed session and lock its postings, so peers see the reconciliation
+    pub fn apply_reconciliation(&mut self, session: ReconciliationSession) -> Result<(), &'static str> {
+        if !session.is_completed() {
+            return Err("Reconciliation not completed");
+        }
+        for posting in &session.reconciled {
+            self.set_cleared(*posting, ClearedState::Reconciled)?;
+        }
+        self.upsert_reconciliation(session);
+        Ok(())
+    }
+
+    /// Add or replace a categorization rule
+    pub fn upsert_rule(&mut self, rule: CategorizationRule) {
+        self.rules.insert(rule.id, rule);
+    }
+
+    pub fn remove_rule(&mut self, id: &Uuid) -> Option<CategorizationRule> {
+        self.rules.remove(id)
+    }
+
+    /// Categorize a transaction with the shared rules; returns the id of the rule applied
+    pub fn apply_rules(&self, tx: &mut Transaction) -> Option<Uuid> {
+        rules::apply_rules(self.rules.values(), tx)
+    }
+
+    /// Add or replace a bank statement
+    pub fn upsert_statement(&mut self, statement: Statement) {
+        self.statements.insert(statement.id, statement);
+    }
+
+    /// Statements of an account, oldest first
+    pub fn statements_for(&self, account_id: Uuid) -> Vec<&Statement> {
+        let mut statements: Vec<&Statement> = self.statements.values().filter(|s| s.account_id == account_id).collect();
+        statements.sort_by_key(|s| s.start_date);
+        statements
+    }
+}
+
+/// Approximate size of one object in the document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectSize {
+    pub path: String,
+    pub bytes: usize,
+}
+
+/// Document size report for diagnosing slow sync and deciding when to compact
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocStats {
+    pub byte_size: usize,
+    pub change_count: usize,
+    /// Entry count per collection under root.ledger
+    pub collections: HashMap<String, usize>,
+    /// Biggest entries by encoded value size, largest first
+    pub largest_objects: Vec<ObjectSize>,
+}
+
+/// Put a record collection back to its `local` version, noting the ids that differed
+fn restore_records<T: Clone + Serialize>(merged: &mut HashMap<Uuid, T>, local: &HashMap<Uuid, T>, reverted: &mut Vec<Uuid>) {
+    let same = |a: &T, b: &T| serde_json::to_value(a).ok() == serde_json::to_value(b).ok();
+    reverted.extend(merged.iter().filter(|(id, r)| !local.get(id).is_some_and(|l| same(l, r))).map(|(id, _)| *id));
+    reverted.extend(local.keys().filter(|id| !merged.contains_key(id)));
+    *merged = local.clone();
 }
 
 /// CRDT document for ledger synchronization
@@ -54,6 +532,16 @@ pub enum SyncError {
     Serde(#[from] serde_json::Error),
     #[error("Missing required field: {0}")]
     MissingField(&'static str),
+    #[error("Incompatible peer: {0}")]
+    IncompatiblePeer(String),
+    #[error(transparent)]
+    OutOfRange(#[from] crate::canonical::OutOfRange),
+    #[error(transparent)]
+    Keyring(#[from] crate::keyring::KeyringError),
+    #[error(transparent)]
+    Codec(#[from] crate::codec::CodecError),
+    #[error("Invalid or unverifiable signature")]
+    BadSignature,
 }
 
 impl SyncDoc {
@@ -61,24 +549,49 @@ impl SyncDoc {
     pub fn new() -> Result<Self, SyncError> {
         let mut doc = AutoCommit::new();
         
-        // Initialize ledger structure: { ledger: { accounts: [], transactions: [], balances: {} } }
+        // Initialize ledger structure: { ledger: { accounts: {}, transactions: {}, balances: {} } }
         let ledger_obj = doc.put_object(&automerge::ROOT, "ledger", ObjType::Map)?;
-        doc.put_object(&ledger_obj, "accounts", ObjType::List)?;
-        doc.put_object(&ledger_obj, "transactions", ObjType::List)?;
+        doc.put_object(&ledger_obj, "accounts", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "transactions", ObjType::Map)?;
         doc.put_object(&ledger_obj, "balances", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "close_checklists", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "split_rules", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "documents", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "contacts", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "projects", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "classes", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "budgets", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "prices", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "recurring", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "settings", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "invoices", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "reconciliations", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "rules", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "statements", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "app_settings", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "stock_items", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "stock_movements", ObjType::Map)?;
+        doc.put_object(&ledger_obj, "activity", ObjType::Map)?;
+        doc.commit();
         
         Ok(Self { doc })
     }
 
     /// Load sync document from bytes (e.g., received from network)
-    pub fn from_bytes( &[u8]) -> Result<Self, SyncError> {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SyncError> {
         let doc = AutoCommit::load(data)?;
         Ok(Self { doc })
     }
 
-    /// Serialize document to bytes for network transmission
+    /// Serialize document to bytes for network transmission. Operations not yet committed (e.g.
+    /// of an open changeset) are left out; saving them from a copy would commit them twice.
     pub fn to_bytes(&self) -> Vec<u8> {
-        self.doc.save()
+        self.committed().save()
+    }
+
+    /// Close pending operations into one change, so they are saved and sent
+    pub fn commit(&mut self) {
+        self.doc.commit();
     }
 
     /// Apply local ledger changes to CRDT document
@@ -93,6 +606,134 @@ impl SyncDoc {
         
         // Update balances
         self.update_balances(&ledger_obj, &ledger.balances)?;
+
+        // Update close checklists
+        self.update_json_map(
+            &ledger_obj,
+            "close_checklists",
+            ledger.close_checklists.iter().map(|(id, c)| (id.to_string(), c)),
+        )?;
+
+        // Update account split rules
+        self.update_json_map(
+            &ledger_obj,
+            "split_rules",
+            ledger.split_rules.iter().map(|(id, r)| (id.to_string(), r)),
+        )?;
+
+        // Update documents
+        self.update_json_map(
+            &ledger_obj,
+            "documents",
+            ledger.documents.iter().map(|(id, r)| (id.to_string(), r)),
+        )?;
+
+        // Update contacts
+        self.update_json_map(
+            &ledger_obj,
+            "contacts",
+            ledger.contacts.iter().map(|(id, r)| (id.to_string(), r)),
+        )?;
+
+        // Update projects
+        self.update_json_map(
+            &ledger_obj,
+            "projects",
+            ledger.projects.iter().map(|(id, r)| (id.to_string(), r)),
+        )?;
+
+        // Update classes
+        self.update_json_map(
+            &ledger_obj,
+            "classes",
+            ledger.classes.iter().map(|(id, r)| (id.to_string(), r)),
+        )?;
+
+        // Budgets: shared limits, one record per account and period
+        self.update_json_map(
+            &ledger_obj,
+            "budgets",
+            ledger.budgets.iter().map(|(id, r)| (id.to_string(), r)),
+        )?;
+
+        // Exchange rates: one record per quote, resolved per pair and date by PriceDb
+        self.update_json_map(
+            &ledger_obj,
+            "prices",
+            ledger.prices.iter().map(|(id, r)| (id.to_string(), r)),
+        )?;
+
+        // Recurring transaction definitions
+        self.update_json_map(
+            &ledger_obj,
+            "recurring",
+            ledger.recurring.iter().map(|(id, r)| (id.to_string(), r)),
+        )?;
+
+        // Book-wide settings, one entry per field so peers can change different ones concurrently
+        self.update_settings(&ledger_obj, &ledger.settings)?;
+
+        // Invoices with their line items and payments
+        self.update_json_map(
+            &ledger_obj,
+            "invoices",
+            ledger.invoices.iter().map(|(id, r)| (id.to_string(), r)),
+        )?;
+
+        // Reconciliation sessions; cleared state travels with the postings
+        self.update_json_map(
+            &ledger_obj,
+            "reconciliations",
+            ledger.reconciliations.iter().map(|(id, r)| (id.to_string(), r)),
+        )?;
+
+        // Period lock and close as single dates; never cleared once set
+        if let Some(locked) = ledger.locked_through {
+            self.doc.put(&ledger_obj, "locked_through", locked.to_string())?;
+        }
+        if let Some(closed) = ledger.closed_through {
+            self.doc.put(&ledger_obj, "closed_through", closed.to_string())?;
+        }
+
+        // Categorization rules, tried in priority order
+        self.update_json_map(
+            &ledger_obj,
+            "rules",
+            ledger.rules.iter().map(|(id, r)| (id.to_string(), r)),
+        )?;
+
+        // Official bank statements with their lines
+        self.update_json_map(
+            &ledger_obj,
+            "statements",
+            ledger.statements.iter().map(|(id, r)| (id.to_string(), r)),
+        )?;
+
+        // App settings, one entry per namespaced key so only the same key can conflict
+        self.update_json_map(
+            &ledger_obj,
+            "app_settings",
+            ledger.app_settings.iter().map(|(k, v)| (k.clone(), v)),
+        )?;
+
+        // Inventory items and stock movements; concurrent movements merge to their union
+        self.update_json_map(
+            &ledger_obj,
+            "stock_items",
+            ledger.stock_items.iter().map(|(id, r)| (id.to_string(), r)),
+        )?;
+        self.update_json_map(
+            &ledger_obj,
+            "stock_movements",
+            ledger.stock_movements.iter().map(|(id, r)| (id.to_string(), r)),
+        )?;
+
+        // Activity feed; entries are only ever added
+        self.update_json_map(
+            &ledger_obj,
+            "activity",
+            ledger.activity.iter().map(|(id, r)| (id.to_string(), r)),
+        )?;
         
         Ok(())
     }
@@ -104,20 +745,204 @@ impl SyncDoc {
         let accounts = self.read_accounts(&ledger_obj)?;
         let transactions = self.read_transactions(&ledger_obj)?;
         let balances = self.read_balances(&ledger_obj)?;
+        let close_checklists = self.read_json_map::<CloseChecklist>(&ledger_obj, "close_checklists")?
+            .into_iter()
+            .map(|c| (c.id, c))
+            .collect();
+        let split_rules = self.read_json_map::<SplitRule>(&ledger_obj, "split_rules")?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
+        let documents = self.read_json_map::<Document>(&ledger_obj, "documents")?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
+        let contacts = self.read_json_map::<Contact>(&ledger_obj, "contacts")?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
+        let projects = self.read_json_map::<Project>(&ledger_obj, "projects")?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
+        let classes = self.read_json_map::<ReportingClass>(&ledger_obj, "classes")?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
+        let budgets = self.read_json_map::<Budget>(&ledger_obj, "budgets")?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
+        let prices = self.read_json_map::<PriceQuote>(&ledger_obj, "prices")?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
+        let recurring = self.read_json_map::<RecurringTransaction>(&ledger_obj, "recurring")?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
+        let settings = self.read_settings(&ledger_obj)?;
+        let invoices = self.read_json_map::<Invoice>(&ledger_obj, "invoices")?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
+        let reconciliations = self.read_json_map::<ReconciliationSession>(&ledger_obj, "reconciliations")?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
+        let locked_through = self.doc.get(&ledger_obj, "locked_through")?
+            .and_then(|v| v.cast::<String>())
+            .and_then(|s| s.parse().ok());
+        let closed_through = self.doc.get(&ledger_obj, "closed_through")?
+            .and_then(|v| v.cast::<String>())
+            .and_then(|s| s.parse().ok());
+        let rules = self.read_json_map::<CategorizationRule>(&ledger_obj, "rules")?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
+        let statements = self.read_json_map::<Statement>(&ledger_obj, "statements")?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
+        let app_settings = self.read_app_settings(&ledger_obj)?;
+        let stock_items = self.read_json_map::<Item>(&ledger_obj, "stock_items")?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
+        let stock_movements = self.read_json_map::<StockMovement>(&ledger_obj, "stock_movements")?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
+        let activity = self.read_json_map::<ActivityEntry>(&ledger_obj, "activity")?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
         
         Ok(SyncableLedger {
             accounts,
             transactions,
             balances,
+            close_checklists,
+            split_rules,
+            documents,
+            contacts,
+            projects,
+            classes,
+            budgets,
+            prices,
+            recurring,
+            settings,
+            invoices,
+            reconciliations,
+            locked_through,
+            closed_through,
+            rules,
+            statements,
+            app_settings,
+            stock_items,
+            stock_movements,
+            activity,
         })
     }
 
     /// Merge another sync document (e.g., from peer)
     pub fn merge(&mut self, other: &SyncDoc) -> Result<(), SyncError> {
-        self.doc.merge(&other.doc)?;
+        self.doc.merge(&mut other.committed())?;
         Ok(())
     }
 
+    /// Hex digest of the document heads; equal on replicas that have seen the same changes
+    pub fn state_hash(&self) -> String {
+        self.state_hash_with(&crate::crypto::Sha256Hash)
+    }
+
+    /// `state_hash` with another digest algorithm; replicas must agree on the algorithm
+    pub fn state_hash_with(&self, hash: &dyn crate::crypto::HashAlgorithm) -> String {
+        let mut heads: Vec<[u8; 32]> = self.committed().get_heads().into_iter().map(|h| h.0).collect();
+        heads.sort();
+        let digest = hash.digest(&heads.concat());
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Current heads as sorted hex strings
+    pub fn heads_hex(&self) -> Vec<String> {
+        let mut heads: Vec<String> = self.committed().get_heads().into_iter()
+            .map(|h| h.0.iter().map(|b| format!("{:02x}", b)).collect())
+            .collect();
+        heads.sort();
+        heads
+    }
+
+    /// Whether every given head (as from `heads_hex`) is part of this document's history,
+    /// i.e. the document has seen at least that state
+    pub fn contains_heads(&self, heads: &[String]) -> bool {
+        heads.iter().all(|hex| {
+            let bytes: Option<Vec<u8>> = (0..hex.len())
+                .step_by(2)
+                .map(|i| hex.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+                .collect();
+            bytes.and_then(|b| <[u8; 32]>::try_from(b).ok())
+                .is_some_and(|hash| ReadDoc::get_change_by_hash(&self.doc, &automerge::ChangeHash(hash)).is_some())
+        })
+    }
+
+    /// Size, history length and per-collection statistics of the document
+    pub fn stats(&self) -> Result<DocStats, SyncError> {
+        const LARGEST: usize = 10;
+        let ledger_obj = self.get_ledger_obj()?;
+
+        let mut collections = HashMap::new();
+        let mut largest = Vec::new();
+        for name in self.doc.keys(&ledger_obj) {
+            let Some(obj) = self.doc.get(&ledger_obj, &name)?.and_then(|v| v.cast::<ObjId>()) else { continue };
+            collections.insert(name.clone(), self.doc.length(&obj));
+
+            // Accounts and transactions are map objects (a list of them in older documents);
+            // other collections hold scalar/JSON values
+            let entries: Vec<(String, usize)> = match self.doc.object_type(&obj) {
+                Ok(ObjType::List) => (0..self.doc.length(&obj))
+                    .filter_map(|i| match self.doc.get(&obj, i) {
+                        Ok(Some((Value::Object(ObjType::Map), item))) => Some((format!("{}[{}]", name, i), self.object_bytes(&item))),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => self.doc.keys(&obj)
+                    .map(|key| {
+                        let bytes = match self.doc.get(&obj, &key) {
+                            Ok(Some((Value::Object(ObjType::Map), item))) => self.object_bytes(&item),
+                            _ => self.string_len(&obj, &key),
+                        };
+                        (format!("{}.{}", name, key), bytes)
+                    })
+                    .collect(),
+            };
+            largest.extend(entries.into_iter().map(|(path, bytes)| ObjectSize { path, bytes }));
+        }
+        largest.sort_by_key(|o| std::cmp::Reverse(o.bytes));
+        largest.truncate(LARGEST);
+
+        Ok(DocStats {
+            byte_size: self.to_bytes().len(),
+            change_count: self.committed().get_changes(&[]).len(),
+            collections,
+            largest_objects: largest,
+        })
+    }
+
+    /// Sum of string field lengths in a map object
+    fn object_bytes(&self, obj: &ObjId) -> usize {
+        self.doc.keys(obj).map(|key| key.len() + self.string_len(obj, &key)).sum()
+    }
+
+    fn string_len(&self, obj: &ObjId, key: &str) -> usize {
+        self.doc
+            .get(obj, key)
+            .ok()
+            .flatten()
+            .and_then(|v| v.cast::<String>())
+            .map_or(0, |s| s.len())
+    }
+
     /// Get ledger object ID (root.ledger)
     fn get_ledger_obj(&self) -> Result<ObjId, SyncError> {
         self.doc
@@ -127,54 +952,158 @@ impl SyncDoc {
             .ok_or(SyncError::MissingField("ledger object"))
     }
 
-    /// Update accounts list in CRDT
+    /// Update the accounts map in CRDT
     fn update_accounts(
         &mut self,
         ledger_obj: &ObjId,
         accounts: &HashMap<Uuid, Account>,
     ) -> Result<(), SyncError> {
-        let accounts_list = self.doc
-            .get(ledger_obj, "accounts")
-            .map_err(|_| SyncError::MissingField("accounts"))?
-            .and_then(|v| v.cast::<ObjId>())
-            .ok_or(SyncError::MissingField("accounts list"))?;
-
-        // Clear and rebuild accounts list
-        self.doc.clear_list(&accounts_list)?;
+        let accounts_map = self.record_map(ledger_obj, "accounts")?;
 
+        // Only rewrite changed accounts so concurrent edits to other accounts merge cleanly
         for account in accounts.values() {
-            let acc_obj = self.doc.insert_object(&accounts_list, ObjType::Map)?;
-            self.doc.put(&acc_obj, "id", account.id.to_string())?;
-            self.doc.put(&acc_obj, "name", &account.name)?;
-            self.doc.put(&acc_obj, "type", format!("{:?}", account.account_type))?;
+            let key = account.id.to_string();
+            let current = self.doc.get(&accounts_map, &key)?
+                .and_then(|v| v.cast::<ObjId>())
+                .and_then(|obj| self.read_account(&obj).ok());
+            if current.is_some_and(|c| serde_json::to_value(&c).ok() == serde_json::to_value(account).ok()) {
+                continue;
+            }
+            let acc_obj = self.doc.put_object(&accounts_map, &key, ObjType::Map)?;
+            self.write_account(&acc_obj, account)?;
+        }
+
+        self.delete_stale(&accounts_map, |key| Uuid::parse_str(key).is_ok_and(|id| accounts.contains_key(&id)))
+    }
+
+    /// Write every field of an account into its (new) map object
+    fn write_account(&mut self, acc_obj: &ObjId, account: &Account) -> Result<(), SyncError> {
+        // Destructured so a new Account field fails to compile until it is synced
+        let Account {
+            id, name, r#type, code, parent_id, opened_on, closed_on, commodity, active, display,
+            cash_equivalent, allowed_commodities, cash_flow,
+        } = account;
+        self.doc.put(acc_obj, "id", id.to_string())?;
+        self.doc.put(acc_obj, "name", name)?;
+        self.doc.put(acc_obj, "type", format!("{:?}", r#type))?;
+        if let Some(code) = code {
+            self.doc.put(acc_obj, "code", code)?;
+        }
+        if let Some(parent_id) = *parent_id {
+            self.doc.put(acc_obj, "parent_id", parent_id.to_string())?;
+        }
+        if let Some(opened_on) = *opened_on {
+            self.doc.put(acc_obj, "opened_on", opened_on.to_string())?;
+        }
+        if let Some(closed_on) = *closed_on {
+            self.doc.put(acc_obj, "closed_on", closed_on.to_string())?;
+        }
+        self.doc.put(acc_obj, "commodity", commodity.code())?;
+        if !*active {
+            self.doc.put(acc_obj, "active", false)?;
+        }
+        if let Some(icon) = &display.icon {
+            self.doc.put(acc_obj, "icon", icon)?;
+        }
+        if let Some(color) = &display.color {
+            self.doc.put(acc_obj, "color", color)?;
+        }
+        if display.sort_order != 0 {
+            self.doc.put(acc_obj, "sort_order", display.sort_order as i64)?;
+        }
+        if display.favorite {
+            self.doc.put(acc_obj, "favorite", true)?;
+        }
+        if *cash_equivalent {
+            self.doc.put(acc_obj, "cash_equivalent", true)?;
+        }
+        if !allowed_commodities.is_empty() {
+            self.doc.put(acc_obj, "allowed_commodities", serde_json::to_string(allowed_commodities)?)?;
+        }
+        if let Some(activity) = cash_flow {
+            self.doc.put(acc_obj, "cash_flow", activity.as_str())?;
         }
 
         Ok(())
     }
 
-    /// Update transactions list in CRDT
+    /// Update the transactions map in CRDT
     fn update_transactions(
         &mut self,
         ledger_obj: &ObjId,
         transactions: &[Transaction],
     ) -> Result<(), SyncError> {
-        let tx_list = self.doc
-            .get(ledger_obj, "transactions")
-            .map_err(|_| SyncError::MissingField("transactions"))?
-            .and_then(|v| v.cast::<ObjId>())
-            .ok_or(SyncError::MissingField("transactions list"))?;
-
-        self.doc.clear_list(&tx_list)?;
+        let tx_map = self.record_map(ledger_obj, "transactions")?;
 
+        // Only rewrite changed transactions: peers recording different ones concurrently then
+        // merge to the union instead of each replacing the whole collection
         for tx in transactions {
-            let tx_obj = self.doc.insert_object(&tx_list, ObjType::Map)?;
-            self.doc.put(&tx_obj, "id", tx.id.to_string())?;
-            self.doc.put(&tx_obj, "date", tx.date.to_string())?;
-            self.doc.put(&tx_obj, "description", &tx.description)?;
-            
-            // Serialize postings as JSON array
-            let postings_json = serde_json::to_string(&tx.postings)?;
-            self.doc.put(&tx_obj, "postings", postings_json)?;
+            let key = tx.id.to_string();
+            let current = self.doc.get(&tx_map, &key)?
+                .and_then(|v| v.cast::<ObjId>())
+                .and_then(|obj| self.read_transaction(&obj).ok());
+            if current.as_ref() == Some(tx) {
+                continue;
+            }
+            let tx_obj = self.doc.put_object(&tx_map, &key, ObjType::Map)?;
+            self.write_transaction(&tx_obj, tx)?;
+        }
+
+        let live: std::collections::HashSet<Uuid> = transactions.iter().map(|t| t.id).collect();
+        self.delete_stale(&tx_map, |key| Uuid::parse_str(key).is_ok_and(|id| live.contains(&id)))
+    }
+
+    /// Write every field of a transaction into its (new) map object
+    fn write_transaction(&mut self, tx_obj: &ObjId, tx: &Transaction) -> Result<(), SyncError> {
+        // Destructured so a new Transaction field fails to compile until it is synced
+        let Transaction {
+            id, date, description, postings, payee, contact_id, reference, origin_device,
+            is_closing_entry, is_reversing_entry, is_opening_balance, status, corrects,
+            void_reason, transfer_id, attachments,
+        } = tx;
+        self.doc.put(tx_obj, "id", id.to_string())?;
+        self.doc.put(tx_obj, "date", date.to_string())?;
+        self.doc.put(tx_obj, "description", description)?;
+        
+        // Serialize postings as JSON array
+        let postings_json = serde_json::to_string(postings)?;
+        self.doc.put(tx_obj, "postings", postings_json)?;
+
+        if let Some(payee) = payee {
+            self.doc.put(tx_obj, "payee", payee)?;
+        }
+        if let Some(contact_id) = *contact_id {
+            self.doc.put(tx_obj, "contact_id", contact_id.to_string())?;
+        }
+        if let Some(reference) = reference {
+            self.doc.put(tx_obj, "reference", reference)?;
+        }
+        if let Some(origin) = origin_device {
+            self.doc.put(tx_obj, "origin_device", origin)?;
+        }
+        if *is_closing_entry {
+            self.doc.put(tx_obj, "is_closing_entry", true)?;
+        }
+        if *is_reversing_entry {
+            self.doc.put(tx_obj, "is_reversing_entry", true)?;
+        }
+        if *is_opening_balance {
+            self.doc.put(tx_obj, "is_opening_balance", true)?;
+        }
+        if *status != TransactionStatus::Posted {
+            self.doc.put(tx_obj, "status", status.as_str())?;
+        }
+        if let Some(corrects) = *corrects {
+            self.doc.put(tx_obj, "corrects", corrects.to_string())?;
+        }
+        if let Some(reason) = void_reason {
+            self.doc.put(tx_obj, "void_reason", reason)?;
+        }
+        if let Some(transfer_id) = *transfer_id {
+            self.doc.put(tx_obj, "transfer_id", transfer_id.to_string())?;
+        }
+        if !attachments.is_empty() {
+            self.doc.put(tx_obj, "attachments", serde_json::to_string(attachments)?)?;
         }
 
         Ok(())
@@ -192,17 +1121,21 @@ impl SyncDoc {
             .and_then(|v| v.cast::<ObjId>())
             .ok_or(SyncError::MissingField("balances map"))?;
 
-        // Clear and rebuild balances
-        let keys: Vec<String> = self.doc
+        // Drop balances of removed accounts
+        let stale: Vec<String> = self.doc
             .keys(&balances_obj)
-            .map(|k| k.to_string())
+            .filter(|k| Uuid::parse_str(k).map_or(true, |id| !balances.contains_key(&id)))
             .collect();
-        for key in keys {
+        for key in stale {
             self.doc.delete(&balances_obj, &key)?;
         }
 
+        // Only rewrite balances whose value changed, so equal amounts never produce conflicts
         for (id, balance) in balances {
-            self.doc.put(&balances_obj, &id.to_string(), balance.to_string())?;
+            let key = id.to_string();
+            if self.read_decimal(&balances_obj, &key).ok().flatten() != Some(*balance) {
+                self.put_decimal(&balances_obj, &key, *balance)?;
+            }
         }
 
         Ok(())
@@ -210,100 +1143,690 @@ impl SyncDoc {
 
     /// Read accounts from CRDT
     fn read_accounts(&self, ledger_obj: &ObjId) -> Result<HashMap<Uuid, Account>, SyncError> {
-        let accounts_list = self.doc
-            .get(ledger_obj, "accounts")
-            .map_err(|_| SyncError::MissingField("accounts"))?
-            .and_then(|v| v.cast::<ObjId>())
-            .ok_or(SyncError::MissingField("accounts list"))?;
-
         let mut accounts = HashMap::new();
-        for i in 0..self.doc.length(&accounts_list) {
-            if let Some(Value::Object(ObjType::Map, acc_obj)) = self.doc.get(&accounts_list, i)? {
-                let id_str: String = self.doc
-                    .get(&acc_obj, "id")?
-                    .and_then(|v| v.cast::<String>())
-                    .ok_or(SyncError::MissingField("account.id"))?;
-                let id = Uuid::parse_str(&id_str).map_err(|_| SyncError::MissingField("invalid UUID"))?;
-
-                let name: String = self.doc
-                    .get(&acc_obj, "name")?
-                    .and_then(|v| v.cast::<String>())
-                    .ok_or(SyncError::MissingField("account.name"))?;
-
-                let type_str: String = self.doc
-                    .get(&acc_obj, "type")?
-                    .and_then(|v| v.cast::<String>())
-                    .ok_or(SyncError::MissingField("account.type"))?;
-                let account_type = match type_str.as_str() {
-                    "Asset" => AccountType::Asset,
-                    "Liability" => AccountType::Liability,
-                    "Equity" => AccountType::Equity,
-                    "Revenue" => AccountType::Revenue,
-                    "Expense" => AccountType::Expense,
-                    _ => return Err(SyncError::MissingField("unknown account type")),
-                };
-
-                accounts.insert(id, Account {
-                    id,
-                    name,
-                    account_type,
-                    parent_id: None,
-                });
-            }
+        for acc_obj in self.record_objects(ledger_obj, "accounts")? {
+            let account = self.read_account(&acc_obj)?;
+            accounts.insert(account.id, account);
         }
-
         Ok(accounts)
     }
 
-    /// Read transactions from CRDT
+    /// Read one account object
+    fn read_account(&self, acc_obj: &ObjId) -> Result<Account, SyncError> {
+        let id_str: String = self.doc
+            .get(acc_obj, "id")?
+            .and_then(|v| v.cast::<String>())
+            .ok_or(SyncError::MissingField("account.id"))?;
+        let id = Uuid::parse_str(&id_str).map_err(|_| SyncError::MissingField("invalid UUID"))?;
+
+        let name: String = self.doc
+            .get(acc_obj, "name")?
+            .and_then(|v| v.cast::<String>())
+            .ok_or(SyncError::MissingField("account.name"))?;
+
+        let type_str: String = self.doc
+            .get(acc_obj, "type")?
+            .and_then(|v| v.cast::<String>())
+            .ok_or(SyncError::MissingField("account.type"))?;
+        let account_type = match type_str.as_str() {
+            "Asset" => AccountType::Asset,
+            "Liability" => AccountType::Liability,
+            "Equity" => AccountType::Equity,
+            "Revenue" => AccountType::Revenue,
+            "Expense" => AccountType::Expense,
+            _ => return Err(SyncError::MissingField("unknown account type")),
+        };
+
+        let code = self.doc.get(acc_obj, "code")?.and_then(|v| v.cast::<String>());
+
+        let parent_id = self.doc
+            .get(acc_obj, "parent_id")?
+            .and_then(|v| v.cast::<String>())
+            .and_then(|s| Uuid::parse_str(&s).ok());
+
+        let opened_on = self.read_optional_date(acc_obj, "opened_on")?;
+        let closed_on = self.read_optional_date(acc_obj, "closed_on")?;
+        // Accounts written before multi-currency support are in the default commodity
+        let commodity = self.doc
+            .get(acc_obj, "commodity")?
+            .and_then(|v| v.cast::<String>())
+            .map(|code| Commodity::new(&code))
+            .unwrap_or_default();
+        let active = self.doc
+            .get(acc_obj, "active")?
+            .and_then(|v| v.cast::<bool>())
+            .unwrap_or(true);
+        let display = AccountDisplay {
+            icon: self.doc.get(acc_obj, "icon")?.and_then(|v| v.cast::<String>()),
+            color: self.doc.get(acc_obj, "color")?.and_then(|v| v.cast::<String>()),
+            sort_order: self.doc
+                .get(acc_obj, "sort_order")?
+                .and_then(|v| v.cast::<i64>())
+                .unwrap_or(0) as i32,
+            favorite: self.doc
+                .get(acc_obj, "favorite")?
+                .and_then(|v| v.cast::<bool>())
+                .unwrap_or(false),
+        };
+
+        let cash_equivalent = self.doc
+            .get(acc_obj, "cash_equivalent")?
+            .and_then(|v| v.cast::<bool>())
+            .unwrap_or(false);
+        let allowed_commodities = match self.doc.get(acc_obj, "allowed_commodities")?.and_then(|v| v.cast::<String>()) {
+            Some(json) => serde_json::from_str(&json)?,
+            None => Vec::new(),
+        };
+        let cash_flow = self.doc
+            .get(acc_obj, "cash_flow")?
+            .and_then(|v| v.cast::<String>())
+            .and_then(|s| CashFlowActivity::parse(&s));
+
+        Ok(Account {
+            id,
+            name,
+            r#type: account_type,
+            code,
+            parent_id,
+            opened_on,
+            closed_on,
+            commodity,
+            active,
+            display,
+            cash_equivalent,
+            allowed_commodities,
+            cash_flow,
+        })
+    }
+
+    /// Read an optional YYYY-MM-DD field
+    fn read_optional_date(&self, obj: &ObjId, key: &'static str) -> Result<Option<chrono::NaiveDate>, SyncError> {
+        self.doc
+            .get(obj, key)?
+            .and_then(|v| v.cast::<String>())
+            .map(|s| chrono::NaiveDate::parse_from_str(&s, "%Y-%m-%d"))
+            .transpose()
+            .map_err(|_| SyncError::MissingField("invalid date format"))
+    }
+
+    /// Read transactions from CRDT, by date and then id so every peer sees the same order
     fn read_transactions(&self, ledger_obj: &ObjId) -> Result<Vec<Transaction>, SyncError> {
-        let tx_list = self.doc
-            .get(ledger_obj, "transactions")
-            .map_err(|_| SyncError::MissingField("transactions"))?
+        let mut transactions = self.record_objects(ledger_obj, "transactions")?
+            .iter()
+            .map(|tx_obj| self.read_transaction(tx_obj))
+            .collect::<Result<Vec<_>, _>>()?;
+        transactions.sort_by_key(|t| (t.date, t.id));
+        Ok(transactions)
+    }
+
+    /// Read one transaction object
+    fn read_transaction(&self, tx_obj: &ObjId) -> Result<Transaction, SyncError> {
+        let id_str: String = self.doc
+            .get(tx_obj, "id")?
+            .and_then(|v| v.cast::<String>())
+            .ok_or(SyncError::MissingField("transaction.id"))?;
+        let id = Uuid::parse_str(&id_str).map_err(|_| SyncError::MissingField("invalid UUID"))?;
+
+        let date_str: String = self.doc
+            .get(tx_obj, "date")?
+            .and_then(|v| v.cast::<String>())
+            .ok_or(SyncError::MissingField("transaction.date"))?;
+        let date = chrono::NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
+            .map_err(|_| SyncError::MissingField("invalid date format"))?;
+
+        let description: String = self.doc
+            .get(tx_obj, "description")?
+            .and_then(|v| v.cast::<String>())
+            .ok_or(SyncError::MissingField("transaction.description"))?;
+
+        let postings_json: String = self.doc
+            .get(tx_obj, "postings")?
+            .and_then(|v| v.cast::<String>())
+            .ok_or(SyncError::MissingField("transaction.postings"))?;
+        let postings: Vec<super::ledger::Posting> = serde_json::from_str(&postings_json)?;
+
+        let payee: Option<String> = self.doc
+            .get(tx_obj, "payee")?
+            .and_then(|v| v.cast::<String>());
+        let contact_id = self.doc
+            .get(tx_obj, "contact_id")?
+            .and_then(|v| v.cast::<String>())
+            .and_then(|s| Uuid::parse_str(&s).ok());
+        let reference: Option<String> = self.doc
+            .get(tx_obj, "reference")?
+            .and_then(|v| v.cast::<String>());
+        let origin_device: Option<String> = self.doc
+            .get(tx_obj, "origin_device")?
+            .and_then(|v| v.cast::<String>());
+        let is_closing_entry = self.doc
+            .get(tx_obj, "is_closing_entry")?
+            .and_then(|v| v.cast::<bool>())
+            .unwrap_or(false);
+        let is_reversing_entry = self.doc
+            .get(tx_obj, "is_reversing_entry")?
+            .and_then(|v| v.cast::<bool>())
+            .unwrap_or(false);
+        let is_opening_balance = self.doc
+            .get(tx_obj, "is_opening_balance")?
+            .and_then(|v| v.cast::<bool>())
+            .unwrap_or(false);
+        let status = self.doc
+            .get(tx_obj, "status")?
+            .and_then(|v| v.cast::<String>())
+            .and_then(|s| TransactionStatus::parse(&s))
+            .unwrap_or_default();
+        let corrects = self.doc
+            .get(tx_obj, "corrects")?
+            .and_then(|v| v.cast::<String>())
+            .and_then(|s| Uuid::parse_str(&s).ok());
+        let void_reason: Option<String> = self.doc
+            .get(tx_obj, "void_reason")?
+            .and_then(|v| v.cast::<String>());
+        let transfer_id = self.doc
+            .get(tx_obj, "transfer_id")?
+            .and_then(|v| v.cast::<String>())
+            .and_then(|s| Uuid::parse_str(&s).ok());
+        let attachments = match self.doc.get(tx_obj, "attachments")?.and_then(|v| v.cast::<String>()) {
+            Some(json) => serde_json::from_str(&json)?,
+            None => Vec::new(),
+        };
+
+        Ok(Transaction {
+            id,
+            date,
+            description,
+            postings,
+            payee,
+            contact_id,
+            reference,
+            origin_device,
+            is_closing_entry,
+            is_reversing_entry,
+            is_opening_balance,
+            status,
+            corrects,
+            void_reason,
+            transfer_id,
+            attachments,
+        })
+    }
+
+    /// Read balances from CRDT
+    fn read_balances(&self, ledger_obj: &ObjId) -> Result<HashMap<Uuid, Decimal>, SyncError> {
+        let balances_obj = self.doc
+            .get(ledger_obj, "balances")
+            .map_err(|_| SyncError::MissingField("balances"))?
+            .and_then(|v| v.cast::<ObjId>())
+            .ok_or(SyncError::MissingField("balances map"))?;
+
+        let mut balances = HashMap::new();
+        for key in self.doc.keys(&balances_obj) {
+            let id = Uuid::parse_str(&key).map_err(|_| SyncError::MissingField("invalid UUID"))?;
+            let balance = self.read_decimal(&balances_obj, &key)?
+                .ok_or(SyncError::MissingField("balance"))?;
+            balances.insert(id, balance);
+        }
+
+        Ok(balances)
+    }
+
+    /// Store a monetary value as a canonical { mantissa, exponent } map
+    fn put_decimal(&mut self, obj: &ObjId, key: &str, value: Decimal) -> Result<(), SyncError> {
+        let canonical = CanonicalDecimal::encode(value)?;
+        let value_obj = self.doc.put_object(obj, key, ObjType::Map)?;
+        self.doc.put(&value_obj, "mantissa", canonical.mantissa)?;
+        self.doc.put(&value_obj, "exponent", canonical.exponent as i64)?;
+        Ok(())
+    }
+
+    /// Read a monetary value, accepting the canonical map and the legacy string encoding
+    fn read_decimal(&self, obj: &ObjId, key: &str) -> Result<Option<Decimal>, SyncError> {
+        match self.doc.get(obj, key)? {
+            Some((Value::Object(ObjType::Map), value_obj)) => {
+                let mantissa: i64 = self.doc
+                    .get(&value_obj, "mantissa")?
+                    .and_then(|v| v.cast::<i64>())
+                    .ok_or(SyncError::MissingField("decimal.mantissa"))?;
+                let exponent: i64 = self.doc
+                    .get(&value_obj, "exponent")?
+                    .and_then(|v| v.cast::<i64>())
+                    .ok_or(SyncError::MissingField("decimal.exponent"))?;
+                let exponent = u32::try_from(exponent).map_err(|_| OutOfRange::Exponent(exponent))?;
+                Ok(Some(CanonicalDecimal { mantissa, exponent }.decode()?))
+            }
+            Some(value) => value
+                .cast::<String>()
+                .map(|s| Decimal::from_str(&s).map_err(|_| SyncError::MissingField("invalid decimal")))
+                .transpose(),
+            None => Ok(None),
+        }
+    }
+
+    /// Write records as JSON strings into a map keyed by record id (created if missing)
+    fn update_json_map<'a, T: Serialize + 'a>(
+        &mut self,
+        ledger_obj: &ObjId,
+        key: &'static str,
+        records: impl Iterator<Item = (String, &'a T)>,
+    ) -> Result<(), SyncError> {
+        let map_obj = match self.doc
+            .get(ledger_obj, key)
+            .map_err(|_| SyncError::MissingField(key))?
             .and_then(|v| v.cast::<ObjId>())
-            .ok_or(SyncError::MissingField("transactions list"))?;
-
-        let mut transactions = Vec::new();
-        for i in 0..self.doc.length(&tx_list) {
-            if let Some(Value::Object(ObjType::Map, tx_obj)) = self.doc.get(&tx_list, i)? {
-                let id_str: String = self.doc
-                    .get(&tx_obj, "id")?
-                    .and_then(|v| v.cast::<String>())
-                    .ok_or(SyncError::MissingField("transaction.id"))?;
-                let id = Uuid::parse_str(&id_str).map_err(|_| SyncError::MissingField("invalid UUID"))?;
-
-                let date_str: String = self.doc
-                    .get(&tx_obj, "date")?
-                    .and_then(|v| v.cast::<String>())
-                    .ok_or(SyncError::MissingField("transaction.date"))?;
-                let date = chrono::NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
-                    .map_err(|_| SyncError::MissingField("invalid date format"))?;
-
-                let description: String = self.doc
-                    .get(&tx_obj, "description")?
-                    .and_then(|v| v.cast::<String>())
-                    .ok_or(SyncError::MissingField("transaction.description"))?;
-
-                let postings_json: String = self.doc
-                    .get(&tx_obj, "postings")?
-                    .and_then(|v| v.cast::<String>())
-                    .ok_or(SyncError::MissingField("transaction.postings"))?;
-                let postings: Vec<super::ledger::Posting> = serde_json::from_str(&postings_json)?;
-
-                transactions.push(Transaction {
-                    id,
-                    date,
-                    description,
-                    postings,
-                    is_closing_entry: false,
-                    is_reversing_entry: false,
-                    meta Default::default(),
-                });
+        {
+            Some(obj) => obj,
+            None => self.doc.put_object(ledger_obj, key, ObjType::Map)?,
+        };
+
+        let mut live = std::collections::HashSet::new();
+        for (id, record) in records {
+            let json = serde_json::to_string(record)?;
+            // Only touch changed entries so concurrent edits to other records don't conflict
+            let current: Option<String> = self.doc.get(&map_obj, &id)?.and_then(|v| v.cast::<String>());
+            if current.as_deref() != Some(json.as_str()) {
+                self.doc.put(&map_obj, &id, json)?;
             }
+            live.insert(id);
         }
 
-        Ok(transactions)
+        let stale: Vec<String> = self.doc
+            .keys(&map_obj)
+            .filter(|k| !live.contains(k))
+            .collect();
+        for id in stale {
+            self.doc.delete(&map_obj, &id)?;
+        }
+
+        Ok(())
     }
 
-    /// Read balances from CRDT
-    fn read_balances(&self, ledger_obj: &ObjId)
\ No newline at end of file
+    /// Write each set settings field as a JSON value under its name; unset fields are removed
+    fn update_settings(&mut self, ledger_obj: &ObjId, settings: &SharedSettings) -> Result<(), SyncError> {
+        let serde_json::Value::Object(fields) = serde_json::to_value(settings)? else {
+            return Err(SyncError::MissingField("settings"));
+        };
+        self.update_json_map(ledger_obj, "settings", fields.iter().map(|(k, v)| (k.clone(), v)))
+    }
+
+    /// Read settings fields back; documents without the map yield defaults
+    fn read_settings(&self, ledger_obj: &ObjId) -> Result<SharedSettings, SyncError> {
+        let Some(map_obj) = self.doc.get(ledger_obj, "settings")?.and_then(|v| v.cast::<ObjId>()) else {
+            return Ok(SharedSettings::default());
+        };
+        let mut fields = serde_json::Map::new();
+        for key in self.doc.keys(&map_obj) {
+            let Some(json) = self.doc.get(&map_obj, &key)?.and_then(|v| v.cast::<String>()) else { continue };
+            fields.insert(key, serde_json::from_str(&json)?);
+        }
+        Ok(serde_json::from_value(serde_json::Value::Object(fields))?)
+    }
+
+    /// App settings alone, without decoding the rest of the ledger
+    pub(crate) fn app_settings(&self) -> Result<AppSettings, SyncError> {
+        self.read_app_settings(&self.get_ledger_obj()?)
+    }
+
+    /// Write only the app settings map; the other ledger fields are not touched
+    pub(crate) fn update_app_settings(&mut self, settings: &AppSettings) -> Result<(), SyncError> {
+        let ledger_obj = self.get_ledger_obj()?;
+        self.update_json_map(&ledger_obj, "app_settings", settings.iter().map(|(k, v)| (k.clone(), v)))
+    }
+
+    /// Read app settings entries; documents without the map yield none
+    fn read_app_settings(&self, ledger_obj: &ObjId) -> Result<AppSettings, SyncError> {
+        let mut settings = AppSettings::default();
+        let Some(map_obj) = self.doc.get(ledger_obj, "app_settings")?.and_then(|v| v.cast::<ObjId>()) else {
+            return Ok(settings);
+        };
+        for key in self.doc.keys(&map_obj) {
+            let Some(json) = self.doc.get(&map_obj, &key)?.and_then(|v| v.cast::<String>()) else { continue };
+            settings.insert_raw(key, serde_json::from_str(&json)?);
+        }
+        Ok(settings)
+    }
+
+    /// Read JSON records from a map; documents created before the map existed yield nothing
+    fn read_json_map<T: DeserializeOwned>(
+        &self,
+        ledger_obj: &ObjId,
+        key: &'static str,
+    ) -> Result<Vec<T>, SyncError> {
+        let map_obj = match self.doc
+            .get(ledger_obj, key)
+            .map_err(|_| SyncError::MissingField(key))?
+            .and_then(|v| v.cast::<ObjId>())
+        {
+            Some(obj) => obj,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut records = Vec::new();
+        for id in self.doc.keys(&map_obj) {
+            let json: String = self.doc
+                .get(&map_obj, &id)?
+                .and_then(|v| v.cast::<String>())
+                .ok_or(SyncError::MissingField("record"))?;
+            records.push(serde_json::from_str(&json)?);
+        }
+
+        Ok(records)
+    }
+}
+
+/// A peer document checked against local state, waiting for decisions on its conflicts. Every
+/// merge goes through here, so the period lock and delegation scopes hold whichever way the
+/// conflicts are decided.
+pub struct PendingMerge<'a> {
+    remote: &'a SyncDoc,
+    local: SyncableLedger,
+    remote_ledger: SyncableLedger,
+    /// Transactions edited differently on both sides, in the order `apply` takes decisions
+    pub conflicts: Vec<Conflict>,
+}
+
+/// What a merge brought in once enforced
+#[derive(Debug, Clone, Default)]
+pub struct MergeOutcome {
+    /// Transactions new to this device that were kept
+    pub arrived: Vec<Transaction>,
+    /// Postings of arrived transactions in a commodity their account does not allow; kept, only flagged
+    pub mismatches: Vec<CommodityMismatch>,
+    /// Transactions sent back to their local version, restored or dropped
+    pub reverted: Vec<Uuid>,
+}
+
+impl SyncDoc {
+    /// Compare a peer document with this one before merging it; `peer` labels the conflicts
+    pub fn prepare_merge<'a>(&self, remote: &'a SyncDoc, peer: &str) -> Result<PendingMerge<'a>, SyncError> {
+        let local = self.to_ledger()?;
+        let remote_ledger = remote.to_ledger()?;
+        let conflicts = conflict::detect(&local, &remote_ledger, peer);
+        Ok(PendingMerge { remote, local, remote_ledger, conflicts })
+    }
+}
+
+impl PendingMerge<'_> {
+    /// Merge into `doc` with one decision per conflict (missing ones accept the remote version),
+    /// then undo what the period lock and, for a delegated helper, its grant don't allow. The
+    /// corrected state is written back so it syncs on.
+    pub fn apply(
+        self,
+        doc: &mut SyncDoc,
+        resolutions: &[Resolution],
+        grant: Option<&WriteGrant>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<MergeOutcome, SyncError> {
+        let PendingMerge { remote, local, remote_ledger, conflicts } = self;
+        let known: std::collections::HashSet<Uuid> = local.transactions.iter().map(|t| t.id).collect();
+        let arrived: Vec<Transaction> = remote_ledger.transactions.iter()
+            .filter(|t| !known.contains(&t.id))
+            .cloned()
+            .collect();
+
+        doc.merge(remote)?;
+        let mut merged = doc.to_ledger()?;
+        let stored = (merged.locked_through, merged.closed_through);
+        // Concurrent locks and closes resolve to the later date, whichever write the document kept
+        merged.locked_through = local.locked_through.max(remote_ledger.locked_through);
+        merged.closed_through = local.closed_through.max(remote_ledger.closed_through);
+
+        let mut dirty = std::collections::HashSet::new();
+        for (i, c) in conflicts.iter().enumerate() {
+            dirty.extend(conflict::affected_accounts(c));
+            conflict::apply(&mut merged, c, resolutions.get(i).copied().unwrap_or(Resolution::AcceptRemote));
+        }
+        // Backdated edits from the peer are undone after conflict resolution so no choice can let them through
+        let mut reverted = merged.enforce_lock(&local);
+        // A delegated helper only gets its in-scope new transactions through
+        if let Some(grant) = grant {
+            reverted.extend(merged.enforce_delegation(&local, grant, now));
+        }
+        let arrived: Vec<Transaction> = arrived.into_iter().filter(|t| !reverted.contains(&t.id)).collect();
+        let mismatches = merged.commodity_mismatches(&arrived);
+        // Each side stored balances without the other's new transactions; whichever write the
+        // document kept misses some of them
+        dirty.extend(arrived.iter().flat_map(|t| &t.postings).map(|p| p.account_id));
+        if conflicts.is_empty() && reverted.is_empty() && grant.is_none() && arrived.is_empty()
+            && (merged.locked_through, merged.closed_through) == stored
+        {
+            return Ok(MergeOutcome { arrived, mismatches, reverted });
+        }
+
+        if reverted.is_empty() {
+            merged.recompute_balances_for(&dirty);
+        } else {
+            merged.recompute_balances();
+        }
+        doc.update_from_ledger(&merged)?;
+        doc.commit();
+        Ok(MergeOutcome { arrived, mismatches, reverted })
+    }
+}
+
+impl SyncDoc {
+    /// Copy without the pending operations. Reading heads or changes of a plain copy would
+    /// commit them there, and the same actor would later commit different ones under that number.
+    fn committed(&self) -> AutoCommit {
+        let mut doc = self.doc.clone();
+        doc.rollback();
+        doc
+    }
+
+    /// The map of records keyed by id under `key`. Documents from before records were keyed
+    /// hold a list there, which is replaced by a map the first time they are written.
+    fn record_map(&mut self, ledger_obj: &ObjId, key: &'static str) -> Result<ObjId, SyncError> {
+        let current = self.doc
+            .get(ledger_obj, key)
+            .map_err(|_| SyncError::MissingField(key))?
+            .and_then(|v| v.cast::<ObjId>());
+        match current {
+            Some(obj) if matches!(self.doc.object_type(&obj), Ok(ObjType::Map)) => Ok(obj),
+            _ => Ok(self.doc.put_object(ledger_obj, key, ObjType::Map)?),
+        }
+    }
+
+    /// Record objects under `key`, from the map keyed by id or a list in older documents
+    fn record_objects(&self, ledger_obj: &ObjId, key: &'static str) -> Result<Vec<ObjId>, SyncError> {
+        let obj = self.doc
+            .get(ledger_obj, key)
+            .map_err(|_| SyncError::MissingField(key))?
+            .and_then(|v| v.cast::<ObjId>())
+            .ok_or(SyncError::MissingField(key))?;
+        let items = match self.doc.object_type(&obj) {
+            Ok(ObjType::List) => (0..self.doc.length(&obj))
+                .map(|i| self.doc.get(&obj, i))
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => self.doc.keys(&obj)
+                .map(|k| self.doc.get(&obj, &k))
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        Ok(items.into_iter()
+            .filter_map(|item| match item {
+                Some((Value::Object(ObjType::Map), record)) => Some(record),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Delete the entries of a map whose key is no longer live
+    fn delete_stale(&mut self, map: &ObjId, live: impl Fn(&str) -> bool) -> Result<(), SyncError> {
+        let stale: Vec<String> = self.doc.keys(map).filter(|k| !live(k)).collect();
+        for key in stale {
+            self.doc.delete(map, &key)?;
+        }
+        Ok(())
+    }
+}
+
+/// A value read from the document, converted to the type the field is stored as
+trait Cast {
+    fn cast<T: FromDoc>(self) -> Option<T>;
+}
+
+impl Cast for (Value<'_>, ObjId) {
+    fn cast<T: FromDoc>(self) -> Option<T> {
+        T::from_doc(self)
+    }
+}
+
+trait FromDoc: Sized {
+    fn from_doc(value: (Value<'_>, ObjId)) -> Option<Self>;
+}
+
+impl FromDoc for String {
+    fn from_doc((value, _): (Value<'_>, ObjId)) -> Option<Self> {
+        value.into_string().ok()
+    }
+}
+
+impl FromDoc for ObjId {
+    fn from_doc((value, id): (Value<'_>, ObjId)) -> Option<Self> {
+        value.is_object().then_some(id)
+    }
+}
+
+impl FromDoc for i64 {
+    fn from_doc((value, _): (Value<'_>, ObjId)) -> Option<Self> {
+        value.to_i64()
+    }
+}
+
+impl FromDoc for bool {
+    fn from_doc((value, _): (Value<'_>, ObjId)) -> Option<Self> {
+        value.to_bool()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peer_exponent_out_of_range_is_an_error() {
+        let mut doc = SyncDoc::new().unwrap();
+        let mut ledger = SyncableLedger::new();
+        let account = Uuid::new_v4();
+        ledger.balances.insert(account, Decimal::new(125, 2));
+        doc.update_from_ledger(&ledger).unwrap();
+
+        for exponent in [-1i64, 29, i64::from(u32::MAX) + 1] {
+            let ledger_obj = doc.get_ledger_obj().unwrap();
+            let balances: ObjId = doc.doc.get(&ledger_obj, "balances").unwrap().and_then(|v| v.cast()).unwrap();
+            let value: ObjId = doc.doc.get(&balances, account.to_string()).unwrap().and_then(|v| v.cast()).unwrap();
+            doc.doc.put(&value, "exponent", exponent).unwrap();
+            assert!(matches!(doc.to_ledger(), Err(SyncError::OutOfRange(_))), "exponent {}", exponent);
+        }
+    }
+
+    #[test]
+    fn delegated_merge_only_keeps_new_transactions_in_scope() {
+        use crate::ledger::{AccountType, Posting};
+
+        let mut local = SyncableLedger::new();
+        let cash = Account::new("Cash", AccountType::Asset);
+        let expenses = Account::new("Expenses", AccountType::Expense);
+        let payroll = Account::new("Payroll", AccountType::Expense);
+        for account in [&cash, &expenses, &payroll] {
+            local.accounts.insert(account.id, account.clone());
+        }
+        let now = chrono::Utc::now();
+        let grant = WriteGrant::new("Scanner", [cash.id, expenses.id], now + chrono::Duration::days(1));
+
+        let mut merged = local.clone();
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let in_scope = Transaction::new(date, "Receipt", vec![
+            Posting::new(expenses.id, Decimal::from(10)),
+            Posting::new(cash.id, Decimal::from(-10)),
+        ]);
+        let out_of_scope = Transaction::new(date, "Salary", vec![
+            Posting::new(payroll.id, Decimal::from(900)),
+            Posting::new(cash.id, Decimal::from(-900)),
+        ]);
+        merged.transactions.extend([in_scope.clone(), out_of_scope.clone()]);
+        let rogue = Account::new("Rogue", AccountType::Asset);
+        merged.accounts.insert(rogue.id, rogue.clone());
+        merged.accounts.get_mut(&cash.id).unwrap().name = "Renamed".to_string();
+        merged.locked_through = Some(date);
+        merged.app_settings.set("dashboard", "layout", &"grid").unwrap();
+
+        let reverted = merged.enforce_delegation(&local, &grant, now);
+
+        assert_eq!(merged.transactions.iter().map(|t| t.id).collect::<Vec<_>>(), vec![in_scope.id]);
+        assert!(reverted.contains(&out_of_scope.id));
+        assert!(reverted.contains(&rogue.id) && reverted.contains(&cash.id));
+        assert!(!merged.accounts.contains_key(&rogue.id));
+        assert_eq!(merged.accounts[&cash.id].name, "Cash");
+        assert_eq!(merged.locked_through, None);
+        assert!(merged.app_settings.is_empty());
+    }
+
+    #[test]
+    fn lock_restores_deleted_locked_transactions() {
+        use crate::ledger::{AccountType, Posting};
+
+        let mut local = SyncableLedger::new();
+        let cash = Account::new("Cash", AccountType::Asset);
+        let sales = Account::new("Sales", AccountType::Revenue);
+        let lock = chrono::NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        let entry = |date| Transaction::new(date, "Sale", vec![
+            Posting::new(cash.id, Decimal::from(5)),
+            Posting::new(sales.id, Decimal::from(-5)),
+        ]);
+        let locked = entry(lock);
+        let open = entry(lock.succ_opt().unwrap());
+        local.transactions.extend([locked.clone(), open.clone()]);
+        local.locked_through = Some(lock);
+
+        let mut merged = local.clone();
+        merged.transactions.clear();
+        let reverted = merged.enforce_lock(&local);
+
+        assert_eq!(reverted, vec![locked.id]);
+        assert_eq!(merged.transactions, vec![locked]);
+    }
+
+    #[test]
+    fn concurrent_transactions_merge_to_their_union() {
+        use crate::ledger::{AccountType, Posting};
+
+        let cash = Account::new("Cash", AccountType::Asset);
+        let sales = Account::new("Sales", AccountType::Revenue);
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let sale = |amount: i64| Transaction::new(date, "Sale", vec![
+            Posting::new(cash.id, Decimal::from(amount)),
+            Posting::new(sales.id, Decimal::from(-amount)),
+        ]);
+        let mut base = SyncableLedger::new();
+        for account in [&cash, &sales] {
+            base.accounts.insert(account.id, account.clone());
+        }
+        base.push_transaction(sale(1));
+        let mut a = SyncDoc::new().unwrap();
+        a.update_from_ledger(&base).unwrap();
+        a.commit();
+        let mut b = SyncDoc::from_bytes(&a.to_bytes()).unwrap();
+
+        for (doc, amount) in [(&mut a, 2), (&mut b, 3)] {
+            let mut ledger = doc.to_ledger().unwrap();
+            ledger.push_transaction(sale(amount));
+            doc.update_from_ledger(&ledger).unwrap();
+            doc.commit();
+        }
+
+        let mut plain = b.clone();
+        plain.merge(&a).unwrap();
+        let mut merged = plain.to_ledger().unwrap();
+        assert_eq!(merged.transactions.len(), 3);
+        merged.recompute_balances();
+        assert_eq!(merged.balances[&cash.id], Decimal::from(6));
+
+        let pending = a.prepare_merge(&b, "b").unwrap();
+        assert!(pending.conflicts.is_empty());
+        let outcome = pending.apply(&mut a, &[], None, chrono::Utc::now()).unwrap();
+        assert_eq!(outcome.arrived.len(), 1);
+        let merged = a.to_ledger().unwrap();
+        assert_eq!(merged.transactions.len(), 3);
+        assert_eq!(merged.balances[&cash.id], Decimal::from(6));
+        assert_eq!(merged.balances[&sales.id], Decimal::from(-6));
+    }
+}
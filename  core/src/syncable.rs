@@ -0,0 +1,614 @@
+//! One model for local and synced state. `Syncable` pairs a validating `Ledger` with its CRDT
+//! document: changes go through the ledger and are mirrored into the document, merges rebuild the
+//! ledger from the document. The mapping between `Ledger` and `SyncableLedger` lives only here.
+use std::collections::HashSet;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::activity::ActivityKind;
+use crate::app_settings::AppSettings;
+use crate::conflict::{Conflict, Resolution};
+use crate::crypto::CryptoSuite;
+use crate::delegation::{DelegationError, DelegationRegistry, WriteToken};
+use crate::inventory::{Inventory, InventoryError};
+use crate::storage::{LocalStorage, SnapshotInfo};
+use crate::ledger::{Ledger, LedgerError, RecordSummary, Transaction};
+use crate::sync::{SyncDoc, SyncError, SyncableLedger};
+
+#[derive(Debug, Error)]
+pub enum SyncableError {
+    #[error(transparent)]
+    Ledger(#[from] LedgerError),
+    #[error(transparent)]
+    Sync(#[from] SyncError),
+    #[error(transparent)]
+    Delegation(#[from] DelegationError),
+    #[error(transparent)]
+    Inventory(#[from] InventoryError),
+}
+
+/// A ledger and the document it syncs through, kept in step
+pub struct Syncable {
+    ledger: Ledger,
+    doc: SyncDoc,
+}
+
+impl Syncable {
+    pub fn new() -> Result<Self, SyncError> {
+        Ok(Self { ledger: Ledger::new(), doc: SyncDoc::new()? })
+    }
+
+    /// Start syncing an existing ledger in a fresh document
+    pub fn from_ledger(ledger: Ledger) -> Result<Self, SyncError> {
+        let mut doc = SyncDoc::new()?;
+        let mut synced = doc.to_ledger()?;
+        export(&Ledger::new(), &ledger, &mut synced);
+        doc.update_from_ledger(&synced)?;
+        doc.commit();
+        Ok(Self { ledger, doc })
+    }
+
+    /// Rebuild the ledger from a stored or received document. Transactions the ledger rejects are
+    /// reported and stay in the document untouched.
+    pub fn from_doc(doc: SyncDoc) -> Result<(Self, RecordSummary), SyncError> {
+        let (ledger, summary) = Ledger::from_synced(&doc.to_ledger()?);
+        Ok((Self { ledger, doc }, summary))
+    }
+
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    pub fn doc(&self) -> &SyncDoc {
+        &self.doc
+    }
+
+    /// Everything in the document, including collections the ledger doesn't model
+    pub fn synced(&self) -> Result<SyncableLedger, SyncError> {
+        self.doc.to_ledger()
+    }
+
+    /// Change the ledger and mirror the result into the document. `f` runs on a copy, so neither
+    /// side changes if it fails.
+    pub fn update<T, E>(&mut self, f: impl FnOnce(&mut Ledger) -> Result<T, E>) -> Result<T, E>
+    where
+        E: From<SyncError>,
+    {
+        let mut next = self.ledger.clone();
+        let out = f(&mut next)?;
+        let mut synced = self.doc.to_ledger()?;
+        export(&self.ledger, &next, &mut synced);
+        self.doc.update_from_ledger(&synced)?;
+        self.doc.commit();
+        self.ledger = next;
+        Ok(out)
+    }
+
+    /// Change document-only collections (contacts, invoices, ...). Transactions added here must
+    /// pass ledger validation or nothing is written.
+    pub fn update_synced<T, E>(&mut self, f: impl FnOnce(&mut SyncableLedger) -> Result<T, E>) -> Result<T, E>
+    where
+        E: From<SyncError> + From<LedgerError>,
+    {
+        let mut synced = self.doc.to_ledger()?;
+        let out = f(&mut synced)?;
+        let (mut ledger, summary) = Ledger::from_synced(&synced);
+        if let Some((_, e)) = summary.rejected.into_iter().next() {
+            return Err(e.into());
+        }
+        for id in edited_transactions(&self.ledger, &ledger) {
+            ledger.log_activity(ActivityKind::TransactionEdited { transaction_id: id });
+        }
+        synced.recompute_balances();
+        export_activity(&ledger, &mut synced);
+        self.doc.update_from_ledger(&synced)?;
+        self.doc.commit();
+        self.ledger = ledger;
+        Ok(out)
+    }
+
+    /// Shared app settings as currently in the document
+    pub fn app_settings(&self) -> Result<AppSettings, SyncError> {
+        self.doc.app_settings()
+    }
+
+    /// Change app settings; only the touched keys are written, the ledger is left alone
+    pub fn update_app_settings<T>(&mut self, f: impl FnOnce(&mut AppSettings) -> T) -> Result<T, SyncError> {
+        let mut settings = self.doc.app_settings()?;
+        let out = f(&mut settings);
+        self.doc.update_app_settings(&settings)?;
+        self.doc.commit();
+        Ok(out)
+    }
+
+    /// Stocked items and their movements as currently in the document
+    pub fn inventory(&self) -> Result<Inventory, SyncError> {
+        Ok(self.doc.to_ledger()?.inventory())
+    }
+
+    /// Change the inventory and the ledger together, e.g. a purchase or sale recording its entry
+    /// and stock movement. `f` runs on copies, so neither side changes if it fails.
+    pub fn update_inventory<T, E>(&mut self, f: impl FnOnce(&mut Inventory, &mut Ledger) -> Result<T, E>) -> Result<T, E>
+    where
+        E: From<SyncError>,
+    {
+        let mut next = self.ledger.clone();
+        let mut synced = self.doc.to_ledger()?;
+        let mut inventory = synced.inventory();
+        let out = f(&mut inventory, &mut next)?;
+        export(&self.ledger, &next, &mut synced);
+        synced.set_inventory(&inventory);
+        self.doc.update_from_ledger(&synced)?;
+        self.doc.commit();
+        self.ledger = next;
+        Ok(out)
+    }
+
+    /// Store the document as a local snapshot and log the backup in the feed
+    pub fn backup(&mut self, storage: &LocalStorage, id: &str) -> Result<SnapshotInfo, SyncError> {
+        let info = storage.store_snapshot(id, &self.doc.to_bytes());
+        self.update(|ledger| {
+            ledger.log_activity(ActivityKind::BackupCreated { location: format!("snapshot:{}", id) });
+            Ok::<_, SyncError>(())
+        })?;
+        Ok(info)
+    }
+
+    pub fn record_transaction(&mut self, tx: Transaction) -> Result<(), SyncableError> {
+        self.update(|ledger| ledger.record_transaction(tx).map_err(SyncableError::from))
+    }
+
+    /// Record a transaction a helper device or HTTP client sends with its write token. The token
+    /// must verify, be unrevoked and be redeemed by `device` (the first device to present it);
+    /// the transaction must fit the grant and pass ledger validation.
+    pub fn record_delegated(
+        &mut self,
+        tx: Transaction,
+        token: &WriteToken,
+        device: &str,
+        registry: &mut DelegationRegistry,
+        suite: &CryptoSuite,
+        issuer_key: &[u8],
+    ) -> Result<(), SyncableError> {
+        let now = chrono::Utc::now();
+        registry.redeem(token, device, suite, issuer_key, now)?.permits(&tx, now)?;
+        self.record_transaction(tx)
+    }
+
+    pub fn void_transaction(&mut self, id: &Uuid, reason: impl Into<String>) -> Result<Transaction, SyncableError> {
+        self.update(|ledger| ledger.void_transaction(id, reason).map_err(SyncableError::from))
+    }
+
+    /// Merge a peer's document and rebuild the ledger; returns what the ledger could not replay.
+    /// Transactions edited on both sides take the remote version.
+    pub fn merge(&mut self, remote: &SyncDoc) -> Result<RecordSummary, SyncError> {
+        self.merge_resolved(remote, "", |_| Resolution::AcceptRemote)
+    }
+
+    /// Merge a peer's document, deciding each transaction edited on both sides with `resolve`.
+    /// The period lock is enforced as in `SyncClient` merges.
+    pub fn merge_resolved(
+        &mut self,
+        remote: &SyncDoc,
+        peer: &str,
+        resolve: impl FnMut(&Conflict) -> Resolution,
+    ) -> Result<RecordSummary, SyncError> {
+        let pending = self.doc.prepare_merge(remote, peer)?;
+        let resolutions: Vec<Resolution> = pending.conflicts.iter().map(resolve).collect();
+        let outcome = pending.apply(&mut self.doc, &resolutions, None, chrono::Utc::now())?;
+        let mut synced = self.doc.to_ledger()?;
+        let (mut ledger, summary) = Ledger::from_synced(&synced);
+        for id in edited_transactions(&self.ledger, &ledger) {
+            ledger.log_activity(ActivityKind::TransactionEdited { transaction_id: id });
+        }
+        ledger.log_activity(ActivityKind::MergeReceived { peer: peer.to_string(), changes: outcome.arrived.len() });
+        export_activity(&ledger, &mut synced);
+        self.doc.update_from_ledger(&synced)?;
+        self.doc.commit();
+        self.ledger = ledger;
+        Ok(summary)
+    }
+
+    pub fn into_parts(self) -> (Ledger, SyncDoc) {
+        (self.ledger, self.doc)
+    }
+}
+
+impl Ledger {
+    /// Replay synced state through ledger validation: accounts, budgets, rules and book settings
+    /// first, then the closed and locked periods, then transactions by date
+    pub fn from_synced(synced: &SyncableLedger) -> (Ledger, RecordSummary) {
+        // Listed in full so a new SyncableLedger field fails to compile until it is mapped here
+        let SyncableLedger {
+            accounts,
+            transactions,
+            budgets,
+            settings,
+            locked_through,
+            closed_through,
+            rules,
+            // Derived from the transactions
+            balances: _,
+            // Only kept in the document
+            close_checklists: _,
+            split_rules: _,
+            documents: _,
+            contacts: _,
+            projects: _,
+            classes: _,
+            prices: _,
+            recurring: _,
+            invoices: _,
+            reconciliations: _,
+            statements: _,
+            app_settings: _,
+            stock_items: _,
+            stock_movements: _,
+            activity,
+        } = synced;
+
+        let mut ledger = match &settings.base_currency {
+            Some(base) => Ledger::with_base_currency(base.clone()),
+            None => Ledger::new(),
+        };
+        let mut summary = RecordSummary::default();
+        for account in accounts.values() {
+            if let Err(e) = ledger.add_account(account.clone()) {
+                summary.rejected.push((account.id, e.into()));
+            }
+        }
+        for budget in budgets.values() {
+            if let Err(e) = ledger.set_budget(budget.clone()) {
+                summary.rejected.push((budget.id, e.into()));
+            }
+        }
+        let mut rules: Vec<_> = rules.values().collect();
+        rules.sort_by_key(|r| r.id);
+        for rule in rules {
+            if let Err(e) = ledger.add_rule(rule.clone()) {
+                summary.rejected.push((rule.id, e.into()));
+            }
+        }
+        // An unknown or non-equity account leaves the setting unset, like on the device that chose it
+        if let Some(rounding) = &settings.rounding {
+            let _ = ledger.set_rounding_policy(rounding.clone());
+        }
+        if let Some(id) = settings.retained_earnings_account {
+            let _ = ledger.set_retained_earnings_account(id);
+        }
+        if let Some(id) = settings.opening_balances_account {
+            let _ = ledger.set_opening_balances_account(id);
+        }
+        if let Some(table) = &settings.tax_table {
+            ledger.set_tax_table(table.clone());
+        }
+
+        // Merges already kept peers out of the closed and locked periods
+        ledger.restore_periods(*closed_through, *locked_through);
+        let mut transactions = transactions.clone();
+        transactions.sort_by_key(|t| t.date);
+        let replayed = ledger.replay_transactions(transactions);
+        summary.recorded += replayed.recorded;
+        summary.duplicates += replayed.duplicates;
+        summary.rejected.extend(replayed.rejected);
+        // The feed is stored, not derived: replaying logs nothing that happened
+        ledger.restore_activity(activity.values().cloned());
+        (ledger, summary)
+    }
+}
+
+/// Write the difference between two ledger states into synced state. Entries removed from the
+/// ledger are removed; entries the ledger never held (e.g. rejected on replay) are left alone.
+fn export(before: &Ledger, after: &Ledger, synced: &mut SyncableLedger) {
+    let kept: HashSet<Uuid> = after.account_tree().into_iter().map(|(_, a)| a.id).collect();
+    for (_, account) in before.account_tree() {
+        if !kept.contains(&account.id) {
+            synced.accounts.remove(&account.id);
+        }
+    }
+    for (_, account) in after.account_tree() {
+        synced.accounts.insert(account.id, account.clone());
+    }
+
+    let kept: HashSet<Uuid> = after.budgets().map(|b| b.id).collect();
+    for budget in before.budgets().filter(|b| !kept.contains(&b.id)) {
+        synced.budgets.remove(&budget.id);
+    }
+    for budget in after.budgets() {
+        synced.budgets.insert(budget.id, budget.clone());
+    }
+
+    let kept: HashSet<Uuid> = after.rules().iter().map(|r| r.id).collect();
+    for rule in before.rules().iter().filter(|r| !kept.contains(&r.id)) {
+        synced.rules.remove(&rule.id);
+    }
+    for rule in after.rules() {
+        synced.rules.insert(rule.id, rule.clone());
+    }
+
+    // The journal only grows; voiding rewrites the original in place
+    for tx in after.transactions() {
+        match synced.transactions.iter_mut().find(|t| t.id == tx.id) {
+            Some(existing) => *existing = tx.clone(),
+            None => synced.transactions.push(tx.clone()),
+        }
+    }
+    synced.recompute_balances();
+
+    if after.locked_through() > synced.locked_through {
+        synced.locked_through = after.locked_through();
+    }
+    if after.closed_through() > synced.closed_through {
+        synced.closed_through = after.closed_through();
+    }
+
+    let settings = &mut synced.settings;
+    if before.base_currency() != after.base_currency() {
+        settings.base_currency = Some(after.base_currency().clone());
+    }
+    if before.rounding_policy() != after.rounding_policy() {
+        settings.rounding = Some(after.rounding_policy().clone());
+    }
+    if before.retained_earnings_account() != after.retained_earnings_account() {
+        settings.retained_earnings_account = after.retained_earnings_account();
+    }
+    if before.opening_balances_account() != after.opening_balances_account() {
+        settings.opening_balances_account = after.opening_balances_account();
+    }
+    if before.tax_table() != after.tax_table() {
+        settings.tax_table = Some(after.tax_table().clone());
+    }
+    export_activity(after, synced);
+}
+
+/// Add the ledger's new feed entries to synced state
+fn export_activity(ledger: &Ledger, synced: &mut SyncableLedger) {
+    for entry in ledger.activity(chrono::DateTime::<chrono::Utc>::MIN_UTC) {
+        synced.activity.entry(entry.id).or_insert_with(|| entry.clone());
+    }
+}
+
+/// Transactions both ledgers hold that differ between them
+fn edited_transactions(before: &Ledger, after: &Ledger) -> Vec<Uuid> {
+    after.transactions()
+        .filter(|tx| before.transaction(&tx.id).is_some_and(|old| old != *tx))
+        .map(|tx| tx.id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use crate::currency::Commodity;
+    use crate::ledger::tax::{TaxDirection, TaxRate, TaxTable};
+    use crate::ledger::{AccountType, Posting};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn add(ledger: &mut Ledger, name: &str, r#type: AccountType) -> Uuid {
+        let account = ledger.new_account(name, r#type);
+        let id = account.id;
+        ledger.add_account(account).unwrap();
+        id
+    }
+
+    fn sale(on: NaiveDate, cash: Uuid, sales: Uuid, amount: i64) -> Transaction {
+        let postings = vec![Posting::new(cash, Decimal::from(amount)), Posting::new(sales, Decimal::from(-amount))];
+        Transaction::new(on, "Sale", postings)
+    }
+
+    #[test]
+    fn book_settings_and_periods_survive_a_round_trip() {
+        let mut ledger = Ledger::with_base_currency(Commodity::new("EUR"));
+        let cash = add(&mut ledger, "Cash", AccountType::Asset);
+        let sales = add(&mut ledger, "Sales", AccountType::Revenue);
+        let retained = add(&mut ledger, "Retained Earnings", AccountType::Equity);
+        let opening = add(&mut ledger, "Opening Balances", AccountType::Equity);
+        let vat = add(&mut ledger, "VAT", AccountType::Liability);
+        ledger.set_retained_earnings_account(retained).unwrap();
+        ledger.set_opening_balances_account(opening).unwrap();
+        let table = TaxTable::new().with_rate(TaxRate::new("S20", Decimal::new(20, 2), TaxDirection::Output, vat));
+        ledger.set_tax_table(table.clone());
+        ledger.record_transaction(sale(date(2024, 3, 1), cash, sales, 100)).unwrap();
+        ledger.close_period(date(2024, 3, 31)).unwrap();
+        ledger.lock_period(date(2024, 3, 31)).unwrap();
+
+        let book = Syncable::from_ledger(ledger).unwrap();
+        let (restored, summary) = Syncable::from_doc(SyncDoc::from_bytes(&book.doc().to_bytes()).unwrap()).unwrap();
+        let restored = restored.ledger();
+
+        assert!(summary.rejected.is_empty());
+        assert_eq!(restored.base_currency(), &Commodity::new("EUR"));
+        assert_eq!(restored.retained_earnings_account(), Some(retained));
+        assert_eq!(restored.opening_balances_account(), Some(opening));
+        assert_eq!(restored.tax_table(), &table);
+        assert_eq!(restored.closed_through(), Some(date(2024, 3, 31)));
+        assert_eq!(restored.locked_through(), Some(date(2024, 3, 31)));
+        assert_eq!(restored.transactions().count(), 2);
+        assert!(restored.balance(&sales).values().all(|a| a.is_zero()));
+    }
+
+    #[test]
+    fn merge_drops_a_peers_backdated_transaction() {
+        let mut ledger = Ledger::new();
+        let cash = add(&mut ledger, "Cash", AccountType::Asset);
+        let sales = add(&mut ledger, "Sales", AccountType::Revenue);
+        let mut local = Syncable::from_ledger(ledger).unwrap();
+        let (mut peer, _) = Syncable::from_doc(SyncDoc::from_bytes(&local.doc().to_bytes()).unwrap()).unwrap();
+
+        local.update(|l| l.lock_period(date(2024, 6, 30)).map_err(|e| SyncableError::from(LedgerError::from(e)))).unwrap();
+        peer.record_transaction(sale(date(2024, 6, 1), cash, sales, 50)).unwrap();
+        let summary = local.merge(peer.doc()).unwrap();
+
+        assert!(summary.rejected.is_empty());
+        assert_eq!(local.ledger().transactions().count(), 0);
+        assert!(local.synced().unwrap().transactions.is_empty());
+        assert_eq!(local.ledger().locked_through(), Some(date(2024, 6, 30)));
+    }
+
+    #[test]
+    fn delegated_entry_checks_token_scope_and_revocation() {
+        use crate::crypto::{CryptoSuite, Ed25519Signer, Signer};
+        use crate::delegation::{DelegationRegistry, WriteGrant};
+
+        let mut ledger = Ledger::new();
+        let cash = add(&mut ledger, "Cash", AccountType::Asset);
+        let sales = add(&mut ledger, "Sales", AccountType::Revenue);
+        let payroll = add(&mut ledger, "Payroll", AccountType::Expense);
+        let mut book = Syncable::from_ledger(ledger).unwrap();
+        let owner = Ed25519Signer::generate();
+        let suite = CryptoSuite::default();
+        let mut registry = DelegationRegistry::default();
+        let grant = WriteGrant::new("Till", [cash, sales], chrono::Utc::now() + chrono::Duration::hours(1));
+        let token = registry.issue(grant, &owner);
+        let key = owner.public_key();
+
+        book.record_delegated(sale(date(2024, 5, 1), cash, sales, 20), &token, "till", &mut registry, &suite, &key).unwrap();
+        let outside = sale(date(2024, 5, 1), cash, payroll, 20);
+        assert!(matches!(
+            book.record_delegated(outside, &token, "till", &mut registry, &suite, &key),
+            Err(SyncableError::Delegation(DelegationError::AccountNotAllowed(id))) if id == payroll
+        ));
+        assert!(matches!(
+            book.record_delegated(sale(date(2024, 5, 2), cash, sales, 5), &token, "other", &mut registry, &suite, &key),
+            Err(SyncableError::Delegation(DelegationError::AlreadyRedeemed))
+        ));
+        registry.revoke(&token.grant.id);
+        assert!(matches!(
+            book.record_delegated(sale(date(2024, 5, 2), cash, sales, 5), &token, "till", &mut registry, &suite, &key),
+            Err(SyncableError::Delegation(DelegationError::Revoked))
+        ));
+        assert_eq!(book.ledger().transactions().count(), 1);
+    }
+
+    #[test]
+    fn app_settings_write_only_their_key() {
+        let mut ledger = Ledger::new();
+        let cash = add(&mut ledger, "Cash", AccountType::Asset);
+        let sales = add(&mut ledger, "Sales", AccountType::Revenue);
+        ledger.record_transaction(sale(date(2024, 5, 1), cash, sales, 20)).unwrap();
+        let mut book = Syncable::from_ledger(ledger).unwrap();
+        let heads = book.doc().doc.clone().get_heads();
+
+        book.update_app_settings(|s| s.set("dashboard", "layout", &"grid")).unwrap().unwrap();
+
+        let mut doc = book.doc().doc.clone();
+        let changes = doc.get_changes(&heads);
+        assert_eq!(changes.iter().map(|c| c.len()).sum::<usize>(), 1);
+        assert_eq!(book.app_settings().unwrap().get::<String>("dashboard", "layout").as_deref(), Some("grid"));
+    }
+
+    #[test]
+    fn merge_combines_both_feeds_and_logs_the_merge() {
+        let mut ledger = Ledger::new();
+        let cash = add(&mut ledger, "Cash", AccountType::Asset);
+        let sales = add(&mut ledger, "Sales", AccountType::Revenue);
+        let mut local = Syncable::from_ledger(ledger).unwrap();
+        let (mut peer, _) = Syncable::from_doc(SyncDoc::from_bytes(&local.doc().to_bytes()).unwrap()).unwrap();
+        let since = chrono::Utc::now() - chrono::Duration::seconds(1);
+        local.record_transaction(sale(date(2024, 5, 1), cash, sales, 10)).unwrap();
+        peer.record_transaction(sale(date(2024, 5, 2), cash, sales, 20)).unwrap();
+
+        local.merge_resolved(peer.doc(), "peer-1", |_| Resolution::AcceptRemote).unwrap();
+
+        let kinds: Vec<&ActivityKind> = local.ledger().activity(since).iter().map(|e| &e.kind).collect();
+        assert!(matches!(kinds[..2], [ActivityKind::TransactionPosted { .. }, ActivityKind::TransactionPosted { .. }]));
+        assert_eq!(kinds.last(), Some(&&ActivityKind::MergeReceived { peer: "peer-1".to_string(), changes: 1 }));
+        assert_eq!(kinds.len(), 3);
+    }
+
+    #[test]
+    fn base_currency_change_survives_a_round_trip() {
+        let (eur, gbp) = (Commodity::new("EUR"), Commodity::new("GBP"));
+        let mut ledger = Ledger::with_base_currency(eur.clone());
+        let cash = add(&mut ledger, "Cash", AccountType::Asset);
+        let sales = add(&mut ledger, "Sales", AccountType::Revenue);
+        let tx = Transaction::new(date(2024, 3, 1), "Sale", vec![
+            ledger.posting(cash, Decimal::from(100)),
+            ledger.posting(sales, Decimal::from(-100)),
+        ]);
+        ledger.record_transaction(tx).unwrap();
+        let mut book = Syncable::from_ledger(ledger).unwrap();
+
+        let change = book.ledger().begin_base_currency_change(gbp.clone()).with_rate(Decimal::new(85, 2));
+        book.update(|l| l.apply_base_currency_change(&change, date(2024, 4, 1)).map_err(SyncableError::from)).unwrap();
+        let (restored, summary) = Syncable::from_doc(SyncDoc::from_bytes(&book.doc().to_bytes()).unwrap()).unwrap();
+        let restored = restored.ledger();
+
+        assert!(summary.rejected.is_empty());
+        assert_eq!(restored.base_currency(), &gbp);
+        assert_eq!(restored.account(&cash).unwrap().commodity, gbp);
+        assert_eq!(restored.balance(&cash)[&gbp], Decimal::from(85));
+        assert!(restored.balance(&cash)[&eur].is_zero());
+        assert!(restored.equation().balanced);
+    }
+
+    #[test]
+    fn stock_movements_from_two_replicas_merge_to_their_union() {
+        use crate::inventory::Item;
+
+        let mut ledger = Ledger::new();
+        let cash = add(&mut ledger, "Cash", AccountType::Asset);
+        let item = Item {
+            id: Uuid::new_v4(),
+            sku: "W-1".to_string(),
+            name: "Widget".to_string(),
+            inventory_account: add(&mut ledger, "Inventory", AccountType::Asset),
+            cogs_account: add(&mut ledger, "Cost of goods sold", AccountType::Expense),
+            revenue_account: add(&mut ledger, "Sales", AccountType::Revenue),
+        };
+        let item_id = item.id;
+        let mut local = Syncable::from_ledger(ledger).unwrap();
+        local.update_inventory(|inventory, _| inventory.add_item(item).map_err(SyncableError::from)).unwrap();
+        let (mut peer, _) = Syncable::from_doc(SyncDoc::from_bytes(&local.doc().to_bytes()).unwrap()).unwrap();
+
+        let buy = |book: &mut Syncable, quantity: i64| {
+            book.update_inventory(|inventory, ledger| {
+                inventory.purchase(ledger, item_id, Decimal::from(quantity), Decimal::from(2), date(2024, 3, 1), cash)
+                    .map_err(SyncableError::from)
+            }).unwrap();
+        };
+        buy(&mut local, 3);
+        buy(&mut peer, 5);
+        local.merge(peer.doc()).unwrap();
+
+        let stock = local.inventory().unwrap().stock(&item_id);
+        assert_eq!(stock.quantity, Decimal::from(8));
+        assert_eq!(stock.total_cost, Decimal::from(16));
+        assert_eq!(local.ledger().transactions().count(), 2);
+    }
+
+    #[test]
+    fn feed_is_stored_with_edits_and_backups() {
+        use crate::ledger::reconcile::{ClearedState, PostingRef};
+        use crate::storage::StorageConfig;
+
+        let mut ledger = Ledger::new();
+        let cash = add(&mut ledger, "Cash", AccountType::Asset);
+        let sales = add(&mut ledger, "Sales", AccountType::Revenue);
+        let mut book = Syncable::from_ledger(ledger).unwrap();
+        let tx = sale(date(2024, 5, 1), cash, sales, 10);
+        let id = tx.id;
+        book.record_transaction(tx).unwrap();
+        let posting = PostingRef { transaction_id: id, index: 0 };
+        book.update(|l| l.set_cleared(posting, ClearedState::Cleared).map_err(|e| SyncableError::from(LedgerError::from(e)))).unwrap();
+        let storage = LocalStorage::with_config(&StorageConfig { path: ":memory:".to_string(), ..Default::default() });
+        book.backup(&storage, "nightly").unwrap();
+
+        let (restored, _) = Syncable::from_doc(SyncDoc::from_bytes(&book.doc().to_bytes()).unwrap()).unwrap();
+        let kinds: Vec<ActivityKind> = restored.ledger().activity(chrono::DateTime::<chrono::Utc>::MIN_UTC)
+            .iter()
+            .map(|e| e.kind.clone())
+            .collect();
+        assert_eq!(kinds, vec![
+            ActivityKind::TransactionPosted { transaction_id: id },
+            ActivityKind::TransactionEdited { transaction_id: id },
+            ActivityKind::BackupCreated { location: "snapshot:nightly".to_string() },
+        ]);
+        assert!(storage.load_snapshot("nightly").is_some());
+    }
+}
@@ -0,0 +1,102 @@
+//! Application-registered derived views over the storage tables
+use rusqlite::Row;
+use rust_decimal::Decimal;
+use serde::{Serialize, Deserialize};
+
+/// Derived query the storage layer keeps available as `view_<NAME>`.
+/// Materialized views are stored in a table, marked stale on every transaction write and
+/// refreshed on the next query; plain views are recomputed by SQLite each time.
+pub trait DerivedView {
+    type Row;
+    /// SQL identifier: ASCII letters, digits and underscores
+    const NAME: &'static str;
+    const MATERIALIZED: bool = false;
+
+    /// SELECT over the storage tables (`transactions`, `transaction_provenance`, ...)
+    fn select() -> &'static str;
+
+    fn map_row(row: &Row<'_>) -> rusqlite::Result<Self::Row>;
+}
+
+pub(crate) fn table_name<V: DerivedView>() -> String {
+    assert!(
+        !V::NAME.is_empty() && V::NAME.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        "invalid derived view name {:?}",
+        V::NAME,
+    );
+    format!("view_{}", V::NAME)
+}
+
+/// Total posted per account, commodity and calendar month
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MonthlyAccountTotal {
+    pub account_id: String,
+    /// `YYYY-MM`
+    pub month: String,
+    pub commodity: String,
+    pub total: Decimal,
+}
+
+/// Monthly totals per account; only covers JSON-encoded transaction rows
+pub struct MonthlyAccountTotals;
+
+#[derive(Deserialize)]
+struct PostedAmount(#[serde(with = "crate::canonical::serde_decimal")] Decimal);
+
+impl DerivedView for MonthlyAccountTotals {
+    type Row = MonthlyAccountTotal;
+    const NAME: &'static str = "monthly_account_totals";
+    const MATERIALIZED: bool = true;
+
+    fn select() -> &'static str {
+        // Amounts are summed in Rust: SQLite would add them as floating point
+        "SELECT json_extract(p.value, '$.account_id') AS account_id,
+                substr(json_extract(t.data, '$.date'), 1, 7) AS month,
+                coalesce(json_extract(p.value, '$.commodity'), 'USD') AS commodity,
+                json_group_array(json(p.value -> '$.amount')) AS amounts
+         FROM transactions t, json_each(t.data, '$.postings') p
+         WHERE typeof(t.data) = 'text'
+         GROUP BY 1, 2, 3"
+    }
+
+    fn map_row(row: &Row<'_>) -> rusqlite::Result<MonthlyAccountTotal> {
+        let amounts: String = row.get("amounts")?;
+        // Amounts as posted: canonical `{mantissa, exponent}`, or strings in older rows
+        let amounts: Vec<PostedAmount> = serde_json::from_str(&amounts)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e)))?;
+        let total = amounts.into_iter().map(|a| a.0).sum();
+        Ok(MonthlyAccountTotal {
+            account_id: row.get("account_id")?,
+            month: row.get("month")?,
+            commodity: row.get("commodity")?,
+            total,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use uuid::Uuid;
+    use crate::ledger::{Posting, Transaction};
+    use crate::storage::{LocalStorage, StorageConfig};
+
+    #[test]
+    fn monthly_totals_decode_canonical_amounts() {
+        let storage = LocalStorage::with_config(&StorageConfig { path: ":memory:".to_string(), ..Default::default() });
+        storage.register_view::<MonthlyAccountTotals>();
+        let (cash, sales) = (Uuid::new_v4(), Uuid::new_v4());
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        for cents in [1250, 75] {
+            let amount = Decimal::new(cents, 2);
+            let tx = Transaction::new(date, "Sale", vec![Posting::new(cash, amount), Posting::new(sales, -amount)]);
+            storage.store_transaction(&tx).unwrap();
+        }
+
+        let rows = storage.query_view_where::<MonthlyAccountTotals, _>("account_id = ?", [cash.to_string()]);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].month, "2024-05");
+        assert_eq!(rows[0].total, Decimal::new(1325, 2));
+    }
+}
@@ -0,0 +1,268 @@
+//! Several books (e.g. personal and business) in one process and one storage file. Each entity
+//! is a `Syncable` book with its own gossip topic, so peers only receive the books they join.
+use std::collections::BTreeMap;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+use crate::delegation::DelegationError;
+use crate::inventory::InventoryError;
+use crate::ledger::{LedgerError, Posting, Transaction, TransactionStatus};
+use crate::storage::LocalStorage;
+use crate::sync::{SyncDoc, SyncError};
+use crate::syncable::{Syncable, SyncableError};
+
+#[derive(Debug, Error)]
+pub enum WorkspaceError {
+    #[error("Unknown entity: {0}")]
+    UnknownEntity(String),
+    #[error("Entity already exists: {0}")]
+    DuplicateEntity(String),
+    #[error("Invalid entity name: {0}")]
+    InvalidName(String),
+    #[error("Transfer needs two different entities")]
+    SameEntity,
+    #[error("{rejected} stored transactions of {entity} could not be replayed")]
+    Replay { entity: String, rejected: usize },
+    #[error(transparent)]
+    Ledger(#[from] LedgerError),
+    #[error(transparent)]
+    Sync(#[from] SyncError),
+    #[error(transparent)]
+    Delegation(#[from] DelegationError),
+    #[error(transparent)]
+    Inventory(#[from] InventoryError),
+}
+
+impl From<SyncableError> for WorkspaceError {
+    fn from(e: SyncableError) -> Self {
+        match e {
+            SyncableError::Ledger(e) => WorkspaceError::Ledger(e),
+            SyncableError::Sync(e) => WorkspaceError::Sync(e),
+            SyncableError::Delegation(e) => WorkspaceError::Delegation(e),
+            SyncableError::Inventory(e) => WorkspaceError::Inventory(e),
+        }
+    }
+}
+
+/// One named book
+pub struct Entity {
+    pub name: String,
+    pub book: Syncable,
+}
+
+impl Entity {
+    /// Gossip topic the entity's document is published on
+    pub fn topic(&self) -> String {
+        crate::entity_topic(&self.name)
+    }
+}
+
+/// One leg of a transfer between entities: the money account and the intercompany account it is
+/// booked against, "Due from <other>" (an asset) on the paying side and "Due to <other>"
+/// (a liability) on the receiving side
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferLeg {
+    pub entity: String,
+    pub account_id: Uuid,
+    pub due_account_id: Uuid,
+}
+
+/// Money moved from one entity's account to another's
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InterEntityTransfer {
+    /// Shared by the entries on both sides
+    pub id: Uuid,
+    pub date: NaiveDate,
+    pub amount: Decimal,
+    pub description: String,
+    pub from: TransferLeg,
+    pub to: TransferLeg,
+}
+
+impl InterEntityTransfer {
+    pub fn new(date: NaiveDate, amount: Decimal, description: impl Into<String>, from: TransferLeg, to: TransferLeg) -> Self {
+        Self { id: Uuid::new_v4(), date, amount, description: description.into(), from, to }
+    }
+}
+
+/// One side of a transfer as found in an entity's ledger
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferSide {
+    pub entity: String,
+    pub transaction_id: Uuid,
+    pub date: NaiveDate,
+    /// What the side's money account received; negative on the paying side
+    pub amount: Decimal,
+}
+
+/// Both sides of a transfer id across the workspace, for intercompany reconciliation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferPair {
+    pub transfer_id: Uuid,
+    pub outgoing: Option<TransferSide>,
+    pub incoming: Option<TransferSide>,
+}
+
+impl TransferPair {
+    /// Both sides are present and mirror each other
+    pub fn is_matched(&self) -> bool {
+        match (&self.outgoing, &self.incoming) {
+            (Some(out), Some(inc)) => out.entity != inc.entity && out.amount == -inc.amount,
+            _ => false,
+        }
+    }
+}
+
+/// Entities by name
+#[derive(Default)]
+pub struct Workspace {
+    entities: BTreeMap<String, Entity>,
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an empty entity; names are lowercase letters, digits, '-' and '_' so they fit a topic
+    pub fn create(&mut self, name: &str) -> Result<&mut Entity, WorkspaceError> {
+        let valid = !name.is_empty()
+            && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+        if !valid {
+            return Err(WorkspaceError::InvalidName(name.to_string()));
+        }
+        if self.entities.contains_key(name) {
+            return Err(WorkspaceError::DuplicateEntity(name.to_string()));
+        }
+        let entity = Entity { name: name.to_string(), book: Syncable::new()? };
+        Ok(self.entities.entry(name.to_string()).or_insert(entity))
+    }
+
+    /// Drop an entity from the workspace; its stored document stays until `forget` is called
+    pub fn remove(&mut self, name: &str) -> Option<Entity> {
+        self.entities.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Entity> {
+        self.entities.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Entity> {
+        self.entities.get_mut(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entities.keys().map(String::as_str)
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = &Entity> {
+        self.entities.values()
+    }
+
+    /// Entity a gossip message belongs to, for dispatching received payloads
+    pub fn by_topic(&mut self, topic: &str) -> Option<&mut Entity> {
+        self.entities.values_mut().find(|e| e.topic() == topic)
+    }
+
+    fn entity_mut(&mut self, name: &str) -> Result<&mut Entity, WorkspaceError> {
+        self.entities.get_mut(name).ok_or_else(|| WorkspaceError::UnknownEntity(name.to_string()))
+    }
+
+    /// Write every entity's document to storage
+    pub fn save(&self, storage: &LocalStorage) {
+        for entity in self.entities.values() {
+            storage.save_entity_doc(&entity.name, &entity.book.doc().to_bytes());
+        }
+    }
+
+    /// Load every stored entity, rebuilding each ledger from its document
+    pub fn load(storage: &LocalStorage) -> Result<Self, WorkspaceError> {
+        let mut workspace = Self::new();
+        for name in storage.entity_names() {
+            let Some(data) = storage.load_entity_doc(&name) else { continue };
+            let (book, summary) = Syncable::from_doc(SyncDoc::from_bytes(&data)?)?;
+            if !summary.rejected.is_empty() {
+                return Err(WorkspaceError::Replay { entity: name, rejected: summary.rejected.len() });
+            }
+            workspace.entities.insert(name.clone(), Entity { name, book });
+        }
+        Ok(workspace)
+    }
+
+    /// Delete an entity's stored document; only reachable through a quorum `Approval`
+    pub(crate) fn forget(&mut self, name: &str, storage: &LocalStorage) -> bool {
+        self.entities.remove(name);
+        storage.delete_entity_doc(name)
+    }
+
+    /// Record a transfer in both books: the paying side credits its money account and debits
+    /// "due from", the receiving side debits its money account and credits "due to". Both entries
+    /// carry the transfer id; nothing is recorded unless both validate. Returns (from, to) transaction ids.
+    pub fn transfer(&mut self, transfer: &InterEntityTransfer) -> Result<(Uuid, Uuid), WorkspaceError> {
+        if transfer.from.entity == transfer.to.entity {
+            return Err(WorkspaceError::SameEntity);
+        }
+        if transfer.amount <= Decimal::ZERO {
+            return Err(LedgerError::Rejected("Transfer amount must be positive").into());
+        }
+        let reference = format!("XFER-{}", transfer.id.simple());
+        let outgoing = self.transfer_entry(&transfer.from, transfer, -transfer.amount, &reference)?;
+        let incoming = self.transfer_entry(&transfer.to, transfer, transfer.amount, &reference)?;
+
+        self.entity_mut(&transfer.from.entity)?.book.ledger().simulate_transaction(outgoing.clone())?;
+        self.entity_mut(&transfer.to.entity)?.book.ledger().simulate_transaction(incoming.clone())?;
+        let ids = (outgoing.id, incoming.id);
+        self.entity_mut(&transfer.from.entity)?.book.record_transaction(outgoing)?;
+        self.entity_mut(&transfer.to.entity)?.book.record_transaction(incoming)?;
+        Ok(ids)
+    }
+
+    /// Entry on one side; `amount` is what the money account receives (negative when paying)
+    fn transfer_entry(
+        &mut self,
+        leg: &TransferLeg,
+        transfer: &InterEntityTransfer,
+        amount: Decimal,
+        reference: &str,
+    ) -> Result<Transaction, WorkspaceError> {
+        let ledger = self.entity_mut(&leg.entity)?.book.ledger();
+        let commodity = ledger.account(&leg.account_id)
+            .map(|a| a.commodity.clone())
+            .ok_or(LedgerError::Rejected("Account not found"))?;
+        let mut tx = Transaction::new(transfer.date, transfer.description.clone(), vec![
+            Posting::in_commodity(leg.account_id, amount, commodity.clone()),
+            Posting::in_commodity(leg.due_account_id, -amount, commodity),
+        ]);
+        tx.reference = Some(reference.to_string());
+        tx.transfer_id = Some(transfer.id);
+        Ok(tx)
+    }
+
+    /// Transfers paired by id across entities, unmatched ones first. A side is missing when the
+    /// other entity's entry was voided or hasn't synced yet; voided entries are left out.
+    pub fn reconcile_transfers(&self) -> Vec<TransferPair> {
+        let mut pairs: BTreeMap<Uuid, TransferPair> = BTreeMap::new();
+        for entity in self.entities.values() {
+            let sides = entity.book.ledger().transactions()
+                .filter(|t| t.status() != TransactionStatus::Voided)
+                .filter_map(|t| Some((t.transfer_id?, t)));
+            for (transfer_id, tx) in sides {
+                let Some(amount) = tx.postings.first().map(|p| p.amount) else { continue };
+                let side = TransferSide { entity: entity.name.clone(), transaction_id: tx.id, date: tx.date, amount };
+                let pair = pairs.entry(transfer_id)
+                    .or_insert(TransferPair { transfer_id, outgoing: None, incoming: None });
+                if amount.is_sign_negative() {
+                    pair.outgoing = Some(side);
+                } else {
+                    pair.incoming = Some(side);
+                }
+            }
+        }
+        let mut pairs: Vec<TransferPair> = pairs.into_values().collect();
+        pairs.sort_by_key(|p| p.is_matched());
+        pairs
+    }
+}